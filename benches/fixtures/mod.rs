@@ -0,0 +1,49 @@
+//! Generator of synthetic Ion programs used as benchmark fixtures
+
+use std::fmt::Write;
+
+/// Deterministic program with `functions` functions (and a struct for every ten of them) using
+/// most of the syntax of the language
+pub fn generate(functions: usize) -> String {
+    let mut src = String::new();
+    src.push_str("package bench\n\nimport \"std/io\"\n");
+
+    for i in 0..functions {
+        if i % 10 == 0 {
+            write!(src,
+                   "\nstruct S{} {{\n    name: string,\n    values: []int,\n    \
+                    index: [string]int,\n    next: @S{},\n}}\n",
+                   i,
+                   i)
+                .unwrap();
+        }
+
+        let s = i - i % 10;
+        write!(src,
+               "
+func f{i}(a: int, b: &S{s}, c: []string) -> int {{
+    var total: int = (a * {i}) + (a - 1) % 7
+    var names: [string]int = {{\"a\": 1, \"b\": {i}}}
+    for item in c {{
+        if names[item] == 0 {{
+            names[item] = #c
+        }} else if item == \"f{i}\" {{
+            total = total + names[item]
+        }} else {{
+            std::io::println(item <> \" {i}\")
+        }}
+    }}
+    while total != 0 {{
+        total = total - b.values[a % #b.values]
+    }}
+    return total + f{prev}(a - 1, b, c)
+}}
+",
+               i = i,
+               s = s,
+               prev = if i == 0 { 0 } else { i - 1 })
+            .unwrap();
+    }
+
+    src
+}
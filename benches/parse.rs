@@ -0,0 +1,105 @@
+//! Lexer and parser throughput on generated programs of increasing size, in tokens and statements
+//! per second. Run with `cargo bench`.
+
+#![allow(dead_code)]
+
+#[macro_use]
+extern crate criterion;
+extern crate unicode_normalization;
+
+// ion is only built as a binary, so the front-end modules are compiled into the benchmark
+#[path = "../src/source_map.rs"]
+mod source_map;
+#[path = "../src/lexer/mod.rs"]
+mod lexer;
+#[path = "../src/parser/mod.rs"]
+mod parser;
+mod fixtures;
+
+use criterion::{black_box, BenchmarkId, Criterion, Throughput};
+use parser::ast::*;
+use parser::visit;
+use source_map::FileId;
+
+const SIZES: [usize; 3] = [10, 100, 1000];
+
+fn count_statements(ast: &Ast) -> usize {
+    struct StatementCounter(usize);
+
+    impl<'a> visit::Visitor<'a> for StatementCounter {
+        fn visit_statement(&mut self, statement: &'a Statement) {
+            self.0 += 1;
+            visit::walk_statement(self, statement);
+        }
+
+        fn visit_block_statement(&mut self, block_statement: &'a BlockStatement) {
+            self.0 += 1;
+            visit::walk_block_statement(self, block_statement);
+        }
+    }
+
+    let mut counter = StatementCounter(0);
+    visit::walk_ast(&mut counter, ast);
+
+    counter.0
+}
+
+fn bench_lexer(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lexer");
+    for &size in &SIZES {
+        let src = fixtures::generate(size);
+        let tokens = lexer::tokenize(&src, FileId::nil_id()).unwrap().len();
+
+        group.throughput(Throughput::Elements(tokens as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &src, |b, src| {
+            b.iter(|| lexer::tokenize(black_box(src), FileId::nil_id()).unwrap())
+        });
+    }
+    group.finish();
+}
+
+/// Same as `bench_lexer`, with tokens borrowing their text from the source instead of owning it
+fn bench_lexer_borrowed(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lexer_borrowed");
+    for &size in &SIZES {
+        let src = fixtures::generate(size);
+        let tokens = lexer::tokenize(&src, FileId::nil_id()).unwrap().len();
+
+        group.throughput(Throughput::Elements(tokens as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &src, |b, src| {
+            b.iter(|| {
+                let mut reader = lexer::Reader::new(black_box(src), FileId::nil_id());
+                let mut tokens = 0;
+                while reader.next_borrowed_token().unwrap().tok != lexer::Token::EOF {
+                    tokens += 1;
+                }
+                tokens
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_parser(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parser");
+    for &size in &SIZES {
+        let src = fixtures::generate(size);
+        let mut reader = lexer::Reader::new(&src, FileId::nil_id());
+        let mut parser = parser::Parser::new(&mut reader, parser::ParserOptions::default());
+        let statements = count_statements(parser.parse().unwrap());
+
+        group.throughput(Throughput::Elements(statements as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &src, |b, src| {
+            b.iter(|| {
+                let mut reader = lexer::Reader::new(black_box(src), FileId::nil_id());
+                let mut parser = parser::Parser::new(&mut reader,
+                                                     parser::ParserOptions::default());
+                parser.parse().unwrap().statements.len()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_lexer, bench_lexer_borrowed, bench_parser);
+criterion_main!(benches);
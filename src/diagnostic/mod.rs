@@ -0,0 +1,215 @@
+use std;
+use lexer::Span;
+use source_map::SourceMap;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+    /// Context of the diagnostic before it
+    Note,
+}
+
+const RESET: &'static str = "\x1b[0m";
+const BOLD: &'static str = "\x1b[1m";
+const RED: &'static str = "\x1b[1;31m";
+const YELLOW: &'static str = "\x1b[1;33m";
+const GREEN: &'static str = "\x1b[1;32m";
+const BLUE: &'static str = "\x1b[1;34m";
+
+/// Columns taken by a tab in the source lines printed
+const TAB_WIDTH: usize = 4;
+
+/// Formats diagnostics along with the line of source they are about, underlined:
+///
+/// ```text
+/// error: unknown variable countr (did you mean counter?)
+///   --> main.ion:14:11
+///    |
+/// 14 |     print(countr)
+///    |           ^^^^^^
+/// ```
+pub struct Renderer<'a> {
+    source_map: &'a SourceMap,
+    /// Whether to color the output with ANSI escape codes
+    color: bool,
+    /// Column at which messages are wrapped
+    width: usize,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(source_map: &'a SourceMap, color: bool, width: usize) -> Self {
+        Renderer {
+            source_map: source_map,
+            color: color,
+            width: width,
+        }
+    }
+
+    pub fn render(&self, severity: Severity, span: &Span, message: &str) -> String {
+        let (label, label_style) = match severity {
+            Severity::Error => ("error", RED),
+            Severity::Warning => ("warning", YELLOW),
+            Severity::Note => ("note", GREEN),
+        };
+
+        let mut out = String::new();
+        out.push_str(&self.paint(label, label_style));
+        out.push_str(&self.paint(": ", BOLD));
+        let indent = label.len() + 2;
+        let lines = wrap(message, std::cmp::max(self.width, indent + 1) - indent);
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                out.push('\n');
+                out.push_str(&" ".repeat(indent));
+            }
+            out.push_str(&self.paint(line, BOLD));
+        }
+        out.push('\n');
+
+        let file = match self.source_map.file(span.file) {
+            Some(file) => file,
+            None => return out,
+        };
+        let gutter = " ".repeat(span.srow.to_string().len());
+        out.push_str(&format!("{}{} {}:{}:{}\n",
+                              gutter,
+                              self.paint("-->", BLUE),
+                              file.name,
+                              span.srow,
+                              span.scol));
+
+        let line = match file.line(span.srow) {
+            Some(line) => line,
+            None => return out,
+        };
+        let bar = self.paint("|", BLUE);
+        out.push_str(&format!("{} {}\n", gutter, bar));
+        out.push_str(&format!("{} {} {}\n",
+                              self.paint(&span.srow.to_string(), BLUE),
+                              bar,
+                              expand_tabs(line)));
+
+        // Spans covering several lines are underlined until the end of the first one
+        let chars: std::vec::Vec<char> = line.chars().collect();
+        let start = std::cmp::min(std::cmp::max(span.scol, 1) as usize - 1, chars.len());
+        let end = if span.erow == span.srow {
+            std::cmp::min(std::cmp::max(span.ecol, 1) as usize - 1, chars.len())
+        } else {
+            chars.len()
+        };
+        let before: String = chars[..start].iter().cloned().collect();
+        let underlined: String = chars[start..std::cmp::max(start, end)].iter().cloned().collect();
+        let carets = std::cmp::max(1, expand_tabs(&underlined).chars().count());
+        out.push_str(&format!("{} {} {}{}\n",
+                              gutter,
+                              bar,
+                              " ".repeat(expand_tabs(&before).chars().count()),
+                              self.paint(&"^".repeat(carets), label_style)));
+
+        out
+    }
+
+    fn paint(&self, text: &str, style: &str) -> String {
+        if self.color {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Lines of at most `width` chars made of the words of the text, except for longer words which
+/// get a line of their own
+fn wrap(text: &str, width: usize) -> std::vec::Vec<String> {
+    let mut lines: std::vec::Vec<String> = vec![];
+    let mut line = String::new();
+    for word in text.split(' ') {
+        let line_length = line.chars().count();
+        if line_length > 0 && line_length + 1 + word.chars().count() > width {
+            lines.push(std::mem::replace(&mut line, String::new()));
+        } else if line_length > 0 {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    lines.push(line);
+
+    lines
+}
+
+fn expand_tabs(line: &str) -> String {
+    line.replace('\t', &" ".repeat(TAB_WIDTH))
+}
+
+/// Whether diagnostics printed on the standard output should be colored: it must be a terminal,
+/// and the `NO_COLOR` environment variable must not be set
+pub fn use_color() -> bool {
+    std::env::var_os("NO_COLOR").is_none() && stdout_is_terminal()
+}
+
+#[cfg(unix)]
+fn stdout_is_terminal() -> bool {
+    extern "C" {
+        fn isatty(fd: std::os::raw::c_int) -> std::os::raw::c_int;
+    }
+
+    unsafe { isatty(1) == 1 }
+}
+
+#[cfg(not(unix))]
+fn stdout_is_terminal() -> bool {
+    false
+}
+
+/// Width of the terminal, from the `COLUMNS` environment variable or the terminal itself, or 80
+pub fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .or_else(stdout_columns)
+        .and_then(|columns| if columns > 0 { Some(columns) } else { None })
+        .unwrap_or(80)
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn stdout_columns() -> Option<usize> {
+    use std::os::raw::{c_int, c_ulong, c_ushort};
+
+    #[repr(C)]
+    struct WinSize {
+        rows: c_ushort,
+        columns: c_ushort,
+        x_pixels: c_ushort,
+        y_pixels: c_ushort,
+    }
+
+    #[cfg(target_os = "linux")]
+    const TIOCGWINSZ: c_ulong = 0x5413;
+    #[cfg(target_os = "macos")]
+    const TIOCGWINSZ: c_ulong = 0x40087468;
+
+    extern "C" {
+        fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    }
+
+    let mut size = WinSize {
+        rows: 0,
+        columns: 0,
+        x_pixels: 0,
+        y_pixels: 0,
+    };
+    if unsafe { ioctl(1, TIOCGWINSZ, &mut size) } == 0 {
+        Some(size.columns as usize)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn stdout_columns() -> Option<usize> {
+    None
+}
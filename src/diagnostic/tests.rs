@@ -0,0 +1,57 @@
+use super::*;
+use lexer::Span;
+use source_map::SourceMap;
+
+fn span(source_map: &SourceMap, src: &str, row: i32, scol: i32, ecol: i32) -> Span {
+    let file = source_map.add_file("main.ion".to_string(), src.to_string());
+    Span {
+        srow: row,
+        scol: scol,
+        erow: row,
+        ecol: ecol,
+        start: 0,
+        end: 0,
+        file: file,
+    }
+}
+
+#[test]
+fn test_render() {
+    let source_map = SourceMap::new();
+    let sp = span(&source_map, "func main() {\n    print(countr)\n}\n", 2, 11, 17);
+    let renderer = Renderer::new(&source_map, false, 80);
+
+    assert_eq!("error: unknown variable countr\n \
+                --> main.ion:2:11\n  \
+                |\n\
+                2 |     print(countr)\n  \
+                |           ^^^^^^\n",
+               renderer.render(Severity::Error, &sp, "unknown variable countr"));
+    assert_eq!("warning: w\n",
+               renderer.render(Severity::Warning, &Span::nil_span(), "w"));
+    assert_eq!("note: n\n", renderer.render(Severity::Note, &Span::nil_span(), "n"));
+}
+
+#[test]
+fn test_render_tabs_and_multiline() {
+    let source_map = SourceMap::new();
+    let mut sp = span(&source_map, "\tvar x: int = f(\n\t1)\n", 1, 15, 3);
+    sp.erow = 2;
+    let rendered = Renderer::new(&source_map, false, 80).render(Severity::Error, &sp, "e");
+
+    assert!(rendered.ends_with("1 |     var x: int = f(\n  |                  ^^\n"),
+            "{}",
+            rendered);
+}
+
+#[test]
+fn test_render_wrap_and_color() {
+    let source_map = SourceMap::new();
+    let renderer = Renderer::new(&source_map, false, 20);
+    assert_eq!("error: a long\n       message that\n       wraps\n",
+               renderer.render(Severity::Error, &Span::nil_span(), "a long message that wraps"));
+
+    let renderer = Renderer::new(&source_map, true, 80);
+    assert_eq!("\x1b[1;31merror\x1b[0m\x1b[1m: \x1b[0m\x1b[1mm\x1b[0m\n",
+               renderer.render(Severity::Error, &Span::nil_span(), "m"));
+}
@@ -0,0 +1,94 @@
+use std;
+use lexer::Span;
+use interpreter::*;
+
+/// Builtins doing an integer operation with a given overflow behavior, whatever the one of the
+/// options
+const INTEGER_BUILTINS: [(&'static str, BinaryOp, Overflow); 9] =
+    [("int::wrapping_add", BinaryOp::Addition, Overflow::Wrap),
+     ("int::wrapping_sub", BinaryOp::Substraction, Overflow::Wrap),
+     ("int::wrapping_mul", BinaryOp::Multiplication, Overflow::Wrap),
+     ("int::saturating_add", BinaryOp::Addition, Overflow::Saturate),
+     ("int::saturating_sub", BinaryOp::Substraction, Overflow::Saturate),
+     ("int::saturating_mul", BinaryOp::Multiplication, Overflow::Saturate),
+     ("int::checked_add", BinaryOp::Addition, Overflow::Error),
+     ("int::checked_sub", BinaryOp::Substraction, Overflow::Error),
+     ("int::checked_mul", BinaryOp::Multiplication, Overflow::Error)];
+
+/// Operation and overflow behavior of the integer builtin called, if `func` is one
+pub fn integer_builtin(func: &Expression) -> Option<(BinaryOp, Overflow)> {
+    let name = match func.expr {
+        Expression_::Variable(ref p) => p.to_string(),
+        _ => return None,
+    };
+
+    INTEGER_BUILTINS.iter()
+        .find(|&&(builtin, _, _)| builtin == name)
+        .map(|&(_, ref binop, overflow)| (binop.clone(), overflow))
+}
+
+/// Result of an arithmetic operator on ints. When it doesn't fit in an `int`, it wraps around,
+/// saturates at the bounds, or is an error, depending on `overflow`.
+pub fn integer_op<'a>(binop: &BinaryOp,
+                      i1: i64,
+                      i2: i64,
+                      overflow: Overflow,
+                      span: Span)
+                      -> Result<'a, i64> {
+    match *binop {
+        BinaryOp::Division if i2 == 0 => {
+            return Err(Error {
+                kind: ErrorKind::DivisionByZero,
+                span: span,
+                backtrace: vec![],
+            })
+        }
+        BinaryOp::Modulo if i2 == 0 => {
+            return Err(Error {
+                kind: ErrorKind::ModuloByZero,
+                span: span,
+                backtrace: vec![],
+            })
+        }
+        // `std::i64::MIN % -1` overflows in Rust, but the remainder is 0
+        BinaryOp::Modulo if i2 == -1 => return Ok(0),
+        _ => (),
+    }
+
+    let checked = match *binop {
+        BinaryOp::Addition => i1.checked_add(i2),
+        BinaryOp::Substraction => i1.checked_sub(i2),
+        BinaryOp::Multiplication => i1.checked_mul(i2),
+        BinaryOp::Division => i1.checked_div(i2),
+        BinaryOp::Modulo => i1.checked_rem(i2),
+        _ => unreachable!(),
+    };
+
+    match (checked, overflow) {
+        (Some(i), _) => Ok(i),
+        (None, Overflow::Wrap) => {
+            Ok(match *binop {
+                BinaryOp::Addition => i1.wrapping_add(i2),
+                BinaryOp::Substraction => i1.wrapping_sub(i2),
+                BinaryOp::Multiplication => i1.wrapping_mul(i2),
+                _ => i1.wrapping_div(i2),
+            })
+        }
+        (None, Overflow::Saturate) => {
+            Ok(match *binop {
+                BinaryOp::Addition => i1.saturating_add(i2),
+                BinaryOp::Substraction => i1.saturating_sub(i2),
+                BinaryOp::Multiplication => i1.saturating_mul(i2),
+                // Only `std::i64::MIN / -1` overflows
+                _ => std::i64::MAX,
+            })
+        }
+        (None, Overflow::Error) => {
+            Err(Error {
+                kind: ErrorKind::IntegerOverflow,
+                span: span,
+                backtrace: vec![],
+            })
+        }
+    }
+}
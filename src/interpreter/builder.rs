@@ -0,0 +1,18 @@
+use interpreter::*;
+
+impl<'a> Interpreter<'a> {
+    pub fn new_builder(&self) -> Value<'a> {
+        let mut builders = self.builders.borrow_mut();
+        builders.push(String::new());
+
+        Value::Builder(builders.len() - 1)
+    }
+
+    pub fn builder_append(&self, builder: usize, s: &str) {
+        self.builders.borrow_mut()[builder].push_str(s);
+    }
+
+    pub fn builder_content(&self, builder: usize) -> String {
+        self.builders.borrow()[builder].clone()
+    }
+}
@@ -1,6 +1,7 @@
 use parser::ast::*;
 use lexer::Span;
 use interpreter::*;
+use interpreter::arithmetic::integer_op;
 use std::io;
 use std::io::prelude::*;
 
@@ -14,6 +15,7 @@ impl<'a> Interpreter<'a> {
             return Err(Error {
                 kind: ErrorKind::InvalidArgCount,
                 span: span,
+                backtrace: vec![],
             });
         };
 
@@ -25,9 +27,17 @@ impl<'a> Interpreter<'a> {
             Value::Struct(_, s) => print!("{:?}", s),
             Value::Array(_, a) => print!("{:?}", a),
             Value::Map(_, _, m) => print!("{:?}", m),
+            Value::Set(_, s) => print!("{:?}", s),
+            Value::Bytes(b) => print!("{:?}", b),
             Value::Reference(v) => print!("ref {:?}", v),
             Value::MutReference(v) => print!("mutref {:?}", v),
             Value::Func(_, f) => print!("{:?}", f),
+            Value::Task(t) => print!("task {}", t),
+            Value::Chan(_, c) => print!("chan {}", c),
+            Value::Mutex(m) => print!("mutex {}", m),
+            Value::WaitGroup(w) => print!("waitgroup {}", w),
+            Value::Atomic(a) => print!("atomic {}", self.atomic_load(a)),
+            Value::Builder(b) => print!("builder {}", b),
             Value::Nil => print!("nil"),
         };
 
@@ -39,6 +49,7 @@ impl<'a> Interpreter<'a> {
             return Err(Error {
                 kind: ErrorKind::InvalidArgCount,
                 span: span,
+                backtrace: vec![],
             });
         };
 
@@ -53,4 +64,806 @@ impl<'a> Interpreter<'a> {
 
         Ok(Value::String(line))
     }
+
+    pub fn builtin_join(&'a self,
+                        context: *mut InterpreterContext<'a>,
+                        args: &[Box<Expression>],
+                        span: Span)
+                        -> Result<Value> {
+        if args.len() != 1 {
+            return Err(Error {
+                kind: ErrorKind::InvalidArgCount,
+                span: span,
+                backtrace: vec![],
+            });
+        };
+
+        let arg = args.get(0).unwrap();
+        match try!(self.value_from_expression(context, arg)) {
+            Value::Task(t) => self.join_task(t, span),
+            other => {
+                let other_type = try!(Self::type_from_value(&other, arg.span.clone()));
+                Err(Error {
+                    kind: ErrorKind::MismatchedTypes(Type::Task, other_type),
+                    span: arg.span.clone(),
+                    backtrace: vec![],
+                })
+            }
+        }
+    }
+
+    pub fn builtin_chan_new(&self, args: &[Box<Expression>], span: Span) -> Result<Value<'a>> {
+        if !args.is_empty() {
+            return Err(Error {
+                kind: ErrorKind::InvalidArgCount,
+                span: span,
+                backtrace: vec![],
+            });
+        };
+
+        Ok(self.new_chan(Type::None))
+    }
+
+    pub fn builtin_send(&'a self,
+                        context: *mut InterpreterContext<'a>,
+                        args: &[Box<Expression>],
+                        span: Span)
+                        -> Result<Value> {
+        if args.len() != 2 {
+            return Err(Error {
+                kind: ErrorKind::InvalidArgCount,
+                span: span,
+                backtrace: vec![],
+            });
+        };
+
+        let (chan_type, chan) = try!(self.chan_from_argument(context, args.get(0).unwrap()));
+        let arg = args.get(1).unwrap();
+        let value = try!(self.value_from_expression(context, arg));
+        let value_type = try!(Self::type_from_value(&value, arg.span.clone()));
+        if value_type != chan_type {
+            return Err(Error {
+                kind: ErrorKind::MismatchedTypes(chan_type, value_type),
+                span: arg.span.clone(),
+                backtrace: vec![],
+            });
+        }
+
+        try!(self.chan_send(chan, value, span));
+
+        Ok(Value::Nil)
+    }
+
+    pub fn builtin_recv(&'a self,
+                        context: *mut InterpreterContext<'a>,
+                        args: &[Box<Expression>],
+                        span: Span)
+                        -> Result<Value> {
+        if args.len() != 1 {
+            return Err(Error {
+                kind: ErrorKind::InvalidArgCount,
+                span: span,
+                backtrace: vec![],
+            });
+        };
+
+        let (_, chan) = try!(self.chan_from_argument(context, args.get(0).unwrap()));
+        match try!(self.chan_recv(chan, span.clone())) {
+            Some(value) => Ok(value),
+            None => {
+                Err(Error {
+                    kind: ErrorKind::CannotRecvOnClosedChan,
+                    span: span,
+                    backtrace: vec![],
+                })
+            }
+        }
+    }
+
+    pub fn builtin_close(&'a self,
+                         context: *mut InterpreterContext<'a>,
+                         args: &[Box<Expression>],
+                         span: Span)
+                         -> Result<Value> {
+        if args.len() != 1 {
+            return Err(Error {
+                kind: ErrorKind::InvalidArgCount,
+                span: span,
+                backtrace: vec![],
+            });
+        };
+
+        let (_, chan) = try!(self.chan_from_argument(context, args.get(0).unwrap()));
+        try!(self.chan_close(chan, span));
+
+        Ok(Value::Nil)
+    }
+
+    fn chan_from_argument(&'a self,
+                          context: *mut InterpreterContext<'a>,
+                          arg: &Expression)
+                          -> Result<(Type, usize)> {
+        match try!(self.value_from_expression(context, arg)) {
+            Value::Chan(chan_type, chan) => Ok((chan_type, chan)),
+            other => {
+                let other_type = try!(Self::type_from_value(&other, arg.span.clone()));
+                Err(Error {
+                    kind: ErrorKind::MismatchedTypes(Type::Chan(Box::new(Type::None)),
+                                                     other_type),
+                    span: arg.span.clone(),
+                    backtrace: vec![],
+                })
+            }
+        }
+    }
+
+    pub fn builtin_mutex_new(&'a self, args: &[Box<Expression>], span: Span) -> Result<Value> {
+        try!(Self::check_arg_count(args, 0, span));
+
+        Ok(self.new_mutex())
+    }
+
+    pub fn builtin_lock(&'a self,
+                        context: *mut InterpreterContext<'a>,
+                        args: &[Box<Expression>],
+                        span: Span)
+                        -> Result<Value> {
+        try!(Self::check_arg_count(args, 1, span.clone()));
+        let mutex = try!(self.handle_from_argument(context, &args[0], Type::Mutex));
+        try!(self.mutex_lock(mutex, span));
+
+        Ok(Value::Nil)
+    }
+
+    pub fn builtin_unlock(&'a self,
+                          context: *mut InterpreterContext<'a>,
+                          args: &[Box<Expression>],
+                          span: Span)
+                          -> Result<Value> {
+        try!(Self::check_arg_count(args, 1, span.clone()));
+        let mutex = try!(self.handle_from_argument(context, &args[0], Type::Mutex));
+        try!(self.mutex_unlock(mutex, span));
+
+        Ok(Value::Nil)
+    }
+
+    pub fn builtin_waitgroup_new(&'a self, args: &[Box<Expression>], span: Span) -> Result<Value> {
+        try!(Self::check_arg_count(args, 0, span));
+
+        Ok(self.new_wait_group())
+    }
+
+    pub fn builtin_add(&'a self,
+                       context: *mut InterpreterContext<'a>,
+                       args: &[Box<Expression>],
+                       span: Span)
+                       -> Result<Value> {
+        try!(Self::check_arg_count(args, 2, span.clone()));
+        let wait_group = try!(self.handle_from_argument(context, &args[0], Type::WaitGroup));
+        let delta = try!(self.int_from_argument(context, &args[1]));
+        try!(self.wait_group_add(wait_group, delta, span));
+
+        Ok(Value::Nil)
+    }
+
+    pub fn builtin_done(&'a self,
+                        context: *mut InterpreterContext<'a>,
+                        args: &[Box<Expression>],
+                        span: Span)
+                        -> Result<Value> {
+        try!(Self::check_arg_count(args, 1, span.clone()));
+        let wait_group = try!(self.handle_from_argument(context, &args[0], Type::WaitGroup));
+        try!(self.wait_group_add(wait_group, -1, span));
+
+        Ok(Value::Nil)
+    }
+
+    pub fn builtin_wait(&'a self,
+                        context: *mut InterpreterContext<'a>,
+                        args: &[Box<Expression>],
+                        span: Span)
+                        -> Result<Value> {
+        try!(Self::check_arg_count(args, 1, span.clone()));
+        let wait_group = try!(self.handle_from_argument(context, &args[0], Type::WaitGroup));
+        try!(self.wait_group_wait(wait_group, span));
+
+        Ok(Value::Nil)
+    }
+
+    pub fn builtin_atomic_new(&'a self,
+                              context: *mut InterpreterContext<'a>,
+                              args: &[Box<Expression>],
+                              span: Span)
+                              -> Result<Value> {
+        try!(Self::check_arg_count(args, 1, span));
+        let value = try!(self.int_from_argument(context, &args[0]));
+
+        Ok(self.new_atomic(value))
+    }
+
+    pub fn builtin_load(&'a self,
+                        context: *mut InterpreterContext<'a>,
+                        args: &[Box<Expression>],
+                        span: Span)
+                        -> Result<Value> {
+        try!(Self::check_arg_count(args, 1, span));
+        let atomic = try!(self.handle_from_argument(context, &args[0], Type::Atomic));
+
+        Ok(Value::Integer(self.atomic_load(atomic)))
+    }
+
+    pub fn builtin_store(&'a self,
+                         context: *mut InterpreterContext<'a>,
+                         args: &[Box<Expression>],
+                         span: Span)
+                         -> Result<Value> {
+        try!(Self::check_arg_count(args, 2, span));
+        let atomic = try!(self.handle_from_argument(context, &args[0], Type::Atomic));
+        let value = try!(self.int_from_argument(context, &args[1]));
+        self.atomic_store(atomic, value);
+
+        Ok(Value::Nil)
+    }
+
+    pub fn builtin_fetch_add(&'a self,
+                             context: *mut InterpreterContext<'a>,
+                             args: &[Box<Expression>],
+                             span: Span)
+                             -> Result<Value> {
+        try!(Self::check_arg_count(args, 2, span));
+        let atomic = try!(self.handle_from_argument(context, &args[0], Type::Atomic));
+        let delta = try!(self.int_from_argument(context, &args[1]));
+
+        Ok(Value::Integer(self.atomic_fetch_add(atomic, delta)))
+    }
+
+    pub fn builtin_integer_op(&'a self,
+                              context: *mut InterpreterContext<'a>,
+                              args: &[Box<Expression>],
+                              span: Span,
+                              binop: &BinaryOp,
+                              overflow: Overflow)
+                              -> Result<Value> {
+        try!(Self::check_arg_count(args, 2, span.clone()));
+        let i1 = try!(self.int_from_argument(context, &args[0]));
+        let i2 = try!(self.int_from_argument(context, &args[1]));
+
+        Ok(Value::Integer(try!(integer_op(binop, i1, i2, overflow, span))))
+    }
+
+    pub fn builtin_read_file(&'a self,
+                             context: *mut InterpreterContext<'a>,
+                             args: &[Box<Expression>],
+                             span: Span)
+                             -> Result<Value> {
+        try!(self.check_io_allowed("io::read_file", span.clone()));
+        try!(Self::check_arg_count(args, 1, span));
+        let path = try!(self.string_from_argument(context, &args[0]));
+
+        Ok(self.read_file_async(path))
+    }
+
+    pub fn builtin_request(&'a self,
+                           context: *mut InterpreterContext<'a>,
+                           args: &[Box<Expression>],
+                           span: Span)
+                           -> Result<Value> {
+        try!(self.check_io_allowed("net::request", span.clone()));
+        try!(Self::check_arg_count(args, 2, span));
+        let address = try!(self.string_from_argument(context, &args[0]));
+        let request = try!(self.string_from_argument(context, &args[1]));
+
+        Ok(self.request_async(address, request))
+    }
+
+    pub fn builtin_builder_new(&'a self, args: &[Box<Expression>], span: Span) -> Result<Value> {
+        try!(Self::check_arg_count(args, 0, span));
+
+        Ok(self.new_builder())
+    }
+
+    pub fn builtin_append(&'a self,
+                          context: *mut InterpreterContext<'a>,
+                          args: &[Box<Expression>],
+                          span: Span)
+                          -> Result<Value> {
+        try!(Self::check_arg_count(args, 2, span.clone()));
+        let builder = try!(self.handle_from_argument(context, &args[0], Type::Builder));
+        let s = try!(self.string_from_argument(context, &args[1]));
+        try!(self.allocate_bytes(s.len(), &span));
+        self.builder_append(builder, &s);
+
+        Ok(Value::Nil)
+    }
+
+    pub fn builtin_build(&'a self,
+                         context: *mut InterpreterContext<'a>,
+                         args: &[Box<Expression>],
+                         span: Span)
+                         -> Result<Value> {
+        try!(Self::check_arg_count(args, 1, span));
+        let builder = try!(self.handle_from_argument(context, &args[0], Type::Builder));
+
+        Ok(Value::String(self.builder_content(builder)))
+    }
+
+    /// Aborts the execution with the message
+    pub fn builtin_panic(&'a self,
+                         context: *mut InterpreterContext<'a>,
+                         args: &[Box<Expression>],
+                         span: Span)
+                         -> Result<Value> {
+        try!(Self::check_arg_count(args, 1, span.clone()));
+        let message = try!(self.string_from_argument(context, &args[0]));
+
+        Err(Error {
+            kind: ErrorKind::Panic(message),
+            span: span,
+            backtrace: vec![],
+        })
+    }
+
+    /// Int written in decimal in the string, with an optional sign
+    pub fn builtin_int_parse(&'a self,
+                             context: *mut InterpreterContext<'a>,
+                             args: &[Box<Expression>],
+                             span: Span)
+                             -> Result<Value> {
+        try!(Self::check_arg_count(args, 1, span));
+        let s = try!(self.string_from_argument(context, &args[0]));
+
+        match s.parse::<i64>() {
+            Ok(i) => Ok(Value::Integer(i)),
+            Err(_) => {
+                Err(Error {
+                    kind: ErrorKind::InvalidIntString(s),
+                    span: args[0].span.clone(),
+                    backtrace: vec![],
+                })
+            }
+        }
+    }
+
+    /// Int, bool or char written as in a literal, or the string itself
+    pub fn builtin_to_string(&'a self,
+                             context: *mut InterpreterContext<'a>,
+                             args: &[Box<Expression>],
+                             span: Span)
+                             -> Result<Value> {
+        try!(Self::check_arg_count(args, 1, span));
+
+        match try!(self.value_from_expression(context, &args[0])) {
+            Value::String(s) => Ok(Value::String(s)),
+            Value::Integer(i) => Ok(Value::String(i.to_string())),
+            Value::Bool(b) => Ok(Value::String(b.to_string())),
+            Value::Char(c) => Ok(Value::String(c.to_string())),
+            other => {
+                let other_type = try!(Self::type_from_value(&other, args[0].span.clone()));
+                Err(Error {
+                    kind: ErrorKind::CannotConvertToString(other_type),
+                    span: args[0].span.clone(),
+                    backtrace: vec![],
+                })
+            }
+        }
+    }
+
+    /// UTF-8 encoding of the string
+    pub fn builtin_bytes_from_string(&'a self,
+                                     context: *mut InterpreterContext<'a>,
+                                     args: &[Box<Expression>],
+                                     span: Span)
+                                     -> Result<Value> {
+        try!(Self::check_arg_count(args, 1, span));
+        let s = try!(self.string_from_argument(context, &args[0]));
+
+        Ok(Value::Bytes(s.into_bytes()))
+    }
+
+    /// String the bytes are the UTF-8 encoding of
+    pub fn builtin_bytes_to_string(&'a self,
+                                   context: *mut InterpreterContext<'a>,
+                                   args: &[Box<Expression>],
+                                   span: Span)
+                                   -> Result<Value> {
+        try!(Self::check_arg_count(args, 1, span));
+        let bytes = match try!(self.value_from_expression(context, &args[0])) {
+            Value::Bytes(b) => b,
+            other => {
+                let other_type = try!(Self::type_from_value(&other, args[0].span.clone()));
+                return Err(Error {
+                    kind: ErrorKind::MismatchedTypes(Type::Bytes, other_type),
+                    span: args[0].span.clone(),
+                    backtrace: vec![],
+                });
+            }
+        };
+
+        match String::from_utf8(bytes) {
+            Ok(s) => Ok(Value::String(s)),
+            Err(_) => {
+                Err(Error {
+                    kind: ErrorKind::InvalidUtf8,
+                    span: args[0].span.clone(),
+                    backtrace: vec![],
+                })
+            }
+        }
+    }
+
+    /// Adds the item to the set, evaluating to whether it was not in it yet
+    pub fn builtin_set_insert(&'a self,
+                              context: *mut InterpreterContext<'a>,
+                              args: &[Box<Expression>],
+                              span: Span)
+                              -> Result<Value> {
+        try!(Self::check_arg_count(args, 2, span.clone()));
+        let set = try!(self.set_from_argument(context, &args[0], true));
+        let item = try!(self.set_item_from_argument(context, &args[1], set));
+        try!(self.allocate_bytes(std::mem::size_of::<Value>(), &span));
+
+        match unsafe { &mut *set } {
+            &mut Value::Set(_, ref mut s) => Ok(Value::Bool(s.set.insert(item))),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Removes the item from the set, evaluating to whether it was in it
+    pub fn builtin_set_remove(&'a self,
+                              context: *mut InterpreterContext<'a>,
+                              args: &[Box<Expression>],
+                              span: Span)
+                              -> Result<Value> {
+        try!(Self::check_arg_count(args, 2, span));
+        let set = try!(self.set_from_argument(context, &args[0], true));
+        let item = try!(self.set_item_from_argument(context, &args[1], set));
+
+        match unsafe { &mut *set } {
+            &mut Value::Set(_, ref mut s) => Ok(Value::Bool(s.set.remove(&item))),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn builtin_set_contains(&'a self,
+                                context: *mut InterpreterContext<'a>,
+                                args: &[Box<Expression>],
+                                span: Span)
+                                -> Result<Value> {
+        try!(Self::check_arg_count(args, 2, span));
+        let set = try!(self.set_from_argument(context, &args[0], false));
+        let item = try!(self.set_item_from_argument(context, &args[1], set));
+
+        match unsafe { &*set } {
+            &Value::Set(_, ref s) => Ok(Value::Bool(s.set.contains(&item))),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Type of the value, written as in declarations
+    pub fn builtin_typeof(&'a self,
+                          context: *mut InterpreterContext<'a>,
+                          args: &[Box<Expression>],
+                          span: Span)
+                          -> Result<Value> {
+        try!(Self::check_arg_count(args, 1, span));
+        let value = try!(self.value_from_expression(context, &args[0]));
+        let value_type = try!(Self::type_from_value(&value, args[0].span.clone()));
+
+        Ok(Value::String(value_type.to_string()))
+    }
+
+    /// Names of the fields of the struct, in declaration order
+    pub fn builtin_fields(&'a self,
+                          context: *mut InterpreterContext<'a>,
+                          args: &[Box<Expression>],
+                          span: Span)
+                          -> Result<Value> {
+        try!(Self::check_arg_count(args, 1, span));
+        let path = match try!(self.value_from_expression(context, &args[0])) {
+            Value::Struct(path, _) => path,
+            _ => {
+                return Err(Error {
+                    kind: ErrorKind::CannotAccessFieldOnNonStruct,
+                    span: args[0].span.clone(),
+                    backtrace: vec![],
+                })
+            }
+        };
+
+        let struct_decl = match self.structs.get(&path) {
+            Some(s) => s,
+            None => return Err(self.unknown_struct(&path, args[0].span.clone())),
+        };
+
+        Ok(Value::Array(Type::String,
+                        struct_decl.fields
+                            .iter()
+                            .map(|field| Value::String(field.name.clone()))
+                            .collect()))
+    }
+
+    /// Prints the value on several lines, the items of arrays, maps, sets and structs indented
+    /// under the type of their container
+    pub fn builtin_dump(&'a self,
+                        context: *mut InterpreterContext<'a>,
+                        args: &[Box<Expression>],
+                        span: Span)
+                        -> Result<Value> {
+        try!(Self::check_arg_count(args, 1, span));
+        let value = try!(self.value_from_expression(context, &args[0]));
+
+        let mut out = String::new();
+        self.dump_value(&value, 0, &mut vec![], &mut out);
+        println!("{}", out);
+
+        Ok(Value::Nil)
+    }
+
+    /// The value written as Ion source, e.g. `"key"` or `'c'`, as printed by `print`
+    pub fn value_source(&self, value: &Value<'a>) -> String {
+        let mut out = String::new();
+        self.dump_value(value, 0, &mut vec![], &mut out);
+        out
+    }
+
+    /// Appends the rendering of the value to `out`, its items being indented one level deeper
+    /// than `indent`. `followed` holds the values pointed to by the references being rendered, a
+    /// reference back to one of them being written `<cycle>` rather than followed again.
+    fn dump_value(&self,
+                  value: *const Value<'a>,
+                  indent: usize,
+                  followed: &mut std::vec::Vec<*const Value<'a>>,
+                  out: &mut String) {
+        let type_name = match Self::type_from_value(value, Span::nil_span()) {
+            Ok(t) => t.to_string(),
+            Err(_) => String::new(),
+        };
+
+        match unsafe { &*value } {
+            &Value::Nil => out.push_str("nil"),
+            &Value::String(ref s) => out.push_str(&format!("{:?}", s)),
+            &Value::Integer(i) => out.push_str(&i.to_string()),
+            &Value::Bool(b) => out.push_str(&b.to_string()),
+            &Value::Char(c) => out.push_str(&format!("{:?}", c)),
+            &Value::Struct(ref path, ref s) => {
+                let names: std::vec::Vec<&String> = match self.structs.get(path) {
+                    Some(struct_decl) => {
+                        struct_decl.fields.iter().map(|field| &field.name).collect()
+                    }
+                    None => {
+                        let mut names: std::vec::Vec<&String> = s.map.keys().collect();
+                        names.sort();
+                        names
+                    }
+                };
+                let items = names.into_iter()
+                    .filter_map(|name| {
+                        s.map.get(name).map(|v| (format!("{}: ", name), Some(v as *const Value)))
+                    })
+                    .collect();
+                self.dump_items(&type_name, ("{", "}"), items, indent, followed, out);
+            }
+            &Value::Array(_, ref a) => {
+                let items = a.iter().map(|v| (String::new(), Some(v as *const Value))).collect();
+                self.dump_items(&type_name, ("[", "]"), items, indent, followed, out);
+            }
+            &Value::Map(_, _, ref m) => {
+                let mut items: std::vec::Vec<(String, Option<*const Value>)> = m.map
+                    .iter()
+                    .map(|(k, v)| {
+                        let mut key = String::new();
+                        self.dump_value(k, indent + 1, followed, &mut key);
+                        (format!("{}: ", key), Some(v as *const Value))
+                    })
+                    .collect();
+                items.sort_by(|a, b| a.0.cmp(&b.0));
+                self.dump_items(&type_name, ("{", "}"), items, indent, followed, out);
+            }
+            &Value::Set(_, ref s) => {
+                let mut items: std::vec::Vec<String> = s.set
+                    .iter()
+                    .map(|v| {
+                        let mut item = String::new();
+                        self.dump_value(v, indent + 1, followed, &mut item);
+                        item
+                    })
+                    .collect();
+                items.sort();
+                let items = items.into_iter().map(|item| (item, None)).collect();
+                self.dump_items(&type_name, ("{", "}"), items, indent, followed, out);
+            }
+            &Value::Bytes(ref b) => out.push_str(&format!("bytes {:?}", b)),
+            &Value::Reference(r) => self.dump_reference("&", r, indent, followed, out),
+            &Value::MutReference(r) => self.dump_reference("@", r, indent, followed, out),
+            &Value::Func(_, ref f) => out.push_str(&format!("{} {}", type_name, f.name)),
+            &Value::Task(t) => out.push_str(&format!("task {}", t)),
+            &Value::Chan(_, c) => out.push_str(&format!("{} {}", type_name, c)),
+            &Value::Mutex(m) => out.push_str(&format!("mutex {}", m)),
+            &Value::WaitGroup(w) => out.push_str(&format!("waitgroup {}", w)),
+            &Value::Atomic(a) => out.push_str(&format!("atomic {}", self.atomic_load(a))),
+            &Value::Builder(b) => out.push_str(&format!("builder {}", b)),
+        }
+    }
+
+    /// Appends the items of a container, each on its own line: its key, if any, then its value,
+    /// unless it is already rendered as its key like the items of sets
+    fn dump_items(&self,
+                  type_name: &str,
+                  (open, close): (&str, &str),
+                  items: std::vec::Vec<(String, Option<*const Value<'a>>)>,
+                  indent: usize,
+                  followed: &mut std::vec::Vec<*const Value<'a>>,
+                  out: &mut String) {
+        if !type_name.is_empty() {
+            out.push_str(type_name);
+            out.push(' ');
+        }
+        out.push_str(open);
+        if items.is_empty() {
+            out.push_str(close);
+            return;
+        }
+
+        let item_indent = "    ".repeat(indent + 1);
+        for (key, item) in items {
+            out.push('\n');
+            out.push_str(&item_indent);
+            out.push_str(&key);
+            if let Some(item) = item {
+                self.dump_value(item, indent + 1, followed, out);
+            }
+        }
+        out.push('\n');
+        out.push_str(&"    ".repeat(indent));
+        out.push_str(close);
+    }
+
+    fn dump_reference(&self,
+                      symbol: &str,
+                      target: *const Value<'a>,
+                      indent: usize,
+                      followed: &mut std::vec::Vec<*const Value<'a>>,
+                      out: &mut String) {
+        out.push_str(symbol);
+        if followed.contains(&target) {
+            out.push_str("<cycle>");
+            return;
+        }
+
+        followed.push(target);
+        self.dump_value(target, indent, followed, out);
+        followed.pop();
+    }
+
+    fn check_arg_count(args: &[Box<Expression>], count: usize, span: Span) -> Result<'a, ()> {
+        if args.len() != count {
+            return Err(Error {
+                kind: ErrorKind::InvalidArgCount,
+                span: span,
+                backtrace: vec![],
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Handle of the mutex, wait group, atomic integer or string builder given as argument
+    fn handle_from_argument(&'a self,
+                            context: *mut InterpreterContext<'a>,
+                            arg: &Expression,
+                            expected_type: Type)
+                            -> Result<usize> {
+        let value = try!(self.value_from_expression(context, arg));
+        match (value, &expected_type) {
+            (Value::Mutex(handle), &Type::Mutex) |
+            (Value::WaitGroup(handle), &Type::WaitGroup) |
+            (Value::Atomic(handle), &Type::Atomic) |
+            (Value::Builder(handle), &Type::Builder) => Ok(handle),
+            (other, _) => {
+                let other_type = try!(Self::type_from_value(&other, arg.span.clone()));
+                Err(Error {
+                    kind: ErrorKind::MismatchedTypes(expected_type.clone(), other_type),
+                    span: arg.span.clone(),
+                    backtrace: vec![],
+                })
+            }
+        }
+    }
+
+    /// Set a reference given as argument points to, the reference having to be mutable if
+    /// `mutable` is set so that the set can be changed in place
+    fn set_from_argument(&'a self,
+                         context: *mut InterpreterContext<'a>,
+                         arg: &Expression,
+                         mutable: bool)
+                         -> Result<*mut Value<'a>> {
+        let expected_type = Type::Set(Box::new(Type::None));
+        let value = try!(self.value_from_expression(context, arg));
+        let set = match value {
+            Value::MutReference(p) => p,
+            Value::Reference(p) if !mutable => p as *mut Value<'a>,
+            other => {
+                let other_type = try!(Self::type_from_value(&other, arg.span.clone()));
+                let expected_type = if mutable {
+                    Type::MutReference(Box::new(expected_type))
+                } else {
+                    Type::Reference(Box::new(expected_type))
+                };
+                return Err(Error {
+                    kind: ErrorKind::MismatchedTypes(expected_type, other_type),
+                    span: arg.span.clone(),
+                    backtrace: vec![],
+                });
+            }
+        };
+
+        match unsafe { &*set } {
+            &Value::Set(_, _) => Ok(set),
+            other => {
+                let other_type = try!(Self::type_from_value(other, arg.span.clone()));
+                Err(Error {
+                    kind: ErrorKind::MismatchedTypes(expected_type, other_type),
+                    span: arg.span.clone(),
+                    backtrace: vec![],
+                })
+            }
+        }
+    }
+
+    /// Item given as argument, which must have the item type of the set
+    fn set_item_from_argument(&'a self,
+                              context: *mut InterpreterContext<'a>,
+                              arg: &Expression,
+                              set: *mut Value<'a>)
+                              -> Result<Value> {
+        let set_type = match unsafe { &*set } {
+            &Value::Set(ref t, _) => t.clone(),
+            _ => unreachable!(),
+        };
+        let item = try!(self.value_from_expression(context, arg));
+        let item_type = try!(Self::type_from_value(&item, arg.span.clone()));
+        if item_type != set_type {
+            return Err(Error {
+                kind: ErrorKind::MismatchedTypes(set_type, item_type),
+                span: arg.span.clone(),
+                backtrace: vec![],
+            });
+        }
+
+        Ok(item)
+    }
+
+    fn string_from_argument(&'a self,
+                            context: *mut InterpreterContext<'a>,
+                            arg: &Expression)
+                            -> Result<String> {
+        match try!(self.value_from_expression(context, arg)) {
+            Value::String(s) => Ok(s),
+            other => {
+                let other_type = try!(Self::type_from_value(&other, arg.span.clone()));
+                Err(Error {
+                    kind: ErrorKind::MismatchedTypes(Type::String, other_type),
+                    span: arg.span.clone(),
+                    backtrace: vec![],
+                })
+            }
+        }
+    }
+
+    fn int_from_argument(&'a self,
+                         context: *mut InterpreterContext<'a>,
+                         arg: &Expression)
+                         -> Result<i64> {
+        match try!(self.value_from_expression(context, arg)) {
+            Value::Integer(i) => Ok(i),
+            other => {
+                let other_type = try!(Self::type_from_value(&other, arg.span.clone()));
+                Err(Error {
+                    kind: ErrorKind::MismatchedTypes(Type::Int, other_type),
+                    span: arg.span.clone(),
+                    backtrace: vec![],
+                })
+            }
+        }
+    }
 }
@@ -0,0 +1,84 @@
+use std;
+use parser::ast::*;
+use lexer::Span;
+use interpreter::*;
+
+/// Queue of values sent by tasks, made by `chan::new()`. Sending never blocks; receiving from an
+/// empty channel blocks the task until another one sends a value or closes the channel.
+#[derive(Debug)]
+pub struct Channel<'a> {
+    values: std::collections::VecDeque<Value<'a>>,
+    closed: bool,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new_chan(&self, chan_type: Type) -> Value<'a> {
+        let mut channels = self.channels.borrow_mut();
+        channels.push(Channel {
+            values: std::collections::VecDeque::new(),
+            closed: false,
+        });
+
+        Value::Chan(chan_type, channels.len() - 1)
+    }
+
+    /// Channels made by `chan::new()` have no type until they initialize a variable or a
+    /// parameter, whose type they take
+    pub fn with_chan_type(value: Value<'a>, expected_type: &Type) -> Value<'a> {
+        match (value, expected_type) {
+            (Value::Chan(Type::None, chan), &Type::Chan(ref chan_type)) => {
+                Value::Chan((**chan_type).clone(), chan)
+            }
+            (value, _) => value,
+        }
+    }
+
+    pub fn chan_send(&self, chan: usize, value: Value<'a>, span: Span) -> Result<()> {
+        let mut channels = self.channels.borrow_mut();
+        if channels[chan].closed {
+            return Err(Error {
+                kind: ErrorKind::CannotSendOnClosedChan,
+                span: span,
+                backtrace: vec![],
+            });
+        }
+
+        channels[chan].values.push_back(value);
+        self.made_progress();
+
+        Ok(())
+    }
+
+    /// Next value of the channel, `None` once it is closed and every value has been received. This
+    /// is a deadlock if no other task can run.
+    pub fn chan_recv(&'a self, chan: usize, span: Span) -> Result<Option<Value>> {
+        loop {
+            {
+                let mut channels = self.channels.borrow_mut();
+                if let Some(value) = channels[chan].values.pop_front() {
+                    return Ok(Some(value));
+                } else if channels[chan].closed {
+                    return Ok(None);
+                }
+            }
+
+            try!(self.block(ErrorKind::ChanDeadlock, span.clone()));
+        }
+    }
+
+    pub fn chan_close(&self, chan: usize, span: Span) -> Result<()> {
+        let mut channels = self.channels.borrow_mut();
+        if channels[chan].closed {
+            return Err(Error {
+                kind: ErrorKind::CannotCloseClosedChan,
+                span: span,
+                backtrace: vec![],
+            });
+        }
+
+        channels[chan].closed = true;
+        self.made_progress();
+
+        Ok(())
+    }
+}
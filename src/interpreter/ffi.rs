@@ -0,0 +1,185 @@
+use std;
+use std::ffi::CStr;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::os::raw::c_void;
+use libloading;
+use libloading::Library;
+use lexer::Span;
+use interpreter::*;
+
+/// Arguments of C functions are all passed in integer registers, of which there are 6 on x86-64
+const MAX_EXTERN_PARAMS: usize = 6;
+
+impl<'a> Interpreter<'a> {
+    /// Calls a C function, converting ints to `long`s, bools to `int`s, chars to `uint32_t`s and
+    /// strings to nul-terminated `char*`s. Strings returned by the function are copied.
+    pub fn execute_func_call_extern(&'a self,
+                                    context: *mut InterpreterContext<'a>,
+                                    func_decl: &FuncDeclData,
+                                    args: &[Box<Expression>],
+                                    span: Span)
+                                    -> Result<Value> {
+        try!(self.check_io_allowed(&func_decl.name, span.clone()));
+        try!(Self::check_extern_signature(func_decl));
+
+        if args.len() != func_decl.parameters.len() {
+            return Err(Error {
+                kind: ErrorKind::InvalidArgCount,
+                span: span,
+                backtrace: vec![],
+            });
+        }
+
+        // The function only gets pointers to the strings, which must outlive the call
+        let mut strings: std::vec::Vec<CString> = vec![];
+        let mut c_args: std::vec::Vec<i64> = vec![];
+        for (param, arg) in func_decl.parameters.iter().zip(args) {
+            let c_arg = match (try!(self.value_from_expression(context, arg)), &param.param_type) {
+                (Value::Integer(i), &Type::Int) => i,
+                (Value::Bool(b), &Type::Bool) => b as i64,
+                (Value::Char(c), &Type::Char) => c as i64,
+                (Value::String(s), &Type::String) => {
+                    let s = try!(CString::new(s).map_err(|_| {
+                        Error {
+                            kind: ErrorKind::NulInExternString,
+                            span: arg.span.clone(),
+                            backtrace: vec![],
+                        }
+                    }));
+                    let pointer = s.as_ptr() as i64;
+                    strings.push(s);
+                    pointer
+                }
+                (value, param_type) => {
+                    let value_type = try!(Self::type_from_value(&value, arg.span.clone()));
+                    return Err(Error {
+                        kind: ErrorKind::MismatchedTypes(value_type, param_type.clone()),
+                        span: arg.span.clone(),
+                        backtrace: vec![],
+                    });
+                }
+            };
+            c_args.push(c_arg);
+        }
+
+        let address = try!(self.extern_symbol(func_decl, &span));
+        let result = unsafe { call(address, &c_args) };
+
+        Ok(match func_decl.return_type {
+            Type::Int => Value::Integer(result),
+            Type::Bool => Value::Bool(result as i32 != 0),
+            Type::Char => Value::Char(std::char::from_u32(result as u32).unwrap_or('\u{FFFD}')),
+            Type::String if result == 0 => Value::String(String::new()),
+            Type::String => {
+                let s = unsafe { CStr::from_ptr(result as *const c_char) };
+                Value::String(s.to_string_lossy().into_owned())
+            }
+            _ => Value::Nil,
+        })
+    }
+
+    fn check_extern_signature(func_decl: &FuncDeclData) -> Result<'a, ()> {
+        if func_decl.parameters.len() > MAX_EXTERN_PARAMS {
+            return Err(Error {
+                kind: ErrorKind::TooManyExternParams(func_decl.parameters.len()),
+                span: func_decl.span.clone(),
+                backtrace: vec![],
+            });
+        }
+
+        let param_types = func_decl.parameters.iter().map(|param| &param.param_type);
+        for t in param_types.chain(Some(&func_decl.return_type)) {
+            match *t {
+                Type::Int | Type::Bool | Type::Char | Type::String => (),
+                Type::None if *t == func_decl.return_type => (),
+                _ => {
+                    return Err(Error {
+                        kind: ErrorKind::InvalidExternType(t.clone()),
+                        span: func_decl.span.clone(),
+                        backtrace: vec![],
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Address of the function, loading its library the first time one of its functions is called
+    fn extern_symbol(&self, func_decl: &FuncDeclData, span: &Span) -> Result<'a, *const c_void> {
+        let load_error = |e: std::io::Error| {
+            Error {
+                kind: ErrorKind::ExternLoad(func_decl.name.clone(), e.to_string()),
+                span: span.clone(),
+                backtrace: vec![],
+            }
+        };
+
+        let mut libraries = self.libraries.borrow_mut();
+        if !libraries.contains_key(&func_decl.library) {
+            let library = try!(open_library(&func_decl.library).map_err(&load_error));
+            libraries.insert(func_decl.library.clone(), library);
+        }
+
+        unsafe {
+            libraries[&func_decl.library]
+                .get::<*const c_void>(func_decl.name.as_bytes())
+                .map(|symbol| *symbol)
+                .map_err(&load_error)
+        }
+    }
+}
+
+fn open_library(library: &Option<String>) -> std::io::Result<Library> {
+    match *library {
+        Some(ref path) => Library::new(path),
+        None => open_interpreter(),
+    }
+}
+
+/// Symbols of the interpreter process, which include the C library it is linked to
+#[cfg(unix)]
+fn open_interpreter() -> std::io::Result<Library> {
+    Ok(libloading::os::unix::Library::this().into())
+}
+
+#[cfg(not(unix))]
+fn open_interpreter() -> std::io::Result<Library> {
+    Err(std::io::Error::new(std::io::ErrorKind::Other,
+                            "extern funcs must name their library on this platform"))
+}
+
+unsafe fn call(address: *const c_void, args: &[i64]) -> i64 {
+    use std::mem::transmute;
+
+    match args.len() {
+        0 => transmute::<_, extern "C" fn() -> i64>(address)(),
+        1 => transmute::<_, extern "C" fn(i64) -> i64>(address)(args[0]),
+        2 => transmute::<_, extern "C" fn(i64, i64) -> i64>(address)(args[0], args[1]),
+        3 => {
+            transmute::<_, extern "C" fn(i64, i64, i64) -> i64>(address)(args[0], args[1], args[2])
+        }
+        4 => {
+            transmute::<_, extern "C" fn(i64, i64, i64, i64) -> i64>(address)(args[0],
+                                                                              args[1],
+                                                                              args[2],
+                                                                              args[3])
+        }
+        5 => {
+            transmute::<_, extern "C" fn(i64, i64, i64, i64, i64) -> i64>(address)(args[0],
+                                                                                   args[1],
+                                                                                   args[2],
+                                                                                   args[3],
+                                                                                   args[4])
+        }
+        _ => {
+            transmute::<_, extern "C" fn(i64, i64, i64, i64, i64, i64) -> i64>(address)(args[0],
+                                                                                        args[1],
+                                                                                        args[2],
+                                                                                        args[3],
+                                                                                        args[4],
+                                                                                        args[5])
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std;
+use std::io;
+use std::io::prelude::*;
+use interpreter::*;
+use interpreter::task::Task;
+
+impl<'a> Interpreter<'a> {
+    /// Starts reading the file on another thread, returning the handle of a task evaluating to its
+    /// content
+    pub fn read_file_async(&self, path: String) -> Value<'a> {
+        self.start_io(move || {
+            let mut content = String::new();
+            try!(try!(std::fs::File::open(path)).read_to_string(&mut content));
+
+            Ok(content)
+        })
+    }
+
+    /// Starts sending the request to the TCP address on another thread, returning the handle of a
+    /// task evaluating to the whole response, read until the connection is closed
+    pub fn request_async(&self, address: String, request: String) -> Value<'a> {
+        self.start_io(move || {
+            let mut stream = try!(std::net::TcpStream::connect(address.as_str()));
+            try!(stream.write_all(request.as_bytes()));
+            try!(stream.shutdown(std::net::Shutdown::Write));
+
+            let mut response = String::new();
+            try!(stream.read_to_string(&mut response));
+
+            Ok(response)
+        })
+    }
+
+    fn start_io<F>(&self, operation: F) -> Value<'a>
+        where F: FnOnce() -> io::Result<String> + Send + 'static
+    {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            // The receiver is gone if the program ended without awaiting the task
+            let _ = sender.send(operation());
+        });
+
+        let mut tasks = self.tasks.borrow_mut();
+        tasks.push(Task::Io(receiver));
+
+        Value::Task(tasks.len() - 1)
+    }
+}
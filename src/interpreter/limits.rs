@@ -0,0 +1,168 @@
+use std;
+use std::cell::Cell;
+use lexer::Span;
+use interpreter::*;
+
+/// Stack kept free below the stack limit, for the host calls between two steps and for reporting
+/// the error
+const STACK_RESERVE: usize = 2 * 1024 * 1024;
+
+thread_local! {
+    /// Lowest address the stack of the thread of the task may grow to, 0 when unknown
+    static STACK_LIMIT: Cell<usize> = Cell::new(0);
+}
+
+/// Address of a local of the caller, i.e. the current top of the stack, which grows downwards
+#[inline(never)]
+fn stack_pointer() -> usize {
+    let marker = 0u8;
+    &marker as *const u8 as usize
+}
+
+/// Records the stack limit of the thread starting a task, whose stack has `stack_size` bytes
+pub fn start_thread(stack_size: usize) {
+    let limit = stack_pointer().saturating_sub(stack_size.saturating_sub(STACK_RESERVE));
+    STACK_LIMIT.with(|stack_limit| stack_limit.set(limit));
+}
+
+impl<'a> Interpreter<'a> {
+    /// Counts a statement or expression about to be evaluated, checking the step limit, the stack
+    /// and the timeout
+    pub fn step(&self, span: &Span) -> Result<'a, ()> {
+        let steps = self.steps.get() + 1;
+        self.steps.set(steps);
+        if self.options.max_steps.map_or(false, |max_steps| steps > max_steps) {
+            return Err(Error {
+                kind: ErrorKind::StepLimitExceeded,
+                span: span.clone(),
+                backtrace: vec![],
+            });
+        }
+
+        try!(Self::check_stack(span));
+        self.check_timeout(span)
+    }
+
+    /// Fails when the stack of the task is almost exhausted, as the host would otherwise abort
+    /// on a stack overflow
+    fn check_stack(span: &Span) -> Result<'a, ()> {
+        if stack_pointer() < STACK_LIMIT.with(|stack_limit| stack_limit.get()) {
+            return Err(Error {
+                kind: ErrorKind::StackExhausted,
+                span: span.clone(),
+                backtrace: vec![],
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn check_timeout(&self, span: &Span) -> Result<'a, ()> {
+        if self.remaining_time() == Some(std::time::Duration::new(0, 0)) {
+            return Err(Error {
+                kind: ErrorKind::Timeout,
+                span: span.clone(),
+                backtrace: vec![],
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Time left before the timeout, if there is one
+    pub fn remaining_time(&self) -> Option<std::time::Duration> {
+        match (self.options.timeout, self.started_at.get()) {
+            (Some(timeout), Some(started_at)) => {
+                let elapsed = started_at.elapsed();
+                Some(if elapsed < timeout {
+                    timeout - elapsed
+                } else {
+                    std::time::Duration::new(0, 0)
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Counts the memory allocated for the value, checking the heap limit
+    pub fn allocate(&self, value: &Value, span: &Span) -> Result<'a, ()> {
+        if self.options.max_heap_bytes.is_some() {
+            try!(self.allocate_bytes(heap_size(value), span));
+        }
+
+        Ok(())
+    }
+
+    pub fn allocate_bytes(&self, bytes: usize, span: &Span) -> Result<'a, ()> {
+        if let Some(max_heap_bytes) = self.options.max_heap_bytes {
+            let heap_bytes = self.heap_bytes.get().saturating_add(bytes);
+            self.heap_bytes.set(heap_bytes);
+            if heap_bytes > max_heap_bytes {
+                return Err(Error {
+                    kind: ErrorKind::HeapLimitExceeded,
+                    span: span.clone(),
+                    backtrace: vec![],
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Counts a call of a user func about to run, checking the call depth limit
+    pub fn enter_call(&self, span: &Span) -> Result<'a, ()> {
+        let depth = self.call_depth.get();
+        if depth >= self.options.max_call_depth {
+            return Err(Error {
+                kind: ErrorKind::CallDepthExceeded(self.options.max_call_depth),
+                span: span.clone(),
+                backtrace: vec![],
+            });
+        }
+        self.call_depth.set(depth + 1);
+
+        Ok(())
+    }
+
+    pub fn leave_call(&self) {
+        self.call_depth.set(self.call_depth.get() - 1);
+    }
+
+    pub fn check_io_allowed(&self, name: &str, span: Span) -> Result<'a, ()> {
+        if !self.options.allow_io {
+            return Err(Error {
+                kind: ErrorKind::IoDisabled(name.to_string()),
+                span: span,
+                backtrace: vec![],
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Bytes the value owns on the heap
+fn heap_size(value: &Value) -> usize {
+    let value_size = std::mem::size_of::<Value>();
+    match *value {
+        Value::String(ref s) => s.len(),
+        Value::Bytes(ref b) => b.len(),
+        Value::Array(_, ref items) => {
+            items.iter().map(|item| value_size + heap_size(item)).sum()
+        }
+        Value::Map(_, _, ref m) => {
+            m.map
+                .iter()
+                .map(|(key, value)| 2 * value_size + heap_size(key) + heap_size(value))
+                .sum()
+        }
+        Value::Set(_, ref s) => s.set.iter().map(|item| value_size + heap_size(item)).sum(),
+        Value::Struct(_, ref s) => {
+            s.map
+                .iter()
+                .map(|(name, value)| name.len() + value_size + heap_size(value))
+                .sum()
+        }
+        _ => 0,
+    }
+}
@@ -1,31 +1,74 @@
 pub mod builtin;
+pub mod task;
+pub mod channel;
+pub mod sync;
+pub mod io;
+pub mod limits;
+pub mod reference;
+pub mod builder;
+pub mod arithmetic;
+pub mod ffi;
+pub mod suggest;
+
+#[cfg(test)]
+mod tests;
 
 use parser::ast::*;
 use std;
 use lexer;
 use lexer::Span;
 use parser;
-use std::fs::File;
-use std::io::prelude::*;
+use source_map;
+use source_map::SourceMap;
 use std::borrow::Borrow;
 use std::borrow::BorrowMut;
 use std::hash::*;
 use std::fmt::Display;
 use std::fmt;
 use std::error::Error as BaseError;
+use libloading;
+use parser::suggest::did_you_mean;
+use interpreter::task::Task;
+use interpreter::task::Scheduler;
+use interpreter::channel::Channel;
+use interpreter::sync::SyncState;
+use interpreter::arithmetic::integer_builtin;
+use interpreter::arithmetic::integer_op;
 
 #[derive(Debug)]
 pub struct Error<'a> {
     pub kind: ErrorKind<'a>,
     pub span: Span,
+    /// Function calls the error occurred in, innermost first
+    pub backtrace: std::vec::Vec<Frame>,
+}
+
+/// Call of a user function
+#[derive(Debug, Clone)]
+pub struct Frame {
+    pub func: String,
+    /// Span of the call expression, nil for the call of `main`
+    pub span: Span,
+}
+
+impl<'a> Error<'a> {
+    fn in_frame(mut self, func: &str, span: &Span) -> Self {
+        self.backtrace.push(Frame {
+            func: func.to_string(),
+            span: span.clone(),
+        });
+
+        self
+    }
 }
 
 #[derive(Debug)]
 pub enum ErrorKind<'a> {
-    IO(std::io::Error),
+    Load(source_map::Error),
     Parser(parser::Error),
     CannotInferTypeEmptyArray,
     CannotInferTypeEmptyMap,
+    CannotInferTypeChan,
     CannotIterateOver(Value<'a>),
     UnexpectedExprReturn,
     ExpectedExprReturn,
@@ -33,13 +76,15 @@ pub enum ErrorKind<'a> {
     CannotCallNonFunction,
     ExpectedArgument(String),
     CannotMutablyRefFunction,
-    UnknownVariable(std::vec::Vec<SpannedString>),
-    IndexOutOfBounds,
-    UnknownIndex(Value<'a>),
+    UnknownVariable(std::vec::Vec<SpannedString>, Option<String>),
+    /// Index and number of items
+    IndexOutOfBounds(i64, usize),
+    /// Key missing from the indexed map, as Ion source
+    MissingKey(String),
     CannotIndexNonIndexable,
     CannotPushToUntypedArray,
     CannotPushToNonArray,
-    UnknownStructField(String),
+    UnknownStructField(String, Option<String>),
     CannotAccessFieldOnNonStruct,
     CannotDerefConstRefInMutContext,
     CannotDerefNonRef,
@@ -47,70 +92,218 @@ pub enum ErrorKind<'a> {
     CannotGetRef(Expression_),
     HeterogeneousTypesInArray,
     HeterogeneousTypesInMap,
-    UnknownStruct(std::vec::Vec<SpannedString>),
+    HeterogeneousTypesInSet,
+    UnknownStruct(std::vec::Vec<SpannedString>, Option<String>),
     MissingStructField(String),
-    CannotCountNonCountable,
+    CannotCount(Type),
+    CannotSpread(Type),
+    CannotDestructure(Type),
+    DestructureCount(usize, usize),
+    MisplacedSpread,
     NoDefaultValue(Type),
     InvalidArgCount,
+    CannotJoinRunningTask,
+    JoinDeadlock,
+    TaskFailed,
+    /// Ended by `main` returning while the task was waiting to run
+    Aborted,
+    /// The thread of the task could not be started
+    CannotStartTask(std::io::Error),
+    CannotSendOnClosedChan,
+    CannotRecvOnClosedChan,
+    CannotCloseClosedChan,
+    ChanDeadlock,
+    MutexDeadlock(Option<Span>),
+    CannotUnlockUnlockedMutex,
+    /// Unlock of a mutex locked by another task
+    CannotUnlockForeignMutex,
+    WaitGroupDeadlock,
+    NegativeWaitGroupCounter,
+    Io(std::io::Error),
+    StepLimitExceeded,
+    HeapLimitExceeded,
+    /// More nested calls than the maximum call depth
+    CallDepthExceeded(usize),
+    /// Stack of the thread of the task almost exhausted, by calls or nested blocks and expressions
+    StackExhausted,
+    Timeout,
+    IoDisabled(String),
+    CannotOrder(Type),
+    DanglingReference(String),
+    /// Reference to an item of an array or a map, which moves when the collection grows
+    CannotRefItem,
+    IntegerOverflow,
+    DivisionByZero,
+    ModuloByZero,
+    InvalidMapKeyType(Type),
+    InvalidSetItemType(Type),
+    DuplicateMapKey(String),
+    InvalidExternType(Type),
+    TooManyExternParams(usize),
+    ExternLoad(String, String),
+    NulInExternString,
+    InvalidUtf8,
+    InvalidIntString(String),
+    CannotConvertToString(Type),
+    Panic(String),
+    MissingMain,
+    InvalidMainSignature,
+    /// Int returned by `main` which isn't an exit status
+    InvalidExitCode(i64),
+}
+
+impl<'a> Error<'a> {
+    /// Description of the error, without its position
+    pub fn message(&self) -> String {
+        match self.kind {
+            ErrorKind::Load(ref e) => e.message(),
+            ErrorKind::Parser(ref e) => e.message(),
+            ErrorKind::CannotInferTypeEmptyArray |
+            ErrorKind::CannotInferTypeEmptyMap |
+            ErrorKind::CannotInferTypeChan |
+            ErrorKind::UnexpectedExprReturn |
+            ErrorKind::ExpectedExprReturn |
+            ErrorKind::CannotCallNonFunction |
+            ErrorKind::CannotMutablyRefFunction |
+            ErrorKind::CannotIndexNonIndexable |
+            ErrorKind::CannotPushToUntypedArray |
+            ErrorKind::CannotPushToNonArray |
+            ErrorKind::CannotAccessFieldOnNonStruct |
+            ErrorKind::CannotDerefConstRefInMutContext |
+            ErrorKind::CannotDerefNonRef |
+            ErrorKind::HeterogeneousTypesInArray |
+            ErrorKind::HeterogeneousTypesInMap |
+            ErrorKind::HeterogeneousTypesInSet |
+            ErrorKind::InvalidUtf8 |
+            ErrorKind::MissingMain |
+            ErrorKind::InvalidMainSignature |
+            ErrorKind::InvalidArgCount |
+            ErrorKind::CannotJoinRunningTask |
+            ErrorKind::JoinDeadlock |
+            ErrorKind::TaskFailed |
+            ErrorKind::Aborted |
+            ErrorKind::CannotSendOnClosedChan |
+            ErrorKind::CannotRecvOnClosedChan |
+            ErrorKind::CannotCloseClosedChan |
+            ErrorKind::ChanDeadlock |
+            ErrorKind::CannotUnlockUnlockedMutex |
+            ErrorKind::CannotUnlockForeignMutex |
+            ErrorKind::WaitGroupDeadlock |
+            ErrorKind::NegativeWaitGroupCounter |
+            ErrorKind::IntegerOverflow |
+            ErrorKind::DivisionByZero |
+            ErrorKind::ModuloByZero |
+            ErrorKind::NulInExternString |
+            ErrorKind::MisplacedSpread |
+            ErrorKind::CannotRefItem |
+            ErrorKind::StepLimitExceeded |
+            ErrorKind::HeapLimitExceeded |
+            ErrorKind::Timeout => self.description().to_string(),
+            ErrorKind::StackExhausted => {
+                "stack overflow: calls, blocks and expressions are nested too deeply".to_string()
+            }
+            ErrorKind::CallDepthExceeded(max) => {
+                format!("stack overflow: more than {} nested calls", max)
+            }
+            ErrorKind::CannotIterateOver(ref v) => {
+                format!("cannot iterate over {:?}", v)
+            }
+            ErrorKind::MismatchedTypes(ref e, ref g) => {
+                format!("mismatched types (expected {:?}, got {:?})", e, g)
+            }
+            ErrorKind::ExpectedArgument(ref s) => format!("expected argument {}", s),
+            ErrorKind::UnknownVariable(ref parts, ref suggestion) => {
+                format!("unknown variable {}{}",
+                        join_path(parts),
+                        did_you_mean(suggestion))
+            }
+            ErrorKind::MissingKey(ref key) => format!("missing map key {}", key),
+            ErrorKind::UnknownStructField(ref s, ref suggestion) => {
+                format!("unknown struct field {}{}", s, did_you_mean(suggestion))
+            }
+            ErrorKind::CannotGetMutRef(ref e) => {
+                format!("cannot get mutable reference for {:?}", e)
+            }
+            ErrorKind::CannotGetRef(ref e) => {
+                format!("cannot get reference for {:?}", e)
+            }
+            ErrorKind::UnknownStruct(ref parts, ref suggestion) => {
+                format!("unknown struct {}{}",
+                        join_path(parts),
+                        did_you_mean(suggestion))
+            }
+            ErrorKind::MissingStructField(ref s) => {
+                format!("missing field {} in struct init", s)
+            }
+            ErrorKind::NoDefaultValue(ref t) => {
+                format!("no default value for type {:?}", t)
+            }
+            ErrorKind::Io(ref e) => format!("i/o error: {}", e),
+            ErrorKind::CannotStartTask(ref e) => format!("cannot start task: {}", e),
+            ErrorKind::DanglingReference(ref name) => {
+                format!("reference to local variable {} outlives it", name)
+            }
+            ErrorKind::DuplicateMapKey(ref key) => format!("duplicate map key {}", key),
+            ErrorKind::InvalidExitCode(code) => {
+                format!("main returned {}, which is not an exit code from 0 to 255", code)
+            }
+            ErrorKind::InvalidExternType(ref t) => {
+                format!("type {} cannot be passed to or returned from extern funcs", t)
+            }
+            ErrorKind::TooManyExternParams(count) => {
+                format!("extern func has {} parameters (at most 6 are supported)", count)
+            }
+            ErrorKind::ExternLoad(ref name, ref e) => {
+                format!("cannot load extern func {}: {}", name, e)
+            }
+            ErrorKind::InvalidMapKeyType(ref t) => {
+                format!("invalid map key type {} (expected int, string, char or bool)", t)
+            }
+            ErrorKind::InvalidIntString(ref s) => format!("{:?} is not a valid int", s),
+            ErrorKind::Panic(ref message) => format!("panic: {}", message),
+            ErrorKind::CannotConvertToString(ref t) => {
+                format!("cannot convert a value of type {} to a string", t)
+            }
+            ErrorKind::IndexOutOfBounds(index, len) => {
+                format!("index {} out of bounds for {} items", index, len)
+            }
+            ErrorKind::InvalidSetItemType(ref t) => {
+                format!("invalid set item type {} (expected int, string, char or bool)", t)
+            }
+            ErrorKind::CannotOrder(ref t) => {
+                format!("cannot order values of type {}", t)
+            }
+            ErrorKind::CannotCount(ref t) => {
+                format!("cannot count the items of a value of type {}", t)
+            }
+            ErrorKind::CannotSpread(ref t) => {
+                format!("cannot spread a value of type {}, only arrays", t)
+            }
+            ErrorKind::CannotDestructure(ref t) => {
+                format!("cannot destructure a value of type {} into a list, only arrays", t)
+            }
+            ErrorKind::DestructureCount(names, items) => {
+                format!("cannot destructure an array of {} items into {} variables", items, names)
+            }
+            ErrorKind::IoDisabled(ref name) => {
+                format!("cannot call {}: i/o is disabled", name)
+            }
+            ErrorKind::MutexDeadlock(Some(ref locked_at)) => {
+                format!("deadlock: mutex already locked at {}", locked_at)
+            }
+            _ => self.description().to_string(),
+        }
+    }
 }
 
 impl<'a> Display for Error<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        if let ErrorKind::IO(ref e) = self.kind {
+        if let ErrorKind::Load(ref e) = self.kind {
             e.fmt(f)
         } else if let ErrorKind::Parser(ref e) = self.kind {
             e.fmt(f)
         } else {
-            write!(f,
-                   "{}: {}",
-                   self.span,
-                   match self.kind {
-                       ErrorKind::CannotInferTypeEmptyArray |
-                       ErrorKind::CannotInferTypeEmptyMap |
-                       ErrorKind::UnexpectedExprReturn |
-                       ErrorKind::ExpectedExprReturn |
-                       ErrorKind::CannotCallNonFunction |
-                       ErrorKind::CannotMutablyRefFunction |
-                       ErrorKind::IndexOutOfBounds |
-                       ErrorKind::CannotIndexNonIndexable |
-                       ErrorKind::CannotPushToUntypedArray |
-                       ErrorKind::CannotPushToNonArray |
-                       ErrorKind::CannotAccessFieldOnNonStruct |
-                       ErrorKind::CannotDerefConstRefInMutContext |
-                       ErrorKind::CannotDerefNonRef |
-                       ErrorKind::HeterogeneousTypesInArray |
-                       ErrorKind::HeterogeneousTypesInMap |
-                       ErrorKind::InvalidArgCount |
-                       ErrorKind::CannotCountNonCountable => self.description().to_string(),
-                       ErrorKind::CannotIterateOver(ref v) => {
-                           format!("cannot iterate over {:?}", v)
-                       }
-                       ErrorKind::MismatchedTypes(ref e, ref g) => {
-                           format!("mismatched types (expected {:?}, got {:?})", e, g)
-                       }
-                       ErrorKind::ExpectedArgument(ref s) => format!("expected argument {}", s),
-                       ErrorKind::UnknownVariable(ref parts) => {
-                           format!("unknown variable {:?}", parts)
-                       }
-                       ErrorKind::UnknownIndex(ref v) => format!("unknown index {:?}", v),
-                       ErrorKind::UnknownStructField(ref s) => {
-                           format!("unknown struct field {}", s)
-                       }
-                       ErrorKind::CannotGetMutRef(ref e) => {
-                           format!("cannot get mutable reference for {:?}", e)
-                       }
-                       ErrorKind::CannotGetRef(ref e) => {
-                           format!("cannot get reference for {:?}", e)
-                       }
-                       ErrorKind::UnknownStruct(ref parts) => format!("unknown struct {:?}", parts),
-                       ErrorKind::MissingStructField(ref s) => {
-                           format!("missing field {} in struct init", s)
-                       }
-                       ErrorKind::NoDefaultValue(ref t) => {
-                           format!("no default value for type {:?}", t)
-                       }
-                       _ => self.description().to_string(),
-                   })
+            write!(f, "{}: {}", self.span, self.message())
         }
     }
 }
@@ -118,15 +311,16 @@ impl<'a> Display for Error<'a> {
 impl<'a> BaseError for Error<'a> {
     fn description(&self) -> &str {
         match self.kind {
-            ErrorKind::IO(ref e) => e.description(),
+            ErrorKind::Load(ref e) => e.description(),
             ErrorKind::Parser(ref e) => e.description(),
             ErrorKind::CannotInferTypeEmptyArray => "cannot infer type for empty array",
             ErrorKind::CannotInferTypeEmptyMap => "cannot infer type for empty map",
+            ErrorKind::CannotInferTypeChan => "cannot infer type for channel",
             ErrorKind::UnexpectedExprReturn => "unexpected expression for return",
             ErrorKind::ExpectedExprReturn => "exprected an expression for return",
             ErrorKind::CannotCallNonFunction => "cannot call a non-function",
             ErrorKind::CannotMutablyRefFunction => "cannot mutably reference a function",
-            ErrorKind::IndexOutOfBounds => "index out of bounds",
+            ErrorKind::IndexOutOfBounds(_, _) => "index out of bounds",
             ErrorKind::CannotIndexNonIndexable => "cannot index a non-indexable",
             ErrorKind::CannotPushToUntypedArray => "cannot push to untyped array",
             ErrorKind::CannotPushToNonArray => "cannot push to non array",
@@ -137,17 +331,69 @@ impl<'a> BaseError for Error<'a> {
             ErrorKind::CannotDerefNonRef => "cannot dereference a non-reference",
             ErrorKind::HeterogeneousTypesInArray => "heterogeneous types in array",
             ErrorKind::HeterogeneousTypesInMap => "heterogeneous types in map",
-            ErrorKind::CannotCountNonCountable => "cannot count non-countable",
+            ErrorKind::HeterogeneousTypesInSet => "heterogeneous types in set",
+            ErrorKind::CannotCount(_) => "cannot count the items of the value",
+            ErrorKind::CannotSpread(_) => "cannot spread the value",
+            ErrorKind::CannotDestructure(_) => "cannot destructure the value",
+            ErrorKind::DestructureCount(_, _) => "wrong number of variables to destructure into",
+            ErrorKind::MisplacedSpread => {
+                "spread outside of an array literal or call arguments"
+            }
             ErrorKind::InvalidArgCount => "invalid argument count",
+            ErrorKind::CannotJoinRunningTask => "cannot join a task from itself",
+            ErrorKind::JoinDeadlock => "deadlock: every task is blocked",
+            ErrorKind::TaskFailed => "joined task failed",
+            ErrorKind::Aborted => "aborted as main returned",
+            ErrorKind::CannotStartTask(_) => "cannot start task",
+            ErrorKind::CannotSendOnClosedChan => "cannot send on a closed channel",
+            ErrorKind::CannotRecvOnClosedChan => "cannot receive from a closed and empty channel",
+            ErrorKind::CannotCloseClosedChan => "cannot close a closed channel",
+            ErrorKind::ChanDeadlock => "no task left to send on the channel",
+            ErrorKind::MutexDeadlock(_) => "deadlock: mutex already locked",
+            ErrorKind::CannotUnlockUnlockedMutex => "cannot unlock a mutex which is not locked",
+            ErrorKind::CannotUnlockForeignMutex => "cannot unlock a mutex locked by another task",
+            ErrorKind::WaitGroupDeadlock => "no task left to signal the wait group",
+            ErrorKind::NegativeWaitGroupCounter => "negative wait group counter",
+            ErrorKind::Io(_) => "i/o error",
+            ErrorKind::StepLimitExceeded => "step limit exceeded",
+            ErrorKind::HeapLimitExceeded => "heap limit exceeded",
+            ErrorKind::CallDepthExceeded(_) |
+            ErrorKind::StackExhausted => "stack overflow",
+            ErrorKind::Timeout => "execution timed out",
+            ErrorKind::IoDisabled(_) => "i/o is disabled",
+            ErrorKind::CannotOrder(_) => "cannot order values",
+            ErrorKind::DanglingReference(_) => "reference to a local variable outlives it",
+            ErrorKind::CannotRefItem => {
+                "cannot reference an item of an array or a map, only variables and fields"
+            }
+            ErrorKind::IntegerOverflow => "integer overflow",
+            ErrorKind::DivisionByZero => "division by zero",
+            ErrorKind::ModuloByZero => "modulo by zero",
+            ErrorKind::InvalidMapKeyType(_) => "invalid map key type",
+            ErrorKind::InvalidSetItemType(_) => "invalid set item type",
+            ErrorKind::DuplicateMapKey(_) => "duplicate map key",
+            ErrorKind::InvalidExternType(_) => "invalid extern func type",
+            ErrorKind::TooManyExternParams(_) => "too many extern func parameters",
+            ErrorKind::ExternLoad(_, _) => "cannot load extern func",
+            ErrorKind::NulInExternString => "nul byte in string passed to extern func",
+            ErrorKind::InvalidUtf8 => "bytes are not valid UTF-8",
+            ErrorKind::InvalidIntString(_) => "string is not a valid int",
+            ErrorKind::CannotConvertToString(_) => "cannot convert the value to a string",
+            ErrorKind::Panic(_) => "panic",
+            ErrorKind::MissingMain => "no main func",
+            ErrorKind::InvalidMainSignature => {
+                "main must be declared as `func main()` or `func main() -> int`"
+            }
+            ErrorKind::InvalidExitCode(_) => "exit code out of range",
             ErrorKind::CannotIterateOver(_) => "cannot iterate over value",
             ErrorKind::MismatchedTypes(_, _) => "mismatched types",
             ErrorKind::ExpectedArgument(_) => "expected an argument",
-            ErrorKind::UnknownVariable(_) => "unknown variable",
-            ErrorKind::UnknownIndex(_) => "unknown index",
-            ErrorKind::UnknownStructField(_) => "unknown struct field",
+            ErrorKind::UnknownVariable(_, _) => "unknown variable",
+            ErrorKind::MissingKey(_) => "missing map key",
+            ErrorKind::UnknownStructField(_, _) => "unknown struct field",
             ErrorKind::CannotGetMutRef(_) => "cannot get mutable reference",
             ErrorKind::CannotGetRef(_) => "cannot get reference",
-            ErrorKind::UnknownStruct(_) => "unknown struct",
+            ErrorKind::UnknownStruct(_, _) => "unknown struct",
             ErrorKind::MissingStructField(_) => "missing field in struct init",
             ErrorKind::NoDefaultValue(_) => "no default value for type",
         }
@@ -163,29 +409,130 @@ impl<'a> From<parser::Error> for Error<'a> {
         Error {
             kind: ErrorKind::Parser(error.clone()),
             span: error.span,
+            backtrace: vec![],
         }
     }
 }
 
 pub type Result<'a, T> = std::result::Result<T, Error<'a>>;
 
+/// Funcs declared in every file unless disabled, so that basic programs don't need imports
+const PRELUDE: &'static str = include_str!("../../std/prelude.ion");
+
 pub struct Interpreter<'a> {
     ast: &'a Ast,
+    source_map: &'a SourceMap,
     funcs: std::collections::HashMap<Path, Value<'a>>,
     structs: std::collections::HashMap<Path, StructDeclData>,
+    /// Statements of the prelude, empty if it is disabled
+    prelude: std::vec::Vec<Statement>,
+    tasks: std::cell::RefCell<std::vec::Vec<Task<'a>>>,
+    scheduler: Scheduler,
+    channels: std::cell::RefCell<std::vec::Vec<Channel<'a>>>,
+    sync: std::cell::RefCell<SyncState>,
+    /// Content of the `string::builder`s, which grow in place so that appending is amortized
+    /// constant time, unlike `<>` which copies both strings
+    builders: std::cell::RefCell<std::vec::Vec<String>>,
+    /// Libraries of the extern funcs called so far, by name
+    libraries: std::cell::RefCell<std::collections::HashMap<Option<String>, libloading::Library>>,
+    options: InterpreterOptions,
+    /// Statements and expressions evaluated so far
+    steps: std::cell::Cell<u64>,
+    /// Hidden variables created so far to pass spread arguments, numbering the next one
+    spread_args: std::cell::Cell<u64>,
+    /// Bytes allocated for values so far
+    heap_bytes: std::cell::Cell<usize>,
+    /// Calls of user funcs currently running
+    call_depth: std::cell::Cell<usize>,
+    started_at: std::cell::Cell<Option<std::time::Instant>>,
+}
+
+/// Limits on the execution, so that untrusted programs can be run. An execution going over one of
+/// them is aborted with an error.
+#[derive(Debug, Clone)]
+pub struct InterpreterOptions {
+    /// Maximum number of statements and expressions evaluated.
+    pub max_steps: Option<u64>,
+    /// Maximum number of bytes allocated for strings, arrays, maps and structs over the whole
+    /// execution. Memory freed since is still counted, so this bounds the heap used.
+    pub max_heap_bytes: Option<usize>,
+    /// Maximum wall-clock duration of the execution, including the time spent waiting for I/O.
+    pub timeout: Option<std::time::Duration>,
+    /// Whether the builtins accessing the filesystem and the network, and extern funcs, can be
+    /// called.
+    pub allow_io: bool,
+    /// What happens when the result of an arithmetic operator doesn't fit in an `int`.
+    pub overflow: Overflow,
+    /// Whether the funcs of the prelude are declared in every file.
+    pub prelude: bool,
+    /// Maximum number of nested calls of user funcs.
+    pub max_call_depth: usize,
+    /// Syntax accepted in imported files, every feature by default.
+    pub features: std::collections::HashSet<parser::Feature>,
+}
+
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+
+/// Largest maximum call depth, for which the stack of each task takes a bit more than 256 MiB in
+/// release builds. Larger ones are lowered to it.
+pub const MAX_CALL_DEPTH: usize = 8192;
+
+/// Stack reserved for each nested call of a user func with a small body. Calls nesting many
+/// blocks and expressions use more, and exhaust the stack before the call depth limit is reached,
+/// which `check_stack` reports as an error.
+#[cfg(not(debug_assertions))]
+const STACK_BYTES_PER_CALL: usize = 32 * 1024;
+/// The frames of the host are several times larger in debug builds
+#[cfg(debug_assertions)]
+const STACK_BYTES_PER_CALL: usize = 128 * 1024;
+
+/// Stack of the thread of a task
+fn task_stack_size(max_call_depth: usize) -> usize {
+    std::cmp::min(max_call_depth, MAX_CALL_DEPTH) * STACK_BYTES_PER_CALL + 16 * 1024 * 1024
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// Wrap around, e.g. the max int plus 1 is the min int
+    Wrap,
+    /// Stop at the max or min int
+    Saturate,
+    /// Abort the execution
+    Error,
+}
+
+impl Default for InterpreterOptions {
+    fn default() -> Self {
+        InterpreterOptions {
+            max_steps: None,
+            max_heap_bytes: None,
+            timeout: None,
+            allow_io: true,
+            overflow: Overflow::Error,
+            prelude: true,
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            features: parser::Feature::all(),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct InterpreterContext<'a> {
     vars: std::collections::HashMap<String, Variable<'a>>,
     current_path: Path,
+    /// Handles of the tasks spawned in this context
+    spawned: std::vec::Vec<usize>,
+    /// Context of the caller, or of the spawner for a task, which outlives this one. Null for
+    /// `main`.
+    parent: *const InterpreterContext<'a>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Variable<'a> {
     name: String,
     var_type: Type,
-    value: Value<'a>,
+    /// Boxed so that references to the variable stay valid when others are declared
+    value: Box<Value<'a>>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -198,9 +545,17 @@ pub enum Value<'a> {
     Struct(Path, StructValue<'a>),
     Array(Type, std::vec::Vec<Value<'a>>),
     Map(Type, Type, MapValue<'a>),
+    Set(Type, SetValue<'a>),
+    Bytes(std::vec::Vec<u8>),
     Reference(*const Value<'a>),
     MutReference(*mut Value<'a>),
     Func(Path, FuncDeclData),
+    Task(usize),
+    Chan(Type, usize),
+    Mutex(usize),
+    WaitGroup(usize),
+    Atomic(usize),
+    Builder(usize),
 }
 
 
@@ -209,6 +564,11 @@ pub struct MapValue<'a> {
     map: std::collections::HashMap<Value<'a>, Value<'a>>,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetValue<'a> {
+    set: std::collections::HashSet<Value<'a>>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct StructValue<'a> {
     map: std::collections::HashMap<String, Value<'a>>,
@@ -226,6 +586,17 @@ impl<'a> Hash for MapValue<'a> {
     }
 }
 
+#[allow(derive_hash_xor_eq)]
+impl<'a> Hash for SetValue<'a> {
+    fn hash<H>(&self, state: &mut H)
+        where H: Hasher
+    {
+        for item in &self.set {
+            item.hash(state);
+        }
+    }
+}
+
 #[allow(derive_hash_xor_eq)]
 impl<'a> Hash for StructValue<'a> {
     fn hash<H>(&self, state: &mut H)
@@ -239,11 +610,26 @@ impl<'a> Hash for StructValue<'a> {
 }
 
 impl<'a> Interpreter<'a> {
-    pub fn new(ast: &'a Ast) -> Self {
+    pub fn new(ast: &'a Ast, source_map: &'a SourceMap, mut options: InterpreterOptions) -> Self {
+        options.max_call_depth = std::cmp::min(options.max_call_depth, MAX_CALL_DEPTH);
         Interpreter {
             ast: ast,
+            source_map: source_map,
             funcs: std::collections::HashMap::new(),
             structs: std::collections::HashMap::new(),
+            prelude: vec![],
+            tasks: std::cell::RefCell::new(vec![]),
+            scheduler: Scheduler::default(),
+            channels: std::cell::RefCell::new(vec![]),
+            sync: std::cell::RefCell::new(SyncState::default()),
+            builders: std::cell::RefCell::new(vec![]),
+            libraries: std::cell::RefCell::new(std::collections::HashMap::new()),
+            options: options,
+            steps: std::cell::Cell::new(0),
+            spread_args: std::cell::Cell::new(0),
+            heap_bytes: std::cell::Cell::new(0),
+            call_depth: std::cell::Cell::new(0),
+            started_at: std::cell::Cell::new(None),
         }
     }
 
@@ -261,25 +647,70 @@ impl<'a> Interpreter<'a> {
                                               parts: vec![],
                                           },
                                           FuncDeclData {
+                                              id: NodeId::nil_id(),
                                               span: Span::nil_span(),
                                               name: name,
                                               return_type: Type::None,
                                               parameters: vec![],
                                               statements: vec![],
+                                              is_async: false,
+                                              is_extern: false,
+                                              library: None,
+                                              doc: None,
                                           }))
         };
 
         inject_func("print".to_string());
         inject_func("readln".to_string());
+        inject_func("join".to_string());
+        inject_func("send".to_string());
+        inject_func("recv".to_string());
+        inject_func("close".to_string());
+        inject_func("typeof".to_string());
+        inject_func("fields".to_string());
+        inject_func("dump".to_string());
+        inject_func("panic".to_string());
+    }
+
+    /// Parses the prelude, which is part of the interpreter rather than read from the disk
+    fn load_prelude(&mut self) -> Result<()> {
+        let file_id = self.source_map.add_file("<prelude>".to_string(), PRELUDE.to_string());
+        let source_file = self.source_map.file(file_id).unwrap();
+        let mut reader = lexer::Reader::new(source_file.src.as_ref(), file_id);
+
+        let mut parser = parser::Parser::new(&mut reader, parser::ParserOptions::default());
+        self.prelude = try!(parser.parse()).statements.clone();
+
+        Ok(())
+    }
+
+    /// Declares the funcs of the prelude in the file at the path, before its own so that they can
+    /// be redefined
+    fn inject_prelude(&mut self, path: &Path) -> Result<()> {
+        for statement in self.prelude.clone() {
+            unsafe { try!((*(self as *mut Self)).execute_statement(&statement, path.clone())) }
+        }
+
+        Ok(())
     }
 
-    pub fn execute(&'a mut self) -> Result<()> {
+    /// Runs the program from `main`, evaluating to the int it returned, or 0 if it doesn't return
+    /// one
+    pub fn execute(&'a mut self) -> Result<i64> {
         self.inject_builtin_funcs();
+        self.started_at.set(Some(std::time::Instant::now()));
 
         let initial_path = Path {
             span: Span::nil_span(),
             parts: vec![],
         };
+        try!(Self::check_main(self.ast));
+        if self.options.prelude {
+            unsafe {
+                try!((*(self as *mut Self)).load_prelude());
+                try!((*(self as *mut Self)).inject_prelude(&initial_path));
+            }
+        }
 
         // We are forced to use unsafe instead of a standard try!() because the borrow checker has a bug:
         // When a loop contains a return, the borrow checker does not recognize it as an early lifetime end, and therefore imposes
@@ -292,8 +723,10 @@ impl<'a> Interpreter<'a> {
         }
 
         let main_func_call_expr = Expression {
+            id: NodeId::nil_id(),
             span: Span::nil_span(),
             expr: Expression_::FuncCall(Box::new(Expression {
+                                            id: NodeId::nil_id(),
                                             span: Span::nil_span(),
                                             expr: Expression_::Variable(Path {
                                                 span: Span::nil_span(),
@@ -306,17 +739,79 @@ impl<'a> Interpreter<'a> {
                                         vec![]),
         };
 
-        let mut context = InterpreterContext {
-            vars: std::collections::HashMap::new(),
-            current_path: Path {
-                span: Span::nil_span(),
-                parts: vec![],
-            },
-        };
+        let interpreter: &'a Self = self;
+        interpreter.run_tasks(move || {
+            let mut context = InterpreterContext {
+                vars: std::collections::HashMap::new(),
+                current_path: Path {
+                    span: Span::nil_span(),
+                    parts: vec![],
+                },
+                spawned: vec![],
+                parent: std::ptr::null(),
+            };
 
-        try!(self.value_from_expression(&mut context, &main_func_call_expr));
+            match try!(interpreter.value_from_expression(&mut context, &main_func_call_expr)) {
+                Value::Integer(code) => Ok(code),
+                _ => Ok(0),
+            }
+        })
+    }
 
-        Ok(())
+    /// Exit code of the process for the int returned by `main`, which is an error outside of the
+    /// 0 to 255 range of exit statuses rather than being truncated
+    pub fn exit_code(ast: &Ast, code: i64) -> Result<'a, i32> {
+        if code >= 0 && code <= 255 {
+            return Ok(code as i32);
+        }
+
+        let span = ast.statements
+            .iter()
+            .filter_map(|statement| match *statement {
+                Statement::FuncDecl(ref fd) if fd.name == "main" => Some(fd.span.clone()),
+                _ => None,
+            })
+            .next()
+            .unwrap_or_else(Span::nil_span);
+        Err(Error {
+            kind: ErrorKind::InvalidExitCode(code),
+            span: span,
+            backtrace: vec![],
+        })
+    }
+
+    /// Checks that the program has a `main` func, taking no parameters and returning nothing or an
+    /// int, before running anything
+    fn check_main(ast: &Ast) -> Result<'a, ()> {
+        let main = ast.statements.iter().filter_map(|statement| match *statement {
+            Statement::FuncDecl(ref fd) if fd.name == "main" => Some(fd),
+            _ => None,
+        });
+
+        match main.last() {
+            Some(fd) => {
+                let returns_code = match fd.return_type {
+                    Type::None | Type::Int => true,
+                    _ => false,
+                };
+                if !fd.parameters.is_empty() || !returns_code || fd.is_async || fd.is_extern {
+                    return Err(Error {
+                        kind: ErrorKind::InvalidMainSignature,
+                        span: fd.span.clone(),
+                        backtrace: vec![],
+                    });
+                }
+
+                Ok(())
+            }
+            None => {
+                Err(Error {
+                    kind: ErrorKind::MissingMain,
+                    span: Span::nil_span(),
+                    backtrace: vec![],
+                })
+            }
+        }
     }
 
     fn execute_statement(&mut self, statement: &Statement, current_path: Path) -> Result<()> {
@@ -366,6 +861,7 @@ impl<'a> Interpreter<'a> {
                             Err(Error {
                                 kind: ErrorKind::CannotInferTypeEmptyArray,
                                 span: span,
+                                backtrace: vec![],
                             })
                         }
                         ref array_type => Ok(Type::Array(Box::new((*array_type).clone()))),
@@ -397,6 +893,7 @@ impl<'a> Interpreter<'a> {
                             Err(Error {
                                 kind: ErrorKind::CannotInferTypeEmptyMap,
                                 span: span,
+                                backtrace: vec![],
                             })
                         }
                         (map_type1, map_type2) => {
@@ -404,11 +901,36 @@ impl<'a> Interpreter<'a> {
                         }
                     }
                 }
+                Value::Set(ref t, _) => Ok(Type::Set(Box::new(t.clone()))),
+                Value::Bytes(_) => Ok(Type::Bytes),
+                Value::Task(_) => Ok(Type::Task),
+                Value::Chan(Type::None, _) => {
+                    Err(Error {
+                        kind: ErrorKind::CannotInferTypeChan,
+                        span: span,
+                        backtrace: vec![],
+                    })
+                }
+                Value::Chan(ref t, _) => Ok(Type::Chan(Box::new(t.clone()))),
+                Value::Mutex(_) => Ok(Type::Mutex),
+                Value::WaitGroup(_) => Ok(Type::WaitGroup),
+                Value::Atomic(_) => Ok(Type::Atomic),
+                Value::Builder(_) => Ok(Type::Builder),
                 Value::Nil => Ok(Type::None),
             }
         }
     }
 
+    /// Whether the value has the type, an empty array or map having every array or map type
+    fn value_has_type(value: &Value, t: &Type) -> bool {
+        match (value, t) {
+            (&Value::Array(Type::None, _), &Type::Array(_)) |
+            (&Value::Map(Type::None, _, _), &Type::Map(_, _)) |
+            (&Value::Map(_, Type::None, _), &Type::Map(_, _)) => true,
+            _ => Self::type_from_value(value, Span::nil_span()).ok().map_or(false, |vt| vt == *t),
+        }
+    }
+
     fn execute_import(&mut self, import_data: &ImportData, current_path: Path) -> Result<()> {
         // TODO: rework that for more safety and non-naive handling
         let path_string = current_path.parts.iter().fold("".to_string(), |mut acc, ref item| {
@@ -417,27 +939,25 @@ impl<'a> Interpreter<'a> {
             acc
         }) + import_data.path.as_ref() + ".ion";
         let path = std::path::Path::new(AsRef::<str>::as_ref(&path_string[..]));
-        let mut file = match File::open(&path) {
-            Ok(file) => file,
+        let file_id = match self.source_map.load_file(path) {
+            Ok(file_id) => file_id,
             Err(err) => {
+                let span = err.span.clone();
                 return Err(Error {
-                    kind: ErrorKind::IO(err),
-                    span: Span::nil_span(),
-                })
+                    kind: ErrorKind::Load(err),
+                    span: span,
+                    backtrace: vec![],
+                });
             }
         };
+        let source_file = self.source_map.file(file_id).unwrap();
+        let mut reader = lexer::Reader::new(source_file.src.as_ref(), file_id);
 
-        let mut s = String::new();
-        let res = file.read_to_string(&mut s);
-        if let Some(err) = res.err() {
-            return Err(Error {
-                kind: ErrorKind::IO(err),
-                span: Span::nil_span(),
-            });
-        }
-        let mut reader = lexer::Reader::new(s.as_ref(), path_string.clone());
-
-        let mut parser = parser::Parser::new(&mut reader);
+        let options = parser::ParserOptions {
+            features: self.options.features.clone(),
+            ..parser::ParserOptions::default()
+        };
+        let mut parser = parser::Parser::new(&mut reader, options);
         let ast = try!(parser.parse());
 
         let mut new_path = current_path.clone();
@@ -448,6 +968,7 @@ impl<'a> Interpreter<'a> {
             });
         }
 
+        unsafe { try!((*(self as *mut Self)).inject_prelude(&new_path)) }
         for statement in &ast.statements {
             unsafe { try!((*(self as *mut Self)).execute_statement(statement, new_path.clone())) }
         }
@@ -457,8 +978,11 @@ impl<'a> Interpreter<'a> {
 
     fn execute_block_statement(&'a self,
                                context: *mut InterpreterContext<'a>,
-                               block_statement: &'a BlockStatement)
+                               block_statement: &BlockStatement)
                                -> Result<Value> {
+        try!(self.step(block_statement.span()));
+        try!(self.preempt());
+
         match *block_statement {
             BlockStatement::Expression(ref e) => {
                 try!(self.value_from_expression(context, e));
@@ -468,8 +992,12 @@ impl<'a> Interpreter<'a> {
                 try!(self.execute_var_decl(context, vd));
                 Ok(Value::Nil)
             }
-            BlockStatement::VarAssignment(ref lhs, ref rhs) => {
-                try!(self.execute_var_assignment(context, lhs, rhs));
+            BlockStatement::Destructure(ref d) => {
+                try!(self.execute_destructure(context, d));
+                Ok(Value::Nil)
+            }
+            BlockStatement::VarAssignment(ref va) => {
+                try!(self.execute_var_assignment(context, &va.lhs, &va.rhs));
                 Ok(Value::Nil)
             }
             BlockStatement::If(ref i) => self.execute_if(context, i),
@@ -481,7 +1009,7 @@ impl<'a> Interpreter<'a> {
 
     fn execute_forin(&'a self,
                      context: *mut InterpreterContext<'a>,
-                     forin_data: &'a ForInData)
+                     forin_data: &ForInData)
                      -> Result<Value> {
         let coll_value = try!(self.value_from_expression(context, &forin_data.collection));
         match coll_value {
@@ -489,10 +1017,19 @@ impl<'a> Interpreter<'a> {
                 self.execute_forin_array((*array_type).clone(), vec, context, forin_data)
             }
             Value::String(ref string) => self.execute_forin_string(string, context, forin_data),
+            Value::Bytes(ref bytes) => {
+                let ints: std::vec::Vec<Value> =
+                    bytes.iter().map(|&b| Value::Integer(b as i64)).collect();
+                self.execute_forin_array(Type::Int, &ints, context, forin_data)
+            }
+            Value::Chan(ref chan_type, chan) => {
+                self.execute_forin_chan(chan_type.clone(), chan, context, forin_data)
+            }
             other => {
                 Err(Error {
                     kind: ErrorKind::CannotIterateOver(other),
                     span: forin_data.collection.span.clone(),
+                    backtrace: vec![],
                 })
             }
         }
@@ -502,60 +1039,82 @@ impl<'a> Interpreter<'a> {
                            array_type: Type,
                            vec: &[Value<'a>],
                            context: *mut InterpreterContext<'a>,
-                           forin_data: &'a ForInData)
+                           forin_data: &ForInData)
                            -> Result<Value> {
         for elem in vec {
-            unsafe {
-                (*context).vars.insert(forin_data.element_name.clone(),
-                                       Variable {
-                                           name: forin_data.element_name.clone(),
-                                           var_type: array_type.clone(),
-                                           value: (*elem).clone(),
-                                       });
-            }
+            try!(Self::declare(context,
+                               Variable {
+                                   name: forin_data.element_name.clone(),
+                                   var_type: array_type.clone(),
+                                   value: Box::new((*elem).clone()),
+                               },
+                               forin_data.span.clone()));
 
             let return_value = try!(self.execute_block_statements(context, &forin_data.statements));
-
-            self.execute_forin_cleanup(context, forin_data);
-
             if return_value != Value::Nil {
+                try!(self.execute_forin_cleanup(context, forin_data, &return_value));
                 return Ok(return_value);
             }
         }
 
+        try!(self.execute_forin_cleanup(context, forin_data, &Value::Nil));
         Ok(Value::Nil)
     }
 
     fn execute_forin_string(&'a self,
                             string: &str,
                             context: *mut InterpreterContext<'a>,
-                            forin_data: &'a ForInData)
+                            forin_data: &ForInData)
                             -> Result<Value> {
         for c in string.chars() {
-            unsafe {
-                (*context).vars.insert(forin_data.element_name.clone(),
-                                       Variable {
-                                           name: forin_data.element_name.clone(),
-                                           var_type: Type::Char,
-                                           value: Value::Char(c),
-                                       });
-            }
+            try!(Self::declare(context,
+                               Variable {
+                                   name: forin_data.element_name.clone(),
+                                   var_type: Type::Char,
+                                   value: Box::new(Value::Char(c)),
+                               },
+                               forin_data.span.clone()));
 
             let return_value = try!(self.execute_block_statements(context, &forin_data.statements));
+            if return_value != Value::Nil {
+                try!(self.execute_forin_cleanup(context, forin_data, &return_value));
+                return Ok(return_value);
+            }
+        }
 
-            self.execute_forin_cleanup(context, forin_data);
+        try!(self.execute_forin_cleanup(context, forin_data, &Value::Nil));
+        Ok(Value::Nil)
+    }
+
+    fn execute_forin_chan(&'a self,
+                          chan_type: Type,
+                          chan: usize,
+                          context: *mut InterpreterContext<'a>,
+                          forin_data: &ForInData)
+                          -> Result<Value> {
+        while let Some(value) = try!(self.chan_recv(chan, forin_data.collection.span.clone())) {
+            try!(Self::declare(context,
+                               Variable {
+                                   name: forin_data.element_name.clone(),
+                                   var_type: chan_type.clone(),
+                                   value: Box::new(value),
+                               },
+                               forin_data.span.clone()));
 
+            let return_value = try!(self.execute_block_statements(context, &forin_data.statements));
             if return_value != Value::Nil {
+                try!(self.execute_forin_cleanup(context, forin_data, &return_value));
                 return Ok(return_value);
             }
         }
 
+        try!(self.execute_forin_cleanup(context, forin_data, &Value::Nil));
         Ok(Value::Nil)
     }
 
     fn execute_block_statements(&'a self,
                                 context: *mut InterpreterContext<'a>,
-                                statements: &'a [BlockStatement])
+                                statements: &[BlockStatement])
                                 -> Result<Value> {
         for statement in statements {
             let return_value = try!(self.execute_block_statement(context, statement));
@@ -567,12 +1126,21 @@ impl<'a> Interpreter<'a> {
         Ok(Value::Nil)
     }
 
+    /// Drops the element variable at the end of the loop, which nothing may reference anymore
     fn execute_forin_cleanup(&'a self,
                              context: *mut InterpreterContext<'a>,
-                             forin_data: &'a ForInData) {
+                             forin_data: &ForInData,
+                             result: &Value<'a>)
+                             -> Result<()> {
+        let name = AsRef::<str>::as_ref(&forin_data.element_name[..]);
         unsafe {
-            (*context).vars.remove(AsRef::<str>::as_ref(&forin_data.element_name[..]));
+            if let Some(var) = (*context).vars.get(name) {
+                try!(Self::check_not_referenced(var, result, context, forin_data.span.clone()));
+            }
+            (*context).vars.remove(name);
         }
+
+        Ok(())
     }
 
     fn execute_return(&'a self,
@@ -586,6 +1154,7 @@ impl<'a> Interpreter<'a> {
                     return Err(Error {
                         kind: ErrorKind::UnexpectedExprReturn,
                         span: return_data.span.clone(),
+                        backtrace: vec![],
                     });
                 }
 
@@ -602,6 +1171,7 @@ impl<'a> Interpreter<'a> {
                         return Err(Error {
                             kind: ErrorKind::ExpectedExprReturn,
                             span: return_data.span.clone(),
+                            backtrace: vec![],
                         })
                     }
                 };
@@ -611,6 +1181,7 @@ impl<'a> Interpreter<'a> {
                     return Err(Error {
                         kind: ErrorKind::MismatchedTypes(value_type, (*t).clone()),
                         span: span,
+                        backtrace: vec![],
                     });
                 }
 
@@ -627,26 +1198,215 @@ impl<'a> Interpreter<'a> {
                          -> Result<Value> {
         fn is_builtin_func(func: &Expression, name: &str) -> bool {
             match func.expr {
-                Expression_::Variable(ref p) => {
-                    if p.parts.len() == 1 {
-                        p.parts.get(0).unwrap().ident == name
-                    } else {
-                        false
-                    }
-                }
+                Expression_::Variable(ref p) => p.to_string() == name,
                 _ => false,
             }
         }
 
+        if args.iter().any(|arg| match arg.expr {
+            Expression_::Spread(_) => true,
+            _ => false,
+        }) {
+            return self.execute_func_call_spread(context, func, args, span);
+        }
+
+        if let Expression_::Field(ref receiver, ref name) = func.expr {
+            if !try!(self.has_method(context, receiver, name)) {
+                return self.execute_func_call_ufcs(context, receiver, name, args, span);
+            }
+        }
+
         if is_builtin_func(func, "print") {
             self.builtin_print(context, args, span)
         } else if is_builtin_func(func, "readln") {
             self.builtin_readln(args, span)
+        } else if is_builtin_func(func, "join") {
+            self.builtin_join(context, args, span)
+        } else if is_builtin_func(func, "chan::new") {
+            self.builtin_chan_new(args, span)
+        } else if is_builtin_func(func, "send") {
+            self.builtin_send(context, args, span)
+        } else if is_builtin_func(func, "recv") {
+            self.builtin_recv(context, args, span)
+        } else if is_builtin_func(func, "close") {
+            self.builtin_close(context, args, span)
+        } else if is_builtin_func(func, "string::builder") {
+            self.builtin_builder_new(args, span)
+        } else if is_builtin_func(func, "string::append") {
+            self.builtin_append(context, args, span)
+        } else if is_builtin_func(func, "string::build") {
+            self.builtin_build(context, args, span)
+        } else if is_builtin_func(func, "set::insert") {
+            self.builtin_set_insert(context, args, span)
+        } else if is_builtin_func(func, "set::remove") {
+            self.builtin_set_remove(context, args, span)
+        } else if is_builtin_func(func, "set::contains") {
+            self.builtin_set_contains(context, args, span)
+        } else if is_builtin_func(func, "bytes::from_string") {
+            self.builtin_bytes_from_string(context, args, span)
+        } else if is_builtin_func(func, "bytes::to_string") {
+            self.builtin_bytes_to_string(context, args, span)
+        } else if is_builtin_func(func, "panic") {
+            self.builtin_panic(context, args, span)
+        } else if is_builtin_func(func, "int::parse") {
+            self.builtin_int_parse(context, args, span)
+        } else if is_builtin_func(func, "to_string") {
+            self.builtin_to_string(context, args, span)
+        } else if is_builtin_func(func, "typeof") {
+            self.builtin_typeof(context, args, span)
+        } else if is_builtin_func(func, "fields") {
+            self.builtin_fields(context, args, span)
+        } else if is_builtin_func(func, "dump") {
+            self.builtin_dump(context, args, span)
+        } else if is_builtin_func(func, "io::read_file") {
+            self.builtin_read_file(context, args, span)
+        } else if is_builtin_func(func, "net::request") {
+            self.builtin_request(context, args, span)
+        } else if is_builtin_func(func, "sync::mutex::new") {
+            self.builtin_mutex_new(args, span)
+        } else if is_builtin_func(func, "sync::lock") {
+            self.builtin_lock(context, args, span)
+        } else if is_builtin_func(func, "sync::unlock") {
+            self.builtin_unlock(context, args, span)
+        } else if is_builtin_func(func, "sync::waitgroup::new") {
+            self.builtin_waitgroup_new(args, span)
+        } else if is_builtin_func(func, "sync::add") {
+            self.builtin_add(context, args, span)
+        } else if is_builtin_func(func, "sync::done") {
+            self.builtin_done(context, args, span)
+        } else if is_builtin_func(func, "sync::wait") {
+            self.builtin_wait(context, args, span)
+        } else if is_builtin_func(func, "sync::atomic::new") {
+            self.builtin_atomic_new(context, args, span)
+        } else if is_builtin_func(func, "sync::load") {
+            self.builtin_load(context, args, span)
+        } else if is_builtin_func(func, "sync::store") {
+            self.builtin_store(context, args, span)
+        } else if is_builtin_func(func, "sync::fetch_add") {
+            self.builtin_fetch_add(context, args, span)
+        } else if let Some((binop, overflow)) = integer_builtin(func) {
+            self.builtin_integer_op(context, args, span, &binop, overflow)
         } else {
             self.execute_func_call_user(context, func, args, span)
         }
     }
 
+    /// Whether the receiver is a struct with a func in the field, in which case calling
+    /// `receiver.name()` calls that func rather than a free func
+    fn has_method(&'a self,
+                  context: *mut InterpreterContext<'a>,
+                  receiver: &Expression,
+                  name: &SpannedString)
+                  -> Result<bool> {
+        match self.value_p_from_expression(context, receiver) {
+            Ok(value) => {
+                unsafe {
+                    match *value {
+                        Value::Struct(_, ref fields) => {
+                            match fields.map.get(&name.ident) {
+                                Some(&Value::Func(_, _)) => Ok(true),
+                                _ => Ok(false),
+                            }
+                        }
+                        _ => Ok(false),
+                    }
+                }
+            }
+            // Temporaries such as call results can't be called through their fields
+            Err(Error { kind: ErrorKind::CannotGetRef(_), .. }) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Calls the func with spread arguments expanded. The arguments are evaluated once, left to
+    /// right, into hidden variables of the caller's context, which the call is then given as
+    /// plain arguments, so builtins and user funcs don't have to know about spreads.
+    fn execute_func_call_spread(&'a self,
+                                context: *mut InterpreterContext<'a>,
+                                func: &Expression,
+                                args: &[Box<Expression>],
+                                span: Span)
+                                -> Result<Value> {
+        let mut values: std::vec::Vec<(Value, Span)> = vec![];
+        for arg in args {
+            match arg.expr {
+                Expression_::Spread(ref spread) => {
+                    let (_, items) = try!(self.spread_items(context, spread));
+                    values.extend(items.into_iter().map(|item| (item, arg.span.clone())));
+                }
+                _ => {
+                    let value = try!(self.value_from_expression(context, arg));
+                    values.push((value, arg.span.clone()))
+                }
+            }
+        }
+        let mut variables: std::vec::Vec<(Value, Type, Span)> = vec![];
+        for (value, arg_span) in values {
+            let var_type = try!(Self::type_from_value(&value, arg_span.clone()));
+            variables.push((value, var_type, arg_span));
+        }
+
+        let mut names: std::vec::Vec<String> = vec![];
+        let mut expanded: std::vec::Vec<Box<Expression>> = vec![];
+        for (value, var_type, arg_span) in variables {
+            // Not a valid identifier, so it can't clash with a variable of the program
+            let name = format!("...{}", self.spread_args.get());
+            self.spread_args.set(self.spread_args.get() + 1);
+            unsafe {
+                (*context).vars.insert(name.clone(),
+                                       Variable {
+                                           name: name.clone(),
+                                           var_type: var_type,
+                                           value: Box::new(value),
+                                       });
+            }
+            expanded.push(Box::new(Expression {
+                id: NodeId::nil_id(),
+                span: arg_span.clone(),
+                expr: Expression_::Variable(Path {
+                    span: arg_span.clone(),
+                    parts: vec![SpannedString {
+                                    span: arg_span,
+                                    ident: name.clone(),
+                                }],
+                }),
+            }));
+            names.push(name);
+        }
+
+        let result = self.execute_func_call(context, func, &expanded, span);
+        for name in names {
+            unsafe {
+                (*context).vars.remove(&name);
+            }
+        }
+
+        result
+    }
+
+    /// Calls `name(receiver, args...)` for `receiver.name(args...)`
+    fn execute_func_call_ufcs(&'a self,
+                              context: *mut InterpreterContext<'a>,
+                              receiver: &Expression,
+                              name: &SpannedString,
+                              args: &[Box<Expression>],
+                              span: Span)
+                              -> Result<Value> {
+        let func = Expression {
+            id: NodeId::nil_id(),
+            span: name.span.clone(),
+            expr: Expression_::Variable(Path {
+                span: name.span.clone(),
+                parts: vec![name.clone()],
+            }),
+        };
+
+        let mut ufcs_args = vec![Box::new(receiver.clone())];
+        ufcs_args.extend(args.iter().cloned());
+
+        self.execute_func_call(context, &func, &ufcs_args, span)
+    }
+
     fn execute_func_call_user(&'a self,
                               context: *mut InterpreterContext<'a>,
                               func: &Expression,
@@ -654,10 +1414,15 @@ impl<'a> Interpreter<'a> {
                               span: Span)
                               -> Result<Value> {
         let (path, func_decl) = try!(self.execute_func_call_resolve(context, func));
+        if func_decl.is_extern {
+            return self.execute_func_call_extern(context, func_decl, args, span);
+        }
 
         let mut local_context = InterpreterContext {
             vars: std::collections::HashMap::new(),
             current_path: path,
+            spawned: vec![],
+            parent: context,
         };
 
         try!(self.execute_func_call_initialize(func_decl,
@@ -665,9 +1430,21 @@ impl<'a> Interpreter<'a> {
                                                context,
                                                func,
                                                args,
-                                               span));
+                                               span.clone()));
+
+        if func_decl.is_async {
+            return Ok(self.start_task(context, local_context, &func_decl.statements, span));
+        }
 
-        self.execute_func_call_statements(func_decl, &mut local_context)
+        try!(self.enter_call(&span));
+        let result = self.execute_func_call_statements(func_decl, &mut local_context);
+        self.leave_call();
+        let return_value = try!(result.map_err(|e| e.in_frame(&func_decl.name, &span)));
+        try!(self.join_spawned(&mut local_context)
+            .map_err(|e| e.in_frame(&func_decl.name, &span)));
+        try!(Self::check_no_dangling_reference(&return_value, &local_context, span));
+
+        Ok(return_value)
     }
 
     fn execute_func_call_resolve(&'a self,
@@ -681,6 +1458,7 @@ impl<'a> Interpreter<'a> {
                     Err(Error {
                         kind: ErrorKind::CannotCallNonFunction,
                         span: func.span.clone(),
+                        backtrace: vec![],
                     })
                 }
             }
@@ -700,6 +1478,7 @@ impl<'a> Interpreter<'a> {
             return Err(Error {
                 kind: ErrorKind::InvalidArgCount,
                 span: func.span.clone(),
+                backtrace: vec![],
             });
         }
 
@@ -714,24 +1493,27 @@ impl<'a> Interpreter<'a> {
                     return Err(Error {
                         kind: ErrorKind::ExpectedArgument(param.name.clone()),
                         span: span,
+                        backtrace: vec![],
                     });
                 }
             };
 
             let value = try!(self.value_from_expression(context, expression));
+            let value = Self::with_chan_type(value, &param.param_type);
             let value_type = try!(Self::type_from_value(&value, expression.span.clone()));
 
             if value_type != param.param_type {
                 return Err(Error {
                     kind: ErrorKind::MismatchedTypes(value_type, param.param_type.clone()),
                     span: expression.span.clone(),
+                    backtrace: vec![],
                 });
             }
 
             let variable = Variable {
                 name: param.name.clone(),
                 var_type: param.param_type.clone(),
-                value: value,
+                value: Box::new(value),
             };
 
             local_context.vars.insert(param.name.clone(), variable);
@@ -741,7 +1523,7 @@ impl<'a> Interpreter<'a> {
     }
 
     fn execute_func_call_statements(&'a self,
-                                    func_decl: &'a FuncDeclData,
+                                    func_decl: &FuncDeclData,
                                     local_context: &mut InterpreterContext<'a>)
                                     -> Result<Value> {
         let mut return_value: Value = Value::Nil;
@@ -765,7 +1547,7 @@ impl<'a> Interpreter<'a> {
 
     fn execute_var_decl(&'a self,
                         context: *mut InterpreterContext<'a>,
-                        var_decl_data: &'a VarDeclData)
+                        var_decl_data: &VarDeclData)
                         -> Result<()> {
         let span: Span;
         let value = match var_decl_data.value {
@@ -778,6 +1560,7 @@ impl<'a> Interpreter<'a> {
                 try!(self.default_value(var_decl_data.var_type.clone(), var_decl_data.span.clone()))
             }
         };
+        let value = Self::with_chan_type(value, &var_decl_data.var_type);
 
         let value_type = try!(Self::type_from_value(&value, span.clone()));
 
@@ -785,18 +1568,80 @@ impl<'a> Interpreter<'a> {
             return Err(Error {
                 kind: ErrorKind::MismatchedTypes(value_type, var_decl_data.var_type.clone()),
                 span: span,
+                backtrace: vec![],
             });
         }
 
         let variable = Variable {
             name: var_decl_data.name.clone(),
             var_type: var_decl_data.var_type.clone(),
-            value: value,
+            value: Box::new(value),
         };
 
-        unsafe {
-            (*context).vars.insert(var_decl_data.name.clone(), variable);
-        };
+        Self::declare(context, variable, var_decl_data.span.clone())
+    }
+
+    fn execute_destructure(&'a self,
+                           context: *mut InterpreterContext<'a>,
+                           destructure_data: &DestructureData)
+                           -> Result<()> {
+        let value = try!(self.value_from_expression(context, &destructure_data.value));
+        let span = destructure_data.value.span.clone();
+
+        let mut variables: std::vec::Vec<(&SpannedString, Value)> = vec![];
+        match destructure_data.pattern {
+            Pattern::Array(ref names) => {
+                let items = match value {
+                    Value::Array(_, items) => items,
+                    value => {
+                        let value_type = try!(Self::type_from_value(&value, span.clone()));
+                        return Err(Error {
+                            kind: ErrorKind::CannotDestructure(value_type),
+                            span: span,
+                            backtrace: vec![],
+                        });
+                    }
+                };
+                if items.len() != names.len() {
+                    return Err(Error {
+                        kind: ErrorKind::DestructureCount(names.len(), items.len()),
+                        span: span,
+                        backtrace: vec![],
+                    });
+                }
+                variables.extend(names.iter().zip(items));
+            }
+            Pattern::Struct(ref path, ref names) => {
+                let value_type = try!(Self::type_from_value(&value, span.clone()));
+                let struct_type = Type::Struct(path.clone());
+                if value_type != struct_type {
+                    return Err(Error {
+                        kind: ErrorKind::MismatchedTypes(value_type, struct_type),
+                        span: span,
+                        backtrace: vec![],
+                    });
+                }
+                if let Value::Struct(_, ref fields) = value {
+                    for name in names {
+                        match fields.map.get(&name.ident) {
+                            Some(field) => variables.push((name, field.clone())),
+                            None => return Err(self.unknown_struct_field(path, name)),
+                        }
+                    }
+                }
+            }
+        }
+
+        for (name, value) in variables {
+            let var_type = try!(Self::type_from_value(&value, name.span.clone()));
+            try!(Self::declare(context,
+                               Variable {
+                                   name: name.ident.clone(),
+                                   var_type: var_type,
+                                   value: Box::new(value),
+                               },
+                               name.span.clone()));
+        }
 
         Ok(())
     }
@@ -806,21 +1651,27 @@ impl<'a> Interpreter<'a> {
                               lhs: &Expression,
                               rhs: &Expression)
                               -> Result<()> {
-        let lhs_value_ref = try!(self.value_mut_p_from_expression(context, &lhs));
-
+        // The value first, since other tasks may run while it is evaluated and move the place
         let rhs_value = try!(self.value_from_expression(context, &rhs));
 
+        let lhs_value_ref = try!(self.value_mut_p_from_expression(context, &lhs));
+
         let value_type = try!(Self::type_from_value(&rhs_value, rhs.span.clone()));
         let current_type = try!(Self::type_from_value(lhs_value_ref, lhs.span.clone()));
+        try!(Self::check_no_escaping_reference(lhs_value_ref,
+                                               &rhs_value,
+                                               context,
+                                               rhs.span.clone()));
 
         if value_type != current_type {
             return Err(Error {
                 kind: ErrorKind::MismatchedTypes(value_type, current_type),
                 span: rhs.span.clone(),
+                backtrace: vec![],
             });
         } else {
             unsafe {
-                *lhs_value_ref = rhs_value;
+                Self::assign(&mut *lhs_value_ref, rhs_value);
             }
         };
 
@@ -829,7 +1680,7 @@ impl<'a> Interpreter<'a> {
 
     fn execute_if(&'a self,
                   context: *mut InterpreterContext<'a>,
-                  if_data: &'a IfData)
+                  if_data: &IfData)
                   -> Result<Value> {
         match try!(self.value_from_expression(context, &if_data.condition)) {
             Value::Bool(b) => {
@@ -856,6 +1707,7 @@ impl<'a> Interpreter<'a> {
                                                                                     .span
                                                                                     .clone()))),
                     span: if_data.condition.span.clone(),
+                    backtrace: vec![],
                 })
             }
         }
@@ -863,9 +1715,11 @@ impl<'a> Interpreter<'a> {
 
     fn execute_while(&'a self,
                      context: *mut InterpreterContext<'a>,
-                     while_data: &'a WhileData)
+                     while_data: &WhileData)
                      -> Result<Value> {
         loop {
+            // Spinning on a condition another task changes lets it run
+            try!(self.preempt());
             match try!(self.value_from_expression(context, &while_data.condition)) {
                 Value::Bool(b) => {
                     if b {
@@ -878,7 +1732,7 @@ impl<'a> Interpreter<'a> {
                         return Ok(Value::Nil);
                     }
                 }
-                other => return Err(Error { kind: ErrorKind::MismatchedTypes(Type::Bool, try!(Self::type_from_value(&other, while_data.condition.span.clone()))), span: while_data.condition.span.clone()}),
+                other => return Err(Error { kind: ErrorKind::MismatchedTypes(Type::Bool, try!(Self::type_from_value(&other, while_data.condition.span.clone()))), span: while_data.condition.span.clone(), backtrace: vec![] }),
             }
         }
     }
@@ -892,7 +1746,7 @@ impl<'a> Interpreter<'a> {
                 if let Some(ref mut var) = (*context)
                     .vars
                     .get_mut(AsRef::<str>::as_ref(&p.parts.get(0).unwrap().ident[..])) {
-                    return Ok(&mut var.value);
+                    return Ok(&mut *var.value);
                 }
             }
         };
@@ -903,6 +1757,7 @@ impl<'a> Interpreter<'a> {
                 return Err(Error {
                     kind: ErrorKind::CannotMutablyRefFunction,
                     span: p.span.clone(),
+                    backtrace: vec![],
                 });
             }
 
@@ -919,14 +1774,12 @@ impl<'a> Interpreter<'a> {
                 return Err(Error {
                     kind: ErrorKind::CannotMutablyRefFunction,
                     span: p.span.clone(),
+                    backtrace: vec![],
                 });
             };
         }
 
-        Err(Error {
-            kind: ErrorKind::UnknownVariable(p.parts.clone()),
-            span: p.span.clone(),
-        })
+        Err(self.unknown_variable(context, &p))
     }
 
     fn value_mut_p_from_expression_index(&'a self,
@@ -937,26 +1790,19 @@ impl<'a> Interpreter<'a> {
                                          -> Result<*mut Value> {
         match *index {
             Some(ref e) => {
-                let indexed_value_p = try!(self.value_mut_p_from_expression(context, indexed));
-
                 let index_value = try!(self.value_from_expression(context, e));
 
+                let indexed_value_p = try!(self.value_mut_p_from_expression(context, indexed));
+
                 unsafe {
                     match *indexed_value_p {
                         Value::Array(_, ref mut a) => {
                             match index_value {
                                 Value::Integer(i) => {
-                                    match a.get_mut(i as usize) {
-                                        Some(v) => Ok(v),
-                                        None => {
-                                            Err(Error {
-                                                kind: ErrorKind::IndexOutOfBounds,
-                                                span: e.span.clone(),
-                                            })
-                                        }
-                                    }
+                                    let position = try!(Self::item_position(i, a.len(), &e.span));
+                                    Ok(&mut a[position])
                                 }
-                                other => Err(Error { kind: ErrorKind::MismatchedTypes(Type::Int, try!(Self::type_from_value(&other, e.span.clone()))), span: e.span.clone()}),
+                                other => Err(Error { kind: ErrorKind::MismatchedTypes(Type::Int, try!(Self::type_from_value(&other, e.span.clone()))), span: e.span.clone(), backtrace: vec![] }),
                             }
                         }
                         Value::Map(ref t, _, ref mut m) => {
@@ -967,14 +1813,16 @@ impl<'a> Interpreter<'a> {
                                 Err(Error {
                                     kind: ErrorKind::MismatchedTypes((*t).clone(), index_type),
                                     span: e.span.clone(),
+                                    backtrace: vec![],
                                 })
                             } else {
                                 match m.map.get_mut(&index_value) {
                                     Some(v) => Ok(v),
                                     None => {
                                         Err(Error {
-                                            kind: ErrorKind::UnknownIndex(index_value),
+                                            kind: ErrorKind::MissingKey(self.value_source(&index_value)),
                                             span: e.span.clone(),
+                                            backtrace: vec![],
                                         })
                                     }
                                 }
@@ -984,6 +1832,7 @@ impl<'a> Interpreter<'a> {
                             Err(Error {
                                 kind: ErrorKind::CannotIndexNonIndexable,
                                 span: span.clone(),
+                                backtrace: vec![],
                             })
                         }
                     }
@@ -996,9 +1845,10 @@ impl<'a> Interpreter<'a> {
                 unsafe {
                     match *indexed_value_ref {
                         Value::Array(ref t, ref mut a) => {
+                            try!(self.allocate_bytes(std::mem::size_of::<Value>(), &indexed.span));
                             a.push(
                                 match *t {
-                                    Type::None => return Err(Error { kind: ErrorKind::CannotPushToUntypedArray, span: indexed.span.clone()}),
+                                    Type::None => return Err(Error { kind: ErrorKind::CannotPushToUntypedArray, span: indexed.span.clone(), backtrace: vec![] }),
                                     ref array_type => try!(self.default_value((*array_type).clone(), indexed.span.clone())),
                                 }
                             );
@@ -1010,6 +1860,7 @@ impl<'a> Interpreter<'a> {
                             Err(Error {
                                 kind: ErrorKind::CannotPushToNonArray,
                                 span: indexed.span.clone(),
+                                backtrace: vec![],
                             })
                         }
                     }
@@ -1027,21 +1878,17 @@ impl<'a> Interpreter<'a> {
         let struct_value_ref = try!(self.value_mut_p_from_expression(context, struct_expr));
         unsafe {
             match *struct_value_ref {
-                Value::Struct(_, ref mut fields) => {
+                Value::Struct(ref path, ref mut fields) => {
                     match fields.map.get_mut(&field.ident) {
                         Some(f) => Ok(f.borrow_mut()),
-                        None => {
-                            Err(Error {
-                                kind: ErrorKind::UnknownStructField(field.ident.clone()),
-                                span: field.span.clone(),
-                            })
-                        }
+                        None => Err(self.unknown_struct_field(path, field)),
                     }
                 }
                 _ => {
                     Err(Error {
                         kind: ErrorKind::CannotAccessFieldOnNonStruct,
                         span: span.clone(),
+                        backtrace: vec![],
                     })
                 }
             }
@@ -1062,12 +1909,14 @@ impl<'a> Interpreter<'a> {
                         Err(Error {
                             kind: ErrorKind::CannotDerefConstRefInMutContext,
                             span: expression.span.clone(),
+                            backtrace: vec![],
                         })
                     }
                     _ => {
                         Err(Error {
                             kind: ErrorKind::CannotDerefNonRef,
                             span: expression.span.clone(),
+                            backtrace: vec![],
                         })
                     }
                 }
@@ -1076,6 +1925,7 @@ impl<'a> Interpreter<'a> {
                 Err(Error {
                     kind: ErrorKind::CannotGetMutRef(expression.expr.clone()),
                     span: expression.span.clone(),
+                    backtrace: vec![],
                 })
             }
         }
@@ -1112,6 +1962,7 @@ impl<'a> Interpreter<'a> {
                 Err(Error {
                     kind: ErrorKind::CannotGetMutRef(expression.expr.clone()),
                     span: expression.span.clone(),
+                    backtrace: vec![],
                 })
             }
         }
@@ -1126,7 +1977,7 @@ impl<'a> Interpreter<'a> {
                 if let Some(ref var) = (*context)
                     .vars
                     .get(AsRef::<str>::as_ref(&p.parts.get(0).unwrap().ident[..])) {
-                    return Ok(&var.value);
+                    return Ok(&*var.value);
                 }
             }
         };
@@ -1154,10 +2005,7 @@ impl<'a> Interpreter<'a> {
             }
         }
 
-        Err(Error {
-            kind: ErrorKind::UnknownVariable(p.parts.clone()),
-            span: p.span.clone(),
-        })
+        Err(self.unknown_variable(context, &p))
     }
 
     fn value_p_from_expression_index(&'a self,
@@ -1168,26 +2016,19 @@ impl<'a> Interpreter<'a> {
                                      -> Result<*const Value> {
         match *index {
             Some(ref e) => {
-                let indexed_value_ref = try!(self.value_p_from_expression(context, indexed));
-
                 let index_value = try!(self.value_from_expression(context, e));
 
+                let indexed_value_ref = try!(self.value_p_from_expression(context, indexed));
+
                 unsafe {
                     match *indexed_value_ref {
                         Value::Array(_, ref a) => {
                             match index_value {
                                 Value::Integer(i) => {
-                                    match a.get(i as usize) {
-                                        Some(v) => Ok(v),
-                                        None => {
-                                            Err(Error {
-                                                kind: ErrorKind::IndexOutOfBounds,
-                                                span: e.span.clone(),
-                                            })
-                                        }
-                                    }
+                                    let position = try!(Self::item_position(i, a.len(), &e.span));
+                                    Ok(&a[position])
                                 }
-                                other => Err(Error { kind: ErrorKind::MismatchedTypes(Type::Int, try!(Self::type_from_value(&other, e.span.clone()))), span: e.span.clone()}),
+                                other => Err(Error { kind: ErrorKind::MismatchedTypes(Type::Int, try!(Self::type_from_value(&other, e.span.clone()))), span: e.span.clone(), backtrace: vec![] }),
                             }
                         }
                         Value::Map(ref t, _, ref m) => {
@@ -1198,14 +2039,16 @@ impl<'a> Interpreter<'a> {
                                 Err(Error {
                                     kind: ErrorKind::MismatchedTypes((*t).clone(), index_type),
                                     span: e.span.clone(),
+                                    backtrace: vec![],
                                 })
                             } else {
                                 match m.map.get(&index_value) {
                                     Some(v) => Ok(v),
                                     None => {
                                         Err(Error {
-                                            kind: ErrorKind::UnknownIndex(index_value),
+                                            kind: ErrorKind::MissingKey(self.value_source(&index_value)),
                                             span: e.span.clone(),
+                                            backtrace: vec![],
                                         })
                                     }
                                 }
@@ -1215,6 +2058,7 @@ impl<'a> Interpreter<'a> {
                             Err(Error {
                                 kind: ErrorKind::CannotIndexNonIndexable,
                                 span: span.clone(),
+                                backtrace: vec![],
                             })
                         }
                     }
@@ -1227,9 +2071,10 @@ impl<'a> Interpreter<'a> {
                 unsafe {
                     match *indexed_value_ref {
                         Value::Array(ref t, ref mut a) => {
+                            try!(self.allocate_bytes(std::mem::size_of::<Value>(), &indexed.span));
                             a.push(
                                 match *t {
-                                    Type::None => return Err(Error { kind: ErrorKind::CannotPushToUntypedArray, span: indexed.span.clone()}),
+                                    Type::None => return Err(Error { kind: ErrorKind::CannotPushToUntypedArray, span: indexed.span.clone(), backtrace: vec![] }),
                                     ref array_type => try!(self.default_value((*array_type).clone(), indexed.span.clone())),
                                 }
                             );
@@ -1241,6 +2086,7 @@ impl<'a> Interpreter<'a> {
                             Err(Error {
                                 kind: ErrorKind::CannotPushToNonArray,
                                 span: indexed.span.clone(),
+                                backtrace: vec![],
                             })
                         }
                     }
@@ -1259,21 +2105,17 @@ impl<'a> Interpreter<'a> {
 
         unsafe {
             match *struct_value_ref {
-                Value::Struct(_, ref fields) => {
+                Value::Struct(ref path, ref fields) => {
                     match fields.map.get(&field.ident) {
                         Some(f) => Ok(f.borrow()),
-                        None => {
-                            Err(Error {
-                                kind: ErrorKind::UnknownStructField(field.ident.clone()),
-                                span: field.span.clone(),
-                            })
-                        }
+                        None => Err(self.unknown_struct_field(path, field)),
                     }
                 }
                 _ => {
                     Err(Error {
                         kind: ErrorKind::CannotAccessFieldOnNonStruct,
                         span: span.clone(),
+                        backtrace: vec![],
                     })
                 }
             }
@@ -1295,6 +2137,7 @@ impl<'a> Interpreter<'a> {
                         Err(Error {
                             kind: ErrorKind::CannotDerefNonRef,
                             span: expression.span.clone(),
+                            backtrace: vec![],
                         })
                     }
                 }
@@ -1303,6 +2146,7 @@ impl<'a> Interpreter<'a> {
                 Err(Error {
                     kind: ErrorKind::CannotGetRef(expression.expr.clone()),
                     span: expression.span.clone(),
+                    backtrace: vec![],
                 })
             }
         }
@@ -1334,6 +2178,7 @@ impl<'a> Interpreter<'a> {
                 Err(Error {
                     kind: ErrorKind::CannotGetRef(expression.expr.clone()),
                     span: expression.span.clone(),
+                    backtrace: vec![],
                 })
             }
         }
@@ -1347,26 +2192,53 @@ impl<'a> Interpreter<'a> {
         let mut values: std::vec::Vec<Value> = vec![];
         let mut array_type: Type = Type::None;
         for item in array {
-            let value = try!(self.value_from_expression(context, item));
-            let value_type = try!(Self::type_from_value(&value, item.span.clone()));
-            match array_type {
-                Type::None => array_type = value_type,
-                ref t => {
+            let (value_type, item_values) = match item.expr {
+                Expression_::Spread(ref spread) => try!(self.spread_items(context, spread)),
+                _ => {
+                    let value = try!(self.value_from_expression(context, item));
+                    (try!(Self::type_from_value(&value, item.span.clone())), vec![value])
+                }
+            };
+            match (&array_type, value_type) {
+                // Spreading an empty array of unknown type adds nothing to check
+                (_, Type::None) => (),
+                (&Type::None, value_type) => array_type = value_type,
+                (t, value_type) => {
                     if value_type != *t {
                         return Err(Error {
                             kind: ErrorKind::HeterogeneousTypesInArray,
                             span: span.clone(),
+                            backtrace: vec![],
                         });
                     }
                 }
             };
 
-            values.push(value)
+            values.extend(item_values)
         }
 
         Ok(Value::Array(array_type, values))
     }
 
+    /// Type and items of the array a spread expands to
+    fn spread_items(&'a self,
+                    context: *mut InterpreterContext<'a>,
+                    spread: &Expression)
+                    -> Result<(Type, std::vec::Vec<Value>)> {
+        match try!(self.value_from_expression(context, spread)) {
+            Value::Array(item_type, items) => Ok((item_type, items)),
+            value => {
+                Err(Error {
+                    kind: ErrorKind::CannotSpread(try!(Self::type_from_value(&value,
+                                                                             spread.span
+                                                                                 .clone()))),
+                    span: spread.span.clone(),
+                    backtrace: vec![],
+                })
+            }
+        }
+    }
+
     fn value_from_expression_map(&'a self,
                                  map: &Map,
                                  span: Span,
@@ -1375,9 +2247,16 @@ impl<'a> Interpreter<'a> {
         let mut values = MapValue { map: std::collections::HashMap::new() };
         let mut map_type1: Type = Type::None;
         let mut map_type2: Type = Type::None;
-        for (key, value) in &map.map {
+        for &(ref key, ref value) in &map.entries {
             let key_value = try!(self.value_from_expression(context, key));
             let key_type = try!(Self::type_from_value(&key_value, key.span.clone()));
+            if !key_type.is_map_key() {
+                return Err(Error {
+                    kind: ErrorKind::InvalidMapKeyType(key_type),
+                    span: key.span.clone(),
+                    backtrace: vec![],
+                });
+            }
             let value_value = try!(self.value_from_expression(context, value));
             let value_type = try!(Self::type_from_value(&value_value, value.span.clone()));
             match (map_type1.clone(), map_type2.clone()) {
@@ -1390,17 +2269,59 @@ impl<'a> Interpreter<'a> {
                         return Err(Error {
                             kind: ErrorKind::HeterogeneousTypesInMap,
                             span: span.clone(),
+                            backtrace: vec![],
                         });
                     }
                 }
             };
 
+            if values.map.contains_key(&key_value) {
+                return Err(Error {
+                    kind: ErrorKind::DuplicateMapKey(key.to_string()),
+                    span: key.span.clone(),
+                    backtrace: vec![],
+                });
+            }
             values.map.insert(key_value, value_value);
         }
 
         Ok(Value::Map(map_type1, map_type2, values))
     }
 
+    /// Value of a set literal, duplicate items being stored once
+    fn value_from_expression_set(&'a self,
+                                 items: &[Box<Expression>],
+                                 span: Span,
+                                 context: *mut InterpreterContext<'a>)
+                                 -> Result<Value> {
+        let mut values = SetValue { set: std::collections::HashSet::new() };
+        let mut set_type: Type = Type::None;
+        for item in items {
+            let item_value = try!(self.value_from_expression(context, item));
+            let item_type = try!(Self::type_from_value(&item_value, item.span.clone()));
+            if !item_type.is_map_key() {
+                return Err(Error {
+                    kind: ErrorKind::InvalidSetItemType(item_type),
+                    span: item.span.clone(),
+                    backtrace: vec![],
+                });
+            }
+            if set_type == Type::None {
+                set_type = item_type;
+            } else if item_type != set_type {
+                return Err(Error {
+                    kind: ErrorKind::HeterogeneousTypesInSet,
+                    span: span.clone(),
+                    backtrace: vec![],
+                });
+            }
+
+            values.set.insert(item_value);
+        }
+
+        Ok(Value::Set(set_type, values))
+    }
+
     fn value_from_expression_structinit(&'a self,
                                         path: Path,
                                         fields: &[StructInitFieldData],
@@ -1409,12 +2330,7 @@ impl<'a> Interpreter<'a> {
                                         -> Result<Value> {
         let struct_decl = match self.structs.get(&path) {
             Some(s) => s,
-            None => {
-                return Err(Error {
-                    kind: ErrorKind::UnknownStruct(path.parts.clone()),
-                    span: path.span.clone(),
-                })
-            }
+            None => return Err(self.unknown_struct(&path, path.span.clone())),
         };
 
         let mut new_content: std::collections::HashMap<String, Value> =
@@ -1430,10 +2346,9 @@ impl<'a> Interpreter<'a> {
             }
 
             if !found_field {
-                return Err(Error {
-                    kind: ErrorKind::UnknownStructField(new_field.name.ident.clone()),
-                    span: new_field.span.clone(),
-                });
+                let mut error = self.unknown_struct_field(&path, &new_field.name);
+                error.span = new_field.span.clone();
+                return Err(error);
             }
         }
 
@@ -1449,6 +2364,7 @@ impl<'a> Interpreter<'a> {
                         return Err(Error {
                             kind: ErrorKind::MismatchedTypes(value_type, field.field_type.clone()),
                             span: new_field.span.clone(),
+                            backtrace: vec![],
                         });
                     } else {
                         new_content.insert(field.name.clone(), value);
@@ -1462,6 +2378,7 @@ impl<'a> Interpreter<'a> {
                 return Err(Error {
                     kind: ErrorKind::MissingStructField(field.name.clone()),
                     span: span.clone(),
+                    backtrace: vec![],
                 });
             }
         }
@@ -1476,26 +2393,37 @@ impl<'a> Interpreter<'a> {
                                    context: *mut InterpreterContext<'a>)
                                    -> Result<Value> {
         match try!(self.value_from_expression(context, struct_expr)) {
-            Value::Struct(_, ref fields) => {
+            Value::Struct(ref path, ref fields) => {
                 match fields.map.get(&field.ident) {
                     Some(f) => Ok((*f).clone()),
-                    None => {
-                        Err(Error {
-                            kind: ErrorKind::UnknownStructField(field.ident.clone()),
-                            span: field.span.clone(),
-                        })
-                    }
+                    None => Err(self.unknown_struct_field(path, field)),
                 }
             }
             _ => {
                 Err(Error {
                     kind: ErrorKind::CannotAccessFieldOnNonStruct,
                     span: span.clone(),
+                    backtrace: vec![],
                 })
             }
         }
     }
 
+    /// Position of the item at the index among `len` items, a negative index counting from the
+    /// end so that `-1` is the last item
+    fn item_position(index: i64, len: usize, span: &Span) -> Result<'a, usize> {
+        let position = if index < 0 { index + len as i64 } else { index };
+        if position < 0 || position >= len as i64 {
+            return Err(Error {
+                kind: ErrorKind::IndexOutOfBounds(index, len),
+                span: span.clone(),
+                backtrace: vec![],
+            });
+        }
+
+        Ok(position as usize)
+    }
+
     fn value_from_expression_index(&'a self,
                                    indexed: &Expression,
                                    index: &Option<Box<Expression>>,
@@ -1504,26 +2432,36 @@ impl<'a> Interpreter<'a> {
                                    -> Result<Value> {
         match *index {
             Some(ref e) => {
-                let indexed_value_ref = try!(self.value_p_from_expression(context, indexed));
-
                 let index_value = try!(self.value_from_expression(context, e));
 
+                let indexed_value_ref = try!(self.value_p_from_expression(context, indexed));
+
                 unsafe {
                     match *indexed_value_ref {
                         Value::Array(_, ref a) => {
                             match index_value {
                                 Value::Integer(i) => {
-                                    match a.get(i as usize) {
-                                        Some(v) => Ok((*v).clone()),
-                                        None => {
-                                            Err(Error {
-                                                kind: ErrorKind::IndexOutOfBounds,
-                                                span: e.span.clone(),
-                                            })
-                                        }
-                                    }
+                                    let position = try!(Self::item_position(i, a.len(), &e.span));
+                                    Ok(a[position].clone())
+                                }
+                                other => Err(Error { kind: ErrorKind::MismatchedTypes(Type::Int, try!(Self::type_from_value(&other, e.span.clone()))), span: e.span.clone(), backtrace: vec![] }),
+                            }
+                        }
+                        Value::Bytes(ref b) => {
+                            match index_value {
+                                Value::Integer(i) => {
+                                    let position = try!(Self::item_position(i, b.len(), &e.span));
+                                    Ok(Value::Integer(b[position] as i64))
+                                }
+                                other => {
+                                    let other_type = try!(Self::type_from_value(&other,
+                                                                                e.span.clone()));
+                                    Err(Error {
+                                        kind: ErrorKind::MismatchedTypes(Type::Int, other_type),
+                                        span: e.span.clone(),
+                                        backtrace: vec![],
+                                    })
                                 }
-                                other => Err(Error { kind: ErrorKind::MismatchedTypes(Type::Int, try!(Self::type_from_value(&other, e.span.clone()))), span: e.span.clone()}),
                             }
                         }
                         Value::Map(ref t, _, ref m) => {
@@ -1534,14 +2472,16 @@ impl<'a> Interpreter<'a> {
                                 Err(Error {
                                     kind: ErrorKind::MismatchedTypes((*t).clone(), index_type),
                                     span: e.span.clone(),
+                                    backtrace: vec![],
                                 })
                             } else {
                                 match m.map.get(&index_value) {
                                     Some(v) => Ok((*v).clone()),
                                     None => {
                                         Err(Error {
-                                            kind: ErrorKind::UnknownIndex(index_value),
+                                            kind: ErrorKind::MissingKey(self.value_source(&index_value)),
                                             span: e.span.clone(),
+                                            backtrace: vec![],
                                         })
                                     }
                                 }
@@ -1551,6 +2491,7 @@ impl<'a> Interpreter<'a> {
                             Err(Error {
                                 kind: ErrorKind::CannotIndexNonIndexable,
                                 span: span.clone(),
+                                backtrace: vec![],
                             })
                         }
                     }
@@ -1563,9 +2504,10 @@ impl<'a> Interpreter<'a> {
                 unsafe {
                     match *indexed_value_ref {
                         Value::Array(ref t, ref mut a) => {
+                            try!(self.allocate_bytes(std::mem::size_of::<Value>(), &indexed.span));
                             a.push(
                                 match *t {
-                                    Type::None => return Err(Error { kind: ErrorKind::CannotPushToUntypedArray, span: indexed.span.clone()}),
+                                    Type::None => return Err(Error { kind: ErrorKind::CannotPushToUntypedArray, span: indexed.span.clone(), backtrace: vec![] }),
                                     ref array_type => try!(self.default_value((*array_type).clone(), indexed.span.clone())),
                                 }
                             );
@@ -1577,6 +2519,7 @@ impl<'a> Interpreter<'a> {
                             Err(Error {
                                 kind: ErrorKind::CannotPushToNonArray,
                                 span: indexed.span.clone(),
+                                backtrace: vec![],
                             })
                         }
                     }
@@ -1594,19 +2537,60 @@ impl<'a> Interpreter<'a> {
                                      -> Result<Value> {
         match unop {
             UnaryOp::Reference => {
+                try!(Self::check_not_item(e));
                 Ok(Value::Reference(try!(self.value_p_from_expression(context, e))))
             }
             UnaryOp::MutReference => {
+                try!(Self::check_not_item(e));
                 Ok(Value::MutReference(try!(self.value_mut_p_from_expression(context, e))))
             }
             UnaryOp::Count => {
                 match try!(self.value_from_expression(context, e)) {
                     Value::Array(_, ref a) => Ok(Value::Integer(a.len() as i64)),
-                    Value::String(ref s) => Ok(Value::Integer(s.len() as i64)),
-                    _ => {
+                    Value::Map(_, _, ref m) => Ok(Value::Integer(m.map.len() as i64)),
+                    Value::Set(_, ref s) => Ok(Value::Integer(s.set.len() as i64)),
+                    Value::Bytes(ref b) => Ok(Value::Integer(b.len() as i64)),
+                    Value::String(ref s) => Ok(Value::Integer(s.chars().count() as i64)),
+                    other => {
+                        let other_type = try!(Self::type_from_value(&other, e.span.clone()));
                         Err(Error {
-                            kind: ErrorKind::CannotCountNonCountable,
+                            kind: ErrorKind::CannotCount(other_type),
                             span: span.clone(),
+                            backtrace: vec![],
+                        })
+                    }
+                }
+            }
+            UnaryOp::Negation => {
+                match try!(self.value_from_expression(context, e)) {
+                    Value::Integer(i) => {
+                        let overflow = self.options.overflow;
+                        let negated = try!(integer_op(&BinaryOp::Substraction,
+                                                      0,
+                                                      i,
+                                                      overflow,
+                                                      span.clone()));
+                        Ok(Value::Integer(negated))
+                    }
+                    other => {
+                        let other_type = try!(Self::type_from_value(&other, e.span.clone()));
+                        Err(Error {
+                            kind: ErrorKind::MismatchedTypes(Type::Int, other_type),
+                            span: e.span.clone(),
+                            backtrace: vec![],
+                        })
+                    }
+                }
+            }
+            UnaryOp::Not => {
+                match try!(self.value_from_expression(context, e)) {
+                    Value::Bool(b) => Ok(Value::Bool(!b)),
+                    other => {
+                        let other_type = try!(Self::type_from_value(&other, e.span.clone()));
+                        Err(Error {
+                            kind: ErrorKind::MismatchedTypes(Type::Bool, other_type),
+                            span: e.span.clone(),
+                            backtrace: vec![],
                         })
                     }
                 }
@@ -1619,6 +2603,20 @@ impl<'a> Interpreter<'a> {
                         Err(Error {
                             kind: ErrorKind::CannotDerefNonRef,
                             span: span.clone(),
+                            backtrace: vec![],
+                        })
+                    }
+                }
+            }
+            UnaryOp::Await => {
+                match try!(self.value_from_expression(context, e)) {
+                    Value::Task(t) => self.join_task(t, span),
+                    other => {
+                        let other_type = try!(Self::type_from_value(&other, e.span.clone()));
+                        Err(Error {
+                            kind: ErrorKind::MismatchedTypes(Type::Task, other_type),
+                            span: e.span.clone(),
+                            backtrace: vec![],
                         })
                     }
                 }
@@ -1636,7 +2634,7 @@ impl<'a> Interpreter<'a> {
             |e: &Expression| -> Result<'a, i64> {
                 match try!(self.value_from_expression(context, e)) {
                     Value::Integer(i) => Ok(i),
-                    other => Err(Error { kind: ErrorKind::MismatchedTypes(Type::Int, try!(Self::type_from_value(&other, e.span.clone()))), span: e.span.clone()}),
+                    other => Err(Error { kind: ErrorKind::MismatchedTypes(Type::Int, try!(Self::type_from_value(&other, e.span.clone()))), span: e.span.clone(), backtrace: vec![] }),
                 }
             };
 
@@ -1644,20 +2642,27 @@ impl<'a> Interpreter<'a> {
             |e: &Expression| -> Result<'a, String> {
                 match try!(self.value_from_expression(context, e)) {
                     Value::String(s) => Ok(s),
-                    other => Err(Error { kind: ErrorKind::MismatchedTypes(Type::String, try!(Self::type_from_value(&other, e.span.clone()))), span: e.span.clone()}),
+                    other => Err(Error { kind: ErrorKind::MismatchedTypes(Type::String, try!(Self::type_from_value(&other, e.span.clone()))), span: e.span.clone(), backtrace: vec![] }),
+                }
+            };
+
+        let get_bool =
+            |e: &Expression| -> Result<'a, bool> {
+                match try!(self.value_from_expression(context, e)) {
+                    Value::Bool(b) => Ok(b),
+                    other => Err(Error { kind: ErrorKind::MismatchedTypes(Type::Bool, try!(Self::type_from_value(&other, e.span.clone()))), span: e.span.clone(), backtrace: vec![] }),
                 }
             };
 
         match binop {
-            BinaryOp::Addition => Ok(Value::Integer(try!(get_integer(e1)) + try!(get_integer(e2)))),
-            BinaryOp::Substraction => {
-                Ok(Value::Integer(try!(get_integer(e1)) - try!(get_integer(e2))))
-            }
-            BinaryOp::Multiplication => {
-                Ok(Value::Integer(try!(get_integer(e1)) * try!(get_integer(e2))))
+            BinaryOp::Addition | BinaryOp::Substraction | BinaryOp::Multiplication |
+            BinaryOp::Division | BinaryOp::Modulo => {
+                let i1 = try!(get_integer(e1));
+                let i2 = try!(get_integer(e2));
+                let span = Span::concat(e1.span.clone(), e2.span.clone());
+
+                Ok(Value::Integer(try!(integer_op(&binop, i1, i2, self.options.overflow, span))))
             }
-            BinaryOp::Division => Ok(Value::Integer(try!(get_integer(e1)) / try!(get_integer(e2)))),
-            BinaryOp::Modulo => Ok(Value::Integer(try!(get_integer(e1)) % try!(get_integer(e2)))),
 
             BinaryOp::Concatenation => {
                 let mut new_string = String::new();
@@ -1666,17 +2671,124 @@ impl<'a> Interpreter<'a> {
                 Ok(Value::String(new_string))
             }
 
-            BinaryOp::Equality => {
+            BinaryOp::Equality | BinaryOp::Inequality => {
                 let value1 = try!(self.value_from_expression(context, e1));
                 let value2 = try!(self.value_from_expression(context, e2));
 
-                Ok(Value::Bool(value1 == value2))
+                if let (Ok(type1), Ok(type2)) =
+                    (Self::type_from_value(&value1, e1.span.clone()),
+                     Self::type_from_value(&value2, e2.span.clone())) {
+                    if type1 != type2 {
+                        return Err(Error {
+                            kind: ErrorKind::MismatchedTypes(type1, type2),
+                            span: e2.span.clone(),
+                            backtrace: vec![],
+                        });
+                    }
+                }
+
+                let equal = Self::values_equal(&value1, &value2);
+                Ok(Value::Bool(if binop == BinaryOp::Equality {
+                    equal
+                } else {
+                    !equal
+                }))
             }
-            BinaryOp::Inequality => {
+
+            BinaryOp::Less | BinaryOp::LessOrEqual | BinaryOp::More | BinaryOp::MoreOrEqual => {
                 let value1 = try!(self.value_from_expression(context, e1));
                 let value2 = try!(self.value_from_expression(context, e2));
+                let ordering = try!(Self::compare_values(&value1, &value2, e1, e2));
 
-                Ok(Value::Bool(value1 != value2))
+                Ok(Value::Bool(Self::ordering_satisfies(&binop, ordering)))
+            }
+
+            BinaryOp::And => Ok(Value::Bool(try!(get_bool(e1)) && try!(get_bool(e2)))),
+            BinaryOp::Or => Ok(Value::Bool(try!(get_bool(e1)) || try!(get_bool(e2)))),
+        }
+    }
+
+    /// `a < b <= c`, evaluating each operand at most once, from left to right, and stopping at the
+    /// first comparison which doesn't hold
+    fn value_from_comparison_chain(&'a self,
+                                   operands: &[Box<Expression>],
+                                   binops: &[BinaryOp],
+                                   context: *mut InterpreterContext<'a>)
+                                   -> Result<Value> {
+        let mut previous = try!(self.value_from_expression(context, &operands[0]));
+        for (i, binop) in binops.iter().enumerate() {
+            let value = try!(self.value_from_expression(context, &operands[i + 1]));
+            let ordering =
+                try!(Self::compare_values(&previous, &value, &operands[i], &operands[i + 1]));
+            if !Self::ordering_satisfies(binop, ordering) {
+                return Ok(Value::Bool(false));
+            }
+            previous = value;
+        }
+
+        Ok(Value::Bool(true))
+    }
+
+    /// Whether operands ordered this way satisfy the comparison operator
+    fn ordering_satisfies(binop: &BinaryOp, ordering: std::cmp::Ordering) -> bool {
+        match *binop {
+            BinaryOp::Less => ordering == std::cmp::Ordering::Less,
+            BinaryOp::LessOrEqual => ordering != std::cmp::Ordering::Greater,
+            BinaryOp::More => ordering == std::cmp::Ordering::Greater,
+            _ => ordering != std::cmp::Ordering::Less,
+        }
+    }
+
+    /// Structural equality: arrays, maps and structs are equal when their items or fields are,
+    /// whereas references and handles are equal when they designate the same thing
+    fn values_equal(value1: &Value<'a>, value2: &Value<'a>) -> bool {
+        match (value1, value2) {
+            (&Value::Array(_, ref a1), &Value::Array(_, ref a2)) => {
+                a1.len() == a2.len() &&
+                a1.iter().zip(a2.iter()).all(|(item1, item2)| Self::values_equal(item1, item2))
+            }
+            (&Value::Map(_, _, ref m1), &Value::Map(_, _, ref m2)) => {
+                m1.map.len() == m2.map.len() &&
+                m1.map.iter().all(|(key, item1)| {
+                    m2.map.get(key).map_or(false, |item2| Self::values_equal(item1, item2))
+                })
+            }
+            (&Value::Struct(ref p1, ref s1), &Value::Struct(ref p2, ref s2)) => {
+                p1 == p2 && s1.map.len() == s2.map.len() &&
+                s1.map.iter().all(|(name, field1)| {
+                    s2.map.get(name).map_or(false, |field2| Self::values_equal(field1, field2))
+                })
+            }
+            _ => value1 == value2,
+        }
+    }
+
+    /// Ordering of ints, strings (by code points) and chars
+    fn compare_values(value1: &Value<'a>,
+                      value2: &Value<'a>,
+                      e1: &Expression,
+                      e2: &Expression)
+                      -> Result<'a, std::cmp::Ordering> {
+        match (value1, value2) {
+            (&Value::Integer(i1), &Value::Integer(i2)) => Ok(i1.cmp(&i2)),
+            (&Value::String(ref s1), &Value::String(ref s2)) => Ok(s1.cmp(s2)),
+            (&Value::Char(c1), &Value::Char(c2)) => Ok(c1.cmp(&c2)),
+            _ => {
+                let type1 = try!(Self::type_from_value(value1, e1.span.clone()));
+                let type2 = try!(Self::type_from_value(value2, e2.span.clone()));
+                if type1 != type2 {
+                    Err(Error {
+                        kind: ErrorKind::MismatchedTypes(type1, type2),
+                        span: e2.span.clone(),
+                        backtrace: vec![],
+                    })
+                } else {
+                    Err(Error {
+                        kind: ErrorKind::CannotOrder(type1),
+                        span: e1.span.clone(),
+                        backtrace: vec![],
+                    })
+                }
             }
         }
     }
@@ -1685,8 +2797,20 @@ impl<'a> Interpreter<'a> {
                              context: *mut InterpreterContext<'a>,
                              expression: &Expression)
                              -> Result<Value> {
+        try!(self.step(&expression.span));
+        let value = try!(self.value_from_expression_(context, expression));
+        try!(self.allocate(&value, &expression.span));
+
+        Ok(value)
+    }
+
+    fn value_from_expression_(&'a self,
+                              context: *mut InterpreterContext<'a>,
+                              expression: &Expression)
+                              -> Result<Value> {
         match expression.expr {
             Expression_::StringLiteral(ref sl) => Ok(Value::String(sl.clone())),
+            Expression_::BytesLiteral(ref bl) => Ok(Value::Bytes(bl.clone())),
             Expression_::IntegerLiteral(il) => Ok(Value::Integer(il)),
             Expression_::BoolLiteral(bl) => Ok(Value::Bool(bl)),
             Expression_::CharLiteral(cl) => Ok(Value::Char(cl)),
@@ -1702,6 +2826,10 @@ impl<'a> Interpreter<'a> {
                 self.value_from_expression_map(m, expression.span.clone(), context)
             }
 
+            Expression_::Set(ref items) => {
+                self.value_from_expression_set(items, expression.span.clone(), context)
+            }
+
             Expression_::StructInit(ref p, ref fields) => {
                 self.value_from_expression_structinit(p.clone(),
                                                       fields,
@@ -1734,12 +2862,33 @@ impl<'a> Interpreter<'a> {
             Expression_::BinaryOp(ref binop, ref e1, ref e2) => {
                 self.value_from_expression_binaryop(binop.clone(), e1, e2, context)
             }
+
+            Expression_::ComparisonChain(ref operands, ref binops) => {
+                self.value_from_comparison_chain(operands, binops, context)
+            }
+
+            Expression_::Spawn(_, ref statements) => {
+                Ok(self.spawn_task(context, statements, expression.span.clone()))
+            }
+
+            Expression_::TypeTest(ref tested_type, ref e) => {
+                let value = try!(self.value_from_expression(context, e));
+                Ok(Value::Bool(Self::value_has_type(&value, tested_type)))
+            }
+
+            Expression_::Spread(_) => {
+                Err(Error {
+                    kind: ErrorKind::MisplacedSpread,
+                    span: expression.span.clone(),
+                    backtrace: vec![],
+                })
+            }
         }
     }
 
 
 
-    fn default_value(&self, var_type: Type, span: Span) -> Result<Value> {
+    fn default_value(&self, var_type: Type, span: Span) -> Result<Value<'a>> {
         match var_type {
             Type::String => Ok(Value::String("".to_string())),
             Type::Int => Ok(Value::Integer(0)),
@@ -1749,15 +2898,14 @@ impl<'a> Interpreter<'a> {
             Type::Map(t1, t2) => {
                 Ok(Value::Map(*t1, *t2, MapValue { map: std::collections::HashMap::new() }))
             }
+            Type::Set(t) => Ok(Value::Set(*t, SetValue { set: std::collections::HashSet::new() })),
+            Type::Bytes => Ok(Value::Bytes(vec![])),
+            Type::Chan(t) => Ok(self.new_chan(*t)),
+            Type::Builder => Ok(self.new_builder()),
             Type::Struct(ref p) => {
                 let struct_decl = match self.structs.get(p) {
                     Some(s) => s,
-                    None => {
-                        return Err(Error {
-                            kind: ErrorKind::UnknownStruct(p.parts.clone()),
-                            span: span,
-                        })
-                    }
+                    None => return Err(self.unknown_struct(p, span)),
                 };
 
                 let mut fields: std::collections::HashMap<String, Value> =
@@ -1773,8 +2921,15 @@ impl<'a> Interpreter<'a> {
                 Err(Error {
                     kind: ErrorKind::NoDefaultValue(other),
                     span: span,
+                    backtrace: vec![],
                 })
             }
         }
     }
 }
+
+/// `a::b` for the parts of a path
+fn join_path(parts: &[SpannedString]) -> String {
+    let idents: std::vec::Vec<&str> = parts.iter().map(|p| p.ident.as_ref()).collect();
+    idents.join("::")
+}
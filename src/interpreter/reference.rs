@@ -0,0 +1,195 @@
+use std;
+use lexer::Span;
+use interpreter::*;
+
+impl<'a> Interpreter<'a> {
+    /// Fails if the value returned from a function or task holds a reference to one of the
+    /// variables of its context, which are dropped along with it
+    pub fn check_no_dangling_reference(value: &Value<'a>,
+                                       context: &InterpreterContext<'a>,
+                                       span: Span)
+                                       -> Result<'a, ()> {
+        let mut targets = vec![];
+        collect_references(value, &mut targets);
+        if targets.is_empty() {
+            return Ok(());
+        }
+
+        for var in context.vars.values() {
+            if targets.iter().any(|&target| contains(&var.value, target)) {
+                return Err(Error {
+                    kind: ErrorKind::DanglingReference(var.name.clone()),
+                    span: span,
+                    backtrace: vec![],
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fails if the value about to be stored at `destination` holds a reference to a variable of
+    /// a context ending before the one of the destination, which stores through a reference or
+    /// an `@` parameter can reach
+    pub fn check_no_escaping_reference(destination: *const Value<'a>,
+                                       value: &Value<'a>,
+                                       context: *const InterpreterContext<'a>,
+                                       span: Span)
+                                       -> Result<'a, ()> {
+        let mut targets = vec![];
+        collect_references(value, &mut targets);
+
+        // Contexts end in the opposite order they were entered in
+        let mut context = context;
+        while !targets.is_empty() && !context.is_null() {
+            let vars = unsafe { &(*context).vars };
+            if vars.values().any(|var| contains(&var.value, destination)) {
+                return Ok(());
+            }
+
+            for var in vars.values() {
+                if targets.iter().any(|&target| contains(&var.value, target)) {
+                    return Err(Error {
+                        kind: ErrorKind::DanglingReference(var.name.clone()),
+                        span: span,
+                        backtrace: vec![],
+                    });
+                }
+            }
+
+            context = unsafe { (*context).parent };
+        }
+
+        Ok(())
+    }
+
+    /// Fails if a variable of the context or of one outliving it, or `result`, holds a reference
+    /// to the variable, which is about to be dropped
+    pub fn check_not_referenced(variable: &Variable<'a>,
+                                result: &Value<'a>,
+                                context: *const InterpreterContext<'a>,
+                                span: Span)
+                                -> Result<'a, ()> {
+        let mut targets = vec![];
+        collect_references(result, &mut targets);
+
+        let mut context = context;
+        while !context.is_null() {
+            for var in unsafe { (*context).vars.values() } {
+                if &*var.value as *const _ != &*variable.value as *const _ {
+                    collect_references(&var.value, &mut targets);
+                }
+            }
+            context = unsafe { (*context).parent };
+        }
+
+        if targets.iter().any(|&target| contains(&variable.value, target)) {
+            return Err(Error {
+                kind: ErrorKind::DanglingReference(variable.name.clone()),
+                span: span,
+                backtrace: vec![],
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Declares the variable in the context. A variable of the same name and type is assigned
+    /// rather than replaced, so that the references to it stay valid; one of another type can
+    /// only be replaced if nothing references it.
+    pub fn declare(context: *mut InterpreterContext<'a>,
+                   variable: Variable<'a>,
+                   span: Span)
+                   -> Result<'a, ()> {
+        let existing = unsafe { (*context).vars.get_mut(&variable.name).map(|var| var as *mut _) };
+        if let Some(existing) = existing {
+            let existing: &mut Variable<'a> = unsafe { &mut *existing };
+            if existing.var_type == variable.var_type {
+                Self::assign(&mut existing.value, *variable.value);
+                return Ok(());
+            }
+            try!(Self::check_not_referenced(existing, &Value::Nil, context, span));
+        }
+
+        unsafe {
+            (*context).vars.insert(variable.name.clone(), variable);
+        }
+
+        Ok(())
+    }
+
+    /// Fails if the place is an item of an array or a map, or a field of one: the items move when
+    /// the collection grows or is reassigned, which would leave a reference to them dangling
+    pub fn check_not_item(place: &Expression) -> Result<'a, ()> {
+        match place.expr {
+            Expression_::Index(_, _) => {
+                Err(Error {
+                    kind: ErrorKind::CannotRefItem,
+                    span: place.span.clone(),
+                    backtrace: vec![],
+                })
+            }
+            Expression_::Field(ref struct_expr, _) => Self::check_not_item(struct_expr),
+            _ => Ok(()),
+        }
+    }
+
+    /// Stores the value at the place. Structs are assigned field by field, so that references to
+    /// their fields stay valid.
+    pub fn assign(place: &mut Value<'a>, value: Value<'a>) {
+        match (place, value) {
+            (&mut Value::Struct(_, ref mut fields), Value::Struct(_, new_fields)) => {
+                for (name, new_field) in new_fields.map {
+                    match fields.map.get_mut(&name) {
+                        Some(field) => {
+                            Self::assign(field, new_field);
+                            continue;
+                        }
+                        None => (),
+                    }
+                    fields.map.insert(name, new_field);
+                }
+            }
+            (place, value) => *place = value,
+        }
+    }
+}
+
+/// Addresses the references held by the value point to, without following them
+fn collect_references<'a>(value: &Value<'a>, targets: &mut std::vec::Vec<*const Value<'a>>) {
+    match *value {
+        Value::Reference(r) => targets.push(r),
+        Value::MutReference(r) => targets.push(r as *const _),
+        Value::Array(_, ref items) => {
+            for item in items {
+                collect_references(item, targets);
+            }
+        }
+        Value::Map(_, _, ref m) => {
+            for (key, item) in &m.map {
+                collect_references(key, targets);
+                collect_references(item, targets);
+            }
+        }
+        Value::Struct(_, ref s) => {
+            for field in s.map.values() {
+                collect_references(field, targets);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Whether the address is the one of the value or of one of its items or fields
+fn contains<'a>(value: &Value<'a>, target: *const Value<'a>) -> bool {
+    if value as *const _ == target {
+        return true;
+    }
+
+    match *value {
+        Value::Array(_, ref items) => items.iter().any(|item| contains(item, target)),
+        Value::Map(_, _, ref m) => m.map.values().any(|item| contains(item, target)),
+        Value::Struct(_, ref s) => s.map.values().any(|field| contains(field, target)),
+        _ => false,
+    }
+}
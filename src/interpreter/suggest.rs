@@ -0,0 +1,71 @@
+use std;
+use lexer::Span;
+use parser::suggest::closest;
+use interpreter::*;
+
+impl<'a> Interpreter<'a> {
+    /// Error for a path which is neither a variable nor a func in scope, suggesting a close one
+    pub fn unknown_variable<'e>(&self,
+                                context: *const InterpreterContext<'a>,
+                                p: &Path)
+                                -> Error<'e> {
+        let name = p.to_string();
+        let suggestion = unsafe {
+            let mut root_path = (*context).current_path.parts.clone();
+            root_path.pop();
+
+            // Funcs are found relative to the current path or to its parent
+            let funcs: std::vec::Vec<String> = self.funcs
+                .keys()
+                .filter_map(|f| {
+                    relative_path(f, &(*context).current_path.parts)
+                        .or_else(|| relative_path(f, &root_path))
+                })
+                .collect();
+            let vars = (*context).vars.keys().map(|v| v.as_ref());
+
+            closest(&name, vars.chain(funcs.iter().map(|f| f.as_ref())))
+        };
+
+        Error {
+            kind: ErrorKind::UnknownVariable(p.parts.clone(), suggestion),
+            span: p.span.clone(),
+            backtrace: vec![],
+        }
+    }
+
+    pub fn unknown_struct<'e>(&self, path: &Path, span: Span) -> Error<'e> {
+        let structs: std::vec::Vec<String> = self.structs.keys().map(|s| s.to_string()).collect();
+
+        Error {
+            kind: ErrorKind::UnknownStruct(path.parts.clone(),
+                                           closest(&path.to_string(),
+                                                   structs.iter().map(|s| s.as_ref()))),
+            span: span,
+            backtrace: vec![],
+        }
+    }
+
+    pub fn unknown_struct_field<'e>(&self, struct_path: &Path, field: &SpannedString) -> Error<'e> {
+        let suggestion = self.structs
+            .get(struct_path)
+            .and_then(|sd| closest(&field.ident, sd.fields.iter().map(|f| f.name.as_ref())));
+
+        Error {
+            kind: ErrorKind::UnknownStructField(field.ident.clone(), suggestion),
+            span: field.span.clone(),
+            backtrace: vec![],
+        }
+    }
+}
+
+/// Path relative to the prefix, if it starts with it
+fn relative_path(path: &Path, prefix: &[SpannedString]) -> Option<String> {
+    if path.parts.len() > prefix.len() && path.parts[..prefix.len()] == *prefix {
+        let parts: std::vec::Vec<&str> =
+            path.parts[prefix.len()..].iter().map(|p| p.ident.as_ref()).collect();
+        Some(parts.join("::"))
+    } else {
+        None
+    }
+}
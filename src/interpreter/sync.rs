@@ -0,0 +1,152 @@
+use std;
+use lexer::Span;
+use interpreter::*;
+
+/// State of a `sync::mutex`. A task locking a mutex which another task holds blocks until it is
+/// unlocked, whereas locking it again from the task holding it is reported as a deadlock.
+#[derive(Debug)]
+pub struct Mutex {
+    /// Task holding the mutex
+    owner: Option<usize>,
+    /// Where the mutex was locked, only kept in debug builds to tell it in deadlock errors
+    locked_at: Option<Span>,
+}
+
+/// Shared state of the `sync` builtins
+#[derive(Debug, Default)]
+pub struct SyncState {
+    mutexes: std::vec::Vec<Mutex>,
+    /// Counters of the wait groups
+    wait_groups: std::vec::Vec<i64>,
+    /// Values of the atomic integers. Each of their operations runs without letting another task
+    /// run, unlike reading an int and then writing it in another statement.
+    atomics: std::vec::Vec<i64>,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new_mutex(&self) -> Value<'a> {
+        let mut sync = self.sync.borrow_mut();
+        sync.mutexes.push(Mutex {
+            owner: None,
+            locked_at: None,
+        });
+
+        Value::Mutex(sync.mutexes.len() - 1)
+    }
+
+    /// Locks the mutex, waiting for the task holding it to unlock it. This is a deadlock if no
+    /// other task can run.
+    pub fn mutex_lock(&self, mutex: usize, span: Span) -> Result<'a, ()> {
+        let task = self.current_task();
+        loop {
+            let locked_at = {
+                let mut sync = self.sync.borrow_mut();
+                let mutex = &mut sync.mutexes[mutex];
+                match mutex.owner {
+                    None => {
+                        mutex.owner = Some(task);
+                        if cfg!(debug_assertions) {
+                            mutex.locked_at = Some(span);
+                        }
+
+                        return Ok(());
+                    }
+                    Some(owner) if owner == task => {
+                        return Err(Error {
+                            kind: ErrorKind::MutexDeadlock(mutex.locked_at.clone()),
+                            span: span,
+                            backtrace: vec![],
+                        });
+                    }
+                    Some(_) => mutex.locked_at.clone(),
+                }
+            };
+
+            try!(self.block(ErrorKind::MutexDeadlock(locked_at), span.clone()));
+        }
+    }
+
+    /// Unlocks the mutex, which has to be held by the current task
+    pub fn mutex_unlock(&self, mutex: usize, span: Span) -> Result<()> {
+        let task = self.current_task();
+        let mut sync = self.sync.borrow_mut();
+        let mutex = &mut sync.mutexes[mutex];
+        let kind = match mutex.owner {
+            Some(owner) if owner == task => None,
+            Some(_) => Some(ErrorKind::CannotUnlockForeignMutex),
+            None => Some(ErrorKind::CannotUnlockUnlockedMutex),
+        };
+        if let Some(kind) = kind {
+            return Err(Error {
+                kind: kind,
+                span: span,
+                backtrace: vec![],
+            });
+        }
+
+        mutex.owner = None;
+        mutex.locked_at = None;
+        self.made_progress();
+
+        Ok(())
+    }
+
+    pub fn new_wait_group(&self) -> Value<'a> {
+        let mut sync = self.sync.borrow_mut();
+        sync.wait_groups.push(0);
+
+        Value::WaitGroup(sync.wait_groups.len() - 1)
+    }
+
+    pub fn wait_group_add(&self, wait_group: usize, delta: i64, span: Span) -> Result<()> {
+        let mut sync = self.sync.borrow_mut();
+        let counter = &mut sync.wait_groups[wait_group];
+        if *counter + delta < 0 {
+            return Err(Error {
+                kind: ErrorKind::NegativeWaitGroupCounter,
+                span: span,
+                backtrace: vec![],
+            });
+        }
+
+        *counter += delta;
+        if *counter == 0 {
+            self.made_progress();
+        }
+
+        Ok(())
+    }
+
+    /// Waits until the counter of the wait group is 0
+    pub fn wait_group_wait(&'a self, wait_group: usize, span: Span) -> Result<()> {
+        while self.sync.borrow().wait_groups[wait_group] != 0 {
+            try!(self.block(ErrorKind::WaitGroupDeadlock, span.clone()));
+        }
+
+        Ok(())
+    }
+
+    pub fn new_atomic(&self, value: i64) -> Value<'a> {
+        let mut sync = self.sync.borrow_mut();
+        sync.atomics.push(value);
+
+        Value::Atomic(sync.atomics.len() - 1)
+    }
+
+    pub fn atomic_load(&self, atomic: usize) -> i64 {
+        self.sync.borrow().atomics[atomic]
+    }
+
+    pub fn atomic_store(&self, atomic: usize, value: i64) {
+        self.sync.borrow_mut().atomics[atomic] = value;
+    }
+
+    /// Adds to the atomic integer, returning its previous value
+    pub fn atomic_fetch_add(&self, atomic: usize, delta: i64) -> i64 {
+        let mut sync = self.sync.borrow_mut();
+        let previous = sync.atomics[atomic];
+        sync.atomics[atomic] = previous.wrapping_add(delta);
+
+        previous
+    }
+}
@@ -0,0 +1,439 @@
+use std;
+use parser::ast::*;
+use lexer::Span;
+use std::sync::mpsc::RecvTimeoutError;
+use interpreter::*;
+
+/// Handle of the task running `main`
+pub const MAIN_TASK: usize = 0;
+
+/// Statements and loop iterations a task runs before letting the next ready one run
+const TIME_SLICE: u32 = 100;
+
+/// Block started by a `spawn` expression or a call to an async function. Every task runs on a
+/// thread of its own, so that it can block in the middle of a statement, but only one of them
+/// runs at a time: see `Scheduler`.
+#[derive(Debug)]
+pub enum Task<'a> {
+    /// Not started yet, with a copy of the variables in scope when it was spawned and where it was
+    Pending(InterpreterContext<'a>, std::vec::Vec<BlockStatement>, Span),
+    Running,
+    /// I/O operation running on another thread, evaluating to a string
+    Io(std::sync::mpsc::Receiver<std::io::Result<String>>),
+    /// Finished, with the value it returned
+    Done(Value<'a>),
+    /// Finished with an error, which is passed to the first task joining it
+    Failed(Option<Error<'a>>),
+}
+
+/// Decides which task runs. The running task holds the baton, which it hands to the next task
+/// when it blocks, when it finishes, or after `TIME_SLICE` statements if another task is ready;
+/// the threads of the other tasks wait for it, each on a condvar of its own so that handing the
+/// baton over only wakes the thread getting it. The interpreter is only ever used by the thread
+/// holding the baton, which is what makes sharing it between the threads sound.
+#[derive(Default)]
+pub struct Scheduler {
+    state: std::sync::Mutex<SchedulerState>,
+}
+
+#[derive(Default)]
+struct SchedulerState {
+    running: Option<usize>,
+    /// Condvars the threads of the tasks wait on for the baton, by handle
+    turns: std::collections::HashMap<usize, std::sync::Arc<std::sync::Condvar>>,
+    /// Tasks waiting for the baton, in the order they get it
+    ready: std::collections::VecDeque<usize>,
+    /// Tasks waiting for another task to make progress, with the progress count when they
+    /// blocked
+    blocked: std::vec::Vec<(usize, u64)>,
+    /// Number of sends, closes, unlocks, wait group signals and ends of tasks so far, any of which
+    /// may unblock a task
+    progress: u64,
+    /// Set when `main` returned with tasks still waiting, which then end with an error
+    aborting: bool,
+    /// Starts the threads of the spawned tasks, gone once `main` returned
+    spawner: Option<std::sync::mpsc::Sender<usize>>,
+    /// Statements and loop iterations run by the running task since it got the baton
+    slice: u32,
+}
+
+impl SchedulerState {
+    /// Removes the task which gets the baton next from the queues
+    fn next(&mut self) -> Option<usize> {
+        if let Some(handle) = self.ready.pop_front() {
+            return Some(handle);
+        }
+
+        let progress = self.progress;
+        let aborting = self.aborting;
+        match self.blocked.iter().position(|&(_, since)| aborting || since < progress) {
+            Some(i) => Some(self.blocked.remove(i).0),
+            None => None,
+        }
+    }
+}
+
+impl Scheduler {
+    fn lock(&self) -> std::sync::MutexGuard<SchedulerState> {
+        self.state.lock().unwrap()
+    }
+
+    fn hand_over(&self, state: &mut SchedulerState) {
+        state.running = state.next();
+        state.slice = 0;
+        if let Some(turn) = state.running.and_then(|handle| state.turns.get(&handle)) {
+            turn.notify_one();
+        }
+    }
+
+    /// Waits until the task gets the baton, failing if the program is being aborted
+    fn wait_turn<'a>(&self,
+                     mut state: std::sync::MutexGuard<SchedulerState>,
+                     handle: usize)
+                     -> Result<'a, ()> {
+        let turn = state.turns.entry(handle).or_insert_with(Default::default).clone();
+        while state.running != Some(handle) {
+            state = turn.wait(state).unwrap();
+        }
+
+        if state.aborting {
+            return Err(Error {
+                kind: ErrorKind::Aborted,
+                span: Span::nil_span(),
+                backtrace: vec![],
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Interpreter data moved to the threads of the tasks, which only use it while holding the baton
+struct Shared<T>(T);
+
+unsafe impl<T> Send for Shared<T> {}
+
+impl<'a> Interpreter<'a> {
+    /// Runs `main` as the first task, along with the tasks it spawns, returning once they have
+    /// all ended, or failing if the thread of `main` cannot be started.
+    pub fn run_tasks<F>(&'a self, main: F) -> Result<'a, i64>
+        where F: FnOnce() -> Result<'a, i64>
+    {
+        let (spawner, spawned) = std::sync::mpsc::channel();
+        {
+            let mut state = self.scheduler.lock();
+            state.running = Some(MAIN_TASK);
+            state.spawner = Some(spawner);
+        }
+        self.tasks.borrow_mut().push(Task::Running);
+
+        let stack_size = task_stack_size(self.options.max_call_depth);
+        let main = Shared((self, main));
+
+        std::thread::scope(|scope| {
+            let main_thread = match std::thread::Builder::new()
+                .stack_size(stack_size)
+                .spawn_scoped(scope, move || {
+                    limits::start_thread(stack_size);
+                    let (interpreter, main) = main.0;
+                    let result = main();
+                    interpreter.end_main();
+                    Shared(result)
+                }) {
+                Ok(main_thread) => main_thread,
+                Err(e) => {
+                    self.scheduler.lock().spawner = None;
+                    return Err(Error {
+                        kind: ErrorKind::CannotStartTask(e),
+                        span: Span::nil_span(),
+                        backtrace: vec![],
+                    });
+                }
+            };
+
+            // The spawner is dropped when main returns, which ends the loop
+            for handle in spawned {
+                let task = Shared(self);
+                let started = std::thread::Builder::new()
+                    .stack_size(stack_size)
+                    .spawn_scoped(scope, move || {
+                        limits::start_thread(stack_size);
+                        task.0.run_task(handle)
+                    });
+                if let Err(e) = started {
+                    self.fail_task(handle, e);
+                }
+            }
+
+            main_thread.join().unwrap().0
+        })
+    }
+
+    /// Hands the baton over once `main` returned, aborting the tasks still waiting for it
+    fn end_main(&self) {
+        let mut state = self.scheduler.lock();
+        state.spawner = None;
+        state.aborting = true;
+        self.scheduler.hand_over(&mut state);
+    }
+
+    /// Body of the thread of the task
+    fn run_task(&'a self, handle: usize) {
+        let state = self.scheduler.lock();
+        let result = self.scheduler.wait_turn(state, handle).and_then(|_| {
+            self.call_depth.set(0);
+            let task = std::mem::replace(&mut self.tasks.borrow_mut()[handle], Task::Running);
+            match task {
+                Task::Pending(mut task_context, statements, span) => {
+                    let return_value = try!(self.execute_block_statements(&mut task_context,
+                                                                          &statements));
+                    try!(self.join_spawned(&mut task_context));
+                    try!(Self::check_no_dangling_reference(&return_value, &task_context, span));
+
+                    Ok(return_value)
+                }
+                _ => unreachable!(),
+            }
+        });
+
+        self.end_task(handle, result);
+    }
+
+    /// Ends the task whose thread could not be started with an error, once it gets the baton
+    fn fail_task(&'a self, handle: usize, e: std::io::Error) {
+        let state = self.scheduler.lock();
+        let result = self.scheduler.wait_turn(state, handle).and_then(|_| {
+            Err(Error {
+                kind: ErrorKind::CannotStartTask(e),
+                span: Span::nil_span(),
+                backtrace: vec![],
+            })
+        });
+
+        self.end_task(handle, result);
+    }
+
+    /// Records what the task ended with and hands the baton over
+    fn end_task(&'a self, handle: usize, result: Result<'a, Value<'a>>) {
+        let mut state = self.scheduler.lock();
+        self.tasks.borrow_mut()[handle] = match result {
+            Ok(value) => Task::Done(value),
+            Err(err) => Task::Failed(Some(err)),
+        };
+        state.turns.remove(&handle);
+        state.progress += 1;
+        self.scheduler.hand_over(&mut state);
+    }
+
+    /// Registers a task running the statements with a copy of the variables of the context,
+    /// returning its handle
+    pub fn spawn_task(&'a self,
+                      context: *mut InterpreterContext<'a>,
+                      statements: &[BlockStatement],
+                      span: Span)
+                      -> Value {
+        let task_context = unsafe {
+            InterpreterContext {
+                vars: (*context).vars.clone(),
+                current_path: (*context).current_path.clone(),
+                spawned: vec![],
+                parent: context,
+            }
+        };
+
+        self.start_task(context, task_context, statements, span)
+    }
+
+    /// Registers a task running the statements in `task_context`, started from `context`,
+    /// returning its handle. It runs once the tasks ready before it had their turn.
+    pub fn start_task(&'a self,
+                      context: *mut InterpreterContext<'a>,
+                      task_context: InterpreterContext<'a>,
+                      statements: &[BlockStatement],
+                      span: Span)
+                      -> Value {
+        let handle = {
+            let mut tasks = self.tasks.borrow_mut();
+            tasks.push(Task::Pending(task_context, statements.to_vec(), span));
+            tasks.len() - 1
+        };
+        unsafe {
+            (*context).spawned.push(handle);
+        }
+
+        let mut state = self.scheduler.lock();
+        state.ready.push_back(handle);
+        if let Some(ref spawner) = state.spawner {
+            spawner.send(handle).unwrap();
+        }
+
+        Value::Task(handle)
+    }
+
+    /// Counts a statement or a loop iteration of the running task, letting the next ready task
+    /// run once it has run for its time slice
+    pub fn preempt(&self) -> Result<'a, ()> {
+        let mut state = self.scheduler.lock();
+        state.slice += 1;
+        if state.slice < TIME_SLICE || state.ready.is_empty() {
+            return Ok(());
+        }
+
+        let handle = state.running.unwrap();
+        state.ready.push_back(handle);
+        self.switch(state, handle)
+    }
+
+    /// Waits for another task to make progress, failing with `deadlock` if no other task can run
+    pub fn block(&self, deadlock: ErrorKind<'a>, span: Span) -> Result<'a, ()> {
+        let mut state = self.scheduler.lock();
+        if state.ready.is_empty() &&
+           !state.blocked.iter().any(|&(_, since)| since < state.progress) {
+            return Err(Error {
+                kind: deadlock,
+                span: span,
+                backtrace: vec![],
+            });
+        }
+
+        let handle = state.running.unwrap();
+        let progress = state.progress;
+        state.blocked.push((handle, progress));
+        self.switch(state, handle)
+    }
+
+    /// Hands the baton over and waits to get it back, keeping the call depth of the task
+    fn switch(&self,
+              mut state: std::sync::MutexGuard<SchedulerState>,
+              handle: usize)
+              -> Result<'a, ()> {
+        let call_depth = self.call_depth.get();
+        self.scheduler.hand_over(&mut state);
+        try!(self.scheduler.wait_turn(state, handle));
+        self.call_depth.set(call_depth);
+
+        Ok(())
+    }
+
+    /// Handle of the task holding the baton, which is the one calling this
+    pub fn current_task(&self) -> usize {
+        self.scheduler.lock().running.unwrap()
+    }
+
+    /// Records an operation which may unblock a task
+    pub fn made_progress(&self) {
+        self.scheduler.lock().progress += 1;
+    }
+
+    /// Whether a task other than the running one can run
+    fn others_can_run(&self) -> bool {
+        let state = self.scheduler.lock();
+        !state.ready.is_empty() || state.blocked.iter().any(|&(_, since)| since < state.progress)
+    }
+
+    /// Value returned by the task, waiting for it to finish first
+    pub fn join_task(&'a self, handle: usize, span: Span) -> Result<Value> {
+        if handle == self.current_task() {
+            return Err(Error {
+                kind: ErrorKind::CannotJoinRunningTask,
+                span: span,
+                backtrace: vec![],
+            });
+        }
+
+        loop {
+            let task = std::mem::replace(&mut self.tasks.borrow_mut()[handle], Task::Running);
+            match task {
+                Task::Pending(_, _, _) |
+                Task::Running => {
+                    self.tasks.borrow_mut()[handle] = task;
+                    try!(self.block(ErrorKind::JoinDeadlock, span.clone()));
+                }
+                Task::Io(receiver) => return self.join_io(handle, receiver, span),
+                Task::Done(value) => {
+                    self.tasks.borrow_mut()[handle] = Task::Done(value.clone());
+                    return Ok(value);
+                }
+                Task::Failed(err) => {
+                    self.tasks.borrow_mut()[handle] = Task::Failed(None);
+                    return Err(err.unwrap_or(Error {
+                        kind: ErrorKind::TaskFailed,
+                        span: span,
+                        backtrace: vec![],
+                    }));
+                }
+            }
+        }
+    }
+
+    /// String the I/O operation evaluated to. Other tasks run while it is in progress.
+    fn join_io(&'a self,
+               handle: usize,
+               receiver: std::sync::mpsc::Receiver<std::io::Result<String>>,
+               span: Span)
+               -> Result<Value> {
+        let result = loop {
+            match receiver.try_recv() {
+                Ok(result) => break result,
+                Err(std::sync::mpsc::TryRecvError::Empty) => {
+                    if self.others_can_run() {
+                        let mut state = self.scheduler.lock();
+                        let running = state.running.unwrap();
+                        state.ready.push_back(running);
+                        try!(self.switch(state, running));
+                        continue;
+                    }
+
+                    let received = match self.remaining_time() {
+                        Some(remaining) => receiver.recv_timeout(remaining),
+                        None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                    };
+
+                    break match received {
+                        Ok(result) => result,
+                        Err(RecvTimeoutError::Timeout) => {
+                            return Err(Error {
+                                kind: ErrorKind::Timeout,
+                                span: span,
+                                backtrace: vec![],
+                            })
+                        }
+                        Err(RecvTimeoutError::Disconnected) => {
+                            Err(std::io::Error::new(std::io::ErrorKind::Other,
+                                                    "operation interrupted"))
+                        }
+                    };
+                }
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                    break Err(std::io::Error::new(std::io::ErrorKind::Other,
+                                                  "operation interrupted"));
+                }
+            }
+        };
+
+        match result {
+            Ok(s) => {
+                self.tasks.borrow_mut()[handle] = Task::Done(Value::String(s.clone()));
+                self.made_progress();
+                Ok(Value::String(s))
+            }
+            Err(e) => {
+                Err(Error {
+                    kind: ErrorKind::Io(e),
+                    span: span,
+                    backtrace: vec![],
+                })
+            }
+        }
+    }
+
+    /// Waits for the tasks spawned in the context which have not been joined yet
+    pub fn join_spawned(&'a self, context: *mut InterpreterContext<'a>) -> Result<()> {
+        let spawned = unsafe { std::mem::replace(&mut (*context).spawned, vec![]) };
+        for handle in spawned {
+            try!(self.join_task(handle, Span::nil_span()));
+        }
+
+        Ok(())
+    }
+}
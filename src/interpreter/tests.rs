@@ -0,0 +1,779 @@
+use super::*;
+use lexer::Reader;
+use parser::{Parser, ParserOptions};
+
+/// Exit code of the program, or the message of the error it ended with
+fn run_with(src: &str, options: InterpreterOptions) -> std::result::Result<i64, String> {
+    execute(src, options, |err| err.message())
+}
+
+/// Message of the error the program ends with, the source it is about, and the funcs of its
+/// backtrace, innermost first
+fn run_err(src: &str) -> (String, String, std::vec::Vec<String>) {
+    let result = execute(src, InterpreterOptions::default(), |err| {
+        (err.message(),
+         src[err.span.start..err.span.end].to_string(),
+         err.backtrace.iter().map(|frame| frame.func.clone()).collect())
+    });
+    result.unwrap_err()
+}
+
+/// Exit code of the program, or what `on_error` makes of the error it ended with
+fn execute<T, F>(src: &str, options: InterpreterOptions, on_error: F) -> std::result::Result<i64, T>
+    where F: FnOnce(Error) -> T
+{
+    let source_map = SourceMap::new();
+    let file_id = source_map.add_file("<test>".to_string(), src.to_string());
+    let source_file = source_map.file(file_id).unwrap();
+    let mut reader = Reader::new(source_file.src.as_ref(), file_id);
+    let mut parser = Parser::new(&mut reader,
+                                 ParserOptions {
+                                     features: options.features.clone(),
+                                     ..ParserOptions::default()
+                                 });
+    let ast = parser.parse().unwrap();
+
+    let mut interpreter = Interpreter::new(ast, &source_map, options);
+    interpreter.execute().map_err(on_error)
+}
+
+/// Same as `run_with`, aborting programs which loop forever
+fn run(src: &str) -> std::result::Result<i64, String> {
+    run_with(src,
+             InterpreterOptions { max_steps: Some(1_000_000), ..InterpreterOptions::default() })
+}
+
+#[test]
+fn test_join() {
+    assert_eq!(Ok(3),
+               run("func main() -> int {
+                        var t: task = spawn -> int { return 1 + 2 }
+                        return join(t)
+                    }"));
+    assert_eq!(Err("panic: boom".to_string()),
+               run("func main() {
+                        var t: task = spawn { panic(\"boom\") }
+                        join(t)
+                    }"));
+}
+
+#[test]
+fn test_tasks_run_concurrently() {
+    // main only stops spinning once the task had its turn
+    assert_eq!(Ok(1),
+               run("func main() -> int {
+                        var flag: int = 0
+                        var flag_ref: @int = @flag
+                        var t: task = spawn { *flag_ref = 1 }
+                        while *flag_ref == 0 {}
+                        return flag
+                    }"));
+    // Both tasks spin until the other one has started
+    assert_eq!(Ok(2),
+               run("func main() -> int {
+                        var started: int = 0
+                        var started_ref: @int = @started
+                        var a: task = spawn {
+                            *started_ref = *started_ref + 1
+                            while *started_ref < 2 {}
+                        }
+                        var b: task = spawn {
+                            *started_ref = *started_ref + 1
+                            while *started_ref < 2 {}
+                        }
+                        join(a)
+                        join(b)
+                        return started
+                    }"));
+}
+
+#[test]
+fn test_assignment_while_other_tasks_run() {
+    // The task grows the array while main waits for the value of its first item
+    assert_eq!(Ok(7),
+               run("func main() -> int {
+                        var a: []int = [0]
+                        var a_ref: @[]int = @a
+                        var c: [chan]int = chan::new()
+                        var t: task = spawn {
+                            var i: int = 0
+                            while i < 1000 {
+                                (*a_ref)[] = i
+                                i = i + 1
+                            }
+                            send(c, 7)
+                        }
+                        a[0] = recv(c)
+                        join(t)
+                        return a[0]
+                    }"));
+    assert_eq!(Ok(7),
+               run("func main() -> int {
+                        var a: []int = [0, 7]
+                        var a_ref: @[]int = @a
+                        var c: [chan]int = chan::new()
+                        var t: task = spawn {
+                            var i: int = 0
+                            while i < 1000 {
+                                (*a_ref)[] = i
+                                i = i + 1
+                            }
+                            send(c, 1)
+                        }
+                        var item: int = a[recv(c)]
+                        join(t)
+                        return item
+                    }"));
+}
+
+#[test]
+fn test_spawned_tasks_end_before_their_spawner() {
+    assert_eq!(Ok(5),
+               run("func set(r: @int) {
+                        var t: task = spawn { *r = 5 }
+                    }
+                    func main() -> int {
+                        var x: int = 0
+                        set(@x)
+                        return x
+                    }"));
+}
+
+#[test]
+fn test_request_response() {
+    assert_eq!(Ok(2),
+               run("func main() -> int {
+                        var req: [chan]int = chan::new()
+                        var resp: [chan]int = chan::new()
+                        var t: task = spawn -> int {
+                            send(req, 1)
+                            return recv(resp)
+                        }
+                        send(resp, recv(req) + 1)
+                        return join(t)
+                    }"));
+    // The task waits for the values main sends one at a time
+    assert_eq!(Ok(6),
+               run("func main() -> int {
+                        var req: [chan]int = chan::new()
+                        var resp: [chan]int = chan::new()
+                        var t: task = spawn {
+                            for n in req {
+                                send(resp, n * 2)
+                            }
+                        }
+                        var sum: int = 0
+                        var i: int = 1
+                        while i <= 2 {
+                            send(req, i)
+                            sum = sum + recv(resp)
+                            i = i + 1
+                        }
+                        close(req)
+                        join(t)
+                        return sum
+                    }"));
+}
+
+#[test]
+fn test_many_blocked_tasks() {
+    // Handing the baton over only wakes the task getting it
+    assert_eq!(Ok(1000),
+               run("func main() -> int {
+                        var c: [chan]int = chan::new()
+                        var done: [chan]int = chan::new()
+                        var i: int = 0
+                        while i < 1000 {
+                            spawn { send(done, recv(c)) }
+                            i = i + 1
+                        }
+                        var sum: int = 0
+                        i = 0
+                        while i < 1000 {
+                            send(c, 1)
+                            sum = sum + recv(done)
+                            i = i + 1
+                        }
+                        return sum
+                    }"));
+}
+
+#[test]
+fn test_deadlock() {
+    assert_eq!(Err("no task left to send on the channel".to_string()),
+               run("func main() {
+                        var c: [chan]int = chan::new()
+                        var x: int = recv(c)
+                    }"));
+    assert_eq!(Err("no task left to send on the channel".to_string()),
+               run("func main() {
+                        var c: [chan]int = chan::new()
+                        var t: task = spawn { var x: int = recv(c) }
+                        join(t)
+                    }"));
+}
+
+#[test]
+fn test_mutex_contention() {
+    // main is preempted while holding the mutex, and the task waits for it to be unlocked
+    assert_eq!(Ok(12),
+               run("func main() -> int {
+                        var m: sync::mutex = sync::mutex::new()
+                        var log: []int = [0]
+                        var log_ref: @[]int = @log
+                        sync::lock(m)
+                        var t: task = spawn {
+                            sync::lock(m)
+                            (*log_ref)[] = 2
+                            sync::unlock(m)
+                        }
+                        var i: int = 0
+                        while i < 500 {
+                            i = i + 1
+                        }
+                        (*log_ref)[] = 1
+                        sync::unlock(m)
+                        join(t)
+                        return log[1] * 10 + log[2]
+                    }"));
+    // Only the task holding the mutex can unlock it
+    assert_eq!(Err("cannot unlock a mutex locked by another task".to_string()),
+               run("func main() {
+                        var m: sync::mutex = sync::mutex::new()
+                        sync::lock(m)
+                        var t: task = spawn { sync::unlock(m) }
+                        join(t)
+                    }"));
+    assert_eq!(Err("cannot unlock a mutex which is not locked".to_string()),
+               run("func main() {
+                        var m: sync::mutex = sync::mutex::new()
+                        sync::unlock(m)
+                    }"));
+}
+
+#[test]
+fn test_counter_increments() {
+    let program = |increment: &str| {
+        format!("func spin() {{
+                     var i: int = 0
+                     while i < 10 {{
+                         i = i + 1
+                     }}
+                 }}
+                 func add(counter: @int, m: sync::mutex) {{
+                     var i: int = 0
+                     while i < 300 {{
+                         {}
+                         i = i + 1
+                     }}
+                 }}
+                 func main() -> int {{
+                     var m: sync::mutex = sync::mutex::new()
+                     var counter: int = 0
+                     var counter_ref: @int = @counter
+                     var a: task = spawn {{ add(counter_ref, m) }}
+                     var b: task = spawn {{ add(counter_ref, m) }}
+                     join(a)
+                     join(b)
+                     return counter
+                 }}",
+                increment)
+    };
+
+    // Increments are lost when a task is preempted between reading the counter and writing it
+    assert!(run(&program("var value: int = *counter
+                          spin()
+                          *counter = value + 1"))
+        .unwrap() < 600);
+    assert_eq!(Ok(600),
+               run(&program("sync::lock(m)
+                             var value: int = *counter
+                             spin()
+                             *counter = value + 1
+                             sync::unlock(m)")));
+    assert_eq!(Ok(600),
+               run("func add(counter: sync::atomic) {
+                        var i: int = 0
+                        while i < 300 {
+                            sync::fetch_add(counter, 1)
+                            i = i + 1
+                        }
+                    }
+                    func main() -> int {
+                        var counter: sync::atomic = sync::atomic::new(0)
+                        var a: task = spawn { add(counter) }
+                        var b: task = spawn { add(counter) }
+                        join(a)
+                        join(b)
+                        return sync::load(counter)
+                    }"));
+}
+
+#[test]
+fn test_wait_group() {
+    assert_eq!(Ok(3),
+               run("func main() -> int {
+                        var wg: sync::waitgroup = sync::waitgroup::new()
+                        var done: sync::atomic = sync::atomic::new(0)
+                        sync::add(wg, 3)
+                        var a: task = spawn { sync::fetch_add(done, 1)  sync::done(wg) }
+                        var b: task = spawn { sync::fetch_add(done, 1)  sync::done(wg) }
+                        var c: task = spawn { sync::fetch_add(done, 1)  sync::done(wg) }
+                        sync::wait(wg)
+                        return sync::load(done)
+                    }"));
+    assert_eq!(Err("no task left to signal the wait group".to_string()),
+               run("func main() {
+                        var wg: sync::waitgroup = sync::waitgroup::new()
+                        sync::add(wg, 1)
+                        sync::wait(wg)
+                    }"));
+}
+
+#[test]
+fn test_mutex_deadlock() {
+    let deadlock = |src| match run(src) {
+        Err(message) => message.starts_with("deadlock: mutex already locked"),
+        Ok(_) => false,
+    };
+
+    assert!(deadlock("func main() {
+                          var m: sync::mutex = sync::mutex::new()
+                          sync::lock(m)
+                          sync::lock(m)
+                      }"));
+    // The task waits for main, which waits for the task
+    assert!(deadlock("func main() {
+                          var m: sync::mutex = sync::mutex::new()
+                          sync::lock(m)
+                          var t: task = spawn { sync::lock(m) }
+                          join(t)
+                          sync::unlock(m)
+                      }"));
+}
+
+#[test]
+fn test_dangling_references() {
+    let dangling = |name: &str| Err(format!("reference to local variable {} outlives it", name));
+
+    assert_eq!(dangling("local"),
+               run("func set(out: @&int) {
+                        var local: int = 42
+                        *out = &local
+                    }
+                    func main() -> int {
+                        var x: int = 0
+                        var r: &int = &x
+                        set(@r)
+                        return *r
+                    }"));
+    // Through a reference to a caller which isn't the direct one
+    assert_eq!(dangling("b"),
+               run("func store(out: @&int, value: &int) {
+                        *out = value
+                    }
+                    func pass(out: @&int) {
+                        var b: int = 1
+                        store(out, &b)
+                    }
+                    func main() -> int {
+                        var a: int = 0
+                        var r: &int = &a
+                        pass(@r)
+                        return *r
+                    }"));
+    assert_eq!(dangling("local"),
+               run("func get() -> &int {
+                        var local: int = 1
+                        return &local
+                    }
+                    func main() -> int {
+                        return *get()
+                    }"));
+
+    assert_eq!(dangling("b"),
+               run("func main() -> int {
+                        var a: int = 0
+                        var r: &int = &a
+                        var r_ref: @&int = @r
+                        var t: task = spawn {
+                            var b: int = 1
+                            *r_ref = &b
+                        }
+                        join(t)
+                        return *r
+                    }"));
+
+    // References to variables which outlive the destination
+    assert_eq!(Ok(3),
+               run("func set(out: @&int, value: &int) {
+                        *out = value
+                    }
+                    func main() -> int {
+                        var a: int = 0
+                        var b: int = 3
+                        var r: &int = &a
+                        set(@r, &b)
+                        return *r
+                    }"));
+    // Items move when their collection grows, fields stay where they are when their struct is
+    // reassigned
+    assert_eq!(Err("cannot reference an item of an array or a map, only variables and fields"
+                   .to_string()),
+               run("func main() -> int {
+                        var a: []int = [1]
+                        var r: &int = &a[0]
+                        var i: int = 0
+                        while i < 100000 {
+                            a[] = i
+                            i = i + 1
+                        }
+                        return *r
+                    }"));
+    assert_eq!(Err("cannot reference an item of an array or a map, only variables and fields"
+                   .to_string()),
+               run("struct Point {
+                        x: int
+                    }
+                    func main() -> int {
+                        var m: [string]Point = {\"a\": Point { x: 1 }}
+                        var r: @int = @m[\"a\"].x
+                        return *r
+                    }"));
+    assert_eq!(Ok(2),
+               run("struct Point {
+                        x: int
+                    }
+                    func main() -> int {
+                        var p: Point = Point { x: 1 }
+                        var r: &int = &p.x
+                        p = Point { x: 2 }
+                        return *r
+                    }"));
+    // Redeclaring a variable of the same type assigns it, so references to it follow its value
+    assert_eq!(Ok(2),
+               run("func main() -> int {
+                        var i: int = 0
+                        var zero: int = 0
+                        var rr: &int = &zero
+                        while i < 3 {
+                            var y: int = i
+                            rr = &y
+                            i = i + 1
+                        }
+                        return *rr
+                    }"));
+    assert_eq!(Ok(1),
+               run("struct Point {
+                        x: int
+                    }
+                    func main() -> int {
+                        var p: Point = Point { x: 0 }
+                        var r: &int = &p.x
+                        var p: Point = Point { x: 1 }
+                        return *r
+                    }"));
+    // The element of a for loop is dropped once the loop ends
+    assert_eq!(dangling("e"),
+               run("func main() -> int {
+                        var zero: int = 0
+                        var rr: &int = &zero
+                        for e in [10, 20, 30] {
+                            rr = &e
+                        }
+                        return *rr
+                    }"));
+    assert_eq!(dangling("e"),
+               run("func first(a: []int) -> &int {
+                        for e in a {
+                            return &e
+                        }
+                        return &a[0]
+                    }
+                    func main() -> int {
+                        return *first([1])
+                    }"));
+    // Redeclaring a variable with another type replaces it
+    assert_eq!(dangling("y"),
+               run("func main() -> int {
+                        var y: int = 5
+                        var rr: &int = &y
+                        var y: string = \"abc\"
+                        return *rr
+                    }"));
+    assert_eq!(Ok(3),
+               run("func main() -> int {
+                        var y: int = 5
+                        var y: string = \"abc\"
+                        return #y
+                    }"));
+}
+
+#[test]
+fn test_error_spans_and_backtraces() {
+    assert_eq!(("division by zero".to_string(),
+                "a / b".to_string(),
+                vec!["div".to_string(), "main".to_string()]),
+               run_err("func div(a: int, b: int) -> int {
+                            return a / b
+                        }
+
+                        func main() {
+                            div(1, 0)
+                        }"));
+    assert_eq!(("modulo by zero".to_string(), "1 % 0".to_string(), vec!["main".to_string()]),
+               run_err("func main() {
+                            var a: int = 1 % 0
+                        }"));
+    assert_eq!(("missing map key \"zz\"".to_string(),
+                "\"zz\"".to_string(),
+                vec!["get".to_string(), "main".to_string()]),
+               run_err("func get(m: &[string]int) -> int {
+                            return (*m)[\"zz\"]
+                        }
+
+                        func main() {
+                            var m: [string]int = {\"a\": 1}
+                            get(&m)
+                        }"));
+    assert_eq!(("missing map key 'c'".to_string(), "'c'".to_string(), vec!["main".to_string()]),
+               run_err("func main() {
+                            var m: [char]int = {'a': 1}
+                            m['c'] = 2
+                        }"));
+}
+
+#[test]
+fn test_step_limit() {
+    let options = || InterpreterOptions { max_steps: Some(1000), ..InterpreterOptions::default() };
+    assert_eq!(Ok(0),
+               run_with("func main() -> int {
+                             var i: int = 0
+                             while i < 10 {
+                                 i = i + 1
+                             }
+                             return 0
+                         }",
+                        options()));
+    assert_eq!(Err("step limit exceeded".to_string()),
+               run_with("func main() {
+                             while true {}
+                         }",
+                        options()));
+    // Steps are counted across tasks
+    assert_eq!(Err("step limit exceeded".to_string()),
+               run_with("func main() {
+                             var t: task = spawn {
+                                 while true {}
+                             }
+                             join(t)
+                         }",
+                        options()));
+}
+
+#[test]
+fn test_heap_limit() {
+    let options = || {
+        InterpreterOptions { max_heap_bytes: Some(10_000), ..InterpreterOptions::default() }
+    };
+    assert_eq!(Ok(0),
+               run_with("func main() -> int {
+                             var a: []int = [1, 2, 3]
+                             return 0
+                         }",
+                        options()));
+    assert_eq!(Err("heap limit exceeded".to_string()),
+               run_with("func main() {
+                             var a: []int = [0]
+                             while true {
+                                 a[] = 1
+                             }
+                         }",
+                        options()));
+    assert_eq!(Err("heap limit exceeded".to_string()),
+               run_with("func main() {
+                             var t: task = spawn {
+                                 var a: []string = [\"\"]
+                                 while true {
+                                     a[] = \"0123456789\"
+                                 }
+                             }
+                             join(t)
+                         }",
+                        options()));
+}
+
+#[test]
+fn test_timeout() {
+    let options = || InterpreterOptions {
+        timeout: Some(std::time::Duration::from_millis(50)),
+        ..InterpreterOptions::default()
+    };
+    assert_eq!(Ok(0), run_with("func main() -> int { return 0 }", options()));
+    assert_eq!(Err("execution timed out".to_string()),
+               run_with("func main() {
+                             while true {}
+                         }",
+                        options()));
+    assert_eq!(Err("execution timed out".to_string()),
+               run_with("func main() {
+                             var t: task = spawn {
+                                 while true {}
+                             }
+                             join(t)
+                         }",
+                        options()));
+}
+
+#[test]
+fn test_io_disabled() {
+    let options = || InterpreterOptions { allow_io: false, ..InterpreterOptions::default() };
+    assert_eq!(Err("cannot call io::read_file: i/o is disabled".to_string()),
+               run_with("func main() {
+                             var s: string = await io::read_file(\"/dev/null\")
+                         }",
+                        options()));
+    assert_eq!(Err("cannot call net::request: i/o is disabled".to_string()),
+               run_with("func main() {
+                             var t: task = spawn {
+                                 var s: string = await net::request(\"127.0.0.1:1\", \"\")
+                             }
+                             join(t)
+                         }",
+                        options()));
+    assert_eq!(Err("cannot call labs: i/o is disabled".to_string()),
+               run_with("extern \"libm.so.6\" func labs(x: int) -> int
+
+                         func main() {
+                             labs(-1)
+                         }",
+                        options()));
+    // Nothing is refused with the default options
+    assert_eq!(Ok(0),
+               run("func main() -> int {
+                        var s: string = await io::read_file(\"/dev/null\")
+                        return #s
+                    }"));
+}
+
+/// Value of `expr` evaluated with the given overflow behavior, `min` and `max` being the bounds of
+/// an int
+fn eval_overflow(expr: &str, overflow: Overflow) -> std::result::Result<i64, String> {
+    let src = format!("func main() -> int {{
+                           var min: int = -9223372036854775807 - 1
+                           var max: int = 9223372036854775807
+                           return {}
+                       }}",
+                      expr);
+    run_with(&src, InterpreterOptions { overflow: overflow, ..InterpreterOptions::default() })
+}
+
+#[test]
+fn test_overflow_modes() {
+    let min = std::i64::MIN;
+    let max = std::i64::MAX;
+    let overflow = Err("integer overflow".to_string());
+
+    assert_eq!(Ok(min), eval_overflow("max + 1", Overflow::Wrap));
+    assert_eq!(Ok(max), eval_overflow("max + 1", Overflow::Saturate));
+    assert_eq!(overflow, eval_overflow("max + 1", Overflow::Error));
+
+    assert_eq!(Ok(-2), eval_overflow("max * 2", Overflow::Wrap));
+    assert_eq!(Ok(max), eval_overflow("max * 2", Overflow::Saturate));
+    assert_eq!(Ok(min), eval_overflow("min * 2", Overflow::Saturate));
+    assert_eq!(overflow, eval_overflow("max * 2", Overflow::Error));
+
+    assert_eq!(Ok(min), eval_overflow("min / -1", Overflow::Wrap));
+    assert_eq!(Ok(max), eval_overflow("min / -1", Overflow::Saturate));
+    assert_eq!(overflow, eval_overflow("min / -1", Overflow::Error));
+    assert_eq!(Ok(0), eval_overflow("min % -1", Overflow::Error));
+
+    assert_eq!(Ok(min), eval_overflow("-min", Overflow::Wrap));
+    assert_eq!(Ok(max), eval_overflow("-min", Overflow::Saturate));
+    assert_eq!(overflow, eval_overflow("-min", Overflow::Error));
+
+    // Operations which don't overflow give the same result whatever the mode
+    for &mode in [Overflow::Wrap, Overflow::Saturate, Overflow::Error].iter() {
+        assert_eq!(Ok(-max), eval_overflow("-max", mode));
+        assert_eq!(Ok(min), eval_overflow("-max - 1", mode));
+        assert_eq!(Ok(-7), eval_overflow("3 * 4 - 19", mode));
+    }
+}
+
+#[test]
+fn test_integer_builtins() {
+    let min = std::i64::MIN;
+    let max = std::i64::MAX;
+    let overflow = Err("integer overflow".to_string());
+
+    // The builtins behave the same whatever the overflow option
+    for &mode in [Overflow::Wrap, Overflow::Saturate, Overflow::Error].iter() {
+        assert_eq!(Ok(min), eval_overflow("int::wrapping_add(max, 1)", mode));
+        assert_eq!(Ok(max), eval_overflow("int::wrapping_sub(min, 1)", mode));
+        assert_eq!(Ok(-2), eval_overflow("int::wrapping_mul(max, 2)", mode));
+
+        assert_eq!(Ok(max), eval_overflow("int::saturating_add(max, 1)", mode));
+        assert_eq!(Ok(min), eval_overflow("int::saturating_sub(min, 1)", mode));
+        assert_eq!(Ok(min), eval_overflow("int::saturating_mul(max, -2)", mode));
+
+        assert_eq!(overflow, eval_overflow("int::checked_add(max, 1)", mode));
+        assert_eq!(overflow, eval_overflow("int::checked_sub(min, 1)", mode));
+        assert_eq!(overflow, eval_overflow("int::checked_mul(min, -1)", mode));
+
+        assert_eq!(Ok(5), eval_overflow("int::checked_add(2, 3)", mode));
+        assert_eq!(Ok(-1), eval_overflow("int::saturating_sub(2, 3)", mode));
+        assert_eq!(Ok(6), eval_overflow("int::wrapping_mul(2, 3)", mode));
+    }
+}
+
+#[test]
+fn test_stack_exhausted() {
+    // Each call nests 40 blocks, using much more stack than the call depth limit accounts for
+    let mut body = "return f(n + 1)".to_string();
+    for _ in 0..40 {
+        body = format!("if n >= 0 {{\n{}\n}}", body);
+    }
+    let recursion = format!("func f(n: int) -> int {{
+                                 {}
+                                 return 0
+                             }}",
+                            body);
+    let options = || {
+        InterpreterOptions { max_call_depth: MAX_CALL_DEPTH, ..InterpreterOptions::default() }
+    };
+    let stack_overflow =
+        Err("stack overflow: calls, blocks and expressions are nested too deeply".to_string());
+
+    assert_eq!(stack_overflow,
+               run_with(&format!("{}
+                                  func main() -> int {{
+                                      return f(0)
+                                  }}",
+                                 recursion),
+                        options()));
+    assert_eq!(stack_overflow,
+               run_with(&format!("{}
+                                  func main() -> int {{
+                                      var t: task = spawn -> int {{ return f(0) }}
+                                      return join(t)
+                                  }}",
+                                 recursion),
+                        options()));
+}
+
+#[test]
+fn test_exit_code() {
+    let src = "func main() -> int {\n    return 256\n}";
+    let mut reader = Reader::new(src, source_map::FileId::nil_id());
+    let mut parser = Parser::new(&mut reader, ParserOptions::default());
+    let ast = parser.parse().unwrap();
+
+    assert_eq!(0, Interpreter::exit_code(ast, 0).unwrap());
+    assert_eq!(255, Interpreter::exit_code(ast, 255).unwrap());
+    for &code in [256, -1, std::i64::MAX].iter() {
+        let err = Interpreter::exit_code(ast, code).unwrap_err();
+        assert_eq!(format!("main returned {}, which is not an exit code from 0 to 255", code),
+                   err.message());
+        assert_eq!(1, err.span.srow);
+    }
+}
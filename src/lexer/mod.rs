@@ -3,6 +3,13 @@ use std::fmt;
 use std::fmt::Formatter;
 use std::fmt::Display;
 use std::error::Error as BaseError;
+use std::borrow::Cow;
+use source_map::FileId;
+use unicode_normalization::UnicodeNormalization;
+
+pub mod stream;
+pub mod trivia;
+mod unicode;
 
 #[cfg(test)]
 mod tests;
@@ -22,24 +29,31 @@ pub enum ErrorKind {
     InvalidSymbol,
     UnexpectedChar(char),
     UnknownEscapeChar(char),
+    /// Escape sequence as written, e.g. `\x8` or `\u{d800}`
+    InvalidEscape(String),
+    InvalidInput,
+}
+
+impl Error {
+    /// Description of the error, without its position
+    pub fn message(&self) -> String {
+        match self.kind {
+            ErrorKind::InvalidChar |
+            ErrorKind::InvalidString |
+            ErrorKind::InvalidInteger |
+            ErrorKind::InvalidFloat |
+            ErrorKind::InvalidSymbol |
+            ErrorKind::InvalidInput => self.description().to_string(),
+            ErrorKind::UnexpectedChar(c) => format!("unexpected '{}'", c),
+            ErrorKind::UnknownEscapeChar(c) => format!("unknown escape character {}", c),
+            ErrorKind::InvalidEscape(ref s) => format!("invalid escape sequence {}", s),
+        }
+    }
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f,
-               "{}: {}",
-               self.span,
-               match self.kind {
-                   ErrorKind::InvalidChar |
-                   ErrorKind::InvalidString |
-                   ErrorKind::InvalidInteger |
-                   ErrorKind::InvalidFloat |
-                   ErrorKind::InvalidSymbol => self.description().to_string(),
-                   ErrorKind::UnexpectedChar(c) => format!("unexpected '{}'", c),
-                   ErrorKind::UnknownEscapeChar(c) => {
-                       format!("unknown escape character {} in string literal", c)
-                   }
-               })
+        write!(f, "{}: {}", self.span, self.message())
     }
 }
 
@@ -54,7 +68,9 @@ impl BaseError for Error {
             ErrorKind::InvalidFloat => "failed to parse float",
             ErrorKind::InvalidSymbol => "failed to parse symbol",
             ErrorKind::UnexpectedChar(_) => "unexpected char",
-            ErrorKind::UnknownEscapeChar(_) => "unknown escape character in string literal",
+            ErrorKind::UnknownEscapeChar(_) => "unknown escape character",
+            ErrorKind::InvalidEscape(_) => "invalid escape sequence",
+            ErrorKind::InvalidInput => "failed to read input",
         }
     }
 
@@ -79,6 +95,36 @@ pub enum Keyword {
     For,
     In,
     New,
+    Spawn,
+    Async,
+    Await,
+    Extern,
+    Guard,
+}
+
+impl Keyword {
+    /// The keyword as written in the source
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Keyword::Import => "import",
+            Keyword::Package => "package",
+            Keyword::Func => "func",
+            Keyword::Var => "var",
+            Keyword::If => "if",
+            Keyword::Else => "else",
+            Keyword::While => "while",
+            Keyword::Struct => "struct",
+            Keyword::Return => "return",
+            Keyword::For => "for",
+            Keyword::In => "in",
+            Keyword::New => "new",
+            Keyword::Spawn => "spawn",
+            Keyword::Async => "async",
+            Keyword::Await => "await",
+            Keyword::Extern => "extern",
+            Keyword::Guard => "guard",
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -91,6 +137,7 @@ pub enum Symbol {
     RightBrace,
     NewLine,
     Dot,
+    Ellipsis,
     Comma,
     Colon,
     Equal,
@@ -111,22 +158,76 @@ pub enum Symbol {
     Concat,
     Return,
     Amp,
+    AmpAmp,
+    PipePipe,
+    Bang,
     At,
 }
 
+impl Symbol {
+    /// The symbol as written in the source
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Symbol::LeftParenthesis => "(",
+            Symbol::RightParenthesis => ")",
+            Symbol::LeftBracket => "[",
+            Symbol::RightBracket => "]",
+            Symbol::LeftBrace => "{",
+            Symbol::RightBrace => "}",
+            Symbol::NewLine => "\n",
+            Symbol::Dot => ".",
+            Symbol::Ellipsis => "...",
+            Symbol::Comma => ",",
+            Symbol::Colon => ":",
+            Symbol::Equal => "=",
+            Symbol::EqualEqual => "==",
+            Symbol::Plus => "+",
+            Symbol::PlusPlus => "++",
+            Symbol::Minus => "-",
+            Symbol::Star => "*",
+            Symbol::Over => "/",
+            Symbol::Modulo => "%",
+            Symbol::NotEqual => "!=",
+            Symbol::ColonColon => "::",
+            Symbol::Hash => "#",
+            Symbol::Less => "<",
+            Symbol::LessOrEqual => "<=",
+            Symbol::More => ">",
+            Symbol::MoreOrEqual => ">=",
+            Symbol::Concat => "<>",
+            Symbol::Return => "->",
+            Symbol::Amp => "&",
+            Symbol::AmpAmp => "&&",
+            Symbol::PipePipe => "||",
+            Symbol::Bang => "!",
+            Symbol::At => "@",
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
-pub struct SToken {
-    pub tok: Token,
+pub struct SToken<S = String> {
+    pub tok: Token<S>,
     pub sp: Span,
 }
 
+/// Rows and columns start at 1, and the end position (`erow`, `ecol`) is exclusive. `start` and
+/// `end` are the corresponding byte offsets in the source, so that `&src[sp.start..sp.end]` is the
+/// spanned text.
+///
+/// Columns count characters (Unicode scalar values) from the start of the line: a tab or a
+/// multi-byte character is a single column, and the `\r` of a `\r\n` is the last column of its
+/// line. `SourceFile::position` gives the same positions for byte offsets, and
+/// `SourceFile::utf16_column` converts columns for editors counting UTF-16 code units.
 #[derive(Debug, PartialEq, Clone, Hash, Eq)]
 pub struct Span {
     pub srow: i32,
     pub scol: i32,
     pub erow: i32,
     pub ecol: i32,
-    pub file: String,
+    pub start: usize,
+    pub end: usize,
+    pub file: FileId,
 }
 
 impl Span {
@@ -136,6 +237,8 @@ impl Span {
             srow: sp1.srow,
             ecol: sp2.ecol,
             erow: sp2.erow,
+            start: sp1.start,
+            end: sp2.end,
             file: sp1.file,
         }
     }
@@ -146,84 +249,370 @@ impl Span {
             srow: 0,
             ecol: 0,
             erow: 0,
-            file: "".to_string(),
+            start: 0,
+            end: 0,
+            file: FileId::nil_id(),
         }
     }
+
+    pub fn contains(&self, row: i32, col: i32) -> bool {
+        (self.srow, self.scol) <= (row, col) && (row, col) < (self.erow, self.ecol)
+    }
+
+    /// Whether both spans are in the same file and share at least one byte
+    pub fn intersects(&self, other: &Span) -> bool {
+        self.file == other.file && self.start < other.end && other.start < self.end
+    }
+
+    /// Smallest span covering both spans, whatever their order (unlike `concat`)
+    pub fn merge(&self, other: &Span) -> Span {
+        let first = if self.start <= other.start { self } else { other };
+        let last = if self.end >= other.end { self } else { other };
+
+        Span {
+            scol: first.scol,
+            srow: first.srow,
+            ecol: last.ecol,
+            erow: last.erow,
+            start: first.start,
+            end: last.end,
+            file: self.file,
+        }
+    }
+
+    /// Length in bytes
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
 }
 
 impl fmt::Display for Span {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "{}:{}", self.file, self.srow)
+        write!(f, "{}", self.srow)
     }
 }
 
+/// Token whose text is held in an `S`, a `String` unless the token borrows from the source
 #[derive(Debug, PartialEq, Clone)]
-pub enum Token {
+pub enum Token<S = String> {
     EOF,
-    Identifier(String),
+    Identifier(S),
     Keyword(Keyword),
-    StringLiteral(String),
+    StringLiteral(S),
+    /// `b"..."`, the bytes of the content encoded in UTF-8
+    BytesLiteral(std::vec::Vec<u8>),
     CharLiteral(char),
     IntegerLiteral(i64),
     FloatLiteral(f64),
     BoolLiteral(bool),
     Symbol(Symbol),
+    /// Text following `//` up to the end of the line
+    Comment(S),
+    /// Text following `///` up to the end of the line, documenting the declaration below
+    DocComment(S),
+}
+
+/// Token borrowing its text from the source, unless it differs from it: identifiers which aren't
+/// in NFC and string literals with escape sequences
+pub type BorrowedToken<'a> = Token<Cow<'a, str>>;
+
+/// Identifiers follow UAX #31: they start with a character having the `XID_Start` property or
+/// `_`, followed by characters having the `XID_Continue` property.
+fn is_identifier_start(c: char) -> bool {
+    match c {
+        'a'...'z' | 'A'...'Z' | '_' => true,
+        _ => !c.is_ascii() && unicode::is_xid_start(c),
+    }
+}
+
+fn is_identifier_continue(c: char) -> bool {
+    match c {
+        'a'...'z' | 'A'...'Z' | '0'...'9' | '_' => true,
+        _ => !c.is_ascii() && unicode::is_xid_continue(c),
+    }
+}
+
+/// Lexes the whole input, excluding the final `EOF` token. Lexing carries on after errors so that
+/// all of them are reported.
+pub fn tokenize(src: &str,
+                file: FileId)
+                -> std::result::Result<std::vec::Vec<SToken>, std::vec::Vec<Error>> {
+    let mut reader = Reader::new(src, file);
+    let mut tokens = vec![];
+    let mut errors = vec![];
+
+    loop {
+        let offset = reader.current_offset;
+        match reader.next_token() {
+            Ok(SToken { tok: Token::EOF, .. }) => break,
+            Ok(t) => tokens.push(t),
+            Err(e) => {
+                errors.push(e);
+                // Make sure not to report the same error again
+                if reader.current_offset == offset {
+                    reader.next_char();
+                }
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(tokens)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Replaces the escape sequences of the content of a string literal, `raw` being the source
+/// between the quotes and `span` where it starts. Along with the string, returns the byte offset
+/// in the source of each of its chars, that of the `\` for a char written as an escape sequence.
+pub fn unescape_string(raw: &str, span: &Span) -> Result<(String, std::vec::Vec<usize>)> {
+    let mut reader = Reader::new_at(raw, span.file, 0, span.srow, span.scol);
+    let mut unescaped = String::with_capacity(raw.len());
+    let mut offsets = vec![];
+
+    while let Some(c) = reader.current_char {
+        offsets.push(span.start + reader.current_offset);
+        if c == '\\' {
+            let value = try!(reader.read_escape(false).map_err(|mut e| {
+                e.span.start += span.start;
+                e.span.end += span.start;
+                e
+            }));
+            unescaped.push(std::char::from_u32(value).unwrap());
+        } else {
+            unescaped.push(c);
+            reader.next_char();
+        }
+    }
+
+    Ok((unescaped, offsets))
+}
+
+/// Kind of a token, regardless of its payload
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TokenKind {
+    EOF,
+    Identifier,
+    Keyword,
+    StringLiteral,
+    BytesLiteral,
+    CharLiteral,
+    IntegerLiteral,
+    FloatLiteral,
+    BoolLiteral,
+    Symbol,
+    Comment,
+    DocComment,
+}
+
+impl TokenKind {
+    /// Name of the kind of token, for diagnostics
+    pub fn display(&self) -> &'static str {
+        match *self {
+            TokenKind::EOF => "end of file",
+            TokenKind::Identifier => "identifier",
+            TokenKind::Keyword => "keyword",
+            TokenKind::StringLiteral => "string literal",
+            TokenKind::BytesLiteral => "byte string literal",
+            TokenKind::CharLiteral => "char literal",
+            TokenKind::IntegerLiteral => "integer literal",
+            TokenKind::FloatLiteral => "float literal",
+            TokenKind::BoolLiteral => "bool literal",
+            TokenKind::Symbol => "symbol",
+            TokenKind::Comment => "comment",
+            TokenKind::DocComment => "doc comment",
+        }
+    }
+}
+
+impl<S> Token<S> {
+    pub fn kind(&self) -> TokenKind {
+        match *self {
+            Token::EOF => TokenKind::EOF,
+            Token::Identifier(_) => TokenKind::Identifier,
+            Token::Keyword(_) => TokenKind::Keyword,
+            Token::StringLiteral(_) => TokenKind::StringLiteral,
+            Token::BytesLiteral(_) => TokenKind::BytesLiteral,
+            Token::CharLiteral(_) => TokenKind::CharLiteral,
+            Token::IntegerLiteral(_) => TokenKind::IntegerLiteral,
+            Token::FloatLiteral(_) => TokenKind::FloatLiteral,
+            Token::BoolLiteral(_) => TokenKind::BoolLiteral,
+            Token::Symbol(_) => TokenKind::Symbol,
+            Token::Comment(_) => TokenKind::Comment,
+            Token::DocComment(_) => TokenKind::DocComment,
+        }
+    }
+
+}
+
+impl<'a> BorrowedToken<'a> {
+    pub fn into_owned(self) -> Token {
+        match self {
+            Token::EOF => Token::EOF,
+            Token::Identifier(s) => Token::Identifier(s.into_owned()),
+            Token::Keyword(k) => Token::Keyword(k),
+            Token::StringLiteral(s) => Token::StringLiteral(s.into_owned()),
+            Token::BytesLiteral(b) => Token::BytesLiteral(b),
+            Token::CharLiteral(c) => Token::CharLiteral(c),
+            Token::IntegerLiteral(i) => Token::IntegerLiteral(i),
+            Token::FloatLiteral(f) => Token::FloatLiteral(f),
+            Token::BoolLiteral(b) => Token::BoolLiteral(b),
+            Token::Symbol(s) => Token::Symbol(s),
+            Token::Comment(s) => Token::Comment(s.into_owned()),
+            Token::DocComment(s) => Token::DocComment(s.into_owned()),
+        }
+    }
+}
+
+impl Token {
+    /// The token as the user typed it, quoted with backticks, for diagnostics
+    pub fn display(&self) -> String {
+        match *self {
+            Token::EOF => "end of file".to_string(),
+            Token::Symbol(Symbol::NewLine) => "end of line".to_string(),
+            Token::Identifier(ref s) => format!("`{}`", s),
+            Token::Keyword(ref k) => format!("`{}`", k.as_str()),
+            Token::StringLiteral(ref s) => format!("`\"{}\"`", s.replace('\n', "\\n")),
+            Token::BytesLiteral(ref b) => {
+                format!("`b\"{}\"`", String::from_utf8_lossy(b).replace('\n', "\\n"))
+            }
+            Token::CharLiteral(c) => format!("`'{}'`", c),
+            Token::IntegerLiteral(i) => format!("`{}`", i),
+            Token::FloatLiteral(f) => format!("`{:?}`", f),
+            Token::BoolLiteral(b) => format!("`{}`", b),
+            Token::Symbol(ref s) => format!("`{}`", s.as_str()),
+            Token::Comment(ref s) => format!("`//{}`", s),
+            Token::DocComment(ref s) => format!("`///{}`", s),
+        }
+    }
+}
+
+/// Source of tokens for the parser
+pub trait TokenStream {
+    /// Returns the next token, `EOF` once the end of the input is reached
+    fn next_token(&mut self) -> Result<SToken>;
 }
 
 pub struct Reader<'a> {
-    pub filename: String,
+    pub file: FileId,
     pub src: &'a str,
     itr: std::str::Chars<'a>,
+    /// The two characters following `current_char`, so that peeking doesn't touch the iterator
+    lookahead: [Option<char>; 2],
     current_char: Option<char>,
     start_row: i32,
     start_col: i32,
+    start_offset: usize,
     current_row: i32,
     current_col: i32,
+    current_offset: usize,
+    finished: bool,
 }
 
 impl<'a> Reader<'a> {
-    pub fn new(input: &'a str, filename: String) -> Self {
+    /// Reader of a whole file, skipping a leading byte order mark, and the first line if it is a
+    /// shebang (`#!...`) so that scripts can be made executable
+    pub fn new(input: &'a str, file: FileId) -> Self {
+        let start = if input.starts_with('\u{FEFF}') {
+            '\u{FEFF}'.len_utf8()
+        } else {
+            0
+        };
+
+        let mut reader = Self::new_at(input, file, start, 1, 1);
+        if input[start..].starts_with("#!") {
+            reader.skip_line();
+        }
+
+        reader
+    }
+
+    /// Reader starting at the byte `offset` of the input, which is at the given row and column.
+    /// Spans are still relative to the start of the input.
+    pub fn new_at(input: &'a str, file: FileId, offset: usize, row: i32, col: i32) -> Self {
+        let mut itr = input[offset..].chars();
+        let lookahead = [itr.next(), itr.next()];
         let mut reader = Reader {
-            filename: filename,
+            file: file,
             src: input,
-            itr: input.chars(),
+            itr: itr,
+            lookahead: lookahead,
             current_char: None,
-            current_col: 0,
-            current_row: 1,
-            start_col: 0,
-            start_row: 1,
+            current_col: col - 1,
+            current_row: row,
+            start_col: col - 1,
+            start_row: row,
+            current_offset: offset,
+            start_offset: offset,
+            finished: false,
         };
         reader.next_char();
 
         reader
     }
 
+    /// Span from the given position to the current one
+    fn span_from(&self, row: i32, col: i32, offset: usize) -> Span {
+        Span {
+            file: self.file,
+            scol: col,
+            srow: row,
+            ecol: self.current_col,
+            erow: self.current_row,
+            start: offset,
+            end: self.current_offset,
+        }
+    }
+
     fn get_current_span(&self) -> Span {
         Span {
-            file: self.filename.clone(),
+            file: self.file,
             scol: self.start_col,
             srow: self.start_row,
             ecol: self.current_col,
             erow: self.current_row,
+            start: self.start_offset,
+            end: self.current_offset,
         }
     }
 
     pub fn next_token(&mut self) -> Result<SToken> {
+        let token = try!(self.next_borrowed_token());
+
+        Ok(SToken {
+            tok: token.tok.into_owned(),
+            sp: token.sp,
+        })
+    }
+
+    /// Same as `next_token`, without allocating for the text of the token when it is the same in
+    /// the source
+    pub fn next_borrowed_token(&mut self) -> Result<SToken<Cow<'a, str>>> {
         self.skip_whitespace();
 
         self.start_col = self.current_col;
         self.start_row = self.current_row;
+        self.start_offset = self.current_offset;
 
         let new_token = match self.current_char {
             Some(c) => {
-                if c.is_alphabetic() || c == '_' || (c as u32) > 127 {
+                if c == 'b' && self.peek_char() == Some('"') {
+                    self.read_bytes()
+                } else if is_identifier_start(c) {
                     self.read_word()
                 } else if c.is_numeric() || (c == '.' && self.peek_char().unwrap_or('\0').is_numeric()) {
                     self.read_number()
                 } else if c == '\'' {
                     self.read_char()
                 } else if c == '"' {
-                    self.read_string()
+                    self.read_string(false)
+                } else if c == '/' && self.peek_char() == Some('/') {
+                    Ok(self.read_comment())
                 } else {
                     self.read_symbol()
                 }
@@ -237,20 +626,20 @@ impl<'a> Reader<'a> {
         })
     }
 
-    fn read_word(&mut self) -> Result<Token> {
-        let mut word = String::new();
+    fn read_word(&mut self) -> Result<BorrowedToken<'a>> {
+        let start = self.current_offset;
 
         while let Some(c) = self.current_char {
-            if c.is_alphabetic() || c.is_numeric() || c == '_' || (c as u32) > 127 {
-                word.push(c);
-            } else {
+            if !is_identifier_continue(c) {
                 break;
             }
 
             self.next_char();
         }
 
-        match word.as_ref() {
+        let word = self.slice_from(start);
+
+        match word {
             "import" => Ok(Token::Keyword(Keyword::Import)),
             "package" => Ok(Token::Keyword(Keyword::Package)),
             "func" => Ok(Token::Keyword(Keyword::Func)),
@@ -265,18 +654,23 @@ impl<'a> Reader<'a> {
             "for" => Ok(Token::Keyword(Keyword::For)),
             "in" => Ok(Token::Keyword(Keyword::In)),
             "new" => Ok(Token::Keyword(Keyword::New)),
-            _ => Ok(Token::Identifier(word)),
+            "spawn" => Ok(Token::Keyword(Keyword::Spawn)),
+            "async" => Ok(Token::Keyword(Keyword::Async)),
+            "await" => Ok(Token::Keyword(Keyword::Await)),
+            "extern" => Ok(Token::Keyword(Keyword::Extern)),
+            "guard" => Ok(Token::Keyword(Keyword::Guard)),
+            // Normalized so that identifiers which look the same are the same
+            _ if word.is_ascii() => Ok(Token::Identifier(Cow::Borrowed(word))),
+            _ => Ok(Token::Identifier(word.nfc().collect())),
         }
     }
 
-    fn read_number(&mut self) -> Result<Token> {
+    fn read_number(&mut self) -> Result<BorrowedToken<'a>> {
+        let start = self.current_offset;
         let mut float = false;
-        let mut number = String::new();
 
         while let Some(c) = self.current_char {
-            if c.is_numeric() {
-                number.push(c);
-            } else if c == '.' {
+            if c == '.' {
                 if float {
                     return Err(Error {
                         kind: ErrorKind::UnexpectedChar('.'),
@@ -284,15 +678,15 @@ impl<'a> Reader<'a> {
                     });
                 } else {
                     float = true;
-                    number.push(c);
                 }
-            } else {
+            } else if !c.is_numeric() {
                 break;
             }
 
             self.next_char();
         }
 
+        let number = self.slice_from(start);
         if float {
             if let Some(f) = number.parse::<f64>().ok() {
                 Ok(Token::FloatLiteral(f))
@@ -312,9 +706,16 @@ impl<'a> Reader<'a> {
         }
     }
 
-    fn read_char(&mut self) -> Result<Token> {
-        let mut c = match self.next_char() {
-            Some(c) => c,
+    fn read_char(&mut self) -> Result<BorrowedToken<'a>> {
+        let c = match self.next_char() {
+            Some('\\') => {
+                // Outside of byte strings, escape sequences are valid chars
+                std::char::from_u32(try!(self.read_escape(false))).unwrap()
+            }
+            Some(c) => {
+                self.next_char();
+                c
+            }
             None => {
                 return Err(Error {
                     kind: ErrorKind::InvalidChar,
@@ -323,21 +724,9 @@ impl<'a> Reader<'a> {
             }
         };
 
-        if c == '\\' {
-            // TODO: make escaping more accurate and complete
-            c = self.next_char().unwrap();
-        };
-
-        if let Some(next_c) = self.next_char() {
-            if next_c == '\'' {
-                self.next_char();
-                Ok(Token::CharLiteral(c))
-            } else {
-                Err(Error {
-                    kind: ErrorKind::InvalidChar,
-                    span: self.get_current_span(),
-                })
-            }
+        if self.current_char == Some('\'') {
+            self.next_char();
+            Ok(Token::CharLiteral(c))
         } else {
             Err(Error {
                 kind: ErrorKind::InvalidChar,
@@ -346,49 +735,149 @@ impl<'a> Reader<'a> {
         }
     }
 
-    fn read_string(&mut self) -> Result<Token> {
-        let mut string = String::new();
+    /// Reads a string literal, or a byte string literal whose `b` has been read if `bytes` is set
+    fn read_string(&mut self, bytes: bool) -> Result<BorrowedToken<'a>> {
+        let content_start = self.current_offset + 1;
+        // Literals without escape sequences are copied from the source in one go, this is only
+        // allocated once an escape sequence needs to be replaced
+        let mut unescaped: Option<std::vec::Vec<u8>> = None;
 
-        let mut escaped = false;
-        let mut closed = false;
-        while let Some(c) = self.next_char() {
-            if c == '\\' && !escaped {
-                escaped = true;
-            } else if c == '"' && !escaped {
-                closed = true;
-                self.next_char();
-                break;
-            } else if escaped {
-                escaped = false;
-                if c == 'n' {
-                    string.push('\n');
-                } else {
+        self.next_char();
+        loop {
+            match self.current_char {
+                Some('"') => break,
+                Some('\\') => {
+                    if unescaped.is_none() {
+                        unescaped = Some(self.slice_from(content_start).as_bytes().to_vec());
+                    }
+                    let value = try!(self.read_escape(bytes));
+                    if let Some(ref mut s) = unescaped {
+                        if bytes {
+                            s.push(value as u8);
+                        } else {
+                            let c = std::char::from_u32(value).unwrap();
+                            s.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                        }
+                    }
+                }
+                Some(c) => {
+                    if let Some(ref mut s) = unescaped {
+                        s.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                    }
+                    self.next_char();
+                }
+                None => {
                     return Err(Error {
-                        kind: ErrorKind::UnknownEscapeChar(c),
+                        kind: ErrorKind::InvalidString,
                         span: self.get_current_span(),
-                    });
+                    })
                 }
-            } else {
-                if c == '\n' {
-                    self.current_col = 0;
-                    self.current_row += 1;
+            }
+        }
+
+        let content = self.slice_from(content_start);
+        self.next_char();
+
+        match (unescaped, bytes) {
+            (Some(b), true) => Ok(Token::BytesLiteral(b)),
+            (None, true) => Ok(Token::BytesLiteral(content.as_bytes().to_vec())),
+            // Escape sequences only produce chars outside of byte strings
+            (Some(s), false) => Ok(Token::StringLiteral(Cow::Owned(String::from_utf8(s).unwrap()))),
+            (None, false) => Ok(Token::StringLiteral(Cow::Borrowed(content))),
+        }
+    }
+
+    fn read_bytes(&mut self) -> Result<BorrowedToken<'a>> {
+        self.next_char();
+
+        self.read_string(true)
+    }
+
+    /// Value of the escape sequence starting at the current `\`, moving past it. `\xNN` is any
+    /// byte in a byte string but only an ASCII char elsewhere, and `\u{N}` is only allowed
+    /// outside of byte strings, so the value is a char unless `bytes` is set.
+    fn read_escape(&mut self, bytes: bool) -> Result<u32> {
+        let (row, col, offset) = (self.current_row, self.current_col, self.current_offset);
+        let c = self.next_char();
+        self.next_char();
+
+        let value = match c {
+            Some('n') => Some('\n' as u32),
+            Some('t') => Some('\t' as u32),
+            Some('r') => Some('\r' as u32),
+            Some('0') => Some(0),
+            Some('\\') => Some('\\' as u32),
+            Some('\'') => Some('\'' as u32),
+            Some('"') => Some('"' as u32),
+            Some('x') => {
+                let digits = self.read_hex_digits(2);
+                match u32::from_str_radix(digits, 16) {
+                    Ok(value) if digits.len() == 2 && (bytes || value < 0x80) => Some(value),
+                    _ => None,
                 }
-                escaped = false;
-                string.push(c);
+            }
+            Some('u') if !bytes => {
+                let mut value = None;
+                if self.current_char == Some('{') {
+                    self.next_char();
+                    let digits = self.read_hex_digits(6);
+                    if self.current_char == Some('}') {
+                        self.next_char();
+                        value = u32::from_str_radix(digits, 16)
+                            .ok()
+                            .and_then(std::char::from_u32)
+                            .map(|c| c as u32);
+                    }
+                }
+                value
+            }
+            Some(c) => {
+                return Err(Error {
+                    kind: ErrorKind::UnknownEscapeChar(c),
+                    span: self.span_from(row, col, offset),
+                })
+            }
+            None => None,
+        };
+
+        match value {
+            Some(value) => Ok(value),
+            None => {
+                Err(Error {
+                    kind: ErrorKind::InvalidEscape(self.slice_from(offset).to_string()),
+                    span: self.span_from(row, col, offset),
+                })
             }
         }
+    }
+
+    /// Up to `max` hex digits starting at the current char
+    fn read_hex_digits(&mut self, max: usize) -> &'a str {
+        let start = self.current_offset;
+        for _ in 0..max {
+            match self.current_char {
+                Some(c) if c.is_digit(16) => self.next_char(),
+                _ => break,
+            };
+        }
+
+        self.slice_from(start)
+    }
 
-        if closed {
-            Ok(Token::StringLiteral(string))
+    fn read_comment(&mut self) -> BorrowedToken<'a> {
+        let content_start = self.current_offset + 2;
+        self.skip_line();
+
+        let text = self.slice_from(content_start).trim_right_matches('\r');
+        // `////` and more is a plain comment, e.g. a separator
+        if text.starts_with('/') && !text.starts_with("//") {
+            Token::DocComment(Cow::Borrowed(&text[1..]))
         } else {
-            Err(Error {
-                kind: ErrorKind::InvalidString,
-                span: self.get_current_span(),
-            })
+            Token::Comment(Cow::Borrowed(text))
         }
     }
 
-    fn read_symbol(&mut self) -> Result<Token> {
+    fn read_symbol(&mut self) -> Result<BorrowedToken<'a>> {
         let tok = match self.current_char.unwrap() {
             '(' => Ok(Token::Symbol(Symbol::LeftParenthesis)),
             ')' => Ok(Token::Symbol(Symbol::RightParenthesis)),
@@ -396,14 +885,41 @@ impl<'a> Reader<'a> {
             ']' => Ok(Token::Symbol(Symbol::RightBracket)),
             '{' => Ok(Token::Symbol(Symbol::LeftBrace)),
             '}' => Ok(Token::Symbol(Symbol::RightBrace)),
-            '&' => Ok(Token::Symbol(Symbol::Amp)),
+            '&' => {
+                match self.peek_char().unwrap_or('\0') {
+                    '&' => {
+                        self.next_char();
+                        Ok(Token::Symbol(Symbol::AmpAmp))
+                    }
+                    _ => Ok(Token::Symbol(Symbol::Amp)),
+                }
+            }
+            '|' => {
+                match self.peek_char().unwrap_or('\0') {
+                    '|' => {
+                        self.next_char();
+                        Ok(Token::Symbol(Symbol::PipePipe))
+                    }
+                    _ => {
+                        Err(Error {
+                            kind: ErrorKind::InvalidSymbol,
+                            span: self.get_current_span(),
+                        })
+                    }
+                }
+            }
             '@' => Ok(Token::Symbol(Symbol::At)),
-            '\n' => {
-                self.current_col = 0;
-                self.current_row += 1;
-                Ok(Token::Symbol(Symbol::NewLine))
+            '\n' => Ok(Token::Symbol(Symbol::NewLine)),
+            '.' => {
+                match (self.peek_char(), self.peek2_char()) {
+                    (Some('.'), Some('.')) => {
+                        self.next_char();
+                        self.next_char();
+                        Ok(Token::Symbol(Symbol::Ellipsis))
+                    }
+                    _ => Ok(Token::Symbol(Symbol::Dot)),
+                }
             }
-            '.' => Ok(Token::Symbol(Symbol::Dot)),
             ',' => Ok(Token::Symbol(Symbol::Comma)),
             ':' => {
                 match self.peek_char().unwrap_or('\0') {
@@ -450,12 +966,7 @@ impl<'a> Reader<'a> {
                         self.next_char();
                         Ok(Token::Symbol(Symbol::NotEqual))
                     }
-                    _ => {
-                        Err(Error {
-                            kind: ErrorKind::InvalidSymbol,
-                            span: self.get_current_span(),
-                        })
-                    }
+                    _ => Ok(Token::Symbol(Symbol::Bang)),
                 }
             }
             '#' => Ok(Token::Symbol(Symbol::Hash)),
@@ -494,16 +1005,44 @@ impl<'a> Reader<'a> {
         tok
     }
 
+    /// Source text from the `start` offset up to the current character (excluded)
+    fn slice_from(&self, start: usize) -> &'a str {
+        &self.src[start..self.current_offset]
+    }
+
+    /// Moves to the next character, which is the only place where the position is updated, so
+    /// that it is right whichever token a newline is part of
     fn next_char(&mut self) -> Option<char> {
-        self.current_char = self.itr.next();
+        if let Some(c) = self.current_char {
+            self.current_offset += c.len_utf8();
+            if c == '\n' {
+                self.current_row += 1;
+                self.current_col = 0;
+            }
+        }
+        self.current_char = self.lookahead[0];
+        self.lookahead[0] = self.lookahead[1];
+        self.lookahead[1] = self.itr.next();
         self.current_col += 1;
         self.current_char
     }
 
     fn peek_char(&self) -> Option<char> {
-        match self.itr.clone().peekable().peek() {
-            Some(c) => Some(*c),
-            None => None,
+        self.lookahead[0]
+    }
+
+    /// Character after the one returned by `peek_char`
+    fn peek2_char(&self) -> Option<char> {
+        self.lookahead[1]
+    }
+
+    /// Moves to the newline ending the current line
+    fn skip_line(&mut self) {
+        while let Some(c) = self.current_char {
+            if c == '\n' {
+                break;
+            }
+            self.next_char();
         }
     }
 
@@ -516,3 +1055,33 @@ impl<'a> Reader<'a> {
         }
     }
 }
+
+/// Yields the tokens until the end of the input (the `EOF` token itself isn't yielded) or until
+/// the first error
+impl<'a> Iterator for Reader<'a> {
+    type Item = Result<SToken>;
+
+    fn next(&mut self) -> Option<Result<SToken>> {
+        if self.finished {
+            return None;
+        }
+
+        match self.next_token() {
+            Ok(SToken { tok: Token::EOF, .. }) => {
+                self.finished = true;
+                None
+            }
+            Ok(t) => Some(Ok(t)),
+            Err(e) => {
+                self.finished = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<'a> TokenStream for Reader<'a> {
+    fn next_token(&mut self) -> Result<SToken> {
+        Reader::next_token(self)
+    }
+}
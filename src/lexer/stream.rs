@@ -0,0 +1,122 @@
+use std;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use lexer::*;
+use source_map::FileId;
+
+/// Lexer reading its input from any `io::Read` one line at a time, so that the input never needs
+/// to be loaded in memory as a whole. Lines are lexed with a `Reader`, which means tokens can't
+/// span several lines, except for string literals: a line ending inside a string is lexed again
+/// along with the following ones until the string is closed.
+pub struct StreamReader<R: Read> {
+    input: BufReader<R>,
+    file: FileId,
+    /// Number of rows and bytes of the input lexed so far
+    rows: i32,
+    offset: usize,
+    tokens: std::collections::VecDeque<Result<SToken>>,
+}
+
+impl<R: Read> StreamReader<R> {
+    pub fn new(input: R, file: FileId) -> Self {
+        StreamReader {
+            input: BufReader::new(input),
+            file: file,
+            rows: 0,
+            offset: 0,
+            tokens: std::collections::VecDeque::new(),
+        }
+    }
+
+    pub fn next_token(&mut self) -> Result<SToken> {
+        while self.tokens.is_empty() {
+            if !try!(self.read_lines()) {
+                return Ok(SToken {
+                    tok: Token::EOF,
+                    sp: self.current_span(),
+                });
+            }
+        }
+
+        self.tokens.pop_front().unwrap()
+    }
+
+    /// Lexes the next line of the input (or the next few ones if a string literal spans them).
+    /// Returns false at the end of the input.
+    fn read_lines(&mut self) -> Result<bool> {
+        let mut buf = String::new();
+        loop {
+            let read = match self.input.read_line(&mut buf) {
+                Ok(read) => read,
+                Err(_) => {
+                    return Err(Error {
+                        kind: ErrorKind::InvalidInput,
+                        span: self.current_span(),
+                    })
+                }
+            };
+            if buf.is_empty() {
+                return Ok(false);
+            }
+
+            // Only the first line can be a shebang
+            let reader = if self.offset == 0 {
+                Reader::new(buf.as_ref(), self.file)
+            } else {
+                Reader::new_at(buf.as_ref(), self.file, 0, 1, 1)
+            };
+            let tokens: std::vec::Vec<Result<SToken>> = reader.collect();
+            let unterminated_string = match tokens.last() {
+                Some(&Err(Error { kind: ErrorKind::InvalidString, .. })) => true,
+                _ => false,
+            };
+            if unterminated_string && read != 0 {
+                continue;
+            }
+
+            for token in tokens {
+                self.tokens.push_back(match token {
+                    Ok(mut t) => {
+                        self.shift(&mut t.sp);
+                        Ok(t)
+                    }
+                    Err(mut e) => {
+                        self.shift(&mut e.span);
+                        Err(e)
+                    }
+                });
+            }
+            self.rows += buf.matches('\n').count() as i32;
+            self.offset += buf.len();
+
+            return Ok(true);
+        }
+    }
+
+    /// Converts a span relative to the lines being lexed to one relative to the whole input
+    fn shift(&self, span: &mut Span) {
+        span.srow += self.rows;
+        span.erow += self.rows;
+        span.start += self.offset;
+        span.end += self.offset;
+    }
+
+    fn current_span(&self) -> Span {
+        Span {
+            srow: self.rows + 1,
+            scol: 1,
+            erow: self.rows + 1,
+            ecol: 1,
+            start: self.offset,
+            end: self.offset,
+            file: self.file,
+        }
+    }
+}
+
+impl<R: Read> TokenStream for StreamReader<R> {
+    fn next_token(&mut self) -> Result<SToken> {
+        StreamReader::next_token(self)
+    }
+}
@@ -1,7 +1,9 @@
 use super::*;
+use source_map;
+use source_map::{SourceFile, SourceMap};
 
 fn nth_token_eq(program: &str, i: i32, token: Token) {
-    let mut lexer = Reader::new(program, "".to_string());
+    let mut lexer = Reader::new(program, FileId::nil_id());
     for _ in 0..i {
         lexer.next_token().unwrap();
     }
@@ -9,7 +11,7 @@ fn nth_token_eq(program: &str, i: i32, token: Token) {
 }
 
 fn nth_token_neq(program: &str, i: i32, token: Token) {
-    let mut lexer = Reader::new(program, "".to_string());
+    let mut lexer = Reader::new(program, FileId::nil_id());
     for _ in 0..i {
         lexer.next_token().unwrap();
     }
@@ -17,7 +19,7 @@ fn nth_token_neq(program: &str, i: i32, token: Token) {
 }
 
 fn nth_token_err(program: &str, i: i32, error_kind: ErrorKind) {
-    let mut lexer = Reader::new(program, "".to_string());
+    let mut lexer = Reader::new(program, FileId::nil_id());
     for _ in 0..i {
         lexer.next_token().unwrap();
     }
@@ -41,6 +43,16 @@ fn test_token_identifier() {
     nth_token_neq("00invalid_identifier",
                   0,
                   Token::Identifier("00invalid_identifier".to_string()));
+
+    nth_token_eq("_été", 0, Token::Identifier("_été".to_string()));
+    nth_token_eq("変数2", 0, Token::Identifier("変数2".to_string()));
+    // Decomposed and precomposed é are the same identifier
+    nth_token_eq("e\u{301}t\u{e9}", 0, Token::Identifier("\u{e9}t\u{e9}".to_string()));
+
+    nth_token_err("\u{1F600}", 0, ErrorKind::InvalidSymbol);
+    nth_token_err("\u{200B}a", 0, ErrorKind::InvalidSymbol);
+    nth_token_eq("a\u{200B}", 0, Token::Identifier("a".to_string()));
+    nth_token_err("\u{301}", 0, ErrorKind::InvalidSymbol);
 }
 
 #[test]
@@ -57,6 +69,10 @@ fn test_token_keyword() {
     nth_token_eq("for", 0, Token::Keyword(Keyword::For));
     nth_token_eq("in", 0, Token::Keyword(Keyword::In));
     nth_token_eq("new", 0, Token::Keyword(Keyword::New));
+    nth_token_eq("spawn", 0, Token::Keyword(Keyword::Spawn));
+    nth_token_eq("await", 0, Token::Keyword(Keyword::Await));
+    nth_token_eq("extern", 0, Token::Keyword(Keyword::Extern));
+    nth_token_eq("guard", 0, Token::Keyword(Keyword::Guard));
 }
 
 #[test]
@@ -71,10 +87,21 @@ fn test_token_string_literal() {
     nth_token_err("\"", 0, ErrorKind::InvalidString);
 }
 
+#[test]
+fn test_token_bytes_literal() {
+    nth_token_eq("b\"ab\\n\"", 0, Token::BytesLiteral(vec![97, 98, 10]));
+    nth_token_eq("b\"é\"", 0, Token::BytesLiteral(vec![0xc3, 0xa9]));
+    nth_token_eq("b \"a\"", 0, Token::Identifier("b".to_string()));
+    nth_token_eq("bb\"a\"", 0, Token::Identifier("bb".to_string()));
+
+    nth_token_err("b\"", 0, ErrorKind::InvalidString);
+}
+
 #[test]
 fn test_token_char_literal() {
     nth_token_eq("'t'", 0, Token::CharLiteral('t'));
-    // TODO: add tests on escaping once it is improved for char
+    nth_token_eq("'\\''", 0, Token::CharLiteral('\''));
+    nth_token_eq("'\\u{e9}'", 0, Token::CharLiteral('é'));
 
     nth_token_err("'te'", 0, ErrorKind::InvalidChar);
     nth_token_err("'t", 0, ErrorKind::InvalidChar);
@@ -94,6 +121,17 @@ fn test_token_float_literal() {
     nth_token_err("42.42.42", 0, ErrorKind::UnexpectedChar('.'));
 }
 
+#[test]
+fn test_token_comment() {
+    nth_token_eq("// a comment", 0, Token::Comment(" a comment".to_string()));
+    nth_token_eq("a // b\r\nc", 1, Token::Comment(" b".to_string()));
+    nth_token_eq("/// doc", 0, Token::DocComment(" doc".to_string()));
+    nth_token_eq("///", 0, Token::DocComment("".to_string()));
+    nth_token_eq("//// not doc", 0, Token::Comment("// not doc".to_string()));
+    nth_token_eq("a // b\nc", 2, Token::Symbol(Symbol::NewLine));
+    nth_token_eq("a / b", 1, Token::Symbol(Symbol::Over));
+}
+
 #[test]
 fn test_token_bool_literal() {
     nth_token_eq("true", 0, Token::BoolLiteral(true));
@@ -109,9 +147,17 @@ fn test_token_symbol_literal() {
     nth_token_eq("{", 0, Token::Symbol(Symbol::LeftBrace));
     nth_token_eq("}", 0, Token::Symbol(Symbol::RightBrace));
     nth_token_eq("&", 0, Token::Symbol(Symbol::Amp));
+    nth_token_eq("&&", 0, Token::Symbol(Symbol::AmpAmp));
+    nth_token_eq("&&&", 1, Token::Symbol(Symbol::Amp));
+    nth_token_eq("||", 0, Token::Symbol(Symbol::PipePipe));
+    nth_token_err("|", 0, ErrorKind::InvalidSymbol);
+    nth_token_eq("!", 0, Token::Symbol(Symbol::Bang));
+    nth_token_eq("!!=", 1, Token::Symbol(Symbol::NotEqual));
     nth_token_eq("@", 0, Token::Symbol(Symbol::At));
     nth_token_eq("\n", 0, Token::Symbol(Symbol::NewLine));
     nth_token_eq(".", 0, Token::Symbol(Symbol::Dot));
+    nth_token_eq("...", 0, Token::Symbol(Symbol::Ellipsis));
+    nth_token_eq("..", 1, Token::Symbol(Symbol::Dot));
     nth_token_eq(",", 0, Token::Symbol(Symbol::Comma));
     nth_token_eq(":", 0, Token::Symbol(Symbol::Colon));
     nth_token_eq("::", 0, Token::Symbol(Symbol::ColonColon));
@@ -132,6 +178,412 @@ fn test_token_symbol_literal() {
     nth_token_eq(">", 0, Token::Symbol(Symbol::More));
     nth_token_eq(">=", 0, Token::Symbol(Symbol::MoreOrEqual));
 
-    nth_token_err("!", 0, ErrorKind::InvalidSymbol);
+    nth_token_err("~", 0, ErrorKind::InvalidSymbol);
     nth_token_err("$", 0, ErrorKind::InvalidSymbol);
 }
+
+#[test]
+fn test_token_span() {
+    let src = "var é: string = \"ab\"\nfoo";
+    let mut lexer = Reader::new(src, FileId::nil_id());
+    let mut spans = vec![];
+    loop {
+        let token = lexer.next_token().unwrap();
+        if token.tok == Token::EOF {
+            break;
+        }
+        spans.push(token.sp);
+    }
+
+    let texts: std::vec::Vec<&str> = spans.iter().map(|sp| &src[sp.start..sp.end]).collect();
+    assert_eq!(vec!["var", "é", ":", "string", "=", "\"ab\"", "\n", "foo"], texts);
+
+    assert_eq!((2, 1, 2, 4), (spans[7].srow, spans[7].scol, spans[7].erow, spans[7].ecol));
+    assert!(spans[7].contains(2, 3));
+    assert!(!spans[7].contains(2, 4));
+}
+
+#[test]
+fn test_span_merge() {
+    let src = "a + b";
+    let mut lexer = Reader::new(src, FileId::nil_id());
+    let a = lexer.next_token().unwrap().sp;
+    let plus = lexer.next_token().unwrap().sp;
+    let b = lexer.next_token().unwrap().sp;
+
+    let merged = b.merge(&a);
+    assert_eq!("a + b", &src[merged.start..merged.end]);
+    assert_eq!(5, merged.len());
+    assert!(merged.intersects(&plus));
+    assert!(!a.intersects(&b));
+}
+
+#[test]
+fn test_source_map() {
+    let source_map = SourceMap::new();
+    let a = source_map.add_file("a.ion".to_string(), "var a: int = 1".to_string());
+    let b = source_map.add_file("b.ion".to_string(), "foo(bar)".to_string());
+    assert!(a != b);
+    assert_eq!("b.ion", source_map.name(b));
+    assert_eq!("", source_map.name(FileId::nil_id()));
+
+    let file = source_map.file(b).unwrap();
+    let mut lexer = Reader::new(file.src.as_ref(), b);
+    lexer.next_token().unwrap();
+    lexer.next_token().unwrap();
+    let sp = lexer.next_token().unwrap().sp;
+    assert_eq!(b, sp.file);
+    assert_eq!(Some("bar".to_string()), source_map.snippet(&sp));
+    assert_eq!(None, source_map.snippet(&Span::nil_span()));
+}
+
+#[test]
+fn test_source_file_positions() {
+    let src = "var é: int\n\nfoo(é)\r\nbar";
+    let file = SourceFile::new("a.ion".to_string(), src.to_string());
+    assert_eq!(4, file.line_count());
+    assert_eq!(Some("foo(é)"), file.line(3));
+    assert_eq!(Some(""), file.line(2));
+    assert_eq!(None, file.line(5));
+
+    let mut lexer = Reader::new(src, FileId::nil_id());
+    loop {
+        let token = lexer.next_token().unwrap();
+        if token.tok == Token::EOF {
+            break;
+        }
+        let sp = token.sp;
+        assert_eq!(Some((sp.srow, sp.scol)), file.position(sp.start));
+        assert_eq!(Some(sp.start), file.offset(sp.srow, sp.scol));
+    }
+
+    assert_eq!(Some((1, 6)), file.position(6));
+    assert_eq!(None, file.position(5));
+    assert_eq!(None, file.offset(1, 12));
+    assert_eq!(None, file.offset(0, 1));
+}
+
+#[test]
+fn test_token_columns() {
+    let src = "\tvar é: int\r\n\"a\r\nbé\" \t x\n'\n' // cé\r\n  \u{1D465}y\r\n";
+    let file = SourceFile::new("a.ion".to_string(), src.to_string());
+
+    let mut lexer = Reader::new(src, FileId::nil_id());
+    loop {
+        let token = lexer.next_token().unwrap();
+        let sp = token.sp;
+        assert_eq!(Some((sp.srow, sp.scol)), file.position(sp.start));
+        assert_eq!(Some((sp.erow, sp.ecol)), file.position(sp.end));
+        if token.tok == Token::EOF {
+            break;
+        }
+    }
+
+    let tokens = tokenize(src, FileId::nil_id()).unwrap();
+    assert_eq!((1, 2), (tokens[0].sp.srow, tokens[0].sp.scol));
+    assert_eq!((3, 7), (tokens[6].sp.srow, tokens[6].sp.scol));
+    // The literal newline of the char literal starts a new row
+    let sp = &tokens[11].sp;
+    assert_eq!((6, 3, 6, 5), (sp.srow, sp.scol, sp.erow, sp.ecol));
+    assert_eq!(Some(6), file.utf16_column(6, 5));
+    assert_eq!(None, file.utf16_column(0, 1));
+}
+
+#[test]
+fn test_token_string_escapes() {
+    nth_token_eq("\"a\\nb\" \"é\\n\" \"\\nc\"", 0, Token::StringLiteral("a\nb".to_string()));
+    nth_token_eq("\"a\\nb\" \"é\\n\" \"\\nc\"", 1, Token::StringLiteral("é\n".to_string()));
+    nth_token_eq("\"a\\nb\" \"é\\n\" \"\\nc\"", 2, Token::StringLiteral("\nc".to_string()));
+    nth_token_eq("\"\"", 0, Token::StringLiteral("".to_string()));
+}
+
+#[test]
+fn test_token_escape_sequences() {
+    nth_token_eq("\"\\t\\r\\\\\\'\\\"\\0\"",
+                 0,
+                 Token::StringLiteral("\t\r\\'\"\0".to_string()));
+    nth_token_eq("\"\\x41\\u{1F600}\\u{e9}\"",
+                 0,
+                 Token::StringLiteral("A\u{1F600}é".to_string()));
+    nth_token_eq("b\"\\xff\\x00\"", 0, Token::BytesLiteral(vec![0xff, 0]));
+    nth_token_eq("'\\x7f' '\\t'", 1, Token::CharLiteral('\t'));
+
+    nth_token_err("\"\\x80\"", 0, ErrorKind::InvalidEscape("\\x80".to_string()));
+    nth_token_err("'\\x4'", 0, ErrorKind::InvalidEscape("\\x4".to_string()));
+    nth_token_err("\"\\u{d800}\"", 0, ErrorKind::InvalidEscape("\\u{d800}".to_string()));
+    nth_token_err("\"\\u{41\"", 0, ErrorKind::InvalidEscape("\\u{41".to_string()));
+    nth_token_err("b\"\\u{41}\"", 0, ErrorKind::UnknownEscapeChar('u'));
+    nth_token_err("'\\q'", 0, ErrorKind::UnknownEscapeChar('q'));
+
+    let mut lexer = Reader::new("x = \"ab\\xz\"", FileId::nil_id());
+    lexer.next_token().unwrap();
+    lexer.next_token().unwrap();
+    let err = lexer.next_token().unwrap_err();
+    assert_eq!((8, 10), (err.span.scol, err.span.ecol));
+    assert_eq!((7, 9), (err.span.start, err.span.end));
+}
+
+#[test]
+fn test_unescape_string() {
+    let src = "x = \"a\u{e9}\\n\\u{41}b\"";
+    let token = Reader::new(src, FileId::nil_id()).nth(2).unwrap().unwrap();
+    let span = Span {
+        scol: token.sp.scol + 1,
+        start: token.sp.start + 1,
+        ..token.sp
+    };
+    let raw = &src[span.start..token.sp.end - 1];
+    let (unescaped, offsets) = unescape_string(raw, &span).unwrap();
+    assert_eq!(Token::StringLiteral(unescaped.clone()), token.tok);
+    assert_eq!("a\u{e9}\nAb", unescaped);
+    assert_eq!(vec![5, 6, 8, 10, 16], offsets);
+
+    let src = "x = \"ab\\xz\"";
+    let span = Span {
+        srow: 1,
+        scol: 6,
+        erow: 1,
+        ecol: 12,
+        start: 5,
+        end: 10,
+        ..Span::nil_span()
+    };
+    let err = unescape_string(&src[5..10], &span).unwrap_err();
+    assert_eq!(ErrorKind::InvalidEscape("\\x".to_string()), err.kind);
+    assert_eq!((8, 10), (err.span.scol, err.span.ecol));
+    assert_eq!((7, 9), (err.span.start, err.span.end));
+}
+
+#[test]
+fn test_borrowed_tokens() {
+    let src = "abc \"d\" \"e\\n\" cafe\u{301} // f";
+    let mut lexer = Reader::new(src, FileId::nil_id());
+    let mut tokens = vec![];
+    loop {
+        let token = lexer.next_borrowed_token().unwrap();
+        if token.tok == Token::EOF {
+            break;
+        }
+        tokens.push(token);
+    }
+
+    let borrowed: std::vec::Vec<_> = tokens.iter()
+        .map(|t| match t.tok {
+            Token::Identifier(ref s) |
+            Token::StringLiteral(ref s) |
+            Token::Comment(ref s) => {
+                match *s {
+                    Cow::Borrowed(_) => true,
+                    Cow::Owned(_) => false,
+                }
+            }
+            _ => panic!("unexpected {:?}", t.tok),
+        })
+        .collect();
+    assert_eq!(vec![true, true, false, false, true], borrowed);
+
+    let owned: std::vec::Vec<_> = tokens.into_iter().map(|t| t.tok.into_owned()).collect();
+    let expected: std::vec::Vec<_> = Reader::new(src, FileId::nil_id())
+        .map(|t| t.unwrap().tok)
+        .collect();
+    assert_eq!(expected, owned);
+}
+
+#[test]
+fn test_tokenize_with_trivia() {
+    let src = "\u{FEFF}#!/usr/bin/ion\r\n/// a\r\nfunc a() {  // b\n\tvar c: int = 1\n}\t// d";
+    let tokens = trivia::tokenize(src, FileId::nil_id()).unwrap();
+
+    let mut rebuilt = String::new();
+    for token in &tokens {
+        for trivia in &token.leading {
+            rebuilt.push_str(&src[trivia.span.start..trivia.span.end]);
+        }
+        rebuilt.push_str(&src[token.token.sp.start..token.token.sp.end]);
+        for trivia in &token.trailing {
+            rebuilt.push_str(&src[trivia.span.start..trivia.span.end]);
+        }
+    }
+    assert_eq!(src, rebuilt);
+
+    let kinds = |trivia: &[trivia::Trivia]| -> std::vec::Vec<trivia::TriviaKind> {
+        trivia.iter().map(|t| t.kind).collect()
+    };
+    assert_eq!(Token::Symbol(Symbol::NewLine), tokens[0].token.tok);
+    assert_eq!(vec![trivia::TriviaKind::Preamble], kinds(&tokens[0].leading));
+    assert_eq!(Token::Keyword(Keyword::Func), tokens[2].token.tok);
+    assert_eq!(vec![trivia::TriviaKind::DocComment], kinds(&tokens[1].leading));
+    assert_eq!(vec![trivia::TriviaKind::Whitespace, trivia::TriviaKind::Comment],
+               kinds(&tokens[6].trailing));
+    assert_eq!(vec![trivia::TriviaKind::Whitespace], kinds(&tokens[8].leading));
+    assert_eq!((3, 11), (tokens[6].trailing[0].span.srow, tokens[6].trailing[0].span.scol));
+
+    let last = &tokens[tokens.len() - 2];
+    assert_eq!(Token::Symbol(Symbol::RightBrace), last.token.tok);
+    assert_eq!(vec![trivia::TriviaKind::Whitespace, trivia::TriviaKind::Comment],
+               kinds(&last.trailing));
+    assert_eq!(Token::EOF, tokens[tokens.len() - 1].token.tok);
+}
+
+#[test]
+fn test_reader_iterator() {
+    let tokens: std::vec::Vec<Token> = Reader::new("var a = 1\n", FileId::nil_id())
+        .map(|t| t.unwrap().tok)
+        .filter(|t| *t != Token::Symbol(Symbol::NewLine))
+        .collect();
+    assert_eq!(vec![Token::Keyword(Keyword::Var),
+                    Token::Identifier("a".to_string()),
+                    Token::Symbol(Symbol::Equal),
+                    Token::IntegerLiteral(1)],
+               tokens);
+
+    let mut reader = Reader::new("a ~ b", FileId::nil_id());
+    assert!(reader.next().unwrap().is_ok());
+    assert!(reader.next().unwrap().is_err());
+    assert!(reader.next().is_none());
+}
+
+#[test]
+fn test_tokenize() {
+    let tokens = tokenize("a(1)", FileId(1)).unwrap();
+    assert_eq!(4, tokens.len());
+    assert_eq!(Token::Symbol(Symbol::RightParenthesis), tokens[3].tok);
+    assert_eq!(FileId(1), tokens[3].sp.file);
+
+    let errors = tokenize("a ~ b ~ c", FileId::nil_id()).unwrap_err();
+    assert_eq!(2, errors.len());
+    assert_eq!(ErrorKind::InvalidSymbol, errors[1].kind);
+    assert_eq!(7, errors[1].span.scol);
+}
+
+#[test]
+fn test_reader_lookahead() {
+    let mut reader = Reader::new("aéc", FileId::nil_id());
+    assert_eq!(Some('a'), reader.current_char);
+    assert_eq!(Some('é'), reader.peek_char());
+    assert_eq!(Some('c'), reader.peek2_char());
+
+    reader.next_char();
+    assert_eq!(Some('c'), reader.peek_char());
+    assert_eq!(None, reader.peek2_char());
+
+    reader.next_char();
+    reader.next_char();
+    assert_eq!(None, reader.current_char);
+    assert_eq!(None, reader.peek_char());
+    assert_eq!(4, reader.current_offset);
+}
+
+#[test]
+fn test_shebang() {
+    let tokens = tokenize("#!/usr/bin/env ion\nfunc", FileId::nil_id()).unwrap();
+    assert_eq!(Token::Symbol(Symbol::NewLine), tokens[0].tok);
+    assert_eq!(Token::Keyword(Keyword::Func), tokens[1].tok);
+    assert_eq!((2, 1), (tokens[1].sp.srow, tokens[1].sp.scol));
+
+    // Only on the first line
+    nth_token_eq("a\n#!b", 2, Token::Symbol(Symbol::Hash));
+
+    let mut stream = stream::StreamReader::new("#!ion\na\n#!b".as_bytes(), FileId(1));
+    assert_eq!(Token::Symbol(Symbol::NewLine), stream.next_token().unwrap().tok);
+    assert_eq!(Token::Identifier("a".to_string()), stream.next_token().unwrap().tok);
+    assert_eq!(Token::Symbol(Symbol::NewLine), stream.next_token().unwrap().tok);
+    assert_eq!(Token::Symbol(Symbol::Hash), stream.next_token().unwrap().tok);
+}
+
+#[test]
+fn test_byte_order_mark() {
+    let src = "\u{FEFF}#!ion\nfunc";
+    let tokens = tokenize(src, FileId::nil_id()).unwrap();
+    assert_eq!(Token::Symbol(Symbol::NewLine), tokens[0].tok);
+    assert_eq!(Token::Keyword(Keyword::Func), tokens[1].tok);
+
+    let tokens = tokenize("\u{FEFF}a", FileId::nil_id()).unwrap();
+    assert_eq!((1, 1, 3), (tokens[0].sp.srow, tokens[0].sp.scol, tokens[0].sp.start));
+    let file = SourceFile::new("a.ion".to_string(), "\u{FEFF}a".to_string());
+    assert_eq!(Some("a"), file.line(1));
+    assert_eq!(Some((1, 1)), file.position(3));
+    assert_eq!(Some(3), file.offset(1, 1));
+}
+
+#[test]
+fn test_load_file_invalid_utf8() {
+    let path = std::env::temp_dir().join("ion_test_load_file_invalid_utf8.ion");
+    std::fs::write(&path, b"var a: int\nb(\"\xff\")").unwrap();
+
+    let source_map = SourceMap::new();
+    let err = source_map.load_file(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+    match err.kind {
+        source_map::ErrorKind::InvalidUtf8 => (),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!((2, 4, 14), (err.span.srow, err.span.scol, err.span.start));
+    assert_eq!(Some("\u{FFFD}".to_string()), source_map.snippet(&err.span));
+}
+
+#[test]
+fn test_stream_reader() {
+    let src = "func é() {\n  print(\"a\nb\")\n}\r\n\nvar c";
+    let expected = tokenize(src, FileId(1)).unwrap();
+
+    let mut stream = stream::StreamReader::new(src.as_bytes(), FileId(1));
+    let mut tokens = vec![];
+    loop {
+        let token = stream.next_token().unwrap();
+        if token.tok == Token::EOF {
+            break;
+        }
+        tokens.push(token);
+    }
+    assert_eq!(expected, tokens);
+
+    let mut stream = stream::StreamReader::new("a\n\"b".as_bytes(), FileId(1));
+    assert_eq!(Token::Identifier("a".to_string()), stream.next_token().unwrap().tok);
+    assert_eq!(Token::Symbol(Symbol::NewLine), stream.next_token().unwrap().tok);
+    let err = stream.next_token().unwrap_err();
+    assert_eq!(ErrorKind::InvalidString, err.kind);
+    assert_eq!(2, err.span.srow);
+
+    let mut stream = stream::StreamReader::new(&[b'a', 0xff][..], FileId(1));
+    assert_eq!(ErrorKind::InvalidInput, stream.next_token().unwrap_err().kind);
+}
+
+#[test]
+fn test_token_as_str() {
+    let keywords = vec![Keyword::Import, Keyword::Package, Keyword::Func, Keyword::Var,
+                        Keyword::If, Keyword::Else, Keyword::While, Keyword::Struct,
+                        Keyword::Return, Keyword::For, Keyword::In, Keyword::New,
+                        Keyword::Spawn, Keyword::Async, Keyword::Await, Keyword::Extern,
+                        Keyword::Guard];
+    for keyword in keywords {
+        nth_token_eq(keyword.as_str(), 0, Token::Keyword(keyword));
+    }
+
+    let symbols = vec![Symbol::LeftParenthesis, Symbol::RightParenthesis, Symbol::LeftBracket,
+                       Symbol::RightBracket, Symbol::LeftBrace, Symbol::RightBrace,
+                       Symbol::NewLine, Symbol::Dot, Symbol::Ellipsis, Symbol::Comma,
+                       Symbol::Colon, Symbol::Equal, Symbol::EqualEqual, Symbol::Plus,
+                       Symbol::PlusPlus, Symbol::Minus, Symbol::Star, Symbol::Over,
+                       Symbol::Modulo, Symbol::NotEqual, Symbol::ColonColon, Symbol::Hash,
+                       Symbol::Less, Symbol::LessOrEqual, Symbol::More, Symbol::MoreOrEqual,
+                       Symbol::Concat, Symbol::Return, Symbol::Amp, Symbol::AmpAmp,
+                       Symbol::PipePipe, Symbol::Bang, Symbol::At];
+    for symbol in symbols {
+        nth_token_eq(symbol.as_str(), 0, Token::Symbol(symbol));
+    }
+}
+
+#[test]
+fn test_token_display() {
+    assert_eq!("`)`", Token::Symbol(Symbol::RightParenthesis).display());
+    assert_eq!("`func`", Token::Keyword(Keyword::Func).display());
+    assert_eq!("`a`", Token::Identifier("a".to_string()).display());
+    assert_eq!("`\"a\\nb\"`", Token::StringLiteral("a\nb".to_string()).display());
+    assert_eq!("`b\"a\"`", Token::BytesLiteral(vec![97]).display());
+    assert_eq!("`'é'`", Token::CharLiteral('é').display());
+    assert_eq!("`3.0`", Token::FloatLiteral(3.0).display());
+    assert_eq!("end of line", Token::Symbol(Symbol::NewLine).display());
+    assert_eq!("end of file", Token::EOF.display());
+    assert_eq!("identifier", TokenKind::Identifier.display());
+}
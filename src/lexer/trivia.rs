@@ -0,0 +1,109 @@
+use std;
+use lexer::*;
+use source_map::FileId;
+
+/// Token along with the source text around it which the parser ignores, so that the source can be
+/// rebuilt byte for byte from the tokens: the text of each token's leading trivia, then of the
+/// token itself, then of its trailing trivia.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriviaToken {
+    pub token: SToken,
+    /// Trivia between the end of the line of the previous token and this token, e.g. indentation
+    pub leading: std::vec::Vec<Trivia>,
+    /// Trivia following the token on the same line, up to the newline
+    pub trailing: std::vec::Vec<Trivia>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TriviaKind {
+    /// Spaces, tabs and carriage returns. Newlines are tokens.
+    Whitespace,
+    Comment,
+    DocComment,
+    /// Byte order mark and shebang line skipped at the start of a file
+    Preamble,
+}
+
+/// Lexes the whole input like `Reader`, comments included, except that whitespace and comments are
+/// returned as the trivia of the tokens rather than dropped or returned as tokens. The last token
+/// is the `EOF` one, whose leading trivia is what follows the last newline.
+pub fn tokenize(src: &str, file: FileId) -> Result<std::vec::Vec<TriviaToken>> {
+    let mut reader = Reader::new(src, file);
+    let mut tokens: std::vec::Vec<TriviaToken> = vec![];
+    let mut pending = vec![];
+    let mut end = Span {
+        srow: 1,
+        scol: 1,
+        erow: reader.current_row,
+        ecol: reader.current_col,
+        start: 0,
+        end: reader.current_offset,
+        file: file,
+    };
+    if !end.is_empty() {
+        pending.push(Trivia {
+            kind: TriviaKind::Preamble,
+            span: end.clone(),
+        });
+    }
+
+    loop {
+        let token = try!(reader.next_token());
+        if token.sp.start > end.end {
+            pending.push(Trivia {
+                kind: TriviaKind::Whitespace,
+                span: Span {
+                    srow: end.erow,
+                    scol: end.ecol,
+                    erow: token.sp.srow,
+                    ecol: token.sp.scol,
+                    start: end.end,
+                    end: token.sp.start,
+                    file: file,
+                },
+            });
+        }
+        end = token.sp.clone();
+
+        let kind = match token.tok {
+            Token::Comment(_) => TriviaKind::Comment,
+            Token::DocComment(_) => TriviaKind::DocComment,
+            _ => {
+                // Trivia following a token on its line is its trailing trivia, the newline
+                // ending the line aside
+                let at_line_start = match tokens.last() {
+                    Some(previous) => previous.token.tok == Token::Symbol(Symbol::NewLine),
+                    None => true,
+                };
+                let leading = if at_line_start {
+                    pending
+                } else {
+                    tokens.last_mut().unwrap().trailing = pending;
+                    vec![]
+                };
+                pending = vec![];
+
+                let is_eof = token.tok == Token::EOF;
+                tokens.push(TriviaToken {
+                    token: token,
+                    leading: leading,
+                    trailing: vec![],
+                });
+                if is_eof {
+                    return Ok(tokens);
+                }
+                continue;
+            }
+        };
+        pending.push(Trivia {
+            kind: kind,
+            span: token.sp,
+        });
+    }
+}
@@ -0,0 +1,321 @@
+use std;
+use std::fmt;
+use std::fmt::Display;
+use lexer::Span;
+use parser::ast::*;
+use parser::visit;
+use parser::visit::Visitor;
+
+#[cfg(test)]
+mod tests;
+
+/// Valid code which is likely to be a mistake
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub kind: WarningKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WarningKind {
+    /// Identifier made of letters from scripts which aren't usually mixed
+    MixedScripts(String),
+    /// Identifier looking like another one of the program, which is the second field
+    Confusable(String, String),
+    /// Expression statement which neither calls a func nor spawns or awaits a task, so that its
+    /// value is computed for nothing
+    NoEffect,
+}
+
+impl Warning {
+    /// Description of the warning, without its position
+    pub fn message(&self) -> String {
+        match self.kind {
+            WarningKind::MixedScripts(ref ident) => {
+                format!("identifier `{}` mixes characters from several scripts", ident)
+            }
+            WarningKind::Confusable(ref ident, ref other) => {
+                format!("identifier `{}` can be confused with `{}`", ident, other)
+            }
+            WarningKind::NoEffect => "statement has no effect, its value is unused".to_string(),
+        }
+    }
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: warning: {}", self.span, self.message())
+    }
+}
+
+/// Warnings about the whole program, in source order
+pub fn check(ast: &Ast) -> std::vec::Vec<Warning> {
+    let mut collector = IdentCollector { idents: vec![] };
+    visit::walk_ast(&mut collector, ast);
+    let mut idents = collector.idents;
+    idents.sort_by_key(|&(_, span)| span.start);
+
+    let mut warnings = vec![];
+    // First identifier found for each skeleton
+    let mut skeletons: std::collections::HashMap<String, &str> = std::collections::HashMap::new();
+    let mut seen = std::collections::HashSet::new();
+    for &(ident, span) in &idents {
+        if !seen.insert(ident) {
+            continue;
+        }
+
+        if mixes_scripts(ident) {
+            warnings.push(Warning {
+                kind: WarningKind::MixedScripts(ident.clone()),
+                span: span.clone(),
+            });
+        }
+
+        let first = *skeletons.entry(skeleton(ident)).or_insert(ident);
+        if first != ident {
+            warnings.push(Warning {
+                kind: WarningKind::Confusable(ident.clone(), first.to_string()),
+                span: span.clone(),
+            });
+        }
+    }
+
+    let mut statements = EffectlessStatementCollector { spans: vec![] };
+    visit::walk_ast(&mut statements, ast);
+    for span in statements.spans {
+        warnings.push(Warning {
+            kind: WarningKind::NoEffect,
+            span: span.clone(),
+        });
+    }
+
+    warnings.sort_by_key(|warning| warning.span.start);
+    warnings
+}
+
+/// Scripts of identifier letters, digits and other characters common to all scripts left aside
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+    Hebrew,
+    Arabic,
+    Devanagari,
+    Thai,
+    Georgian,
+    Hangul,
+    Hiragana,
+    Katakana,
+    Bopomofo,
+    Han,
+    Other,
+}
+
+fn script(c: char) -> Option<Script> {
+    match c as u32 {
+        0x41...0x5A | 0x61...0x7A | 0xAA | 0xBA | 0xC0...0x24F | 0x250...0x2AF |
+        0x1E00...0x1EFF | 0x2C60...0x2C7F | 0xA720...0xA7FF | 0xAB30...0xAB6F |
+        0xFF21...0xFF3A | 0xFF41...0xFF5A => Some(Script::Latin),
+        0x370...0x3FF | 0x1F00...0x1FFF => Some(Script::Greek),
+        0x400...0x52F | 0x1C80...0x1C8F | 0x2DE0...0x2DFF | 0xA640...0xA69F => {
+            Some(Script::Cyrillic)
+        }
+        0x530...0x58F => Some(Script::Armenian),
+        0x590...0x5FF => Some(Script::Hebrew),
+        0x600...0x6FF | 0x750...0x77F | 0x8A0...0x8FF => Some(Script::Arabic),
+        0x900...0x97F => Some(Script::Devanagari),
+        0xE00...0xE7F => Some(Script::Thai),
+        0x10A0...0x10FF => Some(Script::Georgian),
+        0x1100...0x11FF | 0x3130...0x318F | 0xAC00...0xD7AF => Some(Script::Hangul),
+        0x3040...0x309F => Some(Script::Hiragana),
+        0x30A0...0x30FF | 0x31F0...0x31FF | 0xFF66...0xFF9F => Some(Script::Katakana),
+        0x3100...0x312F => Some(Script::Bopomofo),
+        0x3400...0x4DBF | 0x4E00...0x9FFF | 0xF900...0xFAFF | 0x20000...0x2FFFF => {
+            Some(Script::Han)
+        }
+        // ASCII digits and underscore, combining marks, and other characters used with any script
+        0...0x7F | 0x300...0x36F | 0x200C | 0x200D => None,
+        _ if c.is_numeric() => None,
+        _ => Some(Script::Other),
+    }
+}
+
+/// Whether the identifier mixes scripts in a way not allowed by the "highly restrictive" level of
+/// UTS #39: a single script, or Latin along with the scripts used by Chinese, Japanese or Korean
+fn mixes_scripts(ident: &str) -> bool {
+    let scripts: std::collections::HashSet<Script> = ident.chars().filter_map(script).collect();
+    let allowed_sets: [&[Script]; 3] =
+        [&[Script::Latin, Script::Han, Script::Hiragana, Script::Katakana],
+         &[Script::Latin, Script::Han, Script::Bopomofo],
+         &[Script::Latin, Script::Han, Script::Hangul]];
+
+    if scripts.len() <= 1 {
+        return false;
+    }
+
+    !allowed_sets.iter().any(|allowed| scripts.iter().all(|s| allowed.contains(s)))
+}
+
+/// Identifier with the characters which look like an ASCII one replaced by it, so that
+/// identifiers which can be confused have the same skeleton (a subset of the ones of UTS #39)
+fn skeleton(ident: &str) -> String {
+    ident.chars()
+        .map(|c| match c {
+            'а' | 'α' => 'a',
+            'ԁ' => 'd',
+            'е' => 'e',
+            'һ' => 'h',
+            'і' | 'ı' | 'ι' => 'i',
+            'ј' => 'j',
+            'ӏ' => 'l',
+            'о' | 'ο' => 'o',
+            'р' | 'ρ' => 'p',
+            'с' => 'c',
+            'ѕ' => 's',
+            'у' => 'y',
+            'х' => 'x',
+            'ν' => 'v',
+            'А' | 'Α' => 'A',
+            'В' | 'Β' => 'B',
+            'С' => 'C',
+            'Е' | 'Ε' => 'E',
+            'Н' | 'Η' => 'H',
+            'І' | 'Ι' => 'I',
+            'Ј' => 'J',
+            'К' | 'Κ' => 'K',
+            'М' | 'Μ' => 'M',
+            'Ν' => 'N',
+            'О' | 'Ο' => 'O',
+            'Р' | 'Ρ' => 'P',
+            'Ѕ' => 'S',
+            'Т' | 'Τ' => 'T',
+            'Х' | 'Χ' => 'X',
+            'Υ' | 'Ү' => 'Y',
+            'Ζ' => 'Z',
+            c => c,
+        })
+        .collect()
+}
+
+/// Every identifier of the program, with the span of the node it is found in when it has none of
+/// its own
+struct IdentCollector<'a> {
+    idents: std::vec::Vec<(&'a String, &'a Span)>,
+}
+
+impl<'a> IdentCollector<'a> {
+    fn path(&mut self, path: &'a Path) {
+        for part in &path.parts {
+            self.idents.push((&part.ident, &part.span));
+        }
+    }
+}
+
+impl<'a> Visitor<'a> for IdentCollector<'a> {
+    fn visit_func_decl(&mut self, func_decl: &'a FuncDeclData) {
+        self.idents.push((&func_decl.name, &func_decl.span));
+        visit::walk_func_decl(self, func_decl);
+    }
+
+    fn visit_func_decl_param(&mut self, param: &'a FuncDeclParamData) {
+        self.idents.push((&param.name, &param.span));
+        visit::walk_func_decl_param(self, param);
+    }
+
+    fn visit_struct_decl(&mut self, struct_decl: &'a StructDeclData) {
+        self.idents.push((&struct_decl.name, &struct_decl.span));
+        visit::walk_struct_decl(self, struct_decl);
+    }
+
+    fn visit_struct_field(&mut self, field: &'a StructFieldData) {
+        self.idents.push((&field.name, &field.span));
+        visit::walk_struct_field(self, field);
+    }
+
+    fn visit_block_statement(&mut self, block_statement: &'a BlockStatement) {
+        match *block_statement {
+            BlockStatement::VarDecl(ref vd) => self.idents.push((&vd.name, &vd.span)),
+            BlockStatement::Destructure(ref d) => {
+                for name in d.pattern.names() {
+                    self.idents.push((&name.ident, &name.span));
+                }
+            }
+            BlockStatement::ForIn(ref fi) => self.idents.push((&fi.element_name, &fi.span)),
+            _ => (),
+        }
+        visit::walk_block_statement(self, block_statement);
+    }
+
+    fn visit_expression(&mut self, expression: &'a Expression) {
+        match expression.expr {
+            Expression_::Variable(ref path) => self.path(path),
+            Expression_::Field(_, ref field) => self.idents.push((&field.ident, &field.span)),
+            Expression_::StructInit(ref path, ref fields) => {
+                self.path(path);
+                for field in fields {
+                    self.idents.push((&field.name.ident, &field.name.span));
+                }
+            }
+            _ => (),
+        }
+        visit::walk_expression(self, expression);
+    }
+
+    fn visit_type(&mut self, t: &'a Type) {
+        match *t {
+            Type::Struct(ref path) => self.path(path),
+            Type::Reference(ref t) |
+            Type::MutReference(ref t) |
+            Type::Array(ref t) |
+            Type::Set(ref t) |
+            Type::Chan(ref t) => self.visit_type(t),
+            Type::Map(ref k, ref v) => {
+                self.visit_type(k);
+                self.visit_type(v);
+            }
+            Type::Func(ref return_type, ref params) => {
+                self.visit_type(return_type);
+                for param in params {
+                    self.visit_type(param);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Spans of the expression statements without effects
+struct EffectlessStatementCollector<'a> {
+    spans: std::vec::Vec<&'a Span>,
+}
+
+impl<'a> Visitor<'a> for EffectlessStatementCollector<'a> {
+    fn visit_block_statement(&mut self, block_statement: &'a BlockStatement) {
+        if let BlockStatement::Expression(ref expression) = *block_statement {
+            let mut finder = EffectFinder { found: false };
+            finder.visit_expression(expression);
+            if !finder.found {
+                self.spans.push(&expression.span);
+            }
+        }
+        visit::walk_block_statement(self, block_statement);
+    }
+}
+
+/// Whether an expression calls a func, or spawns or awaits a task
+struct EffectFinder {
+    found: bool,
+}
+
+impl<'a> Visitor<'a> for EffectFinder {
+    fn visit_expression(&mut self, expression: &'a Expression) {
+        match expression.expr {
+            Expression_::FuncCall(_, _) |
+            Expression_::Spawn(_, _) |
+            Expression_::UnaryOp(UnaryOp::Await, _) => self.found = true,
+            _ => visit::walk_expression(self, expression),
+        }
+    }
+}
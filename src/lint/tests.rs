@@ -0,0 +1,49 @@
+use super::*;
+use lexer::Reader;
+use parser::{Parser, ParserOptions};
+use source_map::FileId;
+
+fn warnings(src: &str) -> std::vec::Vec<WarningKind> {
+    let mut reader = Reader::new(src, FileId::nil_id());
+    let mut parser = Parser::new(&mut reader, ParserOptions::default());
+    let ast = parser.parse().unwrap();
+
+    check(ast).into_iter().map(|warning| warning.kind).collect()
+}
+
+#[test]
+fn test_mixed_scripts() {
+    // Cyrillic а
+    assert_eq!(vec![WarningKind::MixedScripts("pаss".to_string())],
+               warnings("func f() { var pаss: int = 1 }"));
+    assert_eq!(vec![WarningKind::MixedScripts("αb".to_string())],
+               warnings("func αb() {}"));
+
+    assert!(warnings("func f() { var pass: int = 1 }").is_empty());
+    assert!(warnings("func f() { var café_2: int = 1 }").is_empty());
+    assert!(warnings("func f() { var πρ: int = 1 }").is_empty());
+    assert!(warnings("func f() { var 変数ひらがなカタカナ_x: int = 1 }").is_empty());
+    assert!(warnings("func f() { var 한국어_x: int = 1 }").is_empty());
+}
+
+#[test]
+fn test_confusable() {
+    // Cyrillic а, then Latin a
+    assert_eq!(vec![WarningKind::Confusable("a".to_string(), "а".to_string())],
+               warnings("func f() {\n    var а: int = 1\n    var a: int = 2\n}"));
+    assert_eq!(vec![WarningKind::Confusable("ѕіх".to_string(), "six".to_string())],
+               warnings("func six() {}\nfunc f() { var ѕіх: int = 1 }"));
+
+    assert!(warnings("func f() {\n    var a: int = 1\n    var b: int = a\n}").is_empty());
+    assert!(warnings("func f() {\n    var а: int = 1\n    var б: int = а\n}").is_empty());
+}
+
+#[test]
+fn test_no_effect() {
+    assert_eq!(vec![WarningKind::NoEffect, WarningKind::NoEffect, WarningKind::NoEffect],
+               warnings("func f(x: int) {\n    x == 1\n    \"a\"\n    while true {\n        \
+                         [x + 1]\n    }\n}"));
+
+    assert!(warnings("func f(x: int) {\n    print(x == 1)\n    x = 1\n    [g()]\n}").is_empty());
+    assert!(warnings("async func f() {\n    spawn {}\n    await f()\n}").is_empty());
+}
@@ -2,46 +2,196 @@
 #![plugin(clippy)]
 
 extern crate docopt;
+extern crate unicode_normalization;
+extern crate libloading;
 mod lexer;
 mod parser;
+mod lint;
 mod interpreter;
-use std::fs::File;
+mod source_map;
+mod diagnostic;
+use std::io::Write;
 use std::path::Path;
-use std::io::prelude::*;
 
 static USAGE: &'static str = "
-Usage: ion <src>
+Usage: ion [options] <src>
+       ion stats [options] <src>
+
+Options:
+    --max-steps=<n>      Abort after evaluating n statements and expressions.
+    --max-heap=<bytes>   Abort after allocating this many bytes for values.
+    --timeout=<ms>       Abort after running for this many milliseconds.
+    --max-depth=<n>      Abort when more than n calls are nested, at most
+                         8192 [default: 1000].
+    --no-io              Disable the filesystem and network builtins, and
+                         extern funcs.
+    --no-prelude         Don't declare the funcs of the prelude, e.g. assert,
+                         in every file.
+    --overflow=<mode>    What happens when an int operation overflows: wrap,
+                         saturate or error [default: error].
+    --error-format=<f>   How errors and warnings are printed: human, with the
+                         line of source underlined, or short [default: human].
+    --color=<when>       Color errors and warnings: auto, always or never
+                         [default: auto].
 ";
 
+const MAX_PRINTED_FRAMES: usize = 20;
+
+/// Value of the numeric option, exiting if it is not a number
+fn limit<T: std::str::FromStr>(args: &docopt::ArgvMap, name: &str) -> Option<T> {
+    let value = args.get_str(name);
+    if value.is_empty() {
+        return None;
+    }
+
+    match value.parse() {
+        Ok(limit) => Some(limit),
+        Err(_) => {
+            println!("invalid value for {}: {}", name, value);
+            std::process::exit(1);
+        }
+    }
+}
+
 fn main() {
     let args = docopt::Docopt::new(USAGE)
         .and_then(|d| d.parse())
         .unwrap_or_else(|e| e.exit());
-
     let path = Path::new(args.get_str("<src>"));
-    let mut file = match File::open(&path) {
-        Ok(file) => file,
-        Err(_) => panic!(),
+    let source_map = source_map::SourceMap::new();
+
+    let color = match args.get_str("--color") {
+        "always" => true,
+        "never" => false,
+        "auto" | "" => diagnostic::use_color(),
+        other => {
+            println!("invalid value for --color: {}", other);
+            std::process::exit(1);
+        }
+    };
+    let renderer = match args.get_str("--error-format") {
+        "human" | "" => {
+            Some(diagnostic::Renderer::new(&source_map, color, diagnostic::terminal_width()))
+        }
+        "short" => None,
+        other => {
+            println!("invalid value for --error-format: {}", other);
+            std::process::exit(1);
+        }
+    };
+    let report = |severity, span: &lexer::Span, message: String, short: String| {
+        match renderer {
+            Some(ref renderer) => print!("{}", renderer.render(severity, span, &message)),
+            None => println!("{}:{}", source_map.name(span.file), short),
+        }
+    };
+    let report_context = |err: &parser::Error| {
+        for context in &err.context {
+            let short = format!("{}: note: {}", context.span, context.message());
+            report(diagnostic::Severity::Note, &context.span, context.message(), short);
+        }
     };
 
-    let mut s = String::new();
-    let res = file.read_to_string(&mut s);
-    if let Some(err) = res.err() {
-        println!("{}", err)
-    }
+    let file_id = match source_map.load_file(path) {
+        Ok(file_id) => file_id,
+        Err(err) => {
+            println!("{}: {}", path.display(), err);
+            std::process::exit(1);
+        }
+    };
+    let source_file = source_map.file(file_id).unwrap();
+    let mut reader = lexer::Reader::new(source_file.src.as_ref(), file_id);
 
-    let mut reader = lexer::Reader::new(s.as_ref(), args.get_str("<src>").to_string());
+    let mut parser = parser::Parser::new(&mut reader, parser::ParserOptions::default());
+    let ast = match parser.parse() {
+        Ok(ast) => ast,
+        Err(err) => {
+            report(diagnostic::Severity::Error, &err.span, err.message(), err.to_string());
+            report_context(&err);
+            std::process::exit(1);
+        }
+    };
+
+    if args.get_bool("stats") {
+        let metrics = parser::ast::metrics(ast);
+        println!("funcs: {}", metrics.funcs);
+        println!("structs: {}", metrics.structs);
+        println!("statements: {}", metrics.statements);
+        println!("expressions: {}", metrics.expressions);
+        println!("max depth: {}", metrics.max_depth);
+        println!("complexity:");
+        for &(ref func, complexity) in &metrics.complexity {
+            println!("    {}: {}", func, complexity);
+        }
+        return;
+    }
 
-    let mut parser = parser::Parser::new(&mut reader);
-    let ast_res = parser.parse();
-    if let Some(err) = ast_res.clone().err() {
-        println!("{}", err)
+    for warning in lint::check(ast) {
+        report(diagnostic::Severity::Warning,
+               &warning.span,
+               warning.message(),
+               warning.to_string());
     }
-    let ast = ast_res.ok().unwrap();
 
-    let mut interpreter = interpreter::Interpreter::new(ast);
-    let res2 = interpreter.execute();
-    if let Some(err) = res2.err() {
-        println!("{}", err)
+    let overflow = match args.get_str("--overflow") {
+        "wrap" => interpreter::Overflow::Wrap,
+        "saturate" => interpreter::Overflow::Saturate,
+        "error" | "" => interpreter::Overflow::Error,
+        other => {
+            println!("invalid value for --overflow: {}", other);
+            std::process::exit(1);
+        }
+    };
+    let max_call_depth = limit(&args, "--max-depth")
+        .unwrap_or(interpreter::DEFAULT_MAX_CALL_DEPTH);
+    if max_call_depth > interpreter::MAX_CALL_DEPTH {
+        println!("invalid value for --max-depth: {} (at most {})",
+                 max_call_depth,
+                 interpreter::MAX_CALL_DEPTH);
+        std::process::exit(1);
+    }
+    let options = interpreter::InterpreterOptions {
+        max_steps: limit(&args, "--max-steps"),
+        max_heap_bytes: limit(&args, "--max-heap"),
+        timeout: limit(&args, "--timeout").map(std::time::Duration::from_millis),
+        allow_io: !args.get_bool("--no-io"),
+        overflow: overflow,
+        prelude: !args.get_bool("--no-prelude"),
+        max_call_depth: max_call_depth,
+        features: parser::Feature::all(),
+    };
+    let mut interpreter = interpreter::Interpreter::new(ast, &source_map, options);
+    let result = interpreter.execute()
+        .and_then(|code| interpreter::Interpreter::exit_code(ast, code));
+    let err = match result {
+        Ok(code) => {
+            // Exiting doesn't flush what was printed without a newline
+            let _ = std::io::stdout().flush();
+            std::process::exit(code)
+        }
+        Err(err) => err,
+    };
+    report(diagnostic::Severity::Error, &err.span, err.message(), err.to_string());
+    if let interpreter::ErrorKind::Parser(ref err) = err.kind {
+        report_context(err);
+    }
+    // Only the ends of the backtrace of a deep recursion are worth printing
+    let elided = err.backtrace.len().saturating_sub(MAX_PRINTED_FRAMES);
+    for (i, frame) in err.backtrace.iter().enumerate() {
+        if i >= MAX_PRINTED_FRAMES / 2 && i < MAX_PRINTED_FRAMES / 2 + elided {
+            if i == MAX_PRINTED_FRAMES / 2 {
+                println!("    ... {} more calls", elided);
+            }
+            continue;
+        }
+        if frame.span.file == source_map::FileId::nil_id() {
+            println!("    in {}", frame.func);
+        } else {
+            println!("    in {}, called at {}:{}",
+                     frame.func,
+                     source_map.name(frame.span.file),
+                     frame.span);
+        }
     }
+    std::process::exit(1);
 }
@@ -0,0 +1,488 @@
+use std;
+use lexer::Span;
+use parser::ast::*;
+
+/// Xorshift generator, deterministic for a given seed so that failures can be reproduced
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed.wrapping_mul(0x9E3779B97F4A7C15) | 1 }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// Number in `0..n`
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    pub fn one_in(&mut self, n: usize) -> bool {
+        self.below(n) == 0
+    }
+}
+
+/// Generates random well-formed programs, i.e. trees that the parser could have produced. Nodes
+/// have nil spans and ids, so generated trees are to be compared with parsed ones once stripped
+/// with `Ast::strip_spans`.
+pub struct Generator {
+    rng: Rng,
+    depth: usize,
+    max_depth: usize,
+}
+
+const IDENTS: [&'static str; 8] = ["a", "b", "c", "foo", "bar", "x1", "y_2", "é"];
+
+impl Generator {
+    pub fn new(seed: u64, max_depth: usize) -> Self {
+        Generator {
+            rng: Rng::new(seed),
+            depth: 0,
+            max_depth: max_depth,
+        }
+    }
+
+    pub fn ast(&mut self) -> Ast {
+        let count = self.rng.below(5) + 1;
+        Ast { statements: (0..count).map(|_| self.statement()).collect(), ..Ast::new() }
+    }
+
+    fn nested(&self) -> bool {
+        self.depth < self.max_depth
+    }
+
+    fn ident(&mut self) -> String {
+        IDENTS[self.rng.below(IDENTS.len())].to_string()
+    }
+
+    fn spanned_string(&mut self) -> SpannedString {
+        SpannedString {
+            span: Span::nil_span(),
+            ident: self.ident(),
+        }
+    }
+
+    fn path(&mut self) -> Path {
+        let count = if self.rng.one_in(4) { 2 } else { 1 };
+        Path {
+            span: Span::nil_span(),
+            parts: (0..count).map(|_| self.spanned_string()).collect(),
+        }
+    }
+
+    fn statement(&mut self) -> Statement {
+        match self.rng.below(4) {
+            0 => {
+                Statement::Import(Box::new(ImportData {
+                    id: NodeId::nil_id(),
+                    span: Span::nil_span(),
+                    path: format!("{}/{}", self.ident(), self.ident()),
+                }))
+            }
+            1 => {
+                Statement::Package(Box::new(PackageData {
+                    id: NodeId::nil_id(),
+                    span: Span::nil_span(),
+                    name: self.ident(),
+                }))
+            }
+            2 => Statement::FuncDecl(Box::new(self.func_decl())),
+            _ => {
+                let count = self.rng.below(4);
+                Statement::StructDecl(Box::new(StructDeclData {
+                    id: NodeId::nil_id(),
+                    span: Span::nil_span(),
+                    name: self.ident(),
+                    fields: (0..count)
+                        .map(|_| {
+                            Box::new(StructFieldData {
+                                id: NodeId::nil_id(),
+                                span: Span::nil_span(),
+                                name: self.ident(),
+                                field_type: self.type_(),
+                                default_value: None,
+                                doc: None,
+                            })
+                        })
+                        .collect(),
+                    doc: None,
+                }))
+            }
+        }
+    }
+
+    fn func_decl(&mut self) -> FuncDeclData {
+        let name = self.ident();
+        let count = self.rng.below(4);
+        let parameters = (0..count)
+            .map(|_| {
+                Box::new(FuncDeclParamData {
+                    id: NodeId::nil_id(),
+                    span: Span::nil_span(),
+                    name: self.ident(),
+                    param_type: self.type_(),
+                    default_value: None,
+                })
+            })
+            .collect();
+        let return_type = if self.rng.one_in(2) {
+            Type::None
+        } else {
+            self.type_()
+        };
+
+        if self.rng.one_in(5) {
+            let library = if self.rng.one_in(2) {
+                Some(format!("lib{}.so", self.ident()))
+            } else {
+                None
+            };
+            return FuncDeclData {
+                id: NodeId::nil_id(),
+                span: Span::nil_span(),
+                name: name,
+                statements: vec![],
+                return_type: return_type,
+                parameters: parameters,
+                is_async: false,
+                is_extern: true,
+                library: library,
+                doc: None,
+            };
+        }
+
+        FuncDeclData {
+            id: NodeId::nil_id(),
+            span: Span::nil_span(),
+            name: name,
+            statements: self.block(&return_type),
+            return_type: return_type,
+            parameters: parameters,
+            is_async: self.rng.one_in(4),
+            is_extern: false,
+            library: None,
+            doc: None,
+        }
+    }
+
+    fn type_(&mut self) -> Type {
+        if !self.nested() {
+            return Type::Int;
+        }
+
+        self.depth += 1;
+        let t = match self.rng.below(12) {
+            0 => Type::Reference(Box::new(self.type_())),
+            1 => Type::MutReference(Box::new(self.type_())),
+            2 => Type::Array(Box::new(self.type_())),
+            3 if self.rng.one_in(3) => {
+                let item_type = [Type::Int, Type::String, Type::Char, Type::Bool][self.rng.below(4)]
+                    .clone();
+                Type::Set(Box::new(item_type))
+            }
+            3 => {
+                let key_type = [Type::Int, Type::String, Type::Char, Type::Bool][self.rng.below(4)]
+                    .clone();
+                Type::Map(Box::new(key_type), Box::new(self.type_()))
+            }
+            4 => Type::Struct(self.path()),
+            5 => Type::String,
+            6 => Type::Int,
+            7 if self.rng.one_in(4) => Type::Bytes,
+            7 => Type::Bool,
+            8 => Type::Task,
+            9 => Type::Chan(Box::new(self.type_())),
+            10 => {
+                [Type::Mutex, Type::WaitGroup, Type::Atomic, Type::Builder][self.rng.below(4)]
+                    .clone()
+            }
+            _ => Type::Char,
+        };
+        self.depth -= 1;
+
+        t
+    }
+
+    fn block(&mut self, return_type: &Type) -> std::vec::Vec<BlockStatement> {
+        if !self.nested() {
+            return vec![];
+        }
+
+        self.depth += 1;
+        let count = self.rng.below(4);
+        let statements = (0..count).map(|_| self.block_statement(return_type)).collect();
+        self.depth -= 1;
+
+        statements
+    }
+
+    fn block_statement(&mut self, return_type: &Type) -> BlockStatement {
+        match self.rng.below(7) {
+            0 => BlockStatement::Expression(Box::new(self.expression())),
+            1 if self.rng.one_in(4) => {
+                let count = self.rng.below(3);
+                let names = (0..count).map(|_| self.spanned_string()).collect();
+                BlockStatement::Destructure(Box::new(DestructureData {
+                    id: NodeId::nil_id(),
+                    span: Span::nil_span(),
+                    pattern: if self.rng.one_in(2) {
+                        Pattern::Array(names)
+                    } else {
+                        Pattern::Struct(self.path(), names)
+                    },
+                    value: self.expression(),
+                }))
+            }
+            1 => {
+                BlockStatement::VarDecl(Box::new(VarDeclData {
+                    id: NodeId::nil_id(),
+                    span: Span::nil_span(),
+                    name: self.ident(),
+                    var_type: self.type_(),
+                    value: if self.rng.one_in(2) {
+                        Some(self.expression())
+                    } else {
+                        None
+                    },
+                }))
+            }
+            2 => {
+                BlockStatement::VarAssignment(Box::new(VarAssignmentData {
+                    id: NodeId::nil_id(),
+                    span: Span::nil_span(),
+                    lhs: self.expression(),
+                    rhs: self.expression(),
+                }))
+            }
+            3 => BlockStatement::If(Box::new(self.if_(return_type))),
+            4 => {
+                BlockStatement::While(Box::new(WhileData {
+                    id: NodeId::nil_id(),
+                    span: Span::nil_span(),
+                    condition: self.expression(),
+                    statements: self.block(return_type),
+                }))
+            }
+            5 => {
+                BlockStatement::Return(Box::new(ReturnData {
+                    id: NodeId::nil_id(),
+                    span: Span::nil_span(),
+                    value: match *return_type {
+                        Type::None => None,
+                        _ => Some(self.expression()),
+                    },
+                    expected_type: return_type.clone(),
+                }))
+            }
+            _ => {
+                BlockStatement::ForIn(Box::new(ForInData {
+                    id: NodeId::nil_id(),
+                    span: Span::nil_span(),
+                    element_name: self.ident(),
+                    collection: self.expression(),
+                    statements: self.block(return_type),
+                }))
+            }
+        }
+    }
+
+    fn if_(&mut self, return_type: &Type) -> IfData {
+        IfData {
+            id: NodeId::nil_id(),
+            span: Span::nil_span(),
+            condition: self.expression(),
+            if_statements: self.block(return_type),
+            else_statements: match self.rng.below(3) {
+                0 => None,
+                1 => Some(self.block(return_type)),
+                _ => {
+                    if self.nested() {
+                        self.depth += 1;
+                        let else_if = BlockStatement::If(Box::new(self.if_(return_type)));
+                        self.depth -= 1;
+                        Some(vec![else_if])
+                    } else {
+                        None
+                    }
+                }
+            },
+        }
+    }
+
+    fn expression(&mut self) -> Expression {
+        let expr = if self.nested() {
+            self.depth += 1;
+            let expr = self.compound_expression();
+            self.depth -= 1;
+            expr
+        } else {
+            self.literal()
+        };
+
+        Expression {
+            id: NodeId::nil_id(),
+            span: Span::nil_span(),
+            expr: expr,
+        }
+    }
+
+    fn boxed_expression(&mut self) -> Box<Expression> {
+        Box::new(self.expression())
+    }
+
+    /// Item of an array literal or argument of a call, the only places a spread can appear
+    fn boxed_item(&mut self) -> Box<Expression> {
+        if self.rng.one_in(6) {
+            Box::new(Expression {
+                id: NodeId::nil_id(),
+                span: Span::nil_span(),
+                expr: Expression_::Spread(self.boxed_expression()),
+            })
+        } else {
+            self.boxed_expression()
+        }
+    }
+
+    fn literal(&mut self) -> Expression_ {
+        match self.rng.below(5) {
+            0 if self.rng.one_in(4) => {
+                let len = self.rng.below(4);
+                Expression_::BytesLiteral((0..len)
+                    .map(|_| b"a \n\xff"[self.rng.below(4)])
+                    .collect())
+            }
+            0 => {
+                let len = self.rng.below(4);
+                Expression_::StringLiteral((0..len)
+                    .map(|_| ['a', 'é', ' ', '\n', '"', '\\', '\t'][self.rng.below(7)])
+                    .collect())
+            }
+            1 => Expression_::IntegerLiteral(self.rng.below(1000) as i64),
+            2 => Expression_::BoolLiteral(self.rng.one_in(2)),
+            3 => Expression_::CharLiteral(['a', 'é', ' ', '\'', '\\', '\0'][self.rng.below(6)]),
+            _ => Expression_::Variable(self.path()),
+        }
+    }
+
+    fn compound_expression(&mut self) -> Expression_ {
+        match self.rng.below(12) {
+            0 => {
+                let count = self.rng.below(3);
+                let path = self.path();
+                Expression_::StructInit(path,
+                                        (0..count)
+                                            .map(|_| {
+                                                StructInitFieldData {
+                                                    span: Span::nil_span(),
+                                                    name: self.spanned_string(),
+                                                    value: self.boxed_expression(),
+                                                }
+                                            })
+                                            .collect())
+            }
+            1 => {
+                let count = self.rng.below(3);
+                Expression_::Array((0..count).map(|_| self.boxed_item()).collect())
+            }
+            2 if self.rng.one_in(3) => {
+                let count = self.rng.below(3) + 1;
+                Expression_::Set((0..count).map(|_| self.boxed_expression()).collect())
+            }
+            2 => {
+                let mut map = Map { entries: vec![] };
+                for _ in 0..self.rng.below(3) {
+                    let key = self.boxed_expression();
+                    let value = self.boxed_expression();
+                    // Duplicate literal keys are rejected by the parser
+                    if !map.entries.iter().any(|&(ref k, _)| k.expr == key.expr) {
+                        map.entries.push((key, value));
+                    }
+                }
+                Expression_::Map(map)
+            }
+            3 => {
+                let count = self.rng.below(3);
+                let func = self.boxed_expression();
+                Expression_::FuncCall(func, (0..count).map(|_| self.boxed_item()).collect())
+            }
+            4 => Expression_::Field(self.boxed_expression(), self.spanned_string()),
+            5 => {
+                let indexed = self.boxed_expression();
+                let index = if self.rng.one_in(4) {
+                    None
+                } else {
+                    Some(self.boxed_expression())
+                };
+                Expression_::Index(indexed, index)
+            }
+            6 => {
+                let unop = match self.rng.below(7) {
+                    0 => UnaryOp::Reference,
+                    1 => UnaryOp::MutReference,
+                    2 => UnaryOp::Dereference,
+                    3 => UnaryOp::Await,
+                    4 => UnaryOp::Negation,
+                    5 => UnaryOp::Not,
+                    _ => UnaryOp::Count,
+                };
+                Expression_::UnaryOp(unop, self.boxed_expression())
+            }
+            7 if self.rng.one_in(4) => {
+                let mut operands = vec![self.boxed_expression(),
+                                        self.boxed_expression(),
+                                        self.boxed_expression()];
+                let mut binops = vec![self.ordering(), self.ordering()];
+                while operands.len() < 5 && self.rng.one_in(2) {
+                    operands.push(self.boxed_expression());
+                    binops.push(self.ordering());
+                }
+                Expression_::ComparisonChain(operands, binops)
+            }
+            7 | 8 => {
+                let binop = match self.rng.below(14) {
+                    0 => BinaryOp::Addition,
+                    1 => BinaryOp::Substraction,
+                    2 => BinaryOp::Multiplication,
+                    3 => BinaryOp::Division,
+                    4 => BinaryOp::Modulo,
+                    5 => BinaryOp::Equality,
+                    6 => BinaryOp::Inequality,
+                    7 => BinaryOp::Less,
+                    8 => BinaryOp::LessOrEqual,
+                    9 => BinaryOp::More,
+                    10 => BinaryOp::MoreOrEqual,
+                    11 => BinaryOp::And,
+                    12 => BinaryOp::Or,
+                    _ => BinaryOp::Concatenation,
+                };
+                Expression_::BinaryOp(binop, self.boxed_expression(), self.boxed_expression())
+            }
+            9 => {
+                let return_type = if self.rng.one_in(2) {
+                    Type::None
+                } else {
+                    self.type_()
+                };
+                let statements = self.block(&return_type);
+                Expression_::Spawn(return_type, statements)
+            }
+            10 => Expression_::TypeTest(self.type_(), self.boxed_expression()),
+            _ => self.literal(),
+        }
+    }
+    fn ordering(&mut self) -> BinaryOp {
+        match self.rng.below(4) {
+            0 => BinaryOp::Less,
+            1 => BinaryOp::LessOrEqual,
+            2 => BinaryOp::More,
+            _ => BinaryOp::MoreOrEqual,
+        }
+    }
+}
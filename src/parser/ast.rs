@@ -1,19 +1,457 @@
 use std;
 use lexer::Span;
+use source_map::FileId;
+use parser::visit;
 use std::hash::*;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Ast {
     pub statements: std::vec::Vec<Statement>,
+    /// Comments of the source, by the statement or struct field they are attached to
+    pub trivia: NodeMap<Trivia>,
+    /// Comments after the last top-level statement
+    pub trailing_comments: std::vec::Vec<Comment>,
 }
 
 impl Ast {
     pub fn new() -> Self {
-        Ast { statements: vec![] }
+        Ast {
+            statements: vec![],
+            trivia: NodeMap::new(),
+            trailing_comments: vec![],
+        }
+    }
+
+    /// Copy of the tree with nil spans and node ids everywhere and without comments, so that
+    /// trees parsed from differently laid out sources compare and hash equal
+    pub fn strip_spans(&self) -> Ast {
+        let mut ast = self.clone();
+        ast.trivia = NodeMap::new();
+        ast.trailing_comments.clear();
+        let mut stripper = SpanMapper {
+            map_span: |span: &mut Span| *span = Span::nil_span(),
+            reset_ids: true,
+        };
+        visit::walk_ast_mut(&mut stripper, &mut ast);
+
+        ast
     }
 }
 
-#[derive(Debug, Clone)]
+/// Applies a function to every span of the visited nodes
+struct SpanMapper<F: FnMut(&mut Span)> {
+    map_span: F,
+    reset_ids: bool,
+}
+
+impl<F: FnMut(&mut Span)> SpanMapper<F> {
+    fn node(&mut self, id: &mut NodeId, span: &mut Span) {
+        if self.reset_ids {
+            *id = NodeId::nil_id();
+        }
+        (self.map_span)(span);
+    }
+
+    fn map_path(&mut self, path: &mut Path) {
+        (self.map_span)(&mut path.span);
+        for part in &mut path.parts {
+            (self.map_span)(&mut part.span);
+        }
+    }
+}
+
+impl<F: FnMut(&mut Span)> visit::MutVisitor for SpanMapper<F> {
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        match *statement {
+            Statement::Import(ref mut i) => self.node(&mut i.id, &mut i.span),
+            Statement::Package(ref mut p) => self.node(&mut p.id, &mut p.span),
+            Statement::FuncDecl(ref mut fd) => self.node(&mut fd.id, &mut fd.span),
+            Statement::StructDecl(ref mut sd) => self.node(&mut sd.id, &mut sd.span),
+        }
+        visit::walk_statement_mut(self, statement);
+    }
+
+    fn visit_func_decl_param_mut(&mut self, param: &mut FuncDeclParamData) {
+        self.node(&mut param.id, &mut param.span);
+        visit::walk_func_decl_param_mut(self, param);
+    }
+
+    fn visit_struct_field_mut(&mut self, field: &mut StructFieldData) {
+        self.node(&mut field.id, &mut field.span);
+        visit::walk_struct_field_mut(self, field);
+    }
+
+    fn visit_block_statement_mut(&mut self, block_statement: &mut BlockStatement) {
+        match *block_statement {
+            BlockStatement::Expression(_) => {}
+            BlockStatement::VarDecl(ref mut vd) => self.node(&mut vd.id, &mut vd.span),
+            BlockStatement::Destructure(ref mut d) => {
+                self.node(&mut d.id, &mut d.span);
+                let names = match d.pattern {
+                    Pattern::Array(ref mut names) => names,
+                    Pattern::Struct(ref mut path, ref mut names) => {
+                        self.map_path(path);
+                        names
+                    }
+                };
+                for name in names {
+                    (self.map_span)(&mut name.span);
+                }
+            }
+            BlockStatement::VarAssignment(ref mut va) => self.node(&mut va.id, &mut va.span),
+            BlockStatement::If(ref mut i) => self.node(&mut i.id, &mut i.span),
+            BlockStatement::While(ref mut w) => self.node(&mut w.id, &mut w.span),
+            BlockStatement::Return(ref mut r) => {
+                self.node(&mut r.id, &mut r.span);
+                // Not reached by the walk, which only visits types written in the source
+                self.visit_type_mut(&mut r.expected_type);
+            }
+            BlockStatement::ForIn(ref mut fi) => self.node(&mut fi.id, &mut fi.span),
+        }
+        visit::walk_block_statement_mut(self, block_statement);
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        self.node(&mut expression.id, &mut expression.span);
+        match expression.expr {
+            Expression_::Variable(ref mut path) => self.map_path(path),
+            Expression_::StructInit(ref mut path, ref mut fields) => {
+                self.map_path(path);
+                for field in fields {
+                    (self.map_span)(&mut field.span);
+                    (self.map_span)(&mut field.name.span);
+                }
+            }
+            Expression_::Field(_, ref mut field) => (self.map_span)(&mut field.span),
+            _ => {}
+        }
+        visit::walk_expression_mut(self, expression);
+    }
+
+    fn visit_type_mut(&mut self, t: &mut Type) {
+        match *t {
+            Type::Reference(ref mut t) |
+            Type::MutReference(ref mut t) |
+            Type::Array(ref mut t) |
+            Type::Set(ref mut t) |
+            Type::Chan(ref mut t) => self.visit_type_mut(t),
+            Type::Map(ref mut k, ref mut v) => {
+                self.visit_type_mut(k);
+                self.visit_type_mut(v);
+            }
+            Type::Struct(ref mut path) => self.map_path(path),
+            Type::Func(ref mut return_type, ref mut params) => {
+                self.visit_type_mut(return_type);
+                for param in params {
+                    self.visit_type_mut(param);
+                }
+            }
+            Type::None | Type::String | Type::Int | Type::Bool | Type::Char | Type::Bytes |
+            Type::Task | Type::Mutex | Type::WaitGroup | Type::Atomic | Type::Builder => {}
+        }
+    }
+}
+
+/// Identifies a node of the AST. Ids are assigned by the parser and are unique among the nodes
+/// it produced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(pub u32);
+
+impl NodeId {
+    /// Id of the nodes which were not produced by the parser
+    pub fn nil_id() -> NodeId {
+        NodeId(0)
+    }
+}
+
+/// Side table associating data (types, resolutions...) with AST nodes
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeMap<T> {
+    map: std::collections::HashMap<NodeId, T>,
+}
+
+impl<T> NodeMap<T> {
+    pub fn new() -> Self {
+        NodeMap { map: std::collections::HashMap::new() }
+    }
+
+    pub fn insert(&mut self, id: NodeId, value: T) -> Option<T> {
+        self.map.insert(id, value)
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&T> {
+        self.map.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: NodeId) -> Option<&mut T> {
+        self.map.get_mut(&id)
+    }
+
+    pub fn remove(&mut self, id: NodeId) -> Option<T> {
+        self.map.remove(&id)
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.map.contains_key(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn values(&self) -> std::collections::hash_map::Values<NodeId, T> {
+        self.map.values()
+    }
+}
+
+/// `//` comment, `text` being what follows the slashes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub span: Span,
+    pub text: String,
+}
+
+/// Comments attached to a node, see `parser::trivia`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Trivia {
+    /// Comments on the lines before the node
+    pub leading: std::vec::Vec<Comment>,
+    /// Comment at the end of the last line of the node
+    pub trailing: std::vec::Vec<Comment>,
+    /// Comments inside the node after its last child, e.g. in an empty block
+    pub dangling: std::vec::Vec<Comment>,
+}
+
+/// Reference to any AST node which has a `NodeId`. Expression statements are represented by
+/// their expression.
+#[derive(Debug, Clone, Copy)]
+pub enum NodeRef<'a> {
+    Statement(&'a Statement),
+    FuncDeclParam(&'a FuncDeclParamData),
+    StructField(&'a StructFieldData),
+    BlockStatement(&'a BlockStatement),
+    Expression(&'a Expression),
+}
+
+impl<'a> NodeRef<'a> {
+    pub fn id(&self) -> NodeId {
+        match *self {
+            NodeRef::Statement(s) => s.id(),
+            NodeRef::FuncDeclParam(p) => p.id,
+            NodeRef::StructField(f) => f.id,
+            NodeRef::BlockStatement(bs) => bs.id(),
+            NodeRef::Expression(e) => e.id,
+        }
+    }
+
+    pub fn span(&self) -> &'a Span {
+        match *self {
+            NodeRef::Statement(s) => s.span(),
+            NodeRef::FuncDeclParam(p) => &p.span,
+            NodeRef::StructField(f) => &f.span,
+            NodeRef::BlockStatement(bs) => bs.span(),
+            NodeRef::Expression(e) => &e.span,
+        }
+    }
+}
+
+/// Returns the innermost node whose span covers the given position of `file`. Rows and columns
+/// start at 1, as in spans.
+pub fn find_at<'a>(ast: &'a Ast, file: FileId, row: i32, col: i32) -> Option<NodeRef<'a>> {
+    struct NodeFinder<'a> {
+        file: FileId,
+        row: i32,
+        col: i32,
+        depth: usize,
+        found: Option<(usize, NodeRef<'a>)>,
+    }
+
+    impl<'a> NodeFinder<'a> {
+        fn covers(&self, span: &Span) -> bool {
+            span.file == self.file && span.contains(self.row, self.col)
+        }
+
+        fn check(&mut self, node: NodeRef<'a>) -> bool {
+            if !self.covers(node.span()) {
+                return false;
+            }
+
+            let is_deeper = match self.found {
+                Some((depth, _)) => self.depth >= depth,
+                None => true,
+            };
+            if is_deeper {
+                self.found = Some((self.depth, node));
+            }
+
+            true
+        }
+    }
+
+    impl<'a> visit::Visitor<'a> for NodeFinder<'a> {
+        fn visit_statement(&mut self, statement: &'a Statement) {
+            if self.check(NodeRef::Statement(statement)) {
+                self.depth += 1;
+                visit::walk_statement(self, statement);
+                self.depth -= 1;
+            }
+        }
+
+        fn visit_func_decl_param(&mut self, param: &'a FuncDeclParamData) {
+            if self.check(NodeRef::FuncDeclParam(param)) {
+                self.depth += 1;
+                visit::walk_func_decl_param(self, param);
+                self.depth -= 1;
+            }
+        }
+
+        fn visit_struct_field(&mut self, field: &'a StructFieldData) {
+            if self.check(NodeRef::StructField(field)) {
+                self.depth += 1;
+                visit::walk_struct_field(self, field);
+                self.depth -= 1;
+            }
+        }
+
+        fn visit_block_statement(&mut self, block_statement: &'a BlockStatement) {
+            if let BlockStatement::Expression(_) = *block_statement {
+                return visit::walk_block_statement(self, block_statement);
+            }
+
+            if self.check(NodeRef::BlockStatement(block_statement)) {
+                self.depth += 1;
+                visit::walk_block_statement(self, block_statement);
+                self.depth -= 1;
+            }
+        }
+
+        fn visit_expression(&mut self, expression: &'a Expression) {
+            if self.check(NodeRef::Expression(expression)) {
+                self.depth += 1;
+                visit::walk_expression(self, expression);
+                self.depth -= 1;
+            }
+        }
+    }
+
+    let mut finder = NodeFinder {
+        file: file,
+        row: row,
+        col: col,
+        depth: 0,
+        found: None,
+    };
+    visit::walk_ast(&mut finder, ast);
+
+    finder.found.map(|(_, node)| node)
+}
+
+/// Size and complexity of a program, as reported by `ion stats`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Metrics {
+    pub funcs: usize,
+    pub structs: usize,
+    /// Statements of any kind, top-level ones included
+    pub statements: usize,
+    pub expressions: usize,
+    /// Deepest nesting of blocks, the body of a func being at depth 1
+    pub max_depth: usize,
+    /// Cyclomatic complexity of each func, in source order: 1, plus 1 for each `if`, `while`,
+    /// `for`, `&&` and `||`, chained comparison operators included
+    pub complexity: std::vec::Vec<(String, usize)>,
+}
+
+pub fn metrics(ast: &Ast) -> Metrics {
+    struct MetricsCollector {
+        metrics: Metrics,
+        depth: usize,
+        in_func: bool,
+    }
+
+    impl MetricsCollector {
+        fn add_branch(&mut self) {
+            if !self.in_func {
+                return;
+            }
+            if let Some(&mut (_, ref mut complexity)) = self.metrics.complexity.last_mut() {
+                *complexity += 1;
+            }
+        }
+    }
+
+    impl<'a> visit::Visitor<'a> for MetricsCollector {
+        fn visit_statement(&mut self, statement: &'a Statement) {
+            self.metrics.statements += 1;
+            if let Statement::StructDecl(_) = *statement {
+                self.metrics.structs += 1;
+            }
+            visit::walk_statement(self, statement);
+        }
+
+        fn visit_func_decl(&mut self, func_decl: &'a FuncDeclData) {
+            self.metrics.funcs += 1;
+            self.metrics.complexity.push((func_decl.name.clone(), 1));
+            self.in_func = true;
+            self.depth = 1;
+            visit::walk_func_decl(self, func_decl);
+            self.depth = 0;
+            self.in_func = false;
+        }
+
+        fn visit_block_statement(&mut self, block_statement: &'a BlockStatement) {
+            self.metrics.statements += 1;
+            self.metrics.max_depth = std::cmp::max(self.metrics.max_depth, self.depth);
+            match *block_statement {
+                BlockStatement::If(_) |
+                BlockStatement::While(_) |
+                BlockStatement::ForIn(_) => {
+                    self.add_branch();
+                    self.depth += 1;
+                    visit::walk_block_statement(self, block_statement);
+                    self.depth -= 1;
+                }
+                _ => visit::walk_block_statement(self, block_statement),
+            }
+        }
+
+        fn visit_expression(&mut self, expression: &'a Expression) {
+            self.metrics.expressions += 1;
+            match expression.expr {
+                Expression_::BinaryOp(BinaryOp::And, _, _) |
+                Expression_::BinaryOp(BinaryOp::Or, _, _) => self.add_branch(),
+                // Each comparison after the first one is a branch, as with `&&`
+                Expression_::ComparisonChain(_, ref binops) => {
+                    for _ in 1..binops.len() {
+                        self.add_branch();
+                    }
+                }
+                Expression_::Spawn(_, _) => {
+                    self.depth += 1;
+                    visit::walk_expression(self, expression);
+                    self.depth -= 1;
+                    return;
+                }
+                _ => {}
+            }
+            visit::walk_expression(self, expression);
+        }
+    }
+
+    let mut collector = MetricsCollector {
+        metrics: Metrics::default(),
+        depth: 0,
+        in_func: false,
+    };
+    visit::walk_ast(&mut collector, ast);
+
+    collector.metrics
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Statement {
     Import(Box<ImportData>),
     Package(Box<PackageData>),
@@ -21,40 +459,118 @@ pub enum Statement {
     StructDecl(Box<StructDeclData>),
 }
 
-#[derive(Debug, Clone)]
+impl Statement {
+    /// Applies `f` to every span of the statement and of its children
+    pub fn map_spans<F: FnMut(&mut Span)>(&mut self, f: F) {
+        let mut mapper = SpanMapper {
+            map_span: f,
+            reset_ids: false,
+        };
+        visit::MutVisitor::visit_statement_mut(&mut mapper, self);
+    }
+
+    pub fn id(&self) -> NodeId {
+        match *self {
+            Statement::Import(ref i) => i.id,
+            Statement::Package(ref p) => p.id,
+            Statement::FuncDecl(ref fd) => fd.id,
+            Statement::StructDecl(ref sd) => sd.id,
+        }
+    }
+
+    pub fn span(&self) -> &Span {
+        match *self {
+            Statement::Import(ref i) => &i.span,
+            Statement::Package(ref p) => &p.span,
+            Statement::FuncDecl(ref fd) => &fd.span,
+            Statement::StructDecl(ref sd) => &sd.span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct ImportData {
+    pub id: NodeId,
     pub span: Span,
     pub path: String,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct PackageData {
+    pub id: NodeId,
     pub span: Span,
     pub name: String,
 }
 
 #[derive(Debug, Clone, PartialEq, Hash, Eq)]
 pub struct FuncDeclData {
+    pub id: NodeId,
     pub span: Span,
     pub name: String,
     pub return_type: Type,
     pub parameters: std::vec::Vec<Box<FuncDeclParamData>>,
     pub statements: std::vec::Vec<BlockStatement>,
+    /// Whether calls run the function as a task, evaluating to a handle on it
+    pub is_async: bool,
+    /// Whether the function has no body and is a C function loaded at runtime
+    pub is_extern: bool,
+    /// Library an extern function is loaded from, the interpreter's own symbols if None
+    pub library: Option<String>,
+    /// Text of the `///` comments right above the declaration, see `Parser::doc_before`
+    pub doc: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Hash, Eq)]
 pub enum BlockStatement {
     Expression(Box<Expression>),
     VarDecl(Box<VarDeclData>),
-    VarAssignment(Box<Expression>, Box<Expression>),
+    Destructure(Box<DestructureData>),
+    VarAssignment(Box<VarAssignmentData>),
     If(Box<IfData>),
     While(Box<WhileData>),
     Return(Box<ReturnData>),
     ForIn(Box<ForInData>),
 }
 
+impl BlockStatement {
+    pub fn id(&self) -> NodeId {
+        match *self {
+            BlockStatement::Expression(ref e) => e.id,
+            BlockStatement::VarDecl(ref vd) => vd.id,
+            BlockStatement::Destructure(ref d) => d.id,
+            BlockStatement::VarAssignment(ref va) => va.id,
+            BlockStatement::If(ref i) => i.id,
+            BlockStatement::While(ref w) => w.id,
+            BlockStatement::Return(ref r) => r.id,
+            BlockStatement::ForIn(ref fi) => fi.id,
+        }
+    }
+
+    pub fn span(&self) -> &Span {
+        match *self {
+            BlockStatement::Expression(ref e) => &e.span,
+            BlockStatement::VarDecl(ref vd) => &vd.span,
+            BlockStatement::Destructure(ref d) => &d.span,
+            BlockStatement::VarAssignment(ref va) => &va.span,
+            BlockStatement::If(ref i) => &i.span,
+            BlockStatement::While(ref w) => &w.span,
+            BlockStatement::Return(ref r) => &r.span,
+            BlockStatement::ForIn(ref fi) => &fi.span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Hash, Eq)]
+pub struct VarAssignmentData {
+    pub id: NodeId,
+    pub span: Span,
+    pub lhs: Expression,
+    pub rhs: Expression,
+}
+
 #[derive(Debug, Clone, PartialEq, Hash, Eq)]
 pub struct ForInData {
+    pub id: NodeId,
     pub span: Span,
     pub element_name: String,
     pub collection: Expression,
@@ -63,6 +579,7 @@ pub struct ForInData {
 
 #[derive(Debug, Clone, PartialEq, Hash, Eq)]
 pub struct ReturnData {
+    pub id: NodeId,
     pub span: Span,
     pub value: Option<Expression>,
     pub expected_type: Type,
@@ -70,6 +587,7 @@ pub struct ReturnData {
 
 #[derive(Debug, Clone, PartialEq, Hash, Eq)]
 pub struct IfData {
+    pub id: NodeId,
     pub span: Span,
     pub condition: Expression,
     pub if_statements: std::vec::Vec<BlockStatement>,
@@ -78,6 +596,7 @@ pub struct IfData {
 
 #[derive(Debug, Clone, PartialEq, Hash, Eq)]
 pub struct WhileData {
+    pub id: NodeId,
     pub span: Span,
     pub condition: Expression,
     pub statements: std::vec::Vec<BlockStatement>,
@@ -85,14 +604,44 @@ pub struct WhileData {
 
 #[derive(Debug, Clone, PartialEq, Hash, Eq)]
 pub struct VarDeclData {
+    pub id: NodeId,
     pub span: Span,
     pub name: String,
     pub var_type: Type,
     pub value: Option<Expression>,
 }
 
+/// `var (a, b) = array` or `var Point { x, y } = point`, declaring a variable for each item of the
+/// array or for each named field of the struct
+#[derive(Debug, Clone, PartialEq, Hash, Eq)]
+pub struct DestructureData {
+    pub id: NodeId,
+    pub span: Span,
+    pub pattern: Pattern,
+    pub value: Expression,
+}
+
+#[derive(Debug, Clone, PartialEq, Hash, Eq)]
+pub enum Pattern {
+    /// Binds the items of an array, which must have exactly as many
+    Array(std::vec::Vec<SpannedString>),
+    /// Binds fields of a struct of that type to variables named after them
+    Struct(Path, std::vec::Vec<SpannedString>),
+}
+
+impl Pattern {
+    /// Names of the variables the pattern declares, in source order
+    pub fn names(&self) -> &[SpannedString] {
+        match *self {
+            Pattern::Array(ref names) |
+            Pattern::Struct(_, ref names) => names,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Hash, Eq)]
 pub struct FuncDeclParamData {
+    pub id: NodeId,
     pub span: Span,
     pub name: String,
     pub param_type: Type,
@@ -106,38 +655,80 @@ pub enum Type {
     MutReference(Box<Type>),
     Array(Box<Type>),
     Map(Box<Type>, Box<Type>),
+    /// Unordered collection of distinct values, which can only be of the types of map keys
+    Set(Box<Type>),
     Struct(Path),
     Func(Box<Type>, std::vec::Vec<Box<Type>>),
     String,
     Int,
     Bool,
     Char,
+    /// Sequence of bytes, which unlike a string doesn't have to be valid UTF-8
+    Bytes,
+    Task,
+    Chan(Box<Type>),
+    Mutex,
+    WaitGroup,
+    Atomic,
+    Builder,
 }
 
-#[derive(Debug, Clone)]
+impl Type {
+    /// Whether values of the type can be the keys of a map, which requires them to be hashed and
+    /// compared
+    pub fn is_map_key(&self) -> bool {
+        match *self {
+            Type::Int | Type::String | Type::Char | Type::Bool => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct StructDeclData {
+    pub id: NodeId,
     pub span: Span,
     pub name: String,
     pub fields: std::vec::Vec<Box<StructFieldData>>,
+    pub doc: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct StructFieldData {
+    pub id: NodeId,
     pub span: Span,
     pub name: String,
     pub field_type: Type,
     pub default_value: Option<Expression>,
+    pub doc: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Expression {
+    pub id: NodeId,
     pub expr: Expression_,
     pub span: Span,
 }
 
+impl Expression {
+    /// Copy of the expression with nil spans and node ids everywhere, so that structurally
+    /// identical expressions compare and hash equal
+    pub fn strip_spans(&self) -> Expression {
+        let mut expression = self.clone();
+        let mut stripper = SpanMapper {
+            map_span: |span: &mut Span| *span = Span::nil_span(),
+            reset_ids: true,
+        };
+        visit::MutVisitor::visit_expression_mut(&mut stripper, &mut expression);
+
+        expression
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Expression_ {
     StringLiteral(String),
+    BytesLiteral(std::vec::Vec<u8>),
     IntegerLiteral(i64),
     BoolLiteral(bool),
     CharLiteral(char),
@@ -145,28 +736,29 @@ pub enum Expression_ {
     StructInit(Path, std::vec::Vec<StructInitFieldData>),
     Array(std::vec::Vec<Box<Expression>>),
     Map(Map),
+    /// `{a, b}`, whose duplicate items are only kept once
+    Set(std::vec::Vec<Box<Expression>>),
     FuncCall(Box<Expression>, std::vec::Vec<Box<Expression>>),
     Field(Box<Expression>, SpannedString),
     Index(Box<Expression>, Option<Box<Expression>>),
     UnaryOp(UnaryOp, Box<Expression>),
     BinaryOp(BinaryOp, Box<Expression>, Box<Expression>),
+    /// `a < b <= c`, with the operands and the ordering operators between them, which holds when
+    /// every comparison does. Each operand is evaluated at most once.
+    ComparisonChain(std::vec::Vec<Box<Expression>>, std::vec::Vec<BinaryOp>),
+    /// Block run concurrently, with the type it returns, evaluating to a handle on the task
+    Spawn(Type, std::vec::Vec<BlockStatement>),
+    /// `is<T>(value)`, evaluating to whether the value has the type
+    TypeTest(Type, Box<Expression>),
+    /// `...array`, expanding to the items of the array, only allowed as an item of an array
+    /// literal or as a call argument
+    Spread(Box<Expression>),
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Entries of a map literal, in source order
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Map {
-    pub map: std::collections::HashMap<Box<Expression>, Box<Expression>>,
-}
-
-#[allow(derive_hash_xor_eq)]
-impl Hash for Map {
-    fn hash<H>(&self, state: &mut H)
-        where H: Hasher
-    {
-        for (key, value) in &self.map {
-            key.hash(state);
-            value.hash(state);
-        }
-    }
+    pub entries: std::vec::Vec<(Box<Expression>, Box<Expression>)>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -178,7 +770,25 @@ pub enum BinaryOp {
     Modulo,
     Equality,
     Inequality,
+    Less,
+    LessOrEqual,
+    More,
+    MoreOrEqual,
     Concatenation,
+    /// `&&`, evaluating its right operand only when the left one is true
+    And,
+    /// `||`, evaluating its right operand only when the left one is false
+    Or,
+}
+
+impl BinaryOp {
+    /// Whether the operator orders its operands, which makes it chainable: `a < b <= c`
+    pub fn is_ordering(&self) -> bool {
+        match *self {
+            BinaryOp::Less | BinaryOp::LessOrEqual | BinaryOp::More | BinaryOp::MoreOrEqual => true,
+            _ => false,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -187,6 +797,12 @@ pub enum UnaryOp {
     MutReference,
     Dereference,
     Count,
+    /// `-i`, the opposite of an int
+    Negation,
+    /// `!b`, the negation of a bool
+    Not,
+    /// Waits for the task to finish, evaluating to the value it returned
+    Await,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
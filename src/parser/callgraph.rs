@@ -0,0 +1,212 @@
+use std;
+use std::collections::HashSet;
+use std::fmt::Write;
+use parser::ast::*;
+use parser::visit;
+use parser::visit::Visitor;
+
+/// What a call expression calls
+#[derive(Debug, Clone, Copy)]
+pub enum Callee<'a> {
+    /// Func declared in the same `Ast`
+    Func(&'a FuncDeclData),
+    /// Builtin or func of an imported module, by its path as written, e.g. `io::read_file`
+    External(&'a Path),
+    /// Func value held by a variable or computed by an expression, only known at runtime
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Call<'a> {
+    pub caller: &'a FuncDeclData,
+    pub callee: Callee<'a>,
+    pub expression: &'a Expression,
+}
+
+/// Calls made by the funcs of an `Ast`, in source order. A name is taken for a local variable
+/// holding a func value when it is a parameter or variable of the calling func, whatever the
+/// block it is declared in.
+pub struct CallGraph<'a> {
+    funcs: std::vec::Vec<&'a FuncDeclData>,
+    calls: std::vec::Vec<Call<'a>>,
+}
+
+impl<'a> CallGraph<'a> {
+    pub fn new(ast: &'a Ast) -> Self {
+        let mut funcs = vec![];
+        for statement in &ast.statements {
+            if let Statement::FuncDecl(ref fd) = *statement {
+                funcs.push(&**fd);
+            }
+        }
+
+        let mut graph = CallGraph {
+            funcs: funcs,
+            calls: vec![],
+        };
+        for i in 0..graph.funcs.len() {
+            let func = graph.funcs[i];
+            let mut locals = LocalCollector { names: HashSet::new() };
+            for param in &func.parameters {
+                locals.names.insert(&param.name);
+            }
+            visit::walk_block_statements(&mut locals, &func.statements);
+
+            let mut builder = CallGraphBuilder {
+                graph: &mut graph,
+                caller: func,
+                locals: locals.names,
+            };
+            visit::walk_block_statements(&mut builder, &func.statements);
+        }
+
+        graph
+    }
+
+    pub fn calls(&self) -> &[Call<'a>] {
+        &self.calls
+    }
+
+    pub fn calls_from(&self, name: &str) -> std::vec::Vec<&Call<'a>> {
+        self.calls.iter().filter(|call| call.caller.name == name).collect()
+    }
+
+    /// Funcs calling the func of the same `Ast` with this name, each only once
+    pub fn callers_of(&self, name: &str) -> std::vec::Vec<&'a FuncDeclData> {
+        let mut callers: std::vec::Vec<&'a FuncDeclData> = vec![];
+        for call in &self.calls {
+            if let Callee::Func(callee) = call.callee {
+                if callee.name == name && !callers.iter().any(|c| c.name == call.caller.name) {
+                    callers.push(call.caller);
+                }
+            }
+        }
+
+        callers
+    }
+
+    /// Whether the func makes calls the graph can't follow, through func values
+    pub fn has_unknown_calls(&self, name: &str) -> bool {
+        self.calls_from(name).iter().any(|call| match call.callee {
+            Callee::Unknown => true,
+            _ => false,
+        })
+    }
+
+    /// Whether the func can call itself, directly or through other funcs of the `Ast`. Calls
+    /// through func values aren't followed.
+    pub fn is_recursive(&self, name: &str) -> bool {
+        let mut visited: HashSet<&str> = HashSet::new();
+        let mut stack = vec![name];
+        while let Some(caller) = stack.pop() {
+            for call in self.calls_from(caller) {
+                if let Callee::Func(callee) = call.callee {
+                    if callee.name == name {
+                        return true;
+                    }
+                    if visited.insert(&callee.name) {
+                        stack.push(&callee.name);
+                    }
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Funcs for which `is_recursive` holds, in source order
+    pub fn recursive_funcs(&self) -> std::vec::Vec<&'a FuncDeclData> {
+        self.funcs.iter().cloned().filter(|func| self.is_recursive(&func.name)).collect()
+    }
+
+    /// Graph in the DOT language of Graphviz, with an edge per distinct caller and callee, and
+    /// unknown callees merged into a dashed `<unknown>` node
+    pub fn to_dot(&self) -> String {
+        let mut out = "digraph calls {\n".to_string();
+        for func in &self.funcs {
+            let _ = writeln!(out, "    \"{}\";", func.name);
+        }
+
+        let mut edges = HashSet::new();
+        for call in &self.calls {
+            let edge = match call.callee {
+                Callee::Func(callee) => format!("\"{}\" -> \"{}\";", call.caller.name, callee.name),
+                Callee::External(path) => format!("\"{}\" -> \"{}\";", call.caller.name, path),
+                Callee::Unknown => {
+                    format!("\"{}\" -> \"<unknown>\" [style=dashed];", call.caller.name)
+                }
+            };
+            if edges.insert(edge.clone()) {
+                let _ = writeln!(out, "    {}", edge);
+            }
+        }
+        out.push_str("}\n");
+
+        out
+    }
+}
+
+/// Names of the variables declared in a func body
+struct LocalCollector<'a> {
+    names: HashSet<&'a str>,
+}
+
+impl<'a> Visitor<'a> for LocalCollector<'a> {
+    fn visit_block_statement(&mut self, block_statement: &'a BlockStatement) {
+        match *block_statement {
+            BlockStatement::VarDecl(ref vd) => {
+                self.names.insert(&vd.name);
+            }
+            BlockStatement::Destructure(ref d) => {
+                for name in d.pattern.names() {
+                    self.names.insert(&name.ident);
+                }
+            }
+            BlockStatement::ForIn(ref fi) => {
+                self.names.insert(&fi.element_name);
+            }
+            _ => {}
+        }
+        visit::walk_block_statement(self, block_statement);
+    }
+}
+
+struct CallGraphBuilder<'a: 'g, 'g> {
+    graph: &'g mut CallGraph<'a>,
+    caller: &'a FuncDeclData,
+    locals: HashSet<&'a str>,
+}
+
+impl<'a, 'g> CallGraphBuilder<'a, 'g> {
+    fn callee(&self, func: &'a Expression) -> Callee<'a> {
+        let path = match func.expr {
+            Expression_::Variable(ref path) => path,
+            _ => return Callee::Unknown,
+        };
+        if path.parts.len() == 1 {
+            let name = &path.parts[0].ident;
+            if self.locals.contains(name.as_str()) {
+                return Callee::Unknown;
+            }
+            if let Some(fd) = self.graph.funcs.iter().find(|fd| fd.name == *name) {
+                return Callee::Func(fd);
+            }
+        }
+
+        Callee::External(path)
+    }
+}
+
+impl<'a, 'g> Visitor<'a> for CallGraphBuilder<'a, 'g> {
+    fn visit_expression(&mut self, expression: &'a Expression) {
+        if let Expression_::FuncCall(ref func, _) = expression.expr {
+            let callee = self.callee(func);
+            self.graph.calls.push(Call {
+                caller: self.caller,
+                callee: callee,
+                expression: expression,
+            });
+        }
+        visit::walk_expression(self, expression);
+    }
+}
@@ -0,0 +1,59 @@
+use std;
+use std::collections::HashMap;
+use parser::ast::*;
+use parser::visit;
+use parser::visit::Visitor;
+
+/// Index grouping the structurally identical expressions of an `Ast`, whatever their spans and
+/// node ids, as a basis for common subexpression elimination and duplicate code lints.
+pub struct ExpressionIndex<'a> {
+    /// Classes of identical expressions, in the order of their first occurrence
+    classes: std::vec::Vec<std::vec::Vec<&'a Expression>>,
+    class_ids: HashMap<Expression, usize>,
+    classes_by_node: NodeMap<usize>,
+}
+
+impl<'a> ExpressionIndex<'a> {
+    pub fn new(ast: &'a Ast) -> Self {
+        let mut builder = ExpressionIndexBuilder {
+            index: ExpressionIndex {
+                classes: vec![],
+                class_ids: HashMap::new(),
+                classes_by_node: NodeMap::new(),
+            },
+        };
+
+        visit::walk_ast(&mut builder, ast);
+
+        builder.index
+    }
+
+    /// Expressions identical to the given one, itself included, in source order
+    pub fn class_of(&self, id: NodeId) -> Option<&[&'a Expression]> {
+        self.classes_by_node.get(id).map(|class| &self.classes[*class][..])
+    }
+
+    /// Classes having more than one expression, in the order of their first occurrence
+    pub fn duplicates(&self) -> std::vec::Vec<&[&'a Expression]> {
+        self.classes.iter().filter(|class| class.len() > 1).map(|class| &class[..]).collect()
+    }
+}
+
+struct ExpressionIndexBuilder<'a> {
+    index: ExpressionIndex<'a>,
+}
+
+impl<'a> Visitor<'a> for ExpressionIndexBuilder<'a> {
+    fn visit_expression(&mut self, expression: &'a Expression) {
+        let index = &mut self.index;
+        let next_class = index.classes.len();
+        let class = *index.class_ids.entry(expression.strip_spans()).or_insert(next_class);
+        if class == next_class {
+            index.classes.push(vec![]);
+        }
+        index.classes[class].push(expression);
+        index.classes_by_node.insert(expression.id, class);
+
+        visit::walk_expression(self, expression);
+    }
+}
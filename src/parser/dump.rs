@@ -0,0 +1,247 @@
+use parser::ast::*;
+
+/// Compact textual form of the AST, for snapshot tests. Nodes are s-expressions, with statements
+/// on their own lines and expressions on the line of their statement:
+///
+/// ```text
+/// (func main () none
+///   (var x int (+ 1 (call f "a"))))
+/// ```
+///
+/// Unlike the printer's output, it shows the structure that was parsed (e.g. how operators were
+/// grouped) and nothing which doesn't change it (comments, parentheses, spacing).
+struct Dumper {
+    out: String,
+    indent: usize,
+}
+
+impl Ast {
+    pub fn dump(&self) -> String {
+        let mut dumper = Dumper {
+            out: String::new(),
+            indent: 0,
+        };
+        for statement in &self.statements {
+            dumper.statement(statement);
+            dumper.out.push('\n');
+        }
+
+        dumper.out
+    }
+}
+
+impl Dumper {
+    fn statement(&mut self, statement: &Statement) {
+        match *statement {
+            Statement::Import(ref i) => self.out.push_str(&format!("(import {:?})", i.path)),
+            Statement::Package(ref p) => self.out.push_str(&format!("(package {})", p.name)),
+            Statement::FuncDecl(ref fd) => self.func_decl(fd),
+            Statement::StructDecl(ref sd) => {
+                self.out.push_str(&format!("(struct {}", sd.name));
+                for field in &sd.fields {
+                    self.out.push_str(&format!(" ({} {}", field.name, field.field_type));
+                    if let Some(ref default_value) = field.default_value {
+                        self.out.push(' ');
+                        self.expression(default_value);
+                    }
+                    self.out.push(')');
+                }
+                self.out.push(')');
+            }
+        }
+    }
+
+    fn func_decl(&mut self, func_decl: &FuncDeclData) {
+        self.out.push('(');
+        if func_decl.is_async {
+            self.out.push_str("async ");
+        }
+        if func_decl.is_extern {
+            self.out.push_str("extern ");
+            if let Some(ref library) = func_decl.library {
+                self.out.push_str(&format!("{:?} ", library));
+            }
+        }
+        self.out.push_str(&format!("func {} (", func_decl.name));
+        for (i, param) in func_decl.parameters.iter().enumerate() {
+            if i > 0 {
+                self.out.push(' ');
+            }
+            self.out.push_str(&format!("({} {}", param.name, param.param_type));
+            if let Some(ref default_value) = param.default_value {
+                self.out.push(' ');
+                self.expression(default_value);
+            }
+            self.out.push(')');
+        }
+        self.out.push_str(&format!(") {}", type_name(&func_decl.return_type)));
+        self.block(&func_decl.statements);
+        self.out.push(')');
+    }
+
+    fn block(&mut self, statements: &[BlockStatement]) {
+        self.indent += 1;
+        for statement in statements {
+            self.out.push('\n');
+            self.out.push_str(&"  ".repeat(self.indent));
+            self.block_statement(statement);
+        }
+        self.indent -= 1;
+    }
+
+    fn block_statement(&mut self, statement: &BlockStatement) {
+        match *statement {
+            BlockStatement::Expression(ref e) => {
+                self.out.push_str("(expr ");
+                self.expression(e);
+            }
+            BlockStatement::VarDecl(ref vd) => {
+                self.out.push_str(&format!("(var {} {}", vd.name, vd.var_type));
+                if let Some(ref value) = vd.value {
+                    self.out.push(' ');
+                    self.expression(value);
+                }
+            }
+            BlockStatement::Destructure(ref d) => {
+                let names: Vec<&str> = d.pattern.names().iter().map(|n| n.ident.as_ref()).collect();
+                match d.pattern {
+                    Pattern::Array(_) => self.out.push_str("(var-array ("),
+                    Pattern::Struct(ref path, _) => {
+                        self.out.push_str(&format!("(var-struct {} (", path))
+                    }
+                }
+                self.out.push_str(&names.join(" "));
+                self.out.push_str(") ");
+                self.expression(&d.value);
+            }
+            BlockStatement::VarAssignment(ref va) => {
+                self.out.push_str("(assign ");
+                self.expression(&va.lhs);
+                self.out.push(' ');
+                self.expression(&va.rhs);
+            }
+            BlockStatement::If(ref i) => {
+                self.out.push_str("(if ");
+                self.expression(&i.condition);
+                self.block(&i.if_statements);
+                if let Some(ref else_statements) = i.else_statements {
+                    self.out.push('\n');
+                    self.out.push_str(&"  ".repeat(self.indent));
+                    self.out.push_str("else");
+                    self.block(else_statements);
+                }
+            }
+            BlockStatement::While(ref w) => {
+                self.out.push_str("(while ");
+                self.expression(&w.condition);
+                self.block(&w.statements);
+            }
+            BlockStatement::Return(ref r) => {
+                self.out.push_str("(return");
+                if let Some(ref value) = r.value {
+                    self.out.push(' ');
+                    self.expression(value);
+                }
+            }
+            BlockStatement::ForIn(ref fi) => {
+                self.out.push_str(&format!("(for {} ", fi.element_name));
+                self.expression(&fi.collection);
+                self.block(&fi.statements);
+            }
+        }
+        self.out.push(')');
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        match expression.expr {
+            Expression_::StringLiteral(ref s) => self.out.push_str(&format!("{:?}", s)),
+            Expression_::BytesLiteral(ref b) => {
+                self.out.push_str(&format!("b{:?}", String::from_utf8_lossy(b)))
+            }
+            Expression_::IntegerLiteral(i) => self.out.push_str(&i.to_string()),
+            Expression_::BoolLiteral(b) => self.out.push_str(&b.to_string()),
+            Expression_::CharLiteral(c) => self.out.push_str(&format!("{:?}", c)),
+            Expression_::Variable(ref p) => self.out.push_str(&p.to_string()),
+            Expression_::StructInit(ref p, ref fields) => {
+                self.out.push_str(&format!("(new {}", p));
+                for field in fields {
+                    self.out.push_str(&format!(" ({} ", field.name.ident));
+                    self.expression(&field.value);
+                    self.out.push(')');
+                }
+                self.out.push(')');
+            }
+            Expression_::Array(ref items) => self.node("array", items.iter().map(|i| &**i)),
+            Expression_::Set(ref items) => self.node("set", items.iter().map(|i| &**i)),
+            Expression_::Map(ref map) => {
+                self.out.push_str("(map");
+                for &(ref key, ref value) in &map.entries {
+                    self.out.push_str(" (");
+                    self.expression(key);
+                    self.out.push(' ');
+                    self.expression(value);
+                    self.out.push(')');
+                }
+                self.out.push(')');
+            }
+            Expression_::FuncCall(ref func, ref args) => {
+                let operands = Some(&**func).into_iter().chain(args.iter().map(|a| &**a));
+                self.node("call", operands)
+            }
+            Expression_::Field(ref e, ref field) => {
+                self.out.push_str("(. ");
+                self.expression(e);
+                self.out.push_str(&format!(" {})", field.ident));
+            }
+            Expression_::Index(ref e, ref index) => {
+                self.node("index", Some(&**e).into_iter().chain(index.as_ref().map(|i| &**i)))
+            }
+            Expression_::UnaryOp(ref unop, ref e) => self.node(&unop.to_string(), Some(&**e)),
+            Expression_::BinaryOp(ref binop, ref lhs, ref rhs) => {
+                self.node(&binop.to_string(), vec![&**lhs, &**rhs])
+            }
+            Expression_::ComparisonChain(ref operands, ref binops) => {
+                self.out.push_str("(chain ");
+                for (i, operand) in operands.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(&format!(" {} ", binops[i - 1]));
+                    }
+                    self.expression(operand);
+                }
+                self.out.push(')');
+            }
+            Expression_::Spawn(ref t, ref statements) => {
+                self.out.push_str(&format!("(spawn {}", type_name(t)));
+                self.block(statements);
+                self.out.push(')');
+            }
+            Expression_::TypeTest(ref t, ref e) => {
+                self.out.push_str(&format!("(is {} ", t));
+                self.expression(e);
+                self.out.push(')');
+            }
+            Expression_::Spread(ref e) => self.node("...", Some(&**e)),
+        }
+    }
+
+    /// `(name operand...)`
+    fn node<'e, I>(&mut self, name: &str, operands: I)
+        where I: IntoIterator<Item = &'e Expression>
+    {
+        self.out.push('(');
+        self.out.push_str(name);
+        for operand in operands {
+            self.out.push(' ');
+            self.expression(operand);
+        }
+        self.out.push(')');
+    }
+}
+
+/// `none` for the absence of a type, which the `Display` of types prints as an empty string
+fn type_name(t: &Type) -> String {
+    match *t {
+        Type::None => "none".to_string(),
+        ref t => t.to_string(),
+    }
+}
@@ -0,0 +1,220 @@
+use std;
+use lexer::Reader;
+use lexer::Span;
+use source_map::FileId;
+use parser::ast::*;
+use parser::trivia;
+use parser::visit;
+use parser::visit::Visitor;
+use parser::{Parser, ParserOptions, Result};
+
+/// Replacement of the `start..end` byte range of a source by `text`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEdit {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn apply(&self, src: &str) -> String {
+        let mut new_src = String::with_capacity(src.len() - (self.end - self.start) +
+                                                self.text.len());
+        new_src.push_str(&src[..self.start]);
+        new_src.push_str(&self.text);
+        new_src.push_str(&src[self.end..]);
+
+        new_src
+    }
+}
+
+/// Parses the source resulting from applying `edit` to `src`, given `ast`, the result of parsing
+/// `src`. Top-level statements which aren't affected by the edit are reused: the ones before it
+/// as is, and the ones after it with their spans shifted. Only the part of the source between
+/// them is lexed and parsed again, so nodes produced for it get ids which don't clash with the
+/// reused ones. Comments are attached again to the resulting tree.
+///
+/// The comments between two statements go along with the second one, which holds their doc, so a
+/// statement is parsed again when the edit touches its leading comments. Lexing starts again at
+/// the start of the comment containing the edit if any. A statement ending right where the edit
+/// starts, or whose leading comments start on the row where the edit ends or on the next one, is
+/// parsed again too, since the edit may extend it, move its columns or document it.
+pub fn reparse(ast: &Ast,
+               src: &str,
+               edit: &TextEdit,
+               file: FileId,
+               options: ParserOptions)
+               -> Result<Ast> {
+    let new_src = edit.apply(src);
+    let old_comments = trivia::comments(ast);
+    let leading = |i: usize| leading_start(&ast.statements, &old_comments, i);
+
+    let edit_end_row = count_rows(&src[..edit.end]) + 1;
+    let after = (0..ast.statements.len())
+        .find(|&i| {
+            let (start, row) = leading(i);
+            start > edit.end && row > edit_end_row + 1
+        })
+        .unwrap_or(ast.statements.len());
+    let mut before = ast.statements[..after]
+        .iter()
+        .take_while(|s| s.span().end < edit.start)
+        .count();
+
+    // Offset at which lexing starts again: the leading comments of the first statement parsed
+    // again, the comment containing the start of the edit, or the edit, whichever comes first.
+    // It is at the start of a row or right after the statement before, so that whether a doc
+    // comment is alone on its row is known.
+    let mut offset = edit.start;
+    if let Some(comment) = old_comments.iter()
+        .find(|comment| comment.span.start < offset && offset <= comment.span.end) {
+        offset = comment.span.start;
+    }
+    loop {
+        if before < after {
+            offset = std::cmp::min(offset, leading(before).0);
+        }
+        let row = count_rows(&src[..offset]) + 1;
+        match before {
+            0 => break,
+            n if ast.statements[n - 1].span().erow == row &&
+                 ast.statements[n - 1].span().end < offset => before -= 1,
+            _ => break,
+        }
+    }
+
+    let (row, col) = match before {
+        0 => advance(1, 1, &src[..offset]),
+        n => {
+            let sp = ast.statements[n - 1].span();
+            advance(sp.erow, sp.ecol, &src[sp.end..offset])
+        }
+    };
+
+    let byte_delta = edit.text.len() as isize - (edit.end - edit.start) as isize;
+    let row_delta = count_rows(&edit.text) - count_rows(&src[edit.start..edit.end]);
+    let old_end = if after < ast.statements.len() {
+        leading(after).0
+    } else {
+        src.len()
+    };
+    let end = (old_end as isize + byte_delta) as usize;
+    let shift = |span: &mut Span| {
+        span.srow += row_delta;
+        span.erow += row_delta;
+        span.start = (span.start as isize + byte_delta) as usize;
+        span.end = (span.end as isize + byte_delta) as usize;
+    };
+
+    let mut reader = if offset == 0 {
+        Reader::new(&new_src[..end], file)
+    } else {
+        Reader::new_at(&new_src[..end], file, offset, row, col)
+    };
+    let mut parser = Parser::new(&mut reader, options);
+    parser.next_node_id = max_node_id(ast).0 + 1;
+    let reparsed = try!(parser.parse()).clone();
+
+    let mut statements = std::vec::Vec::with_capacity(before + reparsed.statements.len() +
+                                                      ast.statements.len() - after);
+    statements.extend(ast.statements[..before].iter().cloned());
+    statements.extend(reparsed.statements.iter().cloned());
+    for statement in &ast.statements[after..] {
+        let mut statement = statement.clone();
+        statement.map_spans(&shift);
+        statements.push(statement);
+    }
+
+    // Comments of the part parsed again replace the former ones
+    let mut comments: std::vec::Vec<Comment> = old_comments.iter()
+        .filter(|comment| comment.span.end <= offset)
+        .cloned()
+        .collect();
+    comments.extend(trivia::comments(&reparsed));
+    for comment in old_comments.iter().filter(|comment| comment.span.start >= old_end) {
+        let mut comment = comment.clone();
+        shift(&mut comment.span);
+        comments.push(comment);
+    }
+
+    let mut new_ast = Ast { statements: statements, ..Ast::new() };
+    trivia::attach(&mut new_ast, comments);
+
+    Ok(new_ast)
+}
+
+/// Offset and row where the comments leading the statement at `index` start, or the statement
+/// itself if there are none
+fn leading_start(statements: &[Statement], comments: &[Comment], index: usize) -> (usize, i32) {
+    let sp = statements[index].span();
+    let (prev_end, prev_row) = match index {
+        0 => (0, 0),
+        _ => (statements[index - 1].span().end, statements[index - 1].span().erow),
+    };
+
+    // Comments on the row the statement before ends on trail it
+    match comments.iter().find(|comment| {
+        comment.span.start >= prev_end && comment.span.srow > prev_row &&
+        comment.span.end <= sp.start
+    }) {
+        Some(comment) => (comment.span.start, comment.span.srow),
+        None => (sp.start, sp.srow),
+    }
+}
+
+fn count_rows(text: &str) -> i32 {
+    text.matches('\n').count() as i32
+}
+
+/// Position reached after `text`, starting from `row` and `col`
+fn advance(row: i32, col: i32, text: &str) -> (i32, i32) {
+    text.chars().fold((row, col), |(row, col), c| if c == '\n' {
+        (row + 1, 1)
+    } else {
+        (row, col + 1)
+    })
+}
+
+fn max_node_id(ast: &Ast) -> NodeId {
+    struct MaxNodeId {
+        max: NodeId,
+    }
+
+    impl MaxNodeId {
+        fn node(&mut self, id: NodeId) {
+            self.max = std::cmp::max(self.max, id);
+        }
+    }
+
+    impl<'a> Visitor<'a> for MaxNodeId {
+        fn visit_statement(&mut self, statement: &'a Statement) {
+            self.node(statement.id());
+            visit::walk_statement(self, statement);
+        }
+
+        fn visit_func_decl_param(&mut self, param: &'a FuncDeclParamData) {
+            self.node(param.id);
+            visit::walk_func_decl_param(self, param);
+        }
+
+        fn visit_struct_field(&mut self, field: &'a StructFieldData) {
+            self.node(field.id);
+            visit::walk_struct_field(self, field);
+        }
+
+        fn visit_block_statement(&mut self, block_statement: &'a BlockStatement) {
+            self.node(block_statement.id());
+            visit::walk_block_statement(self, block_statement);
+        }
+
+        fn visit_expression(&mut self, expression: &'a Expression) {
+            self.node(expression.id);
+            visit::walk_expression(self, expression);
+        }
+    }
+
+    let mut visitor = MaxNodeId { max: NodeId::nil_id() };
+    visit::walk_ast(&mut visitor, ast);
+
+    visitor.max
+}
@@ -1,13 +1,31 @@
 pub mod ast;
+pub mod visit;
+pub mod parents;
+pub mod cse;
+pub mod callgraph;
+pub mod print;
+pub mod incremental;
+pub mod trivia;
+pub mod suggest;
+
+#[cfg(test)]
+mod tests;
+#[cfg(test)]
+mod arbitrary;
+#[cfg(test)]
+mod dump;
 
 use std;
 use lexer::SToken;
 use lexer::Token;
+use lexer::TokenKind;
 use lexer::Span;
 use lexer::Reader;
+use lexer::TokenStream;
 use lexer::Keyword;
 use lexer::Symbol;
 use lexer;
+use source_map::FileId;
 use self::ast::*;
 use std::error::Error as BaseError;
 use std::fmt::Display;
@@ -17,14 +35,104 @@ use std::fmt;
 pub struct Error {
     pub kind: ErrorKind,
     pub span: Span,
+    /// Constructs the error occurred in, innermost first
+    pub context: std::vec::Vec<Context>,
+}
+
+/// Construct being parsed, noted along with the errors occurring in it
+#[derive(Debug, Clone, PartialEq)]
+pub enum Construct {
+    FuncDecl(String),
+    StructDecl(String),
+    IfCondition,
+    WhileCondition,
+    GuardCondition,
+    CallArgs,
 }
 
+#[derive(Debug, Clone)]
+pub struct Context {
+    pub construct: Construct,
+    /// Span of the name of the declaration, or of the keyword or callee starting the construct
+    pub span: Span,
+}
+
+impl Context {
+    pub fn message(&self) -> String {
+        match self.construct {
+            Construct::FuncDecl(ref name) => format!("while parsing func `{}`", name),
+            Construct::StructDecl(ref name) => format!("while parsing struct `{}`", name),
+            Construct::IfCondition => "while parsing the condition of this if".to_string(),
+            Construct::WhileCondition => "while parsing the condition of this while".to_string(),
+            Construct::GuardCondition => "while parsing the condition of this guard".to_string(),
+            Construct::CallArgs => "while parsing the arguments of this call".to_string(),
+        }
+    }
+}
+
+/// Number of innermost constructs noted in errors
+const MAX_CONTEXT: usize = 2;
+
 #[derive(Debug, Clone)]
 pub enum ErrorKind {
     Unknown,
     Lexer(lexer::Error),
     UnexpectedToken(Token),
-    ExpectedGotToken(Token, Token),
+    /// Token found where only the listed tokens were accepted
+    ExpectedGotToken(std::vec::Vec<Expected>, Token),
+    TooDeeplyNested,
+    InvalidMapKeyType(Type),
+    InvalidSetItemType(Type),
+    DuplicateMapKey(String),
+    /// Identifier where a statement is expected, which is close to the keyword
+    MisspelledKeyword(String, String),
+    /// Else block of a guard which doesn't end with a return
+    GuardWithoutReturn,
+    /// Experimental syntax which isn't enabled in the options
+    FeatureDisabled(Feature),
+}
+
+impl Error {
+    fn in_context(mut self, construct: &Construct, span: &Span) -> Self {
+        if self.context.len() < MAX_CONTEXT {
+            self.context.push(Context {
+                construct: construct.clone(),
+                span: span.clone(),
+            });
+        }
+
+        self
+    }
+
+    /// Description of the error, without its position
+    pub fn message(&self) -> String {
+        match self.kind {
+            ErrorKind::Lexer(ref e) => e.message(),
+            ErrorKind::Unknown |
+            ErrorKind::GuardWithoutReturn |
+            ErrorKind::TooDeeplyNested => self.description().to_string(),
+            ErrorKind::UnexpectedToken(ref t) => format!("unexpected {}", t.display()),
+            ErrorKind::ExpectedGotToken(ref e, ref g) => {
+                let expected: std::vec::Vec<String> = e.iter().map(Expected::display).collect();
+                if expected.len() == 1 {
+                    format!("expected {}, got {}", expected[0], g.display())
+                } else {
+                    format!("expected one of {}, got {}", expected.join(", "), g.display())
+                }
+            }
+            ErrorKind::DuplicateMapKey(ref key) => format!("duplicate map key {}", key),
+            ErrorKind::MisspelledKeyword(ref ident, ref keyword) => {
+                format!("unexpected identifier {} (did you mean {}?)", ident, keyword)
+            }
+            ErrorKind::InvalidMapKeyType(ref t) => {
+                format!("invalid map key type {} (expected int, string, char or bool)", t)
+            }
+            ErrorKind::InvalidSetItemType(ref t) => {
+                format!("invalid set item type {} (expected int, string, char or bool)", t)
+            }
+            ErrorKind::FeatureDisabled(feature) => format!("syntax disabled: {}", feature.name()),
+        }
+    }
 }
 
 impl Display for Error {
@@ -32,17 +140,7 @@ impl Display for Error {
         if let ErrorKind::Lexer(ref e) = self.kind {
             e.fmt(f)
         } else {
-            write!(f,
-                   "{}: {}",
-                   self.span,
-                   match self.kind {
-                       ErrorKind::Unknown => self.description().to_string(),
-                       ErrorKind::UnexpectedToken(ref t) => format!("unexpected token {:?}", t),
-                       ErrorKind::ExpectedGotToken(ref e, ref g) => {
-                           format!("expected {:?}, got {:?}", e, g)
-                       }
-                       _ => self.description().to_string(),
-                   })
+            write!(f, "{}: {}", self.span, self.message())
         }
     }
 }
@@ -54,6 +152,13 @@ impl BaseError for Error {
             ErrorKind::Lexer(ref e) => e.description(),
             ErrorKind::UnexpectedToken(_) => "unexpected token",
             ErrorKind::ExpectedGotToken(_, _) => "expected a token, got another",
+            ErrorKind::TooDeeplyNested => "program too deeply nested",
+            ErrorKind::InvalidMapKeyType(_) => "invalid map key type",
+            ErrorKind::InvalidSetItemType(_) => "invalid set item type",
+            ErrorKind::DuplicateMapKey(_) => "duplicate map key",
+            ErrorKind::MisspelledKeyword(_, _) => "misspelled keyword",
+            ErrorKind::GuardWithoutReturn => "the else block of a guard must end with a return",
+            ErrorKind::FeatureDisabled(_) => "syntax disabled",
         }
     }
 
@@ -64,70 +169,329 @@ impl BaseError for Error {
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Token, or kind of token, the parser would have accepted at some position
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expected {
+    Token(Token),
+    Kind(TokenKind),
+}
+
+impl Expected {
+    pub fn display(&self) -> String {
+        match *self {
+            Expected::Token(ref t) => t.display(),
+            Expected::Kind(k) => k.display().to_string(),
+        }
+    }
+}
+
+/// Syntax which tools can turn off in `ParserOptions`, e.g. to accept only the programs an older
+/// version of the language could parse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Feature {
+    /// `...array` in array literals and call arguments
+    Spread,
+    /// `guard cond else { return }`
+    Guard,
+    /// `var (a, b) = pair` and `var Point { x, y } = p`
+    Destructuring,
+    /// `{1, 2}` literals and the `set[T]` type
+    Sets,
+    /// `Point { x: 1 }`, without `new`
+    BareStructInit,
+}
+
+impl Feature {
+    pub fn all() -> std::collections::HashSet<Feature> {
+        [Feature::Spread,
+         Feature::Guard,
+         Feature::Destructuring,
+         Feature::Sets,
+         Feature::BareStructInit]
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Name of the feature in error messages
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Feature::Spread => "spread",
+            Feature::Guard => "guard",
+            Feature::Destructuring => "destructuring",
+            Feature::Sets => "set literals and types",
+            Feature::BareStructInit => "struct init without `new`",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ParserOptions {
+    /// Maximum nesting depth of expressions and blocks.
+    pub recursion_limit: usize,
+    /// Number of errors after which parsing is aborted. With a value greater than 1, the parser
+    /// skips to the next top-level declaration after an error and keeps going.
+    pub max_errors: usize,
+    /// Whether a newline ends the expression being parsed, in which case an operator at the start
+    /// of the next line does not continue it.
+    pub newlines_terminate_expressions: bool,
+    /// Syntax which is accepted, every feature by default.
+    pub features: std::collections::HashSet<Feature>,
+}
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        ParserOptions {
+            recursion_limit: 128,
+            max_errors: 1,
+            newlines_terminate_expressions: true,
+            features: Feature::all(),
+        }
+    }
+}
+
+/// Parses `src` as a single expression, e.g. `a + b * 2`.
+pub fn parse_expr(src: &str, file: FileId) -> Result<Expression> {
+    let mut reader = Reader::new(src, file);
+    let mut parser = Parser::new(&mut reader, ParserOptions::default());
+    parser.parse_fragment(|p| p.parse_expression())
+}
+
+/// Parses `src` as a type, e.g. `[string][]int`.
+pub fn parse_type(src: &str, file: FileId) -> Result<Type> {
+    let mut reader = Reader::new(src, file);
+    let mut parser = Parser::new(&mut reader, ParserOptions::default());
+    parser.parse_fragment(|p| p.parse_type())
+}
+
+/// Parses `src` as a single block statement, as found in a function body.
+/// `return_type` is the return type of the (hypothetical) enclosing function,
+/// which decides whether a `return` takes a value.
+pub fn parse_block_statement(src: &str,
+                             file: FileId,
+                             return_type: Type)
+                             -> Result<BlockStatement> {
+    let mut reader = Reader::new(src, file);
+    let mut parser = Parser::new(&mut reader, ParserOptions::default());
+    parser.parse_fragment(|p| p.parse_block_statement(return_type))
+}
+
+/// How an operator combines with its operand(s)
+#[derive(Debug, Clone)]
+enum Operator {
+    Prefix(UnaryOp),
+    Infix(BinaryOp, Associativity),
+    /// Follows its operand, along with the rest of the expression (arguments, field, index)
+    Postfix(PostfixOp),
+}
+
+/// Which way a chain of infix operators of the same precedence groups, e.g. whether `a - b - c`
+/// means `(a - b) - c` (left) or `a - (b - c)` (right)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Associativity {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum PostfixOp {
+    Call,
+    Field,
+    Index,
+}
+
+/// Every operator of expressions, by the token it starts with, with its precedence: the higher, the
+/// tighter it binds. A token can start both a prefix operator and an infix or postfix one.
+const OPERATORS: [(Token, Operator, u8); 24] = [
+    (Token::Symbol(Symbol::PipePipe), Operator::Infix(BinaryOp::Or, Associativity::Left), 1),
+    (Token::Symbol(Symbol::AmpAmp), Operator::Infix(BinaryOp::And, Associativity::Left), 2),
+    (Token::Symbol(Symbol::EqualEqual),
+     Operator::Infix(BinaryOp::Equality, Associativity::Left),
+     3),
+    (Token::Symbol(Symbol::NotEqual),
+     Operator::Infix(BinaryOp::Inequality, Associativity::Left),
+     3),
+    (Token::Symbol(Symbol::Less), Operator::Infix(BinaryOp::Less, Associativity::Left), 4),
+    (Token::Symbol(Symbol::LessOrEqual),
+     Operator::Infix(BinaryOp::LessOrEqual, Associativity::Left),
+     4),
+    (Token::Symbol(Symbol::More), Operator::Infix(BinaryOp::More, Associativity::Left), 4),
+    (Token::Symbol(Symbol::MoreOrEqual),
+     Operator::Infix(BinaryOp::MoreOrEqual, Associativity::Left),
+     4),
+    (Token::Symbol(Symbol::Plus), Operator::Infix(BinaryOp::Addition, Associativity::Left), 5),
+    (Token::Symbol(Symbol::Minus), Operator::Infix(BinaryOp::Substraction, Associativity::Left), 5),
+    (Token::Symbol(Symbol::Concat),
+     Operator::Infix(BinaryOp::Concatenation, Associativity::Left),
+     5),
+    (Token::Symbol(Symbol::Star),
+     Operator::Infix(BinaryOp::Multiplication, Associativity::Left),
+     6),
+    (Token::Symbol(Symbol::Over), Operator::Infix(BinaryOp::Division, Associativity::Left), 6),
+    (Token::Symbol(Symbol::Modulo), Operator::Infix(BinaryOp::Modulo, Associativity::Left), 6),
+    (Token::Symbol(Symbol::Hash), Operator::Prefix(UnaryOp::Count), 7),
+    (Token::Symbol(Symbol::Minus), Operator::Prefix(UnaryOp::Negation), 7),
+    (Token::Symbol(Symbol::Bang), Operator::Prefix(UnaryOp::Not), 7),
+    (Token::Symbol(Symbol::Amp), Operator::Prefix(UnaryOp::Reference), 7),
+    (Token::Symbol(Symbol::At), Operator::Prefix(UnaryOp::MutReference), 7),
+    (Token::Symbol(Symbol::Star), Operator::Prefix(UnaryOp::Dereference), 7),
+    (Token::Keyword(Keyword::Await), Operator::Prefix(UnaryOp::Await), 7),
+    (Token::Symbol(Symbol::LeftParenthesis), Operator::Postfix(PostfixOp::Call), 8),
+    (Token::Symbol(Symbol::Dot), Operator::Postfix(PostfixOp::Field), 8),
+    (Token::Symbol(Symbol::LeftBracket), Operator::Postfix(PostfixOp::Index), 8),
+];
+
 pub struct Parser<'a> {
-    reader: &'a mut Reader<'a>,
+    reader: &'a mut (TokenStream + 'a),
+    options: ParserOptions,
     ast: Box<Ast>,
+    errors: std::vec::Vec<Error>,
+    depth: usize,
+    next_node_id: u32,
     last_sp: Span,
     current_token: SToken,
+    /// Tokens and kinds of tokens tried against `current_token`, reported if none matches
+    expected: std::vec::Vec<Expected>,
+    /// Tokens read past `current_token` by `peek()`, in order
+    lookahead: std::collections::VecDeque<lexer::Result<SToken>>,
+    /// Comments read so far, attached to the tree once it is parsed
+    comments: std::vec::Vec<Comment>,
+    /// Doc comments read so far, with the text following `///`
+    docs: std::vec::Vec<Comment>,
     just_skept_newline: bool,
 }
 
 impl<'a> Parser<'a> {
-    pub fn new<'b>(reader: &'b mut Reader<'b>) -> Parser<'b> {
+    pub fn new<'b>(reader: &'b mut (TokenStream + 'b), options: ParserOptions) -> Parser<'b> {
         Parser {
             reader: reader,
+            options: options,
             ast: Box::new(Ast::new()),
+            errors: vec![],
+            depth: 0,
+            next_node_id: 1,
             last_sp: Span::nil_span(),
             current_token: SToken {
                 tok: Token::EOF,
                 sp: Span::nil_span(),
             },
+            expected: vec![],
+            lookahead: std::collections::VecDeque::new(),
+            comments: vec![],
+            docs: vec![],
             just_skept_newline: false,
         }
     }
 
     pub fn parse(&mut self) -> Result<&Ast> {
         try!(self.next_token());
+        try!(self.skip_newlines());
         while self.current_token.tok != Token::EOF {
-            let statement = try!(self.parse_statement());
-            self.ast.statements.push(statement);
-        }
-
-        Ok(&self.ast)
-    }
-
-    fn binop_for_token(stoken: SToken) -> Option<BinaryOp> {
-        match stoken.tok.clone() {
-            Token::Symbol(s) => {
-                let binop = match s {
-                    Symbol::Plus => BinaryOp::Addition,
-                    Symbol::Minus => BinaryOp::Substraction,
-                    Symbol::Star => BinaryOp::Multiplication,
-                    Symbol::Over => BinaryOp::Division,
-                    Symbol::Modulo => BinaryOp::Modulo,
-                    Symbol::Concat => BinaryOp::Concatenation,
-                    Symbol::EqualEqual => BinaryOp::Equality,
-                    Symbol::NotEqual => BinaryOp::Inequality,
-                    _ => return None,
-                };
-
-                Some(binop)
+            match self.parse_statement() {
+                Ok(statement) => self.ast.statements.push(statement),
+                Err(e) => {
+                    let is_lexer_error = match e.kind {
+                        ErrorKind::Lexer(_) => true,
+                        _ => false,
+                    };
+
+                    self.errors.push(e);
+                    if is_lexer_error || self.errors.len() >= self.options.max_errors {
+                        break;
+                    }
+
+                    try!(self.recover());
+                }
             }
-            _ => None,
+        }
+
+        let comments = std::mem::replace(&mut self.comments, vec![]);
+        trivia::attach(&mut self.ast, comments);
+
+        match self.errors.first() {
+            Some(e) => Err(e.clone()),
+            None => Ok(&self.ast),
+        }
+    }
+
+    /// All the errors encountered by the last call to `parse()`, the first of which is the one
+    /// it returned.
+    pub fn errors(&self) -> &[Error] {
+        &self.errors
+    }
+
+    fn recover(&mut self) -> Result<()> {
+        loop {
+            match self.current_token.tok {
+                Token::EOF |
+                Token::Keyword(Keyword::Import) |
+                Token::Keyword(Keyword::Package) |
+                Token::Keyword(Keyword::Func) |
+                Token::Keyword(Keyword::Async) |
+                Token::Keyword(Keyword::Extern) |
+                Token::Keyword(Keyword::Struct) => return Ok(()),
+                _ => try!(self.next_token()),
+            };
         }
     }
 
+    fn parse_fragment<T, F>(&mut self, parse_fn: F) -> Result<T>
+        where F: FnOnce(&mut Self) -> Result<T>
+    {
+        try!(self.next_token());
+        try!(self.skip_newlines());
+        self.just_skept_newline = false;
+
+        let fragment = try!(parse_fn(self));
+
+        try!(self.skip_newlines());
+        try!(self.expect(Token::EOF));
+
+        Ok(fragment)
+    }
+
+    /// Prefix operator starting with `token`, along with its precedence
+    fn prefix_operator(token: &Token) -> Option<(UnaryOp, u8)> {
+        OPERATORS.iter()
+            .filter_map(|&(ref t, ref operator, precedence)| match *operator {
+                Operator::Prefix(ref unop) if token == t => Some((unop.clone(), precedence)),
+                _ => None,
+            })
+            .next()
+    }
+
+    /// Infix or postfix operator starting with `token`, along with its precedence
+    fn infix_operator(token: &Token) -> Option<(Operator, u8)> {
+        OPERATORS.iter()
+            .filter_map(|&(ref t, ref operator, precedence)| match *operator {
+                Operator::Prefix(_) => None,
+                _ if token == t => Some((operator.clone(), precedence)),
+                _ => None,
+            })
+            .next()
+    }
+
     fn precedence_for_op(op: Op) -> u8 {
-        match op {
-            Op::Unary(_) => std::u8::MAX,
-            Op::Binary(binop) => {
-                match binop {
-                    BinaryOp::Equality | BinaryOp::Inequality => 1,
-                    BinaryOp::Addition | BinaryOp::Substraction | BinaryOp::Concatenation => 2,
-                    BinaryOp::Multiplication | BinaryOp::Division | BinaryOp::Modulo => 3,
+        OPERATORS.iter()
+            .filter_map(|&(_, ref operator, precedence)| match (&op, operator) {
+                (&Op::Unary(ref unop), &Operator::Prefix(ref u)) if unop == u => Some(precedence),
+                (&Op::Binary(ref binop), &Operator::Infix(ref b, _)) if binop == b => {
+                    Some(precedence)
                 }
-            }
-        }
+                _ => None,
+            })
+            .next()
+            .unwrap_or(0)
+    }
+
+    fn associativity_for_op(binop: &BinaryOp) -> Associativity {
+        OPERATORS.iter()
+            .filter_map(|&(_, ref operator, _)| match *operator {
+                Operator::Infix(ref b, associativity) if b == binop => Some(associativity),
+                _ => None,
+            })
+            .next()
+            .unwrap_or(Associativity::Left)
     }
 
     fn parse_statement(&mut self) -> Result<Statement> {
@@ -136,40 +500,56 @@ impl<'a> Parser<'a> {
         } else if let Some(t) = try!(self.accept(Token::Keyword(Keyword::Package))) {
             Ok(Statement::Package(try!(self.parse_package(t.sp))))
         } else if let Some(t) = try!(self.accept(Token::Keyword(Keyword::Func))) {
-            Ok(Statement::FuncDecl(try!(self.parse_func_decl(t.sp))))
+            Ok(Statement::FuncDecl(try!(self.parse_func_decl(t.sp, false))))
+        } else if let Some(t) = try!(self.accept(Token::Keyword(Keyword::Async))) {
+            try!(self.expect(Token::Keyword(Keyword::Func)));
+            Ok(Statement::FuncDecl(try!(self.parse_func_decl(t.sp, true))))
+        } else if let Some(t) = try!(self.accept(Token::Keyword(Keyword::Extern))) {
+            Ok(Statement::FuncDecl(try!(self.parse_extern_func_decl(t.sp))))
         } else if let Some(t) = try!(self.accept(Token::Keyword(Keyword::Struct))) {
             Ok(Statement::StructDecl(try!(self.parse_struct_decl(t.sp))))
+        } else if let Some((ident, keyword)) = self.misspelled_statement_keyword() {
+            Err(Error {
+                kind: ErrorKind::MisspelledKeyword(ident, keyword),
+                span: self.current_token.sp.clone(),
+                context: vec![],
+            })
         } else {
             Err(Error {
                 kind: ErrorKind::UnexpectedToken(self.current_token.tok.clone()),
                 span: self.current_token.sp.clone(),
+                context: vec![],
             })
         }
     }
 
-    fn parse_package(&mut self, start_sp: Span) -> Result<Box<PackageData>> {
-        let name_token = try!(self.expect_any(Token::Identifier("".to_string())));
-        match name_token.tok {
-            Token::Identifier(n) => {
-                Ok(Box::new(PackageData {
-                    span: Span::concat(start_sp, name_token.sp),
-                    name: n,
-                }))
+    /// Identifier found instead of a statement and the keyword it is likely a typo of
+    fn misspelled_statement_keyword(&self) -> Option<(String, String)> {
+        match self.current_token.tok {
+            Token::Identifier(ref ident) => {
+                let keywords = vec!["import", "package", "func", "async", "extern", "struct"];
+                suggest::closest(ident, keywords).map(|keyword| (ident.clone(), keyword))
             }
-            _ => {
-                Err(Error {
-                    kind: ErrorKind::Unknown,
-                    span: name_token.sp,
-                })
-            } // Should never happen
+            _ => None,
         }
     }
 
+    fn parse_package(&mut self, start_sp: Span) -> Result<Box<PackageData>> {
+        let (name, name_sp) = try!(self.expect_identifier());
+
+        Ok(Box::new(PackageData {
+            id: self.new_node_id(),
+            span: Span::concat(start_sp, name_sp),
+            name: name,
+        }))
+    }
+
     fn parse_import(&mut self, start_sp: Span) -> Result<Box<ImportData>> {
-        let path_token = try!(self.expect_any(Token::StringLiteral("".to_string())));
+        let path_token = try!(self.expect_kind(TokenKind::StringLiteral));
         match path_token.tok {
             Token::StringLiteral(p) => {
                 Ok(Box::new(ImportData {
+                    id: self.new_node_id(),
                     span: Span::concat(start_sp, path_token.sp),
                     path: p,
                 }))
@@ -178,50 +558,85 @@ impl<'a> Parser<'a> {
                 Err(Error {
                     kind: ErrorKind::Unknown,
                     span: path_token.sp,
+                    context: vec![],
                 })
             } // Should never happen
         }
     }
 
-    fn parse_func_decl(&mut self, start_sp: Span) -> Result<Box<FuncDeclData>> {
-        let name_token = try!(self.expect_any(Token::Identifier("".to_string())));
-        let name = match name_token.tok {
-            Token::Identifier(s) => s,
-            _ => {
-                return Err(Error {
-                    kind: ErrorKind::Unknown,
-                    span: name_token.sp,
-                })
-            } // Should never happen
+    fn parse_func_decl(&mut self, start_sp: Span, is_async: bool) -> Result<Box<FuncDeclData>> {
+        let doc = self.doc_before(&start_sp);
+        let (name, name_sp) = try!(self.expect_identifier());
+        let construct = Construct::FuncDecl(name.clone());
+
+        let (params, return_type) = try!(self.parse_func_signature()
+            .map_err(|e| e.in_context(&construct, &name_sp)));
+        let statements = try!(self.parse_func_body(&return_type)
+            .map_err(|e| e.in_context(&construct, &name_sp)));
+
+        Ok(Box::new(FuncDeclData {
+            id: self.new_node_id(),
+            span: Span::concat(start_sp, self.last_sp.clone()),
+            name: name,
+            return_type: return_type,
+            parameters: params,
+            statements: statements,
+            is_async: is_async,
+            is_extern: false,
+            library: None,
+            doc: doc,
+        }))
+    }
+
+    /// `extern ["library"] func name(params) -> type`, without a body
+    fn parse_extern_func_decl(&mut self, start_sp: Span) -> Result<Box<FuncDeclData>> {
+        let doc = self.doc_before(&start_sp);
+        let library = match try!(self.accept_kind(TokenKind::StringLiteral)) {
+            Some(SToken { tok: Token::StringLiteral(library), .. }) => Some(library),
+            _ => None,
         };
 
+        try!(self.expect(Token::Keyword(Keyword::Func)));
+        let (name, name_sp) = try!(self.expect_identifier());
+        let (params, return_type) = try!(self.parse_func_signature()
+            .map_err(|e| e.in_context(&Construct::FuncDecl(name.clone()), &name_sp)));
+
+        Ok(Box::new(FuncDeclData {
+            id: self.new_node_id(),
+            span: Span::concat(start_sp, self.last_sp.clone()),
+            name: name,
+            return_type: return_type,
+            parameters: params,
+            statements: vec![],
+            is_async: false,
+            is_extern: true,
+            library: library,
+            doc: doc,
+        }))
+    }
+
+    /// `(params) -> type`, following the name of a func
+    fn parse_func_signature(&mut self)
+                            -> Result<(std::vec::Vec<Box<FuncDeclParamData>>, Type)> {
         try!(self.expect(Token::Symbol(Symbol::LeftParenthesis)));
 
         let mut params: std::vec::Vec<Box<FuncDeclParamData>> = vec![];
         while try!(self.accept(Token::Symbol(Symbol::RightParenthesis))).is_none() {
-            let arg_name_token = try!(self.expect_any(Token::Identifier("".to_string())));
-            let arg_name = match arg_name_token.tok {
-                Token::Identifier(s) => s,
-                _ => {
-                    return Err(Error {
-                        kind: ErrorKind::Unknown,
-                        span: arg_name_token.sp,
-                    })
-                } // Should never happen
-            };
+            let (arg_name, arg_name_sp) = try!(self.expect_identifier());
 
             try!(self.expect(Token::Symbol(Symbol::Colon)));
 
             let arg_type = try!(self.parse_type());
 
             params.push(Box::new(FuncDeclParamData {
-                span: Span::concat(arg_name_token.sp, self.last_sp.clone()),
+                id: self.new_node_id(),
+                span: Span::concat(arg_name_sp, self.last_sp.clone()),
                 name: arg_name,
                 param_type: arg_type,
                 default_value: None,
             }));
 
-            if self.current_token.tok == Token::Symbol(Symbol::RightParenthesis) {
+            if self.check(&Token::Symbol(Symbol::RightParenthesis)) {
                 try!(self.accept(Token::Symbol(Symbol::Comma)));
             } else {
                 try!(self.expect(Token::Symbol(Symbol::Comma)));
@@ -234,6 +649,10 @@ impl<'a> Parser<'a> {
             Type::None
         };
 
+        Ok((params, return_type))
+    }
+
+    fn parse_func_body(&mut self, return_type: &Type) -> Result<std::vec::Vec<BlockStatement>> {
         try!(self.expect(Token::Symbol(Symbol::LeftBrace)));
 
         let mut statements: std::vec::Vec<BlockStatement> = vec![];
@@ -241,72 +660,71 @@ impl<'a> Parser<'a> {
             statements.push(try!(self.parse_block_statement(return_type.clone())));
         }
 
-        Ok(Box::new(FuncDeclData {
+        Ok(statements)
+    }
+
+    fn parse_struct_decl(&mut self, start_sp: Span) -> Result<Box<StructDeclData>> {
+        let doc = self.doc_before(&start_sp);
+        let (name, name_sp) = try!(self.expect_identifier());
+        let fields = try!(self.parse_struct_fields()
+            .map_err(|e| e.in_context(&Construct::StructDecl(name.clone()), &name_sp)));
+
+        Ok(Box::new(StructDeclData {
+            id: self.new_node_id(),
             span: Span::concat(start_sp, self.last_sp.clone()),
             name: name,
-            return_type: return_type,
-            parameters: params,
-            statements: statements,
+            fields: fields,
+            doc: doc,
         }))
     }
 
-    fn parse_struct_decl(&mut self, start_sp: Span) -> Result<Box<StructDeclData>> {
-        let name_token = try!(self.expect_any(Token::Identifier("".to_string())));
-        let name = match name_token.tok {
-            Token::Identifier(s) => s,
-            _ => {
-                return Err(Error {
-                    kind: ErrorKind::Unknown,
-                    span: name_token.sp,
-                })
-            } // Should never happen
-        };
-
+    fn parse_struct_fields(&mut self) -> Result<std::vec::Vec<Box<StructFieldData>>> {
         try!(self.expect(Token::Symbol(Symbol::LeftBrace)));
 
         let mut fields: std::vec::Vec<Box<StructFieldData>> = vec![];
         while try!(self.accept(Token::Symbol(Symbol::RightBrace))).is_none() {
-            let field_name_token = try!(self.expect_any(Token::Identifier("".to_string())));
-            let field_name = match field_name_token.tok {
-                Token::Identifier(s) => s,
-                _ => {
-                    return Err(Error {
-                        kind: ErrorKind::Unknown,
-                        span: field_name_token.sp,
-                    })
-                } // Should never happen
-            };
+            let (field_name, field_name_sp) = try!(self.expect_field_name());
+            let doc = self.doc_before(&field_name_sp);
 
             try!(self.expect(Token::Symbol(Symbol::Colon)));
 
             let field_type = try!(self.parse_type());
 
             fields.push(Box::new(StructFieldData {
-                span: Span::concat(field_name_token.sp, self.last_sp.clone()),
+                id: self.new_node_id(),
+                span: Span::concat(field_name_sp, self.last_sp.clone()),
                 name: field_name,
                 field_type: field_type,
                 default_value: None,
+                doc: doc,
             }));
 
-            if self.current_token.tok == Token::Symbol(Symbol::RightBrace) {
+            if self.check(&Token::Symbol(Symbol::RightBrace)) {
                 try!(self.accept(Token::Symbol(Symbol::Comma)));
             } else {
                 try!(self.expect(Token::Symbol(Symbol::Comma)));
             };
         }
 
-        Ok(Box::new(StructDeclData {
-            span: Span::concat(start_sp, self.last_sp.clone()),
-            name: name,
-            fields: fields,
-        }))
+        Ok(fields)
     }
 
     fn parse_block_statement(&mut self, return_type: Type) -> Result<BlockStatement> {
+        try!(self.enter_nested());
+        let block_statement = self.parse_block_statement_(return_type);
+        self.depth -= 1;
+
+        block_statement
+    }
+
+    fn parse_block_statement_(&mut self, return_type: Type) -> Result<BlockStatement> {
         if let Some(t) = try!(self.accept(Token::Keyword(Keyword::Var))) {
-            Ok(BlockStatement::VarDecl(try!(self.parse_var_decl(t.sp))))
+            self.parse_var_decl(t.sp)
         } else if let Some(t) = try!(self.accept(Token::Keyword(Keyword::If))) {
             Ok(BlockStatement::If(try!(self.parse_if(return_type, t.sp))))
+        } else if let Some(t) = try!(self.accept(Token::Keyword(Keyword::Guard))) {
+            try!(self.check_feature(Feature::Guard, &t.sp));
+            Ok(BlockStatement::If(try!(self.parse_guard(return_type, t.sp))))
         } else if let Some(t) = try!(self.accept(Token::Keyword(Keyword::While))) {
             Ok(BlockStatement::While(try!(self.parse_while(return_type, t.sp))))
         } else if let Some(t) = try!(self.accept(Token::Keyword(Keyword::For))) {
@@ -317,8 +735,14 @@ impl<'a> Parser<'a> {
             let expr = try!(self.parse_expression());
 
             if try!(self.accept(Token::Symbol(Symbol::Equal))).is_some() {
-                Ok(BlockStatement::VarAssignment(Box::new(expr),
-                                                 Box::new(try!(self.parse_expression()))))
+                let rhs = try!(self.parse_expression());
+
+                Ok(BlockStatement::VarAssignment(Box::new(VarAssignmentData {
+                    id: self.new_node_id(),
+                    span: Span::concat(expr.span.clone(), self.last_sp.clone()),
+                    lhs: expr,
+                    rhs: rhs,
+                })))
             } else {
                 Ok(BlockStatement::Expression(Box::new(expr)))
             }
@@ -326,16 +750,7 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_forin(&mut self, return_type: Type, start_sp: Span) -> Result<Box<ForInData>> {
-        let element_token = try!(self.expect_any(Token::Identifier("".to_string())));
-        let element_name = match element_token.tok {
-            Token::Identifier(s) => s,
-            _ => {
-                return Err(Error {
-                    kind: ErrorKind::Unknown,
-                    span: element_token.sp,
-                })
-            } // Should never happen
-        };
+        let (element_name, _) = try!(self.expect_identifier());
 
         try!(self.expect(Token::Keyword(Keyword::In)));
 
@@ -349,6 +764,7 @@ impl<'a> Parser<'a> {
         }
 
         Ok(Box::new(ForInData {
+            id: self.new_node_id(),
             span: Span::concat(start_sp, self.last_sp.clone()),
             element_name: element_name,
             collection: collection,
@@ -358,6 +774,7 @@ impl<'a> Parser<'a> {
 
     fn parse_return(&mut self, return_type: Type, start_sp: Span) -> Result<Box<ReturnData>> {
         Ok(Box::new(ReturnData {
+            id: self.new_node_id(),
             value: match return_type {
                 Type::None => None,
                 _ => Some(try!(self.parse_expression())),
@@ -368,7 +785,8 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_if(&mut self, return_type: Type, start_sp: Span) -> Result<Box<IfData>> {
-        let condition = try!(self.parse_expression());
+        let condition = try!(self.parse_expression()
+            .map_err(|e| e.in_context(&Construct::IfCondition, &start_sp)));
 
         try!(self.expect(Token::Symbol(Symbol::LeftBrace)));
 
@@ -394,6 +812,7 @@ impl<'a> Parser<'a> {
         };
 
         Ok(Box::new(IfData {
+            id: self.new_node_id(),
             span: Span::concat(start_sp, self.last_sp.clone()),
             condition: condition,
             if_statements: if_statements,
@@ -401,8 +820,44 @@ impl<'a> Parser<'a> {
         }))
     }
 
+    /// `guard cond else { ... return }`, which is an `if` with the else block only, run when the
+    /// condition doesn't hold
+    fn parse_guard(&mut self, return_type: Type, start_sp: Span) -> Result<Box<IfData>> {
+        let condition = try!(self.parse_expression()
+            .map_err(|e| e.in_context(&Construct::GuardCondition, &start_sp)));
+
+        try!(self.expect(Token::Keyword(Keyword::Else)));
+        try!(self.expect(Token::Symbol(Symbol::LeftBrace)));
+
+        let mut else_statements: std::vec::Vec<BlockStatement> = vec![];
+        while try!(self.accept(Token::Symbol(Symbol::RightBrace))).is_none() {
+            else_statements.push(try!(self.parse_block_statement(return_type.clone())));
+        }
+
+        let span = Span::concat(start_sp, self.last_sp.clone());
+        match else_statements.last() {
+            Some(&BlockStatement::Return(_)) => (),
+            _ => {
+                return Err(Error {
+                    kind: ErrorKind::GuardWithoutReturn,
+                    span: span,
+                    context: vec![],
+                })
+            }
+        }
+
+        Ok(Box::new(IfData {
+            id: self.new_node_id(),
+            span: span,
+            condition: condition,
+            if_statements: vec![],
+            else_statements: Some(else_statements),
+        }))
+    }
+
     fn parse_while(&mut self, return_type: Type, start_sp: Span) -> Result<Box<WhileData>> {
-        let condition = try!(self.parse_expression());
+        let condition = try!(self.parse_expression()
+            .map_err(|e| e.in_context(&Construct::WhileCondition, &start_sp)));
 
         try!(self.expect(Token::Symbol(Symbol::LeftBrace)));
 
@@ -412,23 +867,29 @@ impl<'a> Parser<'a> {
         }
 
         Ok(Box::new(WhileData {
+            id: self.new_node_id(),
             span: Span::concat(start_sp, self.last_sp.clone()),
             condition: condition,
             statements: statements,
         }))
     }
 
-    fn parse_var_decl(&mut self, start_sp: Span) -> Result<Box<VarDeclData>> {
-        let name_token = try!(self.expect_any(Token::Identifier("".to_string())));
-        let name = match name_token.tok {
-            Token::Identifier(i) => i,
-            _ => {
-                return Err(Error {
-                    kind: ErrorKind::Unknown,
-                    span: name_token.sp,
-                })
-            } // Should never happen
-        };
+    fn parse_var_decl(&mut self, start_sp: Span) -> Result<BlockStatement> {
+        if let Some(lp) = try!(self.accept(Token::Symbol(Symbol::LeftParenthesis))) {
+            try!(self.check_feature(Feature::Destructuring, &lp.sp));
+            let names = try!(self.parse_pattern_names(Symbol::RightParenthesis));
+            return self.parse_destructure(start_sp, Pattern::Array(names));
+        }
+
+        let (name, name_sp) = try!(self.expect_identifier());
+
+        if self.at_struct_pattern() {
+            try!(self.check_feature(Feature::Destructuring, &name_sp));
+            let path = try!(self.parse_path(Some((name, name_sp))));
+            try!(self.expect(Token::Symbol(Symbol::LeftBrace)));
+            let names = try!(self.parse_pattern_names(Symbol::RightBrace));
+            return self.parse_destructure(start_sp, Pattern::Struct(path, names));
+        }
 
         try!(self.expect(Token::Symbol(Symbol::Colon)));
 
@@ -440,97 +901,97 @@ impl<'a> Parser<'a> {
             None
         };
 
-        Ok(Box::new(VarDeclData {
+        Ok(BlockStatement::VarDecl(Box::new(VarDeclData {
+            id: self.new_node_id(),
             span: Span::concat(start_sp, self.last_sp.clone()),
             name: name,
             var_type: var_type,
             value: value,
-        }))
+        })))
     }
 
-    fn parse_path(&mut self, first_part: Option<SToken>) -> Result<Path> {
-        let mut parts: std::vec::Vec<SpannedString> = vec![];
+    /// Whether the name just parsed after `var` starts the path of a struct pattern, i.e. is
+    /// followed by the rest of a path and `{`, rather than being a variable missing its type
+    fn at_struct_pattern(&mut self) -> bool {
+        let mut n = 0;
+        while self.peek(n) == Some(&Token::Symbol(Symbol::ColonColon)) &&
+              self.peek(n + 1).map_or(false, |t| t.kind() == TokenKind::Identifier) {
+            n += 2;
+        }
 
-        let ident_token = match first_part {
-            Some(t) => t,
-            None => try!(self.expect_any(Token::Identifier("".to_string()))),
-        };
+        self.peek(n) == Some(&Token::Symbol(Symbol::LeftBrace))
+    }
 
-        let ident = match ident_token.tok {
-            Token::Identifier(i) => i,
-            _ => {
-                return Err(Error {
-                    kind: ErrorKind::Unknown,
-                    span: ident_token.sp,
-                })
-            } // Should never happen
+    /// Names bound by a destructuring pattern, up to the closing symbol
+    fn parse_pattern_names(&mut self, close: Symbol) -> Result<std::vec::Vec<SpannedString>> {
+        let mut names: std::vec::Vec<SpannedString> = vec![];
+        while try!(self.accept(Token::Symbol(close.clone()))).is_none() {
+            let (name, name_sp) = try!(self.expect_identifier());
+            names.push(SpannedString {
+                span: name_sp,
+                ident: name,
+            });
+
+            if self.check(&Token::Symbol(close.clone())) {
+                try!(self.accept(Token::Symbol(Symbol::Comma)));
+            } else {
+                try!(self.expect(Token::Symbol(Symbol::Comma)));
+            };
+        }
+
+        Ok(names)
+    }
+
+    fn parse_destructure(&mut self, start_sp: Span, pattern: Pattern) -> Result<BlockStatement> {
+        try!(self.expect(Token::Symbol(Symbol::Equal)));
+        let value = try!(self.parse_expression());
+
+        Ok(BlockStatement::Destructure(Box::new(DestructureData {
+            id: self.new_node_id(),
+            span: Span::concat(start_sp, self.last_sp.clone()),
+            pattern: pattern,
+            value: value,
+        })))
+    }
+
+    fn parse_path(&mut self, first_part: Option<(String, Span)>) -> Result<Path> {
+        let mut parts: std::vec::Vec<SpannedString> = vec![];
+
+        let (ident, ident_sp) = match first_part {
+            Some(first_part) => first_part,
+            None => try!(self.expect_identifier()),
         };
 
         parts.push(SpannedString {
-            span: ident_token.sp.clone(),
+            span: ident_sp.clone(),
             ident: ident,
         });
 
         while try!(self.accept(Token::Symbol(Symbol::ColonColon))).is_some() {
-            let next_path_token = try!(self.expect_any(Token::Identifier("".to_string())));
-            let next_path = match next_path_token.tok {
-                Token::Identifier(id) => id,
-                _ => {
-                    return Err(Error {
-                        kind: ErrorKind::Unknown,
-                        span: next_path_token.sp,
-                    })
-                } // Should never happen
-            };
+            // Constructors like `chan::new` are named after the keyword
+            let (next_path, next_path_sp) =
+                match try!(self.accept(Token::Keyword(Keyword::New))) {
+                    Some(n) => ("new".to_string(), n.sp),
+                    None => try!(self.expect_identifier()),
+                };
 
             parts.push(SpannedString {
-                span: next_path_token.sp,
+                span: next_path_sp,
                 ident: next_path,
             });
         }
 
         Ok(Path {
             parts: parts,
-            span: Span::concat(ident_token.sp, self.last_sp.clone()),
+            span: Span::concat(ident_sp, self.last_sp.clone()),
         })
     }
 
-    fn parse_expression_binop(&mut self,
-                              start_sp: Span,
-                              binop: BinaryOp,
-                              lhs: Expression)
-                              -> Result<Expression> {
-        Ok(Expression {
-			expr: Expression_::BinaryOp(
-				binop.clone(),
-				Box::new(
-					lhs
-				),
-				Box::new(
-					try!(self.parse_expression_(None, Self::precedence_for_op(Op::Binary(binop))))
-				)
-			),
-			span: Span::concat(start_sp, self.last_sp.clone()),
-		})
-    }
-
-    fn parse_expression_unop(&mut self, start_sp: Span, unop: UnaryOp) -> Result<Expression> {
-        Ok(Expression {
-			expr: Expression_::UnaryOp(
-				unop.clone(),
-				Box::new(
-					try!(self.parse_expression_(None, Self::precedence_for_op(Op::Unary(unop))))
-				)
-			),
-			span: Span::concat(start_sp, self.last_sp.clone()),
-		})
-    }
-
     fn parse_expression_array(&mut self, start_sp: Span) -> Result<Expression> {
         let mut items: std::vec::Vec<Box<Expression>> = vec![];
         while try!(self.accept(Token::Symbol(Symbol::RightBracket))).is_none() {
-            items.push(Box::new(try!(self.parse_expression())));
-            if self.current_token.tok == Token::Symbol(Symbol::RightBracket) {
+            items.push(Box::new(try!(self.parse_item())));
+            if self.check(&Token::Symbol(Symbol::RightBracket)) {
                 try!(self.accept(Token::Symbol(Symbol::Comma)));
             } else {
                 try!(self.expect(Token::Symbol(Symbol::Comma)));
@@ -538,20 +999,52 @@ impl<'a> Parser<'a> {
         }
 
         Ok(Expression {
+            id: self.new_node_id(),
             expr: Expression_::Array(items),
             span: Span::concat(start_sp, self.last_sp.clone()),
         })
     }
 
+    /// Item of an array literal or argument of a call, which can be spread with `...`
+    fn parse_item(&mut self) -> Result<Expression> {
+        match try!(self.accept(Token::Symbol(Symbol::Ellipsis))) {
+            Some(SToken { sp: start_sp, .. }) => {
+                try!(self.check_feature(Feature::Spread, &start_sp));
+                let spread = try!(self.parse_expression());
+                Ok(Expression {
+                    id: self.new_node_id(),
+                    expr: Expression_::Spread(Box::new(spread)),
+                    span: Span::concat(start_sp, self.last_sp.clone()),
+                })
+            }
+            None => self.parse_expression(),
+        }
+    }
+
+    /// Map literal, or set literal when the first item isn't followed by `:`, `{}` being an
+    /// empty map
     fn parse_expression_map(&mut self, start_sp: Span) -> Result<Expression> {
-        let mut items = Map { map: std::collections::HashMap::new() };
+        let mut items = Map { entries: vec![] };
 
         while try!(self.accept(Token::Symbol(Symbol::RightBrace))).is_none() {
             let key = try!(self.parse_expression());
+            if items.entries.is_empty() && !self.check(&Token::Symbol(Symbol::Colon)) {
+                try!(self.check_feature(Feature::Sets, &start_sp));
+                return self.parse_expression_set(start_sp, key);
+            }
+            let duplicate = items.entries.iter().any(|&(ref k, _)| k.expr == key.expr);
+            if duplicate && Self::is_literal(&key) {
+                return Err(Error {
+                    kind: ErrorKind::DuplicateMapKey(key.to_string()),
+                    span: key.span,
+                    context: vec![],
+                });
+            }
+
             try!(self.expect(Token::Symbol(Symbol::Colon)));
             let value = try!(self.parse_expression());
-            items.map.insert(Box::new(key), Box::new(value));
-            if self.current_token.tok == Token::Symbol(Symbol::RightBrace) {
+            items.entries.push((Box::new(key), Box::new(value)));
+            if self.check(&Token::Symbol(Symbol::RightBrace)) {
                 try!(self.accept(Token::Symbol(Symbol::Comma)));
             } else {
                 try!(self.expect(Token::Symbol(Symbol::Comma)));
@@ -559,43 +1052,74 @@ impl<'a> Parser<'a> {
         }
 
         Ok(Expression {
+            id: self.new_node_id(),
             expr: Expression_::Map(items),
             span: Span::concat(start_sp, self.last_sp.clone()),
         })
     }
 
+    fn parse_expression_set(&mut self, start_sp: Span, first: Expression) -> Result<Expression> {
+        let mut items: std::vec::Vec<Box<Expression>> = vec![Box::new(first)];
+        loop {
+            if self.check(&Token::Symbol(Symbol::RightBrace)) {
+                try!(self.accept(Token::Symbol(Symbol::Comma)));
+            } else {
+                try!(self.expect(Token::Symbol(Symbol::Comma)));
+            };
+            if try!(self.accept(Token::Symbol(Symbol::RightBrace))).is_some() {
+                break;
+            }
+            items.push(Box::new(try!(self.parse_expression())));
+        }
+
+        Ok(Expression {
+            id: self.new_node_id(),
+            expr: Expression_::Set(items),
+            span: Span::concat(start_sp, self.last_sp.clone()),
+        })
+    }
+
+    fn is_literal(expression: &Expression) -> bool {
+        match expression.expr {
+            Expression_::StringLiteral(_) |
+            Expression_::IntegerLiteral(_) |
+            Expression_::BoolLiteral(_) |
+            Expression_::CharLiteral(_) => true,
+            _ => false,
+        }
+    }
+
     fn parse_expression_struct_init(&mut self, start_sp: Span) -> Result<Expression> {
         let path = try!(self.parse_path(None));
 
+        self.parse_expression_struct_init_fields(start_sp, path)
+    }
+
+    /// `{ field: value, ... }` following the name of the struct, with or without `new`
+    fn parse_expression_struct_init_fields(&mut self,
+                                           start_sp: Span,
+                                           path: Path)
+                                           -> Result<Expression> {
         try!(self.expect(Token::Symbol(Symbol::LeftBrace)));
 
         let mut fields: std::vec::Vec<StructInitFieldData> = vec![];
         while try!(self.accept(Token::Symbol(Symbol::RightBrace))).is_none() {
-            let field_name_token = try!(self.expect_any(Token::Identifier("".to_string())));
-            let field_name = match field_name_token.tok {
-                Token::Identifier(s) => s,
-                _ => {
-                    return Err(Error {
-                        kind: ErrorKind::Unknown,
-                        span: field_name_token.sp,
-                    })
-                } // Should never happen
-            };
+            let (field_name, field_name_sp) = try!(self.expect_field_name());
 
             try!(self.expect(Token::Symbol(Symbol::Colon)));
 
             let field_value = try!(self.parse_expression());
 
             fields.push(StructInitFieldData {
-                span: Span::concat(field_name_token.sp.clone(), self.last_sp.clone()),
+                span: Span::concat(field_name_sp.clone(), self.last_sp.clone()),
                 name: SpannedString {
-                    span: field_name_token.sp,
+                    span: field_name_sp,
                     ident: field_name,
                 },
                 value: Box::new(field_value),
             });
 
-            if self.current_token.tok == Token::Symbol(Symbol::RightBrace) {
+            if self.check(&Token::Symbol(Symbol::RightBrace)) {
                 try!(self.accept(Token::Symbol(Symbol::Comma)));
             } else {
                 try!(self.expect(Token::Symbol(Symbol::Comma)));
@@ -603,15 +1127,56 @@ impl<'a> Parser<'a> {
         }
 
         Ok(Expression {
+            id: self.new_node_id(),
             span: Span::concat(start_sp, self.last_sp.clone()),
             expr: Expression_::StructInit(path, fields),
         })
+    }
+
+    /// Whether the current token is a `{` opening the fields of a struct init whose name was
+    /// just parsed, rather than a block: it has to be on the same line and followed by `field:`,
+    /// which can't start a statement
+    fn at_struct_init_fields(&mut self) -> bool {
+        if self.just_skept_newline || self.current_token.tok != Token::Symbol(Symbol::LeftBrace) {
+            return false;
+        }
+
+        let mut n = 1;
+        while self.peek(n) == Some(&Token::Symbol(Symbol::NewLine)) {
+            n += 1;
+        }
+        let is_identifier = self.peek(n).map_or(false, |t| {
+            t.kind() == TokenKind::Identifier || t.kind() == TokenKind::Keyword
+        });
+
+        is_identifier && self.peek(n + 1) == Some(&Token::Symbol(Symbol::Colon))
+    }
+
+    fn parse_expression_spawn(&mut self, start_sp: Span) -> Result<Expression> {
+        let return_type = if try!(self.accept(Token::Symbol(Symbol::Return))).is_some() {
+            try!(self.parse_type())
+        } else {
+            Type::None
+        };
+
+        try!(self.expect(Token::Symbol(Symbol::LeftBrace)));
 
+        let mut statements: std::vec::Vec<BlockStatement> = vec![];
+        while try!(self.accept(Token::Symbol(Symbol::RightBrace))).is_none() {
+            statements.push(try!(self.parse_block_statement(return_type.clone())));
+        }
+
+        Ok(Expression {
+            id: self.new_node_id(),
+            span: Span::concat(start_sp, self.last_sp.clone()),
+            expr: Expression_::Spawn(return_type, statements),
+        })
     }
 
     fn parse_expression_literal(&mut self, stoken: SToken) -> Result<Expression> {
         let expr = match stoken.tok {
             Token::StringLiteral(s) => Expression_::StringLiteral(s),
+            Token::BytesLiteral(b) => Expression_::BytesLiteral(b),
             Token::IntegerLiteral(i) => Expression_::IntegerLiteral(i),
             Token::BoolLiteral(b) => Expression_::BoolLiteral(b),
             Token::CharLiteral(c) => Expression_::CharLiteral(c),
@@ -619,11 +1184,13 @@ impl<'a> Parser<'a> {
                 return Err(Error {
                     kind: ErrorKind::Unknown,
                     span: stoken.sp,
+                    context: vec![],
                 })
             } // Should never happen
         };
 
         Ok(Expression {
+            id: self.new_node_id(),
             expr: expr,
             span: stoken.sp,
         })
@@ -633,43 +1200,43 @@ impl<'a> Parser<'a> {
                                   start_sp: Span,
                                   func_expr: Expression)
                                   -> Result<Expression> {
+        let args = try!(self.parse_call_args()
+            .map_err(|e| e.in_context(&Construct::CallArgs, &func_expr.span)));
+
+        Ok(Expression {
+            id: self.new_node_id(),
+            span: Span::concat(start_sp, self.last_sp.clone()),
+            expr: Expression_::FuncCall(Box::new(func_expr), args),
+        })
+    }
+
+    fn parse_call_args(&mut self) -> Result<std::vec::Vec<Box<Expression>>> {
         let mut args: std::vec::Vec<Box<Expression>> = vec![];
         while try!(self.accept(Token::Symbol(Symbol::RightParenthesis))).is_none() {
-            args.push(Box::new(try!(self.parse_expression())));
+            args.push(Box::new(try!(self.parse_item())));
 
-            if self.current_token.tok == Token::Symbol(Symbol::RightParenthesis) {
+            if self.check(&Token::Symbol(Symbol::RightParenthesis)) {
                 try!(self.accept(Token::Symbol(Symbol::Comma)));
             } else {
                 try!(self.expect(Token::Symbol(Symbol::Comma)));
             };
         }
 
-        Ok(Expression {
-            span: Span::concat(start_sp, self.last_sp.clone()),
-            expr: Expression_::FuncCall(Box::new(func_expr), args),
-        })
+        Ok(args)
     }
 
     fn parse_expression_field(&mut self,
                               start_sp: Span,
                               struct_expr: Expression)
                               -> Result<Expression> {
-        let field_token = try!(self.expect_any(Token::Identifier("".to_string())));
-        let field = match field_token.tok {
-            Token::Identifier(i) => i,
-            _ => {
-                return Err(Error {
-                    kind: ErrorKind::Unknown,
-                    span: field_token.sp,
-                })
-            } // Should never happen
-        };
+        let (field, field_sp) = try!(self.expect_field_name());
 
         Ok(Expression {
-            span: Span::concat(start_sp, field_token.sp.clone()),
+            id: self.new_node_id(),
+            span: Span::concat(start_sp, field_sp.clone()),
             expr: Expression_::Field(Box::new(struct_expr),
                                      SpannedString {
-                                         span: field_token.sp,
+                                         span: field_sp,
                                          ident: field,
                                      }),
         })
@@ -689,132 +1256,260 @@ impl<'a> Parser<'a> {
         };
 
         Ok(Expression {
+            id: self.new_node_id(),
             expr: Expression_::Index(Box::new(indexable_expr), index),
             span: Span::concat(start_sp, self.last_sp.clone()),
         })
     }
 
-    fn parse_expression_variable(&mut self, stoken: SToken) -> Result<Expression> {
-        let path = try!(self.parse_path(Some(stoken)));
+    fn parse_expression_variable(&mut self, first_part: (String, Span)) -> Result<Expression> {
+        let path = try!(self.parse_path(Some(first_part)));
+        if path.to_string() == "is" && self.at_type_test() {
+            return self.parse_expression_type_test(path.span);
+        }
+        if self.at_struct_init_fields() {
+            try!(self.check_feature(Feature::BareStructInit, &path.span));
+            return self.parse_expression_struct_init_fields(path.span.clone(), path);
+        }
 
         Ok(Expression {
+            id: self.new_node_id(),
             span: path.span.clone(),
             expr: Expression_::Variable(path),
         })
     }
 
+    /// Whether the `is` just parsed starts a type test, i.e. is followed by `<`, the tokens of a
+    /// type, `>` and `(`, rather than being a variable compared with `<`
+    fn at_type_test(&mut self) -> bool {
+        if self.current_token.tok != Token::Symbol(Symbol::Less) {
+            return false;
+        }
+
+        let mut n = 1;
+        loop {
+            match self.peek(n) {
+                Some(&Token::Identifier(_)) |
+                Some(&Token::Symbol(Symbol::Amp)) |
+                Some(&Token::Symbol(Symbol::AmpAmp)) |
+                Some(&Token::Symbol(Symbol::At)) |
+                Some(&Token::Symbol(Symbol::LeftBracket)) |
+                Some(&Token::Symbol(Symbol::RightBracket)) |
+                Some(&Token::Symbol(Symbol::ColonColon)) => n += 1,
+                Some(&Token::Symbol(Symbol::More)) => break,
+                _ => return false,
+            }
+        }
+
+        n > 1 && self.peek(n + 1) == Some(&Token::Symbol(Symbol::LeftParenthesis))
+    }
+
+    fn parse_expression_type_test(&mut self, start_sp: Span) -> Result<Expression> {
+        try!(self.expect(Token::Symbol(Symbol::Less)));
+        let tested_type = try!(self.parse_type());
+        try!(self.expect(Token::Symbol(Symbol::More)));
+
+        try!(self.expect(Token::Symbol(Symbol::LeftParenthesis)));
+        let value = try!(self.parse_expression());
+        try!(self.expect(Token::Symbol(Symbol::RightParenthesis)));
+
+        Ok(Expression {
+            id: self.new_node_id(),
+            span: Span::concat(start_sp, self.last_sp.clone()),
+            expr: Expression_::TypeTest(tested_type, Box::new(value)),
+        })
+    }
+
     fn parse_expression(&mut self) -> Result<Expression> {
-        self.parse_expression_(None, 0)
+        self.parse_expression_(0)
     }
 
-    #[allow(cyclomatic_complexity)]
-    fn parse_expression_(&mut self,
-                         prev_expr: Option<Expression>,
-                         minimum_precedence: u8)
-                         -> Result<Expression> {
-        let new_expr = match prev_expr {
-            Some(expr) => {
-                if try!(self.accept(Token::Symbol(Symbol::Plus))).is_some() {
-                    try!(self.parse_expression_binop(expr.span.clone(), BinaryOp::Addition, expr))
-                } else if try!(self.accept(Token::Symbol(Symbol::Minus))).is_some() {
-                    try!(self.parse_expression_binop(expr.span.clone(), BinaryOp::Substraction, expr))
-                } else if try!(self.accept(Token::Symbol(Symbol::Star))).is_some() {
-                    try!(self.parse_expression_binop(expr.span.clone(), BinaryOp::Multiplication, expr))
-                } else if try!(self.accept(Token::Symbol(Symbol::Over))).is_some() {
-                    try!(self.parse_expression_binop(expr.span.clone(), BinaryOp::Division, expr))
-                } else if try!(self.accept(Token::Symbol(Symbol::Modulo))).is_some() {
-                    try!(self.parse_expression_binop(expr.span.clone(), BinaryOp::Modulo, expr))
-                } else if try!(self.accept(Token::Symbol(Symbol::EqualEqual))).is_some() {
-                    try!(self.parse_expression_binop(expr.span.clone(), BinaryOp::Equality, expr))
-                } else if try!(self.accept(Token::Symbol(Symbol::NotEqual))).is_some() {
-                    try!(self.parse_expression_binop(expr.span.clone(), BinaryOp::Inequality, expr))
-                } else if try!(self.accept(Token::Symbol(Symbol::Concat))).is_some() {
-                    try!(self.parse_expression_binop(expr.span.clone(), BinaryOp::Concatenation, expr))
-                } else if try!(self.accept(Token::Symbol(Symbol::LeftParenthesis))).is_some() {
-                    try!(self.parse_expression_func_call(expr.span.clone(), expr))
-                } else if try!(self.accept(Token::Symbol(Symbol::Dot))).is_some() {
-                    try!(self.parse_expression_field(expr.span.clone(), expr))
-                } else if try!(self.accept(Token::Symbol(Symbol::LeftBracket))).is_some() {
-                    try!(self.parse_expression_index(expr.span.clone(), expr))
-                } else {
-                    return Ok(expr);
+    /// Parses an expression made of operators binding tighter than `minimum_precedence`
+    fn parse_expression_(&mut self, minimum_precedence: u8) -> Result<Expression> {
+        try!(self.enter_nested());
+        let expr = self.parse_expression_nested(minimum_precedence);
+        self.depth -= 1;
+
+        expr
+    }
+
+    fn parse_expression_nested(&mut self, minimum_precedence: u8) -> Result<Expression> {
+        let mut expr = match Self::prefix_operator(&self.current_token.tok) {
+            Some((unop, precedence)) => {
+                let start_sp = try!(self.bump()).sp;
+                let operand = try!(self.parse_expression_(precedence));
+
+                Expression {
+                    id: self.new_node_id(),
+                    expr: Expression_::UnaryOp(unop, Box::new(operand)),
+                    span: Span::concat(start_sp, self.last_sp.clone()),
                 }
             }
-            None => {
-                if try!(self.accept(Token::Symbol(Symbol::LeftParenthesis))).is_some() {
-                    let e = try!(self.parse_expression());
-                    try!(self.expect(Token::Symbol(Symbol::RightParenthesis)));
-
-                    e
-                } else if let Some(h) = try!(self.accept(Token::Symbol(Symbol::Hash))) {
-                    try!(self.parse_expression_unop(h.sp, UnaryOp::Count))
-                } else if let Some(a) = try!(self.accept(Token::Symbol(Symbol::Amp))) {
-                    try!(self.parse_expression_unop(a.sp, UnaryOp::Reference))
-                } else if let Some(a) = try!(self.accept(Token::Symbol(Symbol::At))) {
-                    try!(self.parse_expression_unop(a.sp, UnaryOp::MutReference))
-                } else if let Some(s) = try!(self.accept(Token::Symbol(Symbol::Star))) {
-                    try!(self.parse_expression_unop(s.sp, UnaryOp::Dereference))
-                } else if let Some(lb) = try!(self.accept(Token::Symbol(Symbol::LeftBracket))) {
-                    try!(self.parse_expression_array(lb.sp))
-                } else if let Some(lb) = try!(self.accept(Token::Symbol(Symbol::LeftBrace))) {
-                    try!(self.parse_expression_map(lb.sp))
-                } else if let Some(sl) = try!(self.accept_any(Token::StringLiteral("".to_string()))) {
-                    try!(self.parse_expression_literal(sl))
-                } else if let Some(il) = try!(self.accept_any(Token::IntegerLiteral(0))) {
-                    try!(self.parse_expression_literal(il))
-                } else if let Some(bl) = try!(self.accept_any(Token::BoolLiteral(false))) {
-                    try!(self.parse_expression_literal(bl))
-                } else if let Some(cl) = try!(self.accept_any(Token::CharLiteral('\0'))) {
-                    try!(self.parse_expression_literal(cl))
-                } else if let Some(ident_token) =
-                       try!(self.accept_any(Token::Identifier("".to_string()))) {
-                    try!(self.parse_expression_variable(ident_token))
-                } else if let Some(n) = try!(self.accept_any(Token::Keyword(Keyword::New))) {
-                    try!(self.parse_expression_struct_init(n.sp))
-                } else {
-                    return Err(Error {
-                        kind: ErrorKind::UnexpectedToken(self.current_token.tok.clone()),
-                        span: self.current_token.sp.clone(),
-                    });
+            None if self.current_token.tok == Token::Symbol(Symbol::AmpAmp) => {
+                // `&&x` is lexed as the `&&` operator but is a reference to a reference
+                let start_sp = try!(self.bump()).sp;
+                let precedence = Self::precedence_for_op(Op::Unary(UnaryOp::Reference));
+                let operand = try!(self.parse_expression_(precedence));
+                let inner_start_sp = Span {
+                    scol: start_sp.scol + 1,
+                    start: start_sp.start + 1,
+                    ..start_sp.clone()
+                };
+                let inner = Expression {
+                    id: self.new_node_id(),
+                    expr: Expression_::UnaryOp(UnaryOp::Reference, Box::new(operand)),
+                    span: Span::concat(inner_start_sp, self.last_sp.clone()),
+                };
+
+                Expression {
+                    id: self.new_node_id(),
+                    expr: Expression_::UnaryOp(UnaryOp::Reference, Box::new(inner)),
+                    span: Span::concat(start_sp, self.last_sp.clone()),
                 }
             }
+            None => try!(self.parse_expression_primary()),
         };
+        // Whether `expr` is an ordering comparison just parsed, which a following one extends
+        let mut chaining = false;
 
-        if self.just_skept_newline {
-            Ok(new_expr)
-        } else if let Some(binop) = Self::binop_for_token(self.current_token.clone()) {
-            if Self::precedence_for_op(Op::Binary(binop.clone())) > minimum_precedence {
-                self.parse_expression_(Some(new_expr), Self::precedence_for_op(Op::Binary(binop)))
-            } else {
-                Ok(new_expr)
+        loop {
+            if self.options.newlines_terminate_expressions && self.just_skept_newline {
+                break;
             }
-        } else if self.current_token.tok == Token::Symbol(Symbol::LeftParenthesis) ||
-           self.current_token.tok == Token::Symbol(Symbol::LeftBracket) ||
-           self.current_token.tok == Token::Symbol(Symbol::Dot) {
-            // TODO: find a prettier solution
-            self.parse_expression_(Some(new_expr), 0)
+
+            let (operator, precedence) = match Self::infix_operator(&self.current_token.tok) {
+                Some((operator, precedence)) if precedence > minimum_precedence => {
+                    (operator, precedence)
+                }
+                _ => break,
+            };
+            try!(self.bump());
+
+            let start_sp = expr.span.clone();
+            let extend_chain = chaining;
+            chaining = false;
+            expr = match operator {
+                Operator::Infix(binop, associativity) => {
+                    // The right operand of a right-associative operator takes the following
+                    // operators of the same precedence
+                    let rhs_precedence = match associativity {
+                        Associativity::Left => precedence,
+                        Associativity::Right => precedence - 1,
+                    };
+                    let rhs = try!(self.parse_expression_(rhs_precedence));
+                    chaining = binop.is_ordering();
+
+                    let expr_ = match expr.expr {
+                        // `a < b < c` compares `b` to both `a` and `c`
+                        Expression_::BinaryOp(first, lhs, middle) if extend_chain && chaining => {
+                            Expression_::ComparisonChain(vec![lhs, middle, Box::new(rhs)],
+                                                         vec![first, binop])
+                        }
+                        Expression_::ComparisonChain(mut operands, mut binops) if extend_chain &&
+                                                                                  chaining => {
+                            operands.push(Box::new(rhs));
+                            binops.push(binop);
+                            Expression_::ComparisonChain(operands, binops)
+                        }
+                        expr_ => {
+                            Expression_::BinaryOp(binop,
+                                                  Box::new(Expression {
+                                                      expr: expr_,
+                                                      ..expr
+                                                  }),
+                                                  Box::new(rhs))
+                        }
+                    };
+
+                    Expression {
+                        id: self.new_node_id(),
+                        expr: expr_,
+                        span: Span::concat(start_sp, self.last_sp.clone()),
+                    }
+                }
+                Operator::Postfix(PostfixOp::Call) => {
+                    try!(self.parse_expression_func_call(start_sp, expr))
+                }
+                Operator::Postfix(PostfixOp::Field) => {
+                    try!(self.parse_expression_field(start_sp, expr))
+                }
+                Operator::Postfix(PostfixOp::Index) => {
+                    try!(self.parse_expression_index(start_sp, expr))
+                }
+                Operator::Prefix(_) => unreachable!(),
+            };
+        }
+
+        Ok(expr)
+    }
+
+    #[allow(cyclomatic_complexity)]
+    fn parse_expression_primary(&mut self) -> Result<Expression> {
+        if try!(self.accept(Token::Symbol(Symbol::LeftParenthesis))).is_some() {
+            let e = try!(self.parse_expression());
+            try!(self.expect(Token::Symbol(Symbol::RightParenthesis)));
+
+            Ok(e)
+        } else if let Some(lb) = try!(self.accept(Token::Symbol(Symbol::LeftBracket))) {
+            self.parse_expression_array(lb.sp)
+        } else if let Some(lb) = try!(self.accept(Token::Symbol(Symbol::LeftBrace))) {
+            self.parse_expression_map(lb.sp)
+        } else if let Some(sl) = try!(self.accept_kind(TokenKind::StringLiteral)) {
+            self.parse_expression_literal(sl)
+        } else if let Some(bl) = try!(self.accept_kind(TokenKind::BytesLiteral)) {
+            self.parse_expression_literal(bl)
+        } else if let Some(il) = try!(self.accept_kind(TokenKind::IntegerLiteral)) {
+            self.parse_expression_literal(il)
+        } else if let Some(bl) = try!(self.accept_kind(TokenKind::BoolLiteral)) {
+            self.parse_expression_literal(bl)
+        } else if let Some(cl) = try!(self.accept_kind(TokenKind::CharLiteral)) {
+            self.parse_expression_literal(cl)
+        } else if let Some(ident) = try!(self.accept_identifier()) {
+            self.parse_expression_variable(ident)
+        } else if let Some(n) = try!(self.accept(Token::Keyword(Keyword::New))) {
+            self.parse_expression_struct_init(n.sp)
+        } else if let Some(s) = try!(self.accept(Token::Keyword(Keyword::Spawn))) {
+            self.parse_expression_spawn(s.sp)
         } else {
-            Ok(new_expr)
+            Err(Error {
+                kind: ErrorKind::UnexpectedToken(self.current_token.tok.clone()),
+                span: self.current_token.sp.clone(),
+                context: vec![],
+            })
         }
     }
 
     fn parse_type(&mut self) -> Result<Type> {
+        try!(self.enter_nested());
+        let parsed_type = self.parse_type_();
+        self.depth -= 1;
+
+        parsed_type
+    }
+
+    fn parse_type_(&mut self) -> Result<Type> {
         fn get_builtin_type(path: &Path) -> Option<Type> {
-            if path.parts.len() != 1 {
-                None
-            } else {
-                match path.parts.get(0).unwrap().ident.as_ref() {
-                    "int" => Some(Type::Int),
-                    "bool" => Some(Type::Bool),
-                    "char" => Some(Type::Char),
-                    "string" => Some(Type::String),
-                    _ => None,
-                }
+            match path.to_string().as_ref() {
+                "int" => Some(Type::Int),
+                "bool" => Some(Type::Bool),
+                "char" => Some(Type::Char),
+                "string" => Some(Type::String),
+                "bytes" => Some(Type::Bytes),
+                "task" => Some(Type::Task),
+                "sync::mutex" => Some(Type::Mutex),
+                "sync::waitgroup" => Some(Type::WaitGroup),
+                "sync::atomic" => Some(Type::Atomic),
+                "string::builder" => Some(Type::Builder),
+                _ => None,
             }
         }
 
         if try!(self.accept(Token::Symbol(Symbol::Amp))).is_some() {
             return Ok(Type::Reference(Box::new(try!(self.parse_type()))));
+        } else if try!(self.accept(Token::Symbol(Symbol::AmpAmp))).is_some() {
+            let inner_type = Type::Reference(Box::new(try!(self.parse_type())));
+
+            return Ok(Type::Reference(Box::new(inner_type)));
         } else if try!(self.accept(Token::Symbol(Symbol::At))).is_some() {
             return Ok(Type::MutReference(Box::new(try!(self.parse_type()))));
         } else if try!(self.accept(Token::Symbol(Symbol::LeftBracket))).is_some() {
@@ -823,31 +1518,88 @@ impl<'a> Parser<'a> {
 
                 return Ok(Type::Array(Box::new(inner_type)));
             } else {
+                let key_start_sp = self.current_token.sp.clone();
                 let key_type = try!(self.parse_type());
+                let key_sp = Span::concat(key_start_sp, self.last_sp.clone());
 
                 try!(self.expect(Token::Symbol(Symbol::RightBracket)));
 
                 let value_type = try!(self.parse_type());
 
+                if let Type::Struct(ref path) = key_type {
+                    if path.to_string() == "chan" {
+                        return Ok(Type::Chan(Box::new(value_type)));
+                    }
+                }
+
+                if !key_type.is_map_key() {
+                    return Err(Error {
+                        kind: ErrorKind::InvalidMapKeyType(key_type),
+                        span: key_sp,
+                        context: vec![],
+                    });
+                }
+
                 return Ok(Type::Map(Box::new(key_type), Box::new(value_type)));
             }
         };
 
         let path = try!(self.parse_path(None));
 
+        if path.to_string() == "set" &&
+           try!(self.accept(Token::Symbol(Symbol::LeftBracket))).is_some() {
+            try!(self.check_feature(Feature::Sets, &path.span));
+            let item_start_sp = self.current_token.sp.clone();
+            let item_type = try!(self.parse_type());
+            let item_sp = Span::concat(item_start_sp, self.last_sp.clone());
+            try!(self.expect(Token::Symbol(Symbol::RightBracket)));
+
+            if !item_type.is_map_key() {
+                return Err(Error {
+                    kind: ErrorKind::InvalidSetItemType(item_type),
+                    span: item_sp,
+                    context: vec![],
+                });
+            }
+
+            return Ok(Type::Set(Box::new(item_type)));
+        }
+
         match get_builtin_type(&path) {
             Some(t) => Ok(t),
             None => Ok(Type::Struct(path)),
         }
     }
 
+    fn new_node_id(&mut self) -> NodeId {
+        let id = NodeId(self.next_node_id);
+        self.next_node_id += 1;
+        id
+    }
+
+    fn enter_nested(&mut self) -> Result<()> {
+        self.depth += 1;
+        if self.depth > self.options.recursion_limit {
+            self.depth -= 1;
+            return Err(Error {
+                kind: ErrorKind::TooDeeplyNested,
+                span: self.current_token.sp.clone(),
+                context: vec![],
+            });
+        }
+
+        Ok(())
+    }
+
     fn skip_newlines(&mut self) -> Result<Span> {
         let mut sp = Span {
             scol: self.current_token.sp.scol,
             srow: self.current_token.sp.srow,
             ecol: self.current_token.sp.scol, // intended
             erow: self.current_token.sp.srow, // intended
-            file: self.current_token.sp.file.clone(),
+            start: self.current_token.sp.start,
+            end: self.current_token.sp.start, // intended
+            file: self.current_token.sp.file,
         };
 
         while self.current_token.tok == Token::Symbol(Symbol::NewLine) {
@@ -859,110 +1611,236 @@ impl<'a> Parser<'a> {
         Ok(sp)
     }
 
-    fn accept(&mut self, mtoken: Token) -> Result<Option<SToken>> {
-        // TODO: use intrinsics with determinants, but unstable for now. Could also use macros
-        // around enums to be able to automatically expand them into a match, but would be too
-        // invasive.
-        let token = match (self.current_token.tok.clone(), mtoken) {
-            (Token::EOF, Token::EOF) => Some(self.current_token.clone()),
-            (Token::Identifier(ref a), Token::Identifier(ref b)) if (a == b) => {
-                Some(self.current_token.clone())
-            }
-            (Token::Keyword(ref a), Token::Keyword(ref b)) if (a == b) => {
-                Some(self.current_token.clone())
-            }
-            (Token::StringLiteral(ref a), Token::StringLiteral(ref b)) if (a == b) => {
-                Some(self.current_token.clone())
-            }
-            (Token::CharLiteral(ref a), Token::CharLiteral(ref b)) if (a == b) => {
-                Some(self.current_token.clone())
-            }
-            (Token::IntegerLiteral(ref a), Token::IntegerLiteral(ref b)) if (a == b) => {
-                Some(self.current_token.clone())
-            }
-            (Token::FloatLiteral(ref a), Token::FloatLiteral(ref b)) if (a - b).abs() <
-                                                                        std::f64::EPSILON => {
-                Some(self.current_token.clone())
-            }
-            (Token::BoolLiteral(ref a), Token::BoolLiteral(ref b)) if (a == b) => {
-                Some(self.current_token.clone())
-            }
-            (Token::Symbol(ref a), Token::Symbol(ref b)) if (a == b) => {
-                Some(self.current_token.clone())
-            }
-            (_, _) => None,
-        };
+    /// Whether the current token is the given one, which is otherwise reported as expected if
+    /// an error follows at this position
+    fn check(&mut self, token: &Token) -> bool {
+        self.expecting(Expected::Token(token.clone()));
+        self.current_token.tok == *token
+    }
 
-        if token.is_some() {
-            try!(self.next_token());
-            self.just_skept_newline = false;
-            try!(self.skip_newlines());
-        }
+    fn check_kind(&mut self, kind: TokenKind) -> bool {
+        self.expecting(Expected::Kind(kind));
+        self.current_token.tok.kind() == kind
+    }
 
-        Ok(token)
+    fn expecting(&mut self, expected: Expected) {
+        if !self.expected.contains(&expected) {
+            self.expected.push(expected);
+        }
     }
 
-    fn accept_any(&mut self, mtoken: Token) -> Result<Option<SToken>> {
-        // TODO: same as accept()
-        let token = match (self.current_token.tok.clone(), mtoken) {
-            (Token::EOF, Token::EOF) |
-            (Token::Identifier(_), Token::Identifier(_)) |
-            (Token::Keyword(_), Token::Keyword(_)) |
-            (Token::StringLiteral(_), Token::StringLiteral(_)) |
-            (Token::CharLiteral(_), Token::CharLiteral(_)) |
-            (Token::IntegerLiteral(_), Token::IntegerLiteral(_)) |
-            (Token::FloatLiteral(_), Token::FloatLiteral(_)) |
-            (Token::BoolLiteral(_), Token::BoolLiteral(_)) |
-            (Token::Symbol(_), Token::Symbol(_)) => Some(self.current_token.clone()),
-            (_, _) => None,
-        };
+    fn accept(&mut self, mtoken: Token) -> Result<Option<SToken>> {
+        if self.check(&mtoken) {
+            self.bump().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
 
-        if token.is_some() {
-            try!(self.next_token());
-            self.just_skept_newline = false;
-            try!(self.skip_newlines());
+    /// Accepts any token of the given kind, whatever its payload
+    fn accept_kind(&mut self, kind: TokenKind) -> Result<Option<SToken>> {
+        if self.check_kind(kind) {
+            self.bump().map(Some)
+        } else {
+            Ok(None)
         }
+    }
 
-        Ok(token)
+    fn accept_identifier(&mut self) -> Result<Option<(String, Span)>> {
+        match try!(self.accept_kind(TokenKind::Identifier)) {
+            Some(SToken { tok: Token::Identifier(ident), sp }) => Ok(Some((ident, sp))),
+            _ => Ok(None),
+        }
     }
 
     fn expect(&mut self, token: Token) -> Result<SToken> {
-        match try!(self.accept(token.clone())) {
-            Some(t) => Ok(t),
-            None => {
-                Err(Error {
-                    kind: ErrorKind::ExpectedGotToken(token, self.current_token.tok.clone()),
-                    span: self.current_token.sp.clone(),
-                })
-            }
+        if self.check(&token) {
+            self.bump()
+        } else {
+            Err(Error {
+                kind: ErrorKind::ExpectedGotToken(self.expected.clone(),
+                                                  self.current_token.tok.clone()),
+                span: self.current_token.sp.clone(),
+                context: vec![],
+            })
         }
     }
 
-    fn expect_any(&mut self, token: Token) -> Result<SToken> {
-        match try!(self.accept_any(token.clone())) {
-            Some(t) => Ok(t),
-            None => {
+    fn expect_kind(&mut self, kind: TokenKind) -> Result<SToken> {
+        if self.check_kind(kind) {
+            self.bump()
+        } else {
+            Err(Error {
+                kind: ErrorKind::ExpectedGotToken(self.expected.clone(),
+                                                  self.current_token.tok.clone()),
+                span: self.current_token.sp.clone(),
+                context: vec![],
+            })
+        }
+    }
+
+    fn expect_identifier(&mut self) -> Result<(String, Span)> {
+        match try!(self.expect_kind(TokenKind::Identifier)) {
+            SToken { tok: Token::Identifier(ident), sp } => Ok((ident, sp)),
+            SToken { sp, .. } => {
                 Err(Error {
-                    kind: ErrorKind::ExpectedGotToken(token, self.current_token.tok.clone()),
-                    span: self.current_token.sp.clone(),
+                    kind: ErrorKind::Unknown,
+                    span: sp,
+                    context: vec![],
                 })
+            } // Should never happen
+        }
+    }
+
+    /// Name of a struct field, which can be a keyword as it always follows a `.` or precedes a `:`
+    /// where a keyword couldn't be
+    fn expect_field_name(&mut self) -> Result<(String, Span)> {
+        let keyword = match self.current_token.tok {
+            Token::Keyword(ref keyword) => Some(keyword.as_str()),
+            _ => None,
+        };
+        if let Some(keyword) = keyword {
+            let token = try!(self.bump());
+            return Ok((keyword.to_string(), token.sp));
+        }
+
+        self.expect_identifier()
+    }
+
+    /// Moves past the current token, which is returned, and the newlines following it
+    fn bump(&mut self) -> Result<SToken> {
+        let token = try!(self.next_token());
+        self.just_skept_newline = false;
+        try!(self.skip_newlines());
+
+        Ok(token)
+    }
+
+    /// Token `n` positions after the current one (`peek(1)` being the next one), newlines
+    /// included. `None` past the end of the input or a lexer error, which is only reported once
+    /// the parser actually reaches it.
+    fn peek(&mut self, n: usize) -> Option<&Token> {
+        if n == 0 {
+            return Some(&self.current_token.tok);
+        }
+
+        while self.lookahead.len() < n {
+            let is_last = match self.lookahead.back() {
+                Some(&Ok(SToken { tok: Token::EOF, .. })) |
+                Some(&Err(_)) => true,
+                _ => false,
+            };
+            if is_last {
+                break;
             }
+
+            let token = self.read_token();
+            self.lookahead.push_back(token);
+        }
+
+        match self.lookahead.get(n - 1) {
+            Some(&Ok(ref t)) => Some(&t.tok),
+            _ => None,
         }
     }
 
+    /// Replaces the current token with the next one from the reader, returning the former
     fn next_token(&mut self) -> Result<SToken> {
-        match self.reader.next_token() {
+        let next = match self.lookahead.pop_front() {
+            Some(t) => t,
+            None => self.read_token(),
+        };
+
+        match next {
             Ok(t) => {
-                self.last_sp = self.current_token.sp.clone();
-                self.current_token = t;
-                Ok(self.current_token.clone())
+                // Spans of nodes end with their last token, not the newlines after it
+                if self.current_token.tok != Token::Symbol(Symbol::NewLine) {
+                    self.last_sp = self.current_token.sp.clone();
+                }
+                self.expected.clear();
+                Ok(std::mem::replace(&mut self.current_token, t))
             }
             Err(e) => {
                 Err(Error {
                     kind: ErrorKind::Lexer(e.clone()),
                     span: e.span,
+                    context: vec![],
                 })
             }
         }
     }
+
+    /// Fails if the syntax starting at `sp` is turned off in the options
+    fn check_feature(&self, feature: Feature, sp: &Span) -> Result<()> {
+        if self.options.features.contains(&feature) {
+            return Ok(());
+        }
+
+        Err(Error {
+            kind: ErrorKind::FeatureDisabled(feature),
+            span: sp.clone(),
+            context: vec![],
+        })
+    }
+
+    /// Text of the doc comments on the lines right above the declaration starting at `start_sp`,
+    /// one line per comment, without the space following `///`
+    fn doc_before(&self, start_sp: &Span) -> Option<String> {
+        let mut lines: std::vec::Vec<&str> = vec![];
+        let mut row = start_sp.srow - 1;
+        for doc in self.docs.iter().rev().skip_while(|d| d.span.start >= start_sp.start) {
+            if doc.span.file != start_sp.file || doc.span.srow != row {
+                break;
+            }
+            lines.push(if doc.text.starts_with(' ') {
+                &doc.text[1..]
+            } else {
+                &doc.text
+            });
+            row -= 1;
+        }
+
+        if lines.is_empty() {
+            None
+        } else {
+            lines.reverse();
+            Some(lines.join("\n"))
+        }
+    }
+
+    /// Next token from the reader which isn't a comment, the comments before it being set aside
+    fn read_token(&mut self) -> lexer::Result<SToken> {
+        loop {
+            match try!(self.reader.next_token()) {
+                SToken { tok: Token::Comment(text), sp } => {
+                    self.comments.push(Comment {
+                        span: sp,
+                        text: text,
+                    })
+                }
+                // Also kept as a comment starting with `/`, so that printing preserves it
+                SToken { tok: Token::DocComment(text), sp } => {
+                    // Only documents what follows when alone on its line
+                    let previous = match self.lookahead.back() {
+                        Some(&Ok(ref t)) => &t.tok,
+                        _ => &self.current_token.tok,
+                    };
+                    match *previous {
+                        Token::Symbol(Symbol::NewLine) | Token::EOF => {
+                            self.docs.push(Comment {
+                                span: sp.clone(),
+                                text: text.clone(),
+                            })
+                        }
+                        _ => (),
+                    }
+                    self.comments.push(Comment {
+                        span: sp,
+                        text: format!("/{}", text),
+                    })
+                }
+                token => return Ok(token),
+            }
+        }
+    }
 }
@@ -0,0 +1,122 @@
+use std;
+use parser::ast::*;
+use parser::visit;
+use parser::visit::Visitor;
+
+/// Index from every node of an `Ast` to its parent, built in a single pass so that questions like
+/// "which function encloses this expression" don't require walking the whole tree again.
+pub struct ParentMap<'a> {
+    nodes: NodeMap<NodeRef<'a>>,
+    parents: NodeMap<NodeRef<'a>>,
+}
+
+impl<'a> ParentMap<'a> {
+    pub fn new(ast: &'a Ast) -> Self {
+        let mut builder = ParentMapBuilder {
+            stack: vec![],
+            map: ParentMap {
+                nodes: NodeMap::new(),
+                parents: NodeMap::new(),
+            },
+        };
+
+        visit::walk_ast(&mut builder, ast);
+
+        builder.map
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<NodeRef<'a>> {
+        self.nodes.get(id).cloned()
+    }
+
+    /// Parent of the node, `None` for top-level statements and unknown ids
+    pub fn parent(&self, id: NodeId) -> Option<NodeRef<'a>> {
+        self.parents.get(id).cloned()
+    }
+
+    /// Ancestors of the node, starting with its parent and ending with a top-level statement
+    pub fn ancestors(&self, id: NodeId) -> std::vec::Vec<NodeRef<'a>> {
+        let mut ancestors = vec![];
+        let mut current = id;
+        while let Some(parent) = self.parent(current) {
+            ancestors.push(parent);
+            current = parent.id();
+        }
+
+        ancestors
+    }
+
+    pub fn enclosing_func(&self, id: NodeId) -> Option<&'a FuncDeclData> {
+        for ancestor in self.ancestors(id) {
+            if let NodeRef::Statement(&Statement::FuncDecl(ref fd)) = ancestor {
+                return Some(fd);
+            }
+        }
+
+        None
+    }
+
+    /// Whether the node is part of the body (or condition) of a `while` or `for` loop
+    pub fn is_in_loop(&self, id: NodeId) -> bool {
+        self.ancestors(id).iter().any(|ancestor| match *ancestor {
+            NodeRef::BlockStatement(&BlockStatement::While(_)) |
+            NodeRef::BlockStatement(&BlockStatement::ForIn(_)) => true,
+            _ => false,
+        })
+    }
+}
+
+struct ParentMapBuilder<'a> {
+    stack: std::vec::Vec<NodeRef<'a>>,
+    map: ParentMap<'a>,
+}
+
+impl<'a> ParentMapBuilder<'a> {
+    fn enter(&mut self, node: NodeRef<'a>) {
+        if let Some(parent) = self.stack.last() {
+            self.map.parents.insert(node.id(), *parent);
+        }
+        self.map.nodes.insert(node.id(), node);
+        self.stack.push(node);
+    }
+
+    fn leave(&mut self) {
+        self.stack.pop();
+    }
+}
+
+impl<'a> Visitor<'a> for ParentMapBuilder<'a> {
+    fn visit_statement(&mut self, statement: &'a Statement) {
+        self.enter(NodeRef::Statement(statement));
+        visit::walk_statement(self, statement);
+        self.leave();
+    }
+
+    fn visit_func_decl_param(&mut self, param: &'a FuncDeclParamData) {
+        self.enter(NodeRef::FuncDeclParam(param));
+        visit::walk_func_decl_param(self, param);
+        self.leave();
+    }
+
+    fn visit_struct_field(&mut self, field: &'a StructFieldData) {
+        self.enter(NodeRef::StructField(field));
+        visit::walk_struct_field(self, field);
+        self.leave();
+    }
+
+    fn visit_block_statement(&mut self, block_statement: &'a BlockStatement) {
+        if let BlockStatement::Expression(_) = *block_statement {
+            return visit::walk_block_statement(self, block_statement);
+        }
+
+        self.enter(NodeRef::BlockStatement(block_statement));
+        visit::walk_block_statement(self, block_statement);
+        self.leave();
+    }
+
+    fn visit_expression(&mut self, expression: &'a Expression) {
+        self.enter(NodeRef::Expression(expression));
+        visit::walk_expression(self, expression);
+        self.leave();
+    }
+}
@@ -0,0 +1,685 @@
+use std;
+use std::fmt;
+use std::fmt::Formatter;
+use std::fmt::Display;
+use parser::ast::*;
+use parser::Parser;
+use parser::Associativity;
+
+/// Postfix expressions (calls, fields, indexes) bind tighter than unary ones, which bind tighter
+/// than any binary operator
+const POSTFIX_PRECEDENCE: u8 = std::u8::MAX;
+const UNARY_PRECEDENCE: u8 = std::u8::MAX - 1;
+
+fn binop_precedence(binop: &BinaryOp) -> u8 {
+    Parser::precedence_for_op(Op::Binary(binop.clone()))
+}
+
+fn expression_precedence(expression: &Expression) -> u8 {
+    match expression.expr {
+        Expression_::ComparisonChain(_, _) => binop_precedence(&BinaryOp::Less),
+        Expression_::BinaryOp(ref binop, _, _) => binop_precedence(binop),
+        Expression_::UnaryOp(_, _) => UNARY_PRECEDENCE,
+        _ => POSTFIX_PRECEDENCE,
+    }
+}
+
+/// Emits Ion source for AST nodes. Everything is printed in a canonical layout (one statement per
+/// line, four spaces of indentation, minimal parentheses), so printing a parsed tree doesn't
+/// preserve the original formatting, but parsing the output gives back an equivalent tree.
+/// Comments are kept when printing a whole `Ast`, next to the nodes they are attached to.
+struct Printer<'a> {
+    out: String,
+    indent: usize,
+    trivia: Option<&'a NodeMap<Trivia>>,
+}
+
+impl<'a> Printer<'a> {
+    fn new() -> Self {
+        Printer {
+            out: String::new(),
+            indent: 0,
+            trivia: None,
+        }
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        for _ in 0..self.indent {
+            self.out.push_str("    ");
+        }
+    }
+
+    fn print_ast(&mut self, ast: &'a Ast) {
+        self.trivia = Some(&ast.trivia);
+
+        let mut prev: Option<&Statement> = None;
+        for statement in &ast.statements {
+            if let Some(prev_statement) = prev {
+                self.newline();
+                match (prev_statement, statement) {
+                    (&Statement::Import(_), &Statement::Import(_)) |
+                    (&Statement::Package(_), &Statement::Package(_)) => {}
+                    _ => self.newline(),
+                }
+            }
+            self.print_statement(statement);
+            prev = Some(statement);
+        }
+        for (i, comment) in ast.trailing_comments.iter().enumerate() {
+            if prev.is_some() {
+                self.newline();
+                if i == 0 {
+                    self.newline();
+                }
+            }
+            self.print_comment(comment);
+            prev = ast.statements.last();
+        }
+        if prev.is_some() || !ast.trailing_comments.is_empty() {
+            self.newline();
+        }
+    }
+
+    fn node_trivia(&self, id: NodeId) -> Option<&'a Trivia> {
+        self.trivia.and_then(|trivia| trivia.get(id))
+    }
+
+    fn print_comment(&mut self, comment: &Comment) {
+        self.out.push_str("//");
+        self.out.push_str(&comment.text);
+    }
+
+    /// Prints the leading comments of the node on their own lines
+    fn print_leading_comments(&mut self, id: NodeId) {
+        if let Some(trivia) = self.node_trivia(id) {
+            for comment in &trivia.leading {
+                self.print_comment(comment);
+                self.newline();
+            }
+        }
+    }
+
+    fn print_trailing_comments(&mut self, id: NodeId) {
+        if let Some(trivia) = self.node_trivia(id) {
+            for comment in &trivia.trailing {
+                self.out.push(' ');
+                self.print_comment(comment);
+            }
+        }
+    }
+
+    fn dangling_comments(&self, id: NodeId) -> &'a [Comment] {
+        match self.node_trivia(id) {
+            Some(trivia) => &trivia.dangling,
+            None => &[],
+        }
+    }
+
+    fn print_statement(&mut self, statement: &Statement) {
+        self.print_leading_comments(statement.id());
+        self.print_statement_(statement);
+        self.print_trailing_comments(statement.id());
+    }
+
+    fn print_statement_(&mut self, statement: &Statement) {
+        match *statement {
+            Statement::Import(ref i) => {
+                self.out.push_str("import ");
+                self.print_string_literal(&i.path);
+            }
+            Statement::Package(ref p) => {
+                self.out.push_str("package ");
+                self.out.push_str(&p.name);
+            }
+            Statement::FuncDecl(ref fd) => self.print_func_decl(fd),
+            Statement::StructDecl(ref sd) => self.print_struct_decl(sd),
+        }
+    }
+
+    fn print_func_decl(&mut self, func_decl: &FuncDeclData) {
+        if func_decl.is_async {
+            self.out.push_str("async ");
+        }
+        if func_decl.is_extern {
+            self.out.push_str("extern ");
+            if let Some(ref library) = func_decl.library {
+                self.print_string_literal(library);
+                self.out.push(' ');
+            }
+        }
+        self.out.push_str("func ");
+        self.out.push_str(&func_decl.name);
+        self.out.push('(');
+        for (i, param) in func_decl.parameters.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.out.push_str(&param.name);
+            self.out.push_str(": ");
+            self.out.push_str(&param.param_type.to_string());
+            if let Some(ref default_value) = param.default_value {
+                self.out.push_str(" = ");
+                self.print_expression(default_value);
+            }
+        }
+        self.out.push(')');
+        if func_decl.return_type != Type::None {
+            self.out.push_str(" -> ");
+            self.out.push_str(&func_decl.return_type.to_string());
+        }
+        if func_decl.is_extern {
+            return;
+        }
+        self.out.push(' ');
+        let dangling = self.dangling_comments(func_decl.id);
+        self.print_block(&func_decl.statements, dangling);
+    }
+
+    fn print_struct_decl(&mut self, struct_decl: &StructDeclData) {
+        self.out.push_str("struct ");
+        self.out.push_str(&struct_decl.name);
+        self.out.push_str(" {");
+        self.indent += 1;
+        for field in &struct_decl.fields {
+            self.newline();
+            self.print_leading_comments(field.id);
+            self.out.push_str(&field.name);
+            self.out.push_str(": ");
+            self.out.push_str(&field.field_type.to_string());
+            if let Some(ref default_value) = field.default_value {
+                self.out.push_str(" = ");
+                self.print_expression(default_value);
+            }
+            self.out.push(',');
+            self.print_trailing_comments(field.id);
+        }
+        let dangling = self.dangling_comments(struct_decl.id);
+        for comment in dangling {
+            self.newline();
+            self.print_comment(comment);
+        }
+        self.indent -= 1;
+        if !struct_decl.fields.is_empty() || !dangling.is_empty() {
+            self.newline();
+        }
+        self.out.push('}');
+    }
+
+    /// Prints the statements in braces, followed by the comments dangling in the block
+    fn print_block(&mut self, statements: &[BlockStatement], dangling: &[Comment]) {
+        self.out.push('{');
+        self.indent += 1;
+        for statement in statements {
+            self.newline();
+            self.print_block_statement(statement);
+        }
+        for comment in dangling {
+            self.newline();
+            self.print_comment(comment);
+        }
+        self.indent -= 1;
+        if !statements.is_empty() || !dangling.is_empty() {
+            self.newline();
+        }
+        self.out.push('}');
+    }
+
+    fn print_block_statement(&mut self, block_statement: &BlockStatement) {
+        self.print_leading_comments(block_statement.id());
+        self.print_block_statement_(block_statement);
+        self.print_trailing_comments(block_statement.id());
+    }
+
+    fn print_block_statement_(&mut self, block_statement: &BlockStatement) {
+        match *block_statement {
+            BlockStatement::Expression(ref e) => self.print_expression(e),
+            BlockStatement::VarDecl(ref vd) => {
+                self.out.push_str("var ");
+                self.out.push_str(&vd.name);
+                self.out.push_str(": ");
+                self.out.push_str(&vd.var_type.to_string());
+                if let Some(ref value) = vd.value {
+                    self.out.push_str(" = ");
+                    self.print_expression(value);
+                }
+            }
+            BlockStatement::Destructure(ref d) => {
+                let names: std::vec::Vec<&str> =
+                    d.pattern.names().iter().map(|n| n.ident.as_ref()).collect();
+                self.out.push_str("var ");
+                match d.pattern {
+                    Pattern::Array(_) => {
+                        self.out.push('(');
+                        self.out.push_str(&names.join(", "));
+                        self.out.push(')');
+                    }
+                    Pattern::Struct(ref path, _) => {
+                        self.out.push_str(&path.to_string());
+                        if names.is_empty() {
+                            self.out.push_str(" {}");
+                        } else {
+                            self.out.push_str(" { ");
+                            self.out.push_str(&names.join(", "));
+                            self.out.push_str(" }");
+                        }
+                    }
+                }
+                self.out.push_str(" = ");
+                self.print_expression(&d.value);
+            }
+            BlockStatement::VarAssignment(ref va) => {
+                self.print_expression(&va.lhs);
+                self.out.push_str(" = ");
+                self.print_expression(&va.rhs);
+            }
+            BlockStatement::If(ref i) => self.print_if(i),
+            BlockStatement::While(ref w) => {
+                self.out.push_str("while ");
+                self.print_expression(&w.condition);
+                self.out.push(' ');
+                let dangling = self.dangling_comments(w.id);
+                self.print_block(&w.statements, dangling);
+            }
+            BlockStatement::Return(ref r) => {
+                self.out.push_str("return");
+                if let Some(ref value) = r.value {
+                    self.out.push(' ');
+                    self.print_expression(value);
+                }
+            }
+            BlockStatement::ForIn(ref fi) => {
+                self.out.push_str("for ");
+                self.out.push_str(&fi.element_name);
+                self.out.push_str(" in ");
+                self.print_expression(&fi.collection);
+                self.out.push(' ');
+                let dangling = self.dangling_comments(fi.id);
+                self.print_block(&fi.statements, dangling);
+            }
+        }
+    }
+
+    /// Comments dangling in an `if` are printed at the end of its `else` block if it has one (as
+    /// opposed to an `else if`), and of its first block otherwise
+    fn print_if(&mut self, if_data: &IfData) {
+        let dangling = self.dangling_comments(if_data.id);
+        let else_if = match if_data.else_statements {
+            Some(ref else_statements) if else_statements.len() == 1 => {
+                match else_statements[0] {
+                    BlockStatement::If(ref else_if) => Some(else_if),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+
+        self.out.push_str("if ");
+        self.print_expression(&if_data.condition);
+        self.out.push(' ');
+        if if_data.else_statements.is_some() && else_if.is_none() {
+            self.print_block(&if_data.if_statements, &[]);
+        } else {
+            self.print_block(&if_data.if_statements, dangling);
+        }
+
+        if let Some(ref else_statements) = if_data.else_statements {
+            self.out.push_str(" else ");
+            match else_if {
+                Some(else_if) => self.print_if(else_if),
+                None => self.print_block(else_statements, dangling),
+            }
+        }
+    }
+
+    fn print_expression(&mut self, expression: &Expression) {
+        match expression.expr {
+            Expression_::StringLiteral(ref s) => self.print_string_literal(s),
+            Expression_::BytesLiteral(ref b) => self.print_bytes_literal(b),
+            Expression_::IntegerLiteral(i) => self.out.push_str(&i.to_string()),
+            Expression_::BoolLiteral(b) => self.out.push_str(&b.to_string()),
+            Expression_::CharLiteral(c) => {
+                self.out.push('\'');
+                self.print_char_escaped(c, '\'');
+                self.out.push('\'');
+            }
+            Expression_::Variable(ref path) => self.out.push_str(&path.to_string()),
+            Expression_::StructInit(ref path, ref fields) => {
+                self.out.push_str("new ");
+                self.out.push_str(&path.to_string());
+                self.out.push_str(" {");
+                for (i, field) in fields.iter().enumerate() {
+                    self.out.push_str(if i > 0 { ", " } else { " " });
+                    self.out.push_str(&field.name.ident);
+                    self.out.push_str(": ");
+                    self.print_expression(&field.value);
+                }
+                self.out.push_str(if fields.is_empty() { "}" } else { " }" });
+            }
+            Expression_::Array(ref items) => {
+                self.out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.print_expression(item);
+                }
+                self.out.push(']');
+            }
+            Expression_::Map(ref map) => {
+                self.out.push('{');
+                for (i, &(ref key, ref value)) in map.entries.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.print_expression(key);
+                    self.out.push_str(": ");
+                    self.print_expression(value);
+                }
+                self.out.push('}');
+            }
+            Expression_::Set(ref items) => {
+                self.out.push('{');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.print_expression(item);
+                }
+                self.out.push('}');
+            }
+            Expression_::FuncCall(ref func_expr, ref args) => {
+                self.print_operand(func_expr, POSTFIX_PRECEDENCE);
+                self.out.push('(');
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.print_expression(arg);
+                }
+                self.out.push(')');
+            }
+            Expression_::Field(ref struct_expr, ref field) => {
+                if let Expression_::IntegerLiteral(_) = struct_expr.expr {
+                    // Otherwise lexed as a float literal
+                    self.out.push('(');
+                    self.print_expression(struct_expr);
+                    self.out.push(')');
+                } else {
+                    self.print_operand(struct_expr, POSTFIX_PRECEDENCE);
+                }
+                self.out.push('.');
+                self.out.push_str(&field.ident);
+            }
+            Expression_::Index(ref indexable_expr, ref index) => {
+                self.print_operand(indexable_expr, POSTFIX_PRECEDENCE);
+                self.out.push('[');
+                if let Some(ref index) = *index {
+                    self.print_expression(index);
+                }
+                self.out.push(']');
+            }
+            Expression_::UnaryOp(ref unop, ref operand) => {
+                self.out.push_str(&unop.to_string());
+                if *unop == UnaryOp::Await {
+                    self.out.push(' ');
+                }
+                self.print_operand(operand, UNARY_PRECEDENCE);
+            }
+            Expression_::ComparisonChain(ref operands, ref binops) => {
+                let min_precedence = binop_precedence(&BinaryOp::Less) + 1;
+                for (i, operand) in operands.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push(' ');
+                        self.out.push_str(&binops[i - 1].to_string());
+                        self.out.push(' ');
+                    }
+                    match binops.get(i) {
+                        Some(binop) => self.print_comparison_operand(operand, binop, min_precedence),
+                        None => self.print_operand(operand, min_precedence),
+                    }
+                }
+            }
+            Expression_::BinaryOp(ref binop, ref lhs, ref rhs) => {
+                // The operand on the side the operator doesn't group towards has to be
+                // parenthesized if it has the same precedence, and so do comparisons compared,
+                // `a < b < c` being a chained comparison
+                let precedence = binop_precedence(binop);
+                let (lhs_precedence, rhs_precedence) = match Parser::associativity_for_op(binop) {
+                    Associativity::Left if binop.is_ordering() => (precedence + 1, precedence + 1),
+                    Associativity::Left => (precedence, precedence + 1),
+                    Associativity::Right => (precedence + 1, precedence),
+                };
+                self.print_comparison_operand(lhs, binop, lhs_precedence);
+                self.out.push(' ');
+                self.out.push_str(&binop.to_string());
+                self.out.push(' ');
+                self.print_operand(rhs, rhs_precedence);
+            }
+            Expression_::Spawn(ref return_type, ref statements) => {
+                self.out.push_str("spawn ");
+                if *return_type != Type::None {
+                    self.out.push_str("-> ");
+                    self.out.push_str(&return_type.to_string());
+                    self.out.push(' ');
+                }
+                let dangling = self.dangling_comments(expression.id);
+                self.print_block(statements, dangling);
+            }
+            Expression_::TypeTest(ref tested_type, ref value) => {
+                self.out.push_str("is<");
+                self.out.push_str(&tested_type.to_string());
+                self.out.push_str(">(");
+                self.print_expression(value);
+                self.out.push(')');
+            }
+            Expression_::Spread(ref spread) => {
+                self.out.push_str("...");
+                self.print_expression(spread);
+            }
+        }
+    }
+
+    /// Prints the expression, parenthesized if it binds less tightly than `min_precedence`
+    /// Left operand of a binary operator, which is parenthesized if it is a variable named `is`
+    /// and the operator is `<`, since a right operand like `a > (b)` would make it a type test
+    fn print_comparison_operand(&mut self,
+                                expression: &Expression,
+                                binop: &BinaryOp,
+                                min_precedence: u8) {
+        let named_is = match expression.expr {
+            Expression_::Variable(ref path) => path.to_string() == "is",
+            _ => false,
+        };
+        if *binop == BinaryOp::Less && named_is {
+            self.out.push('(');
+            self.print_expression(expression);
+            self.out.push(')');
+        } else {
+            self.print_operand(expression, min_precedence);
+        }
+    }
+
+    fn print_operand(&mut self, expression: &Expression, min_precedence: u8) {
+        if expression_precedence(expression) < min_precedence {
+            self.out.push('(');
+            self.print_expression(expression);
+            self.out.push(')');
+        } else {
+            self.print_expression(expression);
+        }
+    }
+
+    fn print_string_literal(&mut self, s: &str) {
+        self.out.push('"');
+        for c in s.chars() {
+            self.print_char_escaped(c, '"');
+        }
+        self.out.push('"');
+    }
+
+    /// Bytes which aren't valid UTF-8 are written as `\xNN`
+    fn print_bytes_literal(&mut self, b: &[u8]) {
+        self.out.push('b');
+        match std::str::from_utf8(b) {
+            Ok(s) => self.print_string_literal(s),
+            Err(_) => {
+                self.out.push('"');
+                for &byte in b {
+                    if byte < 0x80 {
+                        self.print_char_escaped(byte as char, '"');
+                    } else {
+                        self.out.push_str(&format!("\\x{:02x}", byte));
+                    }
+                }
+                self.out.push('"');
+            }
+        }
+    }
+
+    /// The char as written in a literal delimited by `quote`
+    fn print_char_escaped(&mut self, c: char, quote: char) {
+        match c {
+            '\n' => self.out.push_str("\\n"),
+            '\t' => self.out.push_str("\\t"),
+            '\r' => self.out.push_str("\\r"),
+            '\0' => self.out.push_str("\\0"),
+            '\\' => self.out.push_str("\\\\"),
+            _ if c == quote => {
+                self.out.push('\\');
+                self.out.push(c);
+            }
+            _ => self.out.push(c),
+        }
+    }
+}
+
+impl Ast {
+    /// Ion source for the whole program, ending with a newline
+    pub fn to_source(&self) -> String {
+        let mut printer = Printer::new();
+        printer.print_ast(self);
+
+        printer.out
+    }
+}
+
+impl Display for Ast {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_source())
+    }
+}
+
+impl Display for Statement {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut printer = Printer::new();
+        printer.print_statement(self);
+
+        write!(f, "{}", printer.out)
+    }
+}
+
+impl Display for BlockStatement {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut printer = Printer::new();
+        printer.print_block_statement(self);
+
+        write!(f, "{}", printer.out)
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let mut printer = Printer::new();
+        printer.print_expression(self);
+
+        write!(f, "{}", printer.out)
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Type::None => Ok(()),
+            Type::Reference(ref t) => write!(f, "&{}", t),
+            Type::MutReference(ref t) => write!(f, "@{}", t),
+            Type::Array(ref t) => write!(f, "[]{}", t),
+            Type::Map(ref k, ref v) => write!(f, "[{}]{}", k, v),
+            Type::Set(ref t) => write!(f, "set[{}]", t),
+            Type::Struct(ref path) => write!(f, "{}", path),
+            Type::Func(ref return_type, ref params) => {
+                try!(write!(f, "func("));
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        try!(write!(f, ", "));
+                    }
+                    try!(write!(f, "{}", param));
+                }
+                try!(write!(f, ")"));
+                if **return_type != Type::None {
+                    try!(write!(f, " -> {}", return_type));
+                }
+
+                Ok(())
+            }
+            Type::String => write!(f, "string"),
+            Type::Int => write!(f, "int"),
+            Type::Bool => write!(f, "bool"),
+            Type::Char => write!(f, "char"),
+            Type::Bytes => write!(f, "bytes"),
+            Type::Task => write!(f, "task"),
+            Type::Chan(ref t) => write!(f, "[chan]{}", t),
+            Type::Mutex => write!(f, "sync::mutex"),
+            Type::WaitGroup => write!(f, "sync::waitgroup"),
+            Type::Atomic => write!(f, "sync::atomic"),
+            Type::Builder => write!(f, "string::builder"),
+        }
+    }
+}
+
+impl Display for Path {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let parts: std::vec::Vec<&str> = self.parts.iter().map(|p| p.ident.as_ref()).collect();
+
+        write!(f, "{}", parts.join("::"))
+    }
+}
+
+impl Display for BinaryOp {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f,
+               "{}",
+               match *self {
+                   BinaryOp::Addition => "+",
+                   BinaryOp::Substraction => "-",
+                   BinaryOp::Multiplication => "*",
+                   BinaryOp::Division => "/",
+                   BinaryOp::Modulo => "%",
+                   BinaryOp::Equality => "==",
+                   BinaryOp::Inequality => "!=",
+                   BinaryOp::Less => "<",
+                   BinaryOp::LessOrEqual => "<=",
+                   BinaryOp::More => ">",
+                   BinaryOp::MoreOrEqual => ">=",
+                   BinaryOp::Concatenation => "<>",
+                   BinaryOp::And => "&&",
+                   BinaryOp::Or => "||",
+               })
+    }
+}
+
+impl Display for UnaryOp {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f,
+               "{}",
+               match *self {
+                   UnaryOp::Reference => "&",
+                   UnaryOp::MutReference => "@",
+                   UnaryOp::Dereference => "*",
+                   UnaryOp::Count => "#",
+                   UnaryOp::Negation => "-",
+                   UnaryOp::Not => "!",
+                   UnaryOp::Await => "await",
+               })
+    }
+}
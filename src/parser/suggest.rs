@@ -0,0 +1,56 @@
+use std;
+
+/// Candidate closest to a name which wasn't found, if it is close enough for the name to likely
+/// be a typo of it
+pub fn closest<'c, I>(name: &str, candidates: I) -> Option<String>
+    where I: IntoIterator<Item = &'c str>
+{
+    // Replacing all the chars of a name gives any other name, which isn't a sign of a typo
+    let length = name.chars().count();
+    let max_distance = std::cmp::min(std::cmp::max(1, length / 3), length.saturating_sub(1));
+
+    candidates.into_iter()
+        .filter(|&candidate| candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate.to_string())
+}
+
+/// ` (did you mean x?)` when there is a suggestion, to append to error messages
+pub fn did_you_mean(suggestion: &Option<String>) -> String {
+    match *suggestion {
+        Some(ref s) => format!(" (did you mean {}?)", s),
+        None => String::new(),
+    }
+}
+
+/// Number of chars to insert, delete, substitute or swap with the next one to turn a into b
+/// (optimal string alignment distance)
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: std::vec::Vec<char> = a.chars().collect();
+    let b: std::vec::Vec<char> = b.chars().collect();
+    // distances[i][j] is the distance between the first i chars of a and the first j chars of b
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for j in 0..b.len() + 1 {
+        distances[0][j] = j;
+    }
+
+    for i in 1..a.len() + 1 {
+        for j in 1..b.len() + 1 {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut distance = std::cmp::min(distances[i - 1][j - 1] + cost,
+                                             std::cmp::min(distances[i - 1][j],
+                                                           distances[i][j - 1]) + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = std::cmp::min(distance, distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+
+    distances[a.len()][b.len()]
+}
@@ -0,0 +1,1344 @@
+use super::*;
+use lexer::stream::StreamReader;
+
+fn expr(src: &str) -> Expression_ {
+    parse_expr(src, FileId::nil_id()).unwrap().expr
+}
+
+/// Ast of the source, which must parse
+fn parse_str(src: &str) -> Ast {
+    let mut reader = Reader::new(src, FileId::nil_id());
+    let mut parser = Parser::new(&mut reader, ParserOptions::default());
+
+    parser.parse().unwrap().clone()
+}
+
+#[test]
+fn test_parse_expr_literal() {
+    assert_eq!(Expression_::IntegerLiteral(42), expr("42"));
+    assert_eq!(Expression_::StringLiteral("test".to_string()), expr("\"test\""));
+    assert_eq!(Expression_::BoolLiteral(true), expr("true"));
+    assert_eq!(Expression_::CharLiteral('c'), expr("'c'"));
+}
+
+#[test]
+fn test_parse_expr_precedence() {
+    match expr("1 + 2 * 3") {
+        Expression_::BinaryOp(BinaryOp::Addition, lhs, rhs) => {
+            assert_eq!(Expression_::IntegerLiteral(1), lhs.expr);
+            match rhs.expr {
+                Expression_::BinaryOp(BinaryOp::Multiplication, _, _) => (),
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_expr_operators() {
+    let printed = |src: &str| parse_expr(src, FileId::nil_id()).unwrap().to_string();
+
+    assert_eq!("a * b + c", printed("a * b + c"));
+    assert_eq!("a * (b + c)", printed("a * (b + c)"));
+    assert_eq!("*a.b[c](d) % e", printed("*a.b[c](d) % e"));
+    assert_eq!("#*a * b", printed("#(*a) * b"));
+    assert_eq!("a == (b != c)", printed("a == (b != c)"));
+    assert_eq!("a + 1 < b == c >= d", printed("(a + 1 < b) == (c >= d)"));
+    assert_eq!("a <= (b > c)", printed("a <= (b > c)"));
+    assert_eq!("(is) < a", printed("(is) < a"));
+}
+
+#[test]
+fn test_parse_negation() {
+    let printed = |src: &str| parse_expr(src, FileId::nil_id()).unwrap().to_string();
+
+    match parse_expr("a[-1]", FileId::nil_id()).unwrap().expr {
+        Expression_::Index(_, Some(ref index)) => {
+            match index.expr {
+                Expression_::UnaryOp(UnaryOp::Negation, ref operand) => {
+                    assert_eq!(Expression_::IntegerLiteral(1), operand.expr)
+                }
+                ref other => panic!("unexpected {:?}", other),
+            }
+        }
+        ref other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!("a - -1", printed("a--1"));
+    assert_eq!("-a.b * c", printed("(-(a.b)) * c"));
+    assert_eq!("-(a - b)", printed("-(a - b)"));
+    assert_eq!("--a", printed("-(-a)"));
+}
+
+#[test]
+fn test_parse_logical_operators() {
+    let printed = |src: &str| parse_expr(src, FileId::nil_id()).unwrap().to_string();
+
+    match expr("a || b && !c") {
+        Expression_::BinaryOp(BinaryOp::Or, _, rhs) => {
+            match rhs.expr {
+                Expression_::BinaryOp(BinaryOp::And, _, ref not) => {
+                    match not.expr {
+                        Expression_::UnaryOp(UnaryOp::Not, _) => (),
+                        ref other => panic!("unexpected {:?}", other),
+                    }
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!("a < b && c == d || e", printed("((a < b) && (c == d)) || e"));
+    assert_eq!("a && (b || c)", printed("a && (b || c)"));
+    assert_eq!("!a.b && !(c < d)", printed("(!(a.b)) && !(c < d)"));
+    assert_eq!("a && b && c", printed("(a && b) && c"));
+
+    // `&&` in prefix position is a reference to a reference
+    match expr("&&a") {
+        Expression_::UnaryOp(UnaryOp::Reference, operand) => {
+            match operand.expr {
+                Expression_::UnaryOp(UnaryOp::Reference, _) => {
+                    assert_eq!((2, 4), (operand.span.scol, operand.span.ecol))
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!("a && &&b", printed("a && &(&b)"));
+}
+
+#[test]
+fn test_parse_expr_associativity() {
+    match expr("a - b - c") {
+        Expression_::BinaryOp(BinaryOp::Substraction, lhs, rhs) => {
+            match lhs.expr {
+                Expression_::BinaryOp(BinaryOp::Substraction, _, _) => (),
+                other => panic!("unexpected {:?}", other),
+            }
+            assert_eq!(Expression_::Variable(Path {
+                           span: Span::nil_span(),
+                           parts: vec![SpannedString {
+                                           span: Span::nil_span(),
+                                           ident: "c".to_string(),
+                                       }],
+                       }),
+                       rhs.expr);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    let printed = |src: &str| parse_expr(src, FileId::nil_id()).unwrap().to_string();
+    assert_eq!("a / b / c", printed("(a / b) / c"));
+    assert_eq!("a / (b / c)", printed("a / (b / c)"));
+    assert_eq!("a <> b <> c", printed("a <> b <> c"));
+}
+
+#[test]
+fn test_parse_spawn() {
+    let src = "func main() {\n    var t: task = spawn -> int { return 1 }\n    spawn {\n        // \
+               nothing\n    }\n}";
+    let ast = parse_str(src);
+
+    match func_statements(&ast)[0] {
+        BlockStatement::VarDecl(ref vd) => {
+            assert_eq!(Type::Task, vd.var_type);
+            match vd.value.as_ref().unwrap().expr {
+                Expression_::Spawn(ref return_type, ref statements) => {
+                    assert_eq!(Type::Int, *return_type);
+                    assert_eq!(1, statements.len());
+                }
+                ref other => panic!("unexpected {:?}", other),
+            }
+        }
+        ref other => panic!("unexpected {:?}", other),
+    }
+
+    let printed = "func main() {\n    var t: task = spawn -> int {\n        return 1\n    }\n    \
+                   spawn {\n        // nothing\n    }\n}\n";
+    assert_eq!(printed, ast.to_source());
+}
+
+#[test]
+fn test_parse_expr_trailing_newlines() {
+    assert_eq!(Expression_::IntegerLiteral(42), expr("\n42\n\n"));
+}
+
+#[test]
+fn test_parse_expr_trailing_tokens() {
+    assert!(parse_expr("1 2", FileId::nil_id()).is_err());
+}
+
+#[test]
+fn test_parse_async() {
+    let src = "async func f() -> int { return await g() + 1 }";
+    let ast = parse_str(src);
+
+    match ast.statements[0] {
+        Statement::FuncDecl(ref fd) => assert!(fd.is_async),
+        ref other => panic!("unexpected {:?}", other),
+    }
+    match func_statements(&ast)[0] {
+        BlockStatement::Return(ref r) => {
+            match r.value.as_ref().unwrap().expr {
+                Expression_::BinaryOp(BinaryOp::Addition, ref lhs, _) => {
+                    match lhs.expr {
+                        Expression_::UnaryOp(UnaryOp::Await, _) => (),
+                        ref other => panic!("unexpected {:?}", other),
+                    }
+                }
+                ref other => panic!("unexpected {:?}", other),
+            }
+        }
+        ref other => panic!("unexpected {:?}", other),
+    }
+
+    assert_eq!("async func f() -> int {\n    return await g() + 1\n}\n", ast.to_source());
+}
+
+#[test]
+fn test_parse_extern() {
+    let src = "extern func puts(s: string) -> int\nextern \"libm.so.6\" func labs(x: int) -> int";
+    let ast = parse_str(src);
+
+    match ast.statements[0] {
+        Statement::FuncDecl(ref fd) => {
+            assert!(fd.is_extern);
+            assert_eq!(None, fd.library);
+            assert_eq!(Type::Int, fd.return_type);
+            assert!(fd.statements.is_empty());
+        }
+        ref other => panic!("unexpected {:?}", other),
+    }
+    match ast.statements[1] {
+        Statement::FuncDecl(ref fd) => assert_eq!(Some("libm.so.6".to_string()), fd.library),
+        ref other => panic!("unexpected {:?}", other),
+    }
+
+    assert_eq!(format!("{}\n", src.replace("\n", "\n\n")), ast.to_source());
+
+    let mut reader = Reader::new("extern func f() {}", FileId::nil_id());
+    assert!(Parser::new(&mut reader, ParserOptions::default()).parse().is_err());
+}
+
+#[test]
+fn test_parse_type_test() {
+    match expr("is<[]int>(a) == b") {
+        Expression_::BinaryOp(BinaryOp::Equality, ref lhs, _) => {
+            match lhs.expr {
+                Expression_::TypeTest(ref t, ref value) => {
+                    assert_eq!(Type::Array(Box::new(Type::Int)), *t);
+                    assert_eq!("a", value.to_string());
+                }
+                ref other => panic!("unexpected {:?}", other),
+            }
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    assert_eq!("is<[string]int>(m)",
+               parse_expr("is<[string]int>(m)", FileId::nil_id()).unwrap().to_string());
+    assert!(parse_expr("is<int>", FileId::nil_id()).is_err());
+
+    // A variable named `is` can be compared
+    match expr("is < 3") {
+        Expression_::BinaryOp(BinaryOp::Less, ref lhs, ref rhs) => {
+            assert_eq!("is", lhs.to_string());
+            assert_eq!(Expression_::IntegerLiteral(3), rhs.expr);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    let ast = parse_str("func f() {
+    var is: int = 1
+    if is < 3 {}
+    b = is < c
+}");
+    assert_eq!(3, func_statements(&ast).len());
+}
+
+#[test]
+fn test_parse_type() {
+    assert_eq!(Type::Int, parse_type("int", FileId::nil_id()).unwrap());
+    assert_eq!(Type::Map(Box::new(Type::String), Box::new(Type::Array(Box::new(Type::Int)))),
+               parse_type("[string][]int", FileId::nil_id()).unwrap());
+    assert_eq!(Type::MutReference(Box::new(Type::Char)),
+               parse_type("@char", FileId::nil_id()).unwrap());
+    assert_eq!(Type::Chan(Box::new(Type::Array(Box::new(Type::Int)))),
+               parse_type("[chan][]int", FileId::nil_id()).unwrap());
+    assert_eq!(Type::Task, parse_type("task", FileId::nil_id()).unwrap());
+    assert_eq!(Type::Mutex, parse_type("sync::mutex", FileId::nil_id()).unwrap());
+}
+
+#[test]
+fn test_parse_invalid_map_key_type() {
+    let err = parse_type("[[]int]string", FileId::nil_id()).unwrap_err();
+    match err.kind {
+        ErrorKind::InvalidMapKeyType(ref t) => assert_eq!(Type::Array(Box::new(Type::Int)), *t),
+        ref other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!((2, 7), (err.span.scol, err.span.ecol));
+
+    assert!(parse_type("[[string]int]int", FileId::nil_id()).is_err());
+    assert!(parse_type("[bool]int", FileId::nil_id()).is_ok());
+}
+
+#[test]
+fn test_parse_map_literal() {
+    let expr = parse_expr("{\"b\": 1, \"a\": 2, c: 3}", FileId::nil_id()).unwrap();
+    assert_eq!("{\"b\": 1, \"a\": 2, c: 3}", expr.to_string());
+
+    let err = parse_expr("{1: 1, 2: 2, 1: 3}", FileId::nil_id()).unwrap_err();
+    match err.kind {
+        ErrorKind::DuplicateMapKey(ref key) => assert_eq!("1", key),
+        ref other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!((14, 15), (err.span.scol, err.span.ecol));
+
+    assert!(parse_expr("{a: 1, a: 2}", FileId::nil_id()).is_ok());
+}
+
+#[test]
+fn test_parse_bytes() {
+    match parse_expr("b\"a\\n\"", FileId::nil_id()).unwrap().expr {
+        Expression_::BytesLiteral(ref b) => assert_eq!(&b"a\n"[..], &b[..]),
+        ref other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!("b\"é\\n\"",
+               parse_expr("b\"é\\n\"", FileId::nil_id()).unwrap().to_string());
+
+    match parse_block_statement("var a: []bytes", FileId::nil_id(), Type::None).unwrap() {
+        BlockStatement::VarDecl(vd) => {
+            assert_eq!(Type::Array(Box::new(Type::Bytes)), vd.var_type)
+        }
+        ref other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn test_feature_gates() {
+    let cases = [("func f() { var a: []int = [...b] }", Feature::Spread),
+                 ("func f() { guard a else { return } }", Feature::Guard),
+                 ("func f() { var (a, b) = c }", Feature::Destructuring),
+                 ("func f() { var Point { x, y } = p }", Feature::Destructuring),
+                 ("func f() { var a: set[int] = {1, 2} }", Feature::Sets),
+                 ("func f(a: set[int]) {}", Feature::Sets),
+                 ("func f() { var a: Point = Point { x: 1 } }", Feature::BareStructInit)];
+    for &(src, feature) in cases.iter() {
+        let mut reader = Reader::new(src, FileId::nil_id());
+        assert!(Parser::new(&mut reader, ParserOptions::default()).parse().is_ok(), "{}", src);
+
+        let mut features = Feature::all();
+        features.remove(&feature);
+        let mut reader = Reader::new(src, FileId::nil_id());
+        let options = ParserOptions { features: features, ..ParserOptions::default() };
+        let err = Parser::new(&mut reader, options).parse().unwrap_err();
+        assert_eq!(format!("syntax disabled: {}", feature.name()), err.message(), "{}", src);
+    }
+}
+
+#[test]
+fn test_parse_set() {
+    let expr = parse_expr("{1, 2,}", FileId::nil_id()).unwrap();
+    match expr.expr {
+        Expression_::Set(ref items) => assert_eq!(2, items.len()),
+        ref other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!("{1, 2}", expr.to_string());
+    match parse_expr("{}", FileId::nil_id()).unwrap().expr {
+        Expression_::Map(ref m) => assert!(m.entries.is_empty()),
+        ref other => panic!("unexpected {:?}", other),
+    }
+    assert!(parse_expr("{1, 2: 3}", FileId::nil_id()).is_err());
+
+    match parse_block_statement("var a: set[string]", FileId::nil_id(), Type::None).unwrap() {
+        BlockStatement::VarDecl(vd) => {
+            assert_eq!(Type::Set(Box::new(Type::String)), vd.var_type);
+            assert_eq!("set[string]", vd.var_type.to_string());
+        }
+        ref other => panic!("unexpected {:?}", other),
+    }
+    let err = parse_block_statement("var a: set[[]int]", FileId::nil_id(), Type::None).unwrap_err();
+    match err.kind {
+        ErrorKind::InvalidSetItemType(ref t) => assert_eq!("[]int", t.to_string()),
+        ref other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!((12, 17), (err.span.scol, err.span.ecol));
+}
+
+#[test]
+fn test_suggest() {
+    assert_eq!(0, suggest::edit_distance("label", "label"));
+    assert_eq!(1, suggest::edit_distance("lable", "label"));
+    assert_eq!(1, suggest::edit_distance("countr", "counter"));
+    assert_eq!(3, suggest::edit_distance("", "abc"));
+
+    assert_eq!(Some("counter".to_string()),
+               suggest::closest("countr", vec!["countess", "counter", "other"]));
+    assert_eq!(None, suggest::closest("x", vec!["y", "xyz"]));
+    assert_eq!(None, suggest::closest("zzz", vec!["counter"]));
+
+    let mut reader = Reader::new("fucn main() {}", FileId::nil_id());
+    match Parser::new(&mut reader, ParserOptions::default()).parse().unwrap_err().kind {
+        ErrorKind::MisspelledKeyword(ref ident, ref keyword) => {
+            assert_eq!(("fucn", "func"), (ident.as_ref(), keyword.as_ref()))
+        }
+        ref other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_block_statement() {
+    match parse_block_statement("var a: int = 1", FileId::nil_id(), Type::None).unwrap() {
+        BlockStatement::VarDecl(vd) => {
+            assert_eq!("a", vd.name);
+            assert_eq!(Type::Int, vd.var_type);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    match parse_block_statement("return 1", FileId::nil_id(), Type::Int).unwrap() {
+        BlockStatement::Return(r) => assert!(r.value.is_some()),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_max_errors() {
+    let src = "func a() { ) }\nfunc b() { ] }\nfunc c() {}";
+
+    let mut reader = Reader::new(src, FileId::nil_id());
+    let mut parser = Parser::new(&mut reader, ParserOptions::default());
+    assert!(parser.parse().is_err());
+    assert_eq!(1, parser.errors().len());
+
+    let mut reader = Reader::new(src, FileId::nil_id());
+    let mut parser = Parser::new(&mut reader,
+                                 ParserOptions { max_errors: 10, ..ParserOptions::default() });
+    assert!(parser.parse().is_err());
+    assert_eq!(2, parser.errors().len());
+}
+
+#[test]
+fn test_parse_newlines_terminate_expressions() {
+    let src = "func a() {\nvar b: int = 1\n*c\n}";
+
+    let mut reader = Reader::new(src, FileId::nil_id());
+    let mut parser = Parser::new(&mut reader, ParserOptions::default());
+    assert_eq!(2, func_statements(parser.parse().unwrap()).len());
+
+    let mut reader = Reader::new(src, FileId::nil_id());
+    let mut parser = Parser::new(&mut reader,
+                                 ParserOptions {
+                                     newlines_terminate_expressions: false,
+                                     ..ParserOptions::default()
+                                 });
+    assert_eq!(1, func_statements(parser.parse().unwrap()).len());
+}
+
+fn func_statements(ast: &Ast) -> std::vec::Vec<BlockStatement> {
+    match ast.statements[0] {
+        Statement::FuncDecl(ref fd) => fd.statements.clone(),
+        ref other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_recursion_limit() {
+    let nested = |depth: usize| {
+        let mut src = String::new();
+        for _ in 0..depth {
+            src.push('(');
+        }
+        src.push('1');
+        for _ in 0..depth {
+            src.push(')');
+        }
+        src
+    };
+
+    // The default limit is meant for the 8MB stack of the main thread, while test threads only
+    // get 2MB
+    let deep_parse = std::thread::Builder::new()
+        .stack_size(8 * 1024 * 1024)
+        .spawn(move || {
+            assert!(parse_expr(nested(100).as_ref(), FileId::nil_id()).is_ok());
+            match parse_expr(nested(100000).as_ref(), FileId::nil_id()).unwrap_err().kind {
+                ErrorKind::TooDeeplyNested => (),
+                other => panic!("unexpected {:?}", other),
+            }
+        })
+        .unwrap();
+    assert!(deep_parse.join().is_ok());
+
+    let mut reader = Reader::new("((((1))))", FileId::nil_id());
+    let mut parser = Parser::new(&mut reader,
+                                 ParserOptions { recursion_limit: 3, ..ParserOptions::default() });
+    assert!(parser.parse_fragment(|p| p.parse_expression()).is_err());
+}
+
+#[test]
+fn test_visitor() {
+    struct VariableCounter {
+        count: usize,
+    }
+
+    impl<'a> visit::Visitor<'a> for VariableCounter {
+        fn visit_expression(&mut self, expression: &'a Expression) {
+            if let Expression_::Variable(_) = expression.expr {
+                self.count += 1;
+            }
+            visit::walk_expression(self, expression);
+        }
+    }
+
+    let mut counter = VariableCounter { count: 0 };
+    let e = parse_expr("a(b, c[d], e.f)", FileId::nil_id()).unwrap();
+    visit::Visitor::visit_expression(&mut counter, &e);
+    assert_eq!(5, counter.count);
+}
+
+#[test]
+fn test_mut_visitor() {
+    struct AdditionFolder;
+
+    impl visit::MutVisitor for AdditionFolder {
+        fn visit_expression_mut(&mut self, expression: &mut Expression) {
+            visit::walk_expression_mut(self, expression);
+            let folded = match expression.expr {
+                Expression_::BinaryOp(BinaryOp::Addition, ref e1, ref e2) => {
+                    match (&e1.expr, &e2.expr) {
+                        (&Expression_::IntegerLiteral(i1), &Expression_::IntegerLiteral(i2)) => {
+                            Some(Expression_::IntegerLiteral(i1 + i2))
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+
+            if let Some(f) = folded {
+                expression.expr = f;
+            }
+        }
+    }
+
+    let mut e = parse_expr("[1 + 2, (3 + 4) + 5]", FileId::nil_id()).unwrap();
+    visit::MutVisitor::visit_expression_mut(&mut AdditionFolder, &mut e);
+    match e.expr {
+        Expression_::Array(ref items) => {
+            assert_eq!(Expression_::IntegerLiteral(3), items[0].expr);
+            assert_eq!(Expression_::IntegerLiteral(12), items[1].expr);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn test_node_ids() {
+    struct IdCollector {
+        ids: std::vec::Vec<NodeId>,
+    }
+
+    impl<'a> visit::Visitor<'a> for IdCollector {
+        fn visit_block_statement(&mut self, block_statement: &'a BlockStatement) {
+            // Expression statements share the id of their expression
+            match *block_statement {
+                BlockStatement::Expression(_) => (),
+                _ => self.ids.push(block_statement.id()),
+            }
+            visit::walk_block_statement(self, block_statement);
+        }
+
+        fn visit_expression(&mut self, expression: &'a Expression) {
+            self.ids.push(expression.id);
+            visit::walk_expression(self, expression);
+        }
+    }
+
+    let ast = parse_str("func a() {\nb = c + 1\nif d { e() }\n}");
+
+    let mut collector = IdCollector { ids: vec![] };
+    visit::walk_ast(&mut collector, &ast);
+    assert_eq!(9, collector.ids.len());
+
+    let mut node_map = NodeMap::new();
+    for id in &collector.ids {
+        assert!(*id != NodeId::nil_id());
+        assert!(node_map.insert(*id, ()).is_none());
+    }
+    assert_eq!(collector.ids.len(), node_map.len());
+}
+
+fn find_variable(ast: &Ast, name: &str) -> NodeId {
+    struct VariableFinder<'b> {
+        name: &'b str,
+        id: Option<NodeId>,
+    }
+
+    impl<'a, 'b> visit::Visitor<'a> for VariableFinder<'b> {
+        fn visit_expression(&mut self, expression: &'a Expression) {
+            if let Expression_::Variable(ref p) = expression.expr {
+                if p.parts[0].ident == self.name {
+                    self.id = Some(expression.id);
+                }
+            }
+            visit::walk_expression(self, expression);
+        }
+    }
+
+    let mut finder = VariableFinder {
+        name: name,
+        id: None,
+    };
+    visit::walk_ast(&mut finder, ast);
+    finder.id.unwrap()
+}
+
+#[test]
+fn test_parent_map() {
+    let src = "func a() {\nb = 1\nwhile c {\nd = e + 1\n}\n}\nfunc f(g: int) {\nh\n}";
+    let ast = parse_str(src);
+    let parents = parents::ParentMap::new(&ast);
+
+    let b = find_variable(&ast, "b");
+    let c = find_variable(&ast, "c");
+    let e = find_variable(&ast, "e");
+    let h = find_variable(&ast, "h");
+
+    assert_eq!("a", parents.enclosing_func(e).unwrap().name);
+    assert_eq!("f", parents.enclosing_func(h).unwrap().name);
+    assert!(parents.is_in_loop(e));
+    assert!(parents.is_in_loop(c));
+    assert!(!parents.is_in_loop(b));
+
+    match parents.parent(e) {
+        Some(NodeRef::Expression(p)) => {
+            match p.expr {
+                Expression_::BinaryOp(BinaryOp::Addition, _, _) => (),
+                ref other => panic!("unexpected {:?}", other),
+            }
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(parents.parent(h).is_some());
+    assert_eq!(4, parents.ancestors(e).len());
+}
+
+#[test]
+fn test_find_at() {
+    let src = "func a() {\n  b = cc + 1\n}";
+    let mut reader = Reader::new(src, FileId(1));
+    let mut parser = Parser::new(&mut reader, ParserOptions::default());
+    let ast = parser.parse().unwrap();
+
+    match ast::find_at(ast, FileId(1), 2, 8) {
+        Some(NodeRef::Expression(e)) => {
+            assert_eq!(Expression_::Variable(Path {
+                           span: Span::nil_span(),
+                           parts: vec![SpannedString {
+                                           span: Span::nil_span(),
+                                           ident: "cc".to_string(),
+                                       }],
+                       }),
+                       e.expr)
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    match ast::find_at(ast, FileId(1), 2, 10) {
+        Some(NodeRef::Expression(e)) => {
+            match e.expr {
+                Expression_::BinaryOp(BinaryOp::Addition, _, _) => (),
+                ref other => panic!("unexpected {:?}", other),
+            }
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    match ast::find_at(ast, FileId(1), 1, 2) {
+        Some(NodeRef::Statement(&Statement::FuncDecl(_))) => (),
+        other => panic!("unexpected {:?}", other),
+    }
+
+    assert!(ast::find_at(ast, FileId(2), 2, 8).is_none());
+    assert!(ast::find_at(ast, FileId(1), 5, 1).is_none());
+}
+
+#[test]
+fn test_print_expression() {
+    let print = |src: &str| parse_expr(src, FileId::nil_id()).unwrap().to_string();
+    assert_eq!("a - (b - c) - d", print("(a - (b - c)) - d"));
+    assert_eq!("(a + b) * c", print("(a + b) * c"));
+    assert_eq!("a + b * c", print("a + (b * c)"));
+    assert_eq!("*a.b + (*c).d", print("(*(a.b)) + (*c).d"));
+    assert_eq!("(a <> b)[0](&c, @d, #e)", print("(a <> b)[0](&c, @d, #e)"));
+    assert_eq!("new a::B { c: [1, 2], d: {\"e\": 'f'} }",
+               print("new a::B{c: [1, 2,], d: {\"e\": 'f'}}"));
+    assert_eq!("\"a\\nb\" == '\\''", print("\"a\\nb\" == '\\''"));
+    assert_eq!("send(chan::new(), 1)", print("send(chan::new(), 1)"));
+}
+
+#[test]
+fn test_parse_chained_comparison() {
+    let expression = parse_expr("0 <= x < 10", FileId::nil_id()).unwrap();
+    match expression.expr {
+        Expression_::ComparisonChain(ref operands, ref binops) => {
+            assert_eq!(3, operands.len());
+            assert_eq!(vec![BinaryOp::LessOrEqual, BinaryOp::Less], *binops);
+        }
+        ref other => panic!("unexpected {:?}", other),
+    }
+    // The middle operand is a single node
+    let ast = parse_str("func main() {\n    var a: bool = 0 <= x < 10\n}\n");
+    let mut ids = std::collections::HashSet::new();
+    struct Ids<'b>(&'b mut std::collections::HashSet<NodeId>);
+    impl<'a, 'b> visit::Visitor<'a> for Ids<'b> {
+        fn visit_expression(&mut self, expression: &'a Expression) {
+            assert!(self.0.insert(expression.id));
+            visit::walk_expression(self, expression);
+        }
+    }
+    visit::walk_ast(&mut Ids(&mut ids), &ast);
+    assert_eq!(4, ids.len());
+
+    let print = |src: &str| parse_expr(src, FileId::nil_id()).unwrap().to_string();
+    assert_eq!("0 <= x < 10", print("0 <= x < 10"));
+    assert_eq!("a < b + 1 >= c > (is) < d", print("a < b + 1 >= c > (is) < d"));
+    assert_eq!("(a < b) < c", print("(a < b) < c"));
+    assert_eq!("a < (b < c)", print("a < (b < c)"));
+    assert_eq!("a < b < c == d", print("(a < b < c) == d"));
+    assert_eq!("a < b == c", print("a < b == c"));
+}
+
+#[test]
+fn test_parse_destructure() {
+    let statement = |src: &str| parse_block_statement(src, FileId::nil_id(), Type::None);
+    let names = |pattern: &Pattern| -> std::vec::Vec<String> {
+        pattern.names().iter().map(|n| n.ident.clone()).collect()
+    };
+
+    match statement("var (a, b) = pair").unwrap() {
+        BlockStatement::Destructure(d) => {
+            match d.pattern {
+                Pattern::Array(_) => assert_eq!(vec!["a", "b"], names(&d.pattern)),
+                ref other => panic!("unexpected {:?}", other),
+            }
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+    match statement("var geo::Point { x, y, } = p").unwrap() {
+        BlockStatement::Destructure(d) => {
+            match d.pattern {
+                Pattern::Struct(ref path, _) => {
+                    assert_eq!("geo::Point", path.to_string());
+                    assert_eq!(vec!["x", "y"], names(&d.pattern));
+                }
+                ref other => panic!("unexpected {:?}", other),
+            }
+            assert_eq!("p", d.value.to_string());
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    let printed = |src: &str| statement(src).unwrap().to_string();
+    assert_eq!("var (a, b) = [1, 2]", printed("var (a,b,) = [1, 2]"));
+    assert_eq!("var A::B { c } = d", printed("var A::B{c}=d"));
+    assert_eq!("var A {} = d", printed("var A {} = d"));
+
+    assert!(statement("var (a, b)").is_err());
+    assert!(statement("var Point { x: y } = p").is_err());
+}
+
+#[test]
+fn test_parse_spread() {
+    let expression = parse_expr("f(a, ...b)", FileId::nil_id()).unwrap();
+    match expression.expr {
+        Expression_::FuncCall(_, ref args) => {
+            match args[1].expr {
+                Expression_::Spread(ref spread) => assert_eq!("b", spread.to_string()),
+                ref other => panic!("unexpected {:?}", other),
+            }
+            assert_eq!(5, args[1].span.start);
+        }
+        ref other => panic!("unexpected {:?}", other),
+    }
+
+    let print = |src: &str| parse_expr(src, FileId::nil_id()).unwrap().to_string();
+    assert_eq!("[1, ...a <> b, 9]", print("[1,...a<>b,9]"));
+    assert_eq!("f(...[1, 2], ...g())", print("f(...[1,2],...g())"));
+    assert!(parse_expr("...a", FileId::nil_id()).is_err());
+}
+
+#[test]
+fn test_print_roundtrip() {
+    let src = "package main
+import \"a\"
+import \"b\"
+struct A { b: [string]@int, c: []B::C }
+func f(a: int, b: &A) -> int {
+var c: int = a * (a + 1)
+b.c[0] = c
+if a == 0 { return 1 } else if a == 1 {
+while true {}
+} else { for d in b.c { print(d) } }
+return c
+}
+func main() {
+f(1, &new A {})
+}";
+    let expected = "package main
+
+import \"a\"
+import \"b\"
+
+struct A {
+    b: [string]@int,
+    c: []B::C,
+}
+
+func f(a: int, b: &A) -> int {
+    var c: int = a * (a + 1)
+    b.c[0] = c
+    if a == 0 {
+        return 1
+    } else if a == 1 {
+        while true {}
+    } else {
+        for d in b.c {
+            print(d)
+        }
+    }
+    return c
+}
+
+func main() {
+    f(1, &new A {})
+}
+";
+
+    let printed = parse_str(src).to_source();
+    assert_eq!(expected, printed);
+
+    assert_eq!(expected, parse_str(printed.as_ref()).to_source());
+}
+
+#[test]
+fn test_strip_spans() {
+    let src1 = "import \"a\"\nstruct A { b: &c::D }\nfunc e(f: A) -> c::D {\nreturn *f.b\n}";
+    let src2 = "import   \"a\"\n\n\nstruct A {\n    b: &c::D,\n}\n\nfunc e(f: A)->c::D{ return *(f.b) }";
+
+    let mut reader1 = Reader::new(src1, FileId(1));
+    let mut parser1 = Parser::new(&mut reader1, ParserOptions::default());
+    let ast1 = parser1.parse().unwrap().strip_spans();
+    let mut reader2 = Reader::new(src2, FileId(2));
+    let mut parser2 = Parser::new(&mut reader2, ParserOptions::default());
+    let ast2 = parser2.parse().unwrap().strip_spans();
+
+    assert_eq!(format!("{:?}", ast1), format!("{:?}", ast2));
+    assert!(!format!("{:?}", ast1).contains("FileId(1)"));
+    match ast1.statements[2] {
+        Statement::FuncDecl(ref fd) => {
+            assert_eq!(Span::nil_span(), fd.span);
+            assert_eq!(NodeId::nil_id(), fd.statements[0].id());
+        }
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn test_expected_token_kind() {
+    let mut reader = Reader::new("func 1() {}", FileId::nil_id());
+    let mut parser = Parser::new(&mut reader, ParserOptions::default());
+    let err = parser.parse().unwrap_err();
+    match err.kind {
+        ErrorKind::ExpectedGotToken(ref expected, Token::IntegerLiteral(1))
+            if *expected == vec![Expected::Kind(TokenKind::Identifier)] => {}
+        _ => panic!("unexpected error {:?}", err),
+    }
+    assert_eq!("1: expected identifier, got `1`", err.to_string());
+}
+
+#[test]
+fn test_expected_token_set() {
+    let err = parse_expr("[1 2]", FileId::nil_id()).unwrap_err();
+    match err.kind {
+        ErrorKind::ExpectedGotToken(ref expected, Token::IntegerLiteral(2))
+            if *expected == vec![Expected::Token(Token::Symbol(Symbol::RightBracket)),
+                                 Expected::Token(Token::Symbol(Symbol::Comma))] => {}
+        _ => panic!("unexpected error {:?}", err),
+    }
+    assert_eq!("expected one of `]`, `,`, got `2`", err.message());
+}
+
+#[test]
+fn test_parse_guard() {
+    let statement = |src: &str| parse_block_statement(src, FileId::nil_id(), Type::Int);
+
+    match statement("guard x > 0 else { return 0 }").unwrap() {
+        BlockStatement::If(i) => {
+            assert_eq!("x > 0", i.condition.to_string());
+            assert!(i.if_statements.is_empty());
+            match i.else_statements.as_ref().map(|s| &s[..]) {
+                Some(&[BlockStatement::Return(_)]) => (),
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    for src in &["guard x > 0 else { f() }", "guard x > 0 else {}"] {
+        match statement(src) {
+            Err(Error { kind: ErrorKind::GuardWithoutReturn, .. }) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+    assert!(statement("guard x > 0 { return 0 }").is_err());
+}
+
+#[test]
+fn test_parse_error_context() {
+    let mut reader = Reader::new("func f() {\n    if g(1 2) {\n    }\n}", FileId::nil_id());
+    let mut parser = Parser::new(&mut reader, ParserOptions::default());
+    let err = parser.parse().unwrap_err();
+    let context: std::vec::Vec<(Construct, i32, i32)> = err.context
+        .iter()
+        .map(|c| (c.construct.clone(), c.span.srow, c.span.scol))
+        .collect();
+    assert_eq!(vec![(Construct::CallArgs, 2, 8), (Construct::IfCondition, 2, 5)], context);
+
+    let mut reader = Reader::new("struct S {\n    a int\n}", FileId::nil_id());
+    let mut parser = Parser::new(&mut reader, ParserOptions::default());
+    let err = parser.parse().unwrap_err();
+    assert_eq!(1, err.context.len());
+    assert_eq!(Construct::StructDecl("S".to_string()), err.context[0].construct);
+    assert_eq!("while parsing struct `S`", err.context[0].message());
+}
+
+#[test]
+fn test_parse_struct_init_without_new() {
+    let src1 = "func f() {\n    var p: P = P { x: 1, y: a::Q {\n        z: 2,\n    } }\n    \
+                while b {\n        print(b)\n    }\n}";
+    let src2 = "func f() {\n    var p: P = new P { x: 1, y: new a::Q {\n        z: 2,\n    } }\n    \
+                while b {\n        print(b)\n    }\n}";
+
+    let mut reader1 = Reader::new(src1, FileId::nil_id());
+    let mut parser1 = Parser::new(&mut reader1, ParserOptions::default());
+    let ast1 = parser1.parse().unwrap().strip_spans();
+    let mut reader2 = Reader::new(src2, FileId::nil_id());
+    let mut parser2 = Parser::new(&mut reader2, ParserOptions::default());
+    let ast2 = parser2.parse().unwrap().strip_spans();
+    assert_eq!(ast2, ast1);
+
+    // Without a field, the braces are a block
+    let mut reader = Reader::new("func f() {\n    while b {}\n}", FileId::nil_id());
+    let mut parser = Parser::new(&mut reader, ParserOptions::default());
+    assert!(parser.parse().is_ok());
+}
+
+#[test]
+fn test_peek() {
+    let mut reader = Reader::new("a.b\nc ~ d", FileId::nil_id());
+    let mut parser = Parser::new(&mut reader, ParserOptions::default());
+    parser.next_token().unwrap();
+
+    assert_eq!(Some(&Token::Symbol(Symbol::NewLine)), parser.peek(3));
+    assert_eq!(Some(&Token::Symbol(Symbol::Dot)), parser.peek(1));
+    assert_eq!(Some(&Token::Identifier("c".to_string())), parser.peek(4));
+    assert_eq!(None, parser.peek(5));
+    assert_eq!(None, parser.peek(6));
+
+    let (ident, _) = parser.expect_identifier().unwrap();
+    assert_eq!("a", ident);
+    parser.expect(Token::Symbol(Symbol::Dot)).unwrap();
+    assert_eq!(Some(&Token::Identifier("b".to_string())), parser.peek(0));
+    parser.expect_identifier().unwrap();
+    assert_eq!(Some(&Token::Identifier("c".to_string())), parser.peek(0));
+    match parser.next_token().unwrap_err().kind {
+        ErrorKind::Lexer(_) => {}
+        _ => panic!(),
+    }
+}
+
+#[test]
+fn test_parse_stream() {
+    let src = "func main() {\n    print(\"a\" <> \"b\")\n}\n";
+    let mut stream = StreamReader::new(src.as_bytes(), FileId::nil_id());
+    let mut parser = Parser::new(&mut stream, ParserOptions::default());
+    let ast = parser.parse().unwrap();
+    assert_eq!(src, ast.to_source());
+}
+
+#[test]
+fn test_parse_prelude() {
+    let ast = parse_str(include_str!("../../std/prelude.ion"));
+    for statement in &ast.statements {
+        match *statement {
+            Statement::FuncDecl(ref fd) => assert!(fd.doc.is_some(), "{} is undocumented", fd.name),
+            ref other => panic!("unexpected {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn test_reparse() {
+    use parser::incremental::*;
+
+    let src = "import \"a\"\n\n// f\nfunc f() {\n    g(1) // g\n}\n\nstruct A {\n    b: int,\n}\n// \
+               end\n";
+    let mut reader = Reader::new(src, FileId(1));
+    let mut parser = Parser::new(&mut reader, ParserOptions::default());
+    let ast = parser.parse().unwrap().clone();
+
+    let check = |ast: &Ast, src: &str, edit: TextEdit| -> (Ast, String) {
+        let new_src = edit.apply(src);
+        let reparsed = incremental::reparse(ast, src, &edit, FileId(1), ParserOptions::default())
+            .unwrap();
+
+        let mut reader = Reader::new(new_src.as_ref(), FileId(1));
+        let mut parser = Parser::new(&mut reader, ParserOptions::default());
+        let expected = parser.parse().unwrap().clone();
+        assert_eq!(format!("{:?}", expected.strip_spans()),
+                   format!("{:?}", reparsed.strip_spans()));
+        assert_eq!(expected.to_source(), reparsed.to_source());
+
+        // Spans must be the ones a full parse would give
+        struct Spans(std::vec::Vec<Span>);
+        impl<'a> visit::Visitor<'a> for Spans {
+            fn visit_expression(&mut self, expression: &'a Expression) {
+                self.0.push(expression.span.clone());
+                visit::walk_expression(self, expression);
+            }
+        }
+        let mut expected_spans = Spans(vec![]);
+        visit::walk_ast(&mut expected_spans, &expected);
+        let mut spans = Spans(vec![]);
+        visit::walk_ast(&mut spans, &reparsed);
+        assert_eq!(expected_spans.0, spans.0);
+        for (s1, s2) in expected.statements.iter().zip(reparsed.statements.iter()) {
+            assert_eq!(s1.span(), s2.span());
+        }
+
+        (reparsed, new_src)
+    };
+
+    // Edit inside the function, the struct after it is shifted
+    let start = src.find("1").unwrap();
+    let (ast2, src2) = check(&ast,
+                             src,
+                             TextEdit {
+                                 start: start,
+                                 end: start + 1,
+                                 text: "2,\n    é".to_string(),
+                             });
+    match (&ast.statements[0], &ast2.statements[0]) {
+        (&Statement::Import(ref i1), &Statement::Import(ref i2)) => assert_eq!(i1.id, i2.id),
+        _ => panic!(),
+    }
+    assert_eq!(ast.statements[2].id(), ast2.statements[2].id());
+    assert!(ast2.statements[1].id() != ast.statements[1].id());
+    assert!(ast2.statements[1].id() > ast.statements[2].id());
+
+    // Insertion of a new statement between two others
+    let start = src2.find("struct").unwrap();
+    check(&ast2,
+          &src2,
+          TextEdit {
+              start: start - 1,
+              end: start - 1,
+              text: "\npackage p\n".to_string(),
+          });
+
+    // Edits of comments, which are lexed again from their start, and of the doc of the
+    // statement after them
+    let src = "/// old\nfunc f() {\n}\n// a\nfunc g() {} // b\n";
+    let mut reader = Reader::new(src, FileId(1));
+    let mut parser = Parser::new(&mut reader, ParserOptions::default());
+    let ast = parser.parse().unwrap().clone();
+    let start = src.find("old").unwrap();
+    let (ast2, _) = check(&ast,
+                          src,
+                          TextEdit {
+                              start: start,
+                              end: start + 3,
+                              text: "neu".to_string(),
+                          });
+    match ast2.statements[0] {
+        Statement::FuncDecl(ref fd) => assert_eq!(Some("neu".to_string()), fd.doc),
+        ref other => panic!("unexpected {:?}", other),
+    }
+    let start = src.find("// a").unwrap();
+    let (ast2, _) = check(&ast,
+                          src,
+                          TextEdit {
+                              start: start + 2,
+                              end: start + 2,
+                              text: "/".to_string(),
+                          });
+    match ast2.statements[1] {
+        Statement::FuncDecl(ref fd) => assert_eq!(Some("a".to_string()), fd.doc),
+        ref other => panic!("unexpected {:?}", other),
+    }
+    let start = src.find("// b").unwrap();
+    check(&ast,
+          src,
+          TextEdit {
+              start: start + 2,
+              end: start + 2,
+              text: "/".to_string(),
+          });
+    check(&ast,
+          src,
+          TextEdit {
+              start: 0,
+              end: 8,
+              text: String::new(),
+          });
+}
+
+#[test]
+fn test_comments() {
+    let src = "// leading\nfunc a() { // first\n    b() // trailing\n    // dangling\n}\n\nstruct \
+               S {\n    // field\n    c: int,\n}\n// end\n";
+    let ast = parse_str(src);
+
+    let texts = |comments: &[Comment]| -> std::vec::Vec<String> {
+        comments.iter().map(|c| c.text.clone()).collect()
+    };
+    let func_trivia = ast.trivia.get(ast.statements[0].id()).unwrap();
+    assert_eq!(vec![" leading"], texts(&func_trivia.leading));
+    assert_eq!(vec![" dangling"], texts(&func_trivia.dangling));
+    let call_trivia = ast.trivia.get(func_statements(&ast)[0].id()).unwrap();
+    assert_eq!(vec![" first"], texts(&call_trivia.leading));
+    assert_eq!(vec![" trailing"], texts(&call_trivia.trailing));
+    match ast.statements[1] {
+        Statement::StructDecl(ref sd) => {
+            assert_eq!(vec![" field"], texts(&ast.trivia.get(sd.fields[0].id).unwrap().leading))
+        }
+        ref other => panic!("unexpected {:?}", other),
+    }
+    assert_eq!(vec![" end"], texts(&ast.trailing_comments));
+
+    let printed = "// leading\nfunc a() {\n    // first\n    b() // trailing\n    // dangling\n}\n\n\
+                   struct S {\n    // field\n    c: int,\n}\n\n// end\n";
+    assert_eq!(printed, ast.to_source());
+
+    assert_eq!(printed, parse_str(printed).to_source());
+}
+
+#[test]
+fn test_doc_comments() {
+    let src = "/// Adds one.\n///\n///Really.\nfunc inc(x: int) -> int { return x + 1 }\n\n/// \
+               Detached\n\nstruct P {\n    /// Abscissa\n    x: int,\n    y: int, /// Not y's\n    \
+               z: int,\n}\n// Plain\nextern func f()\n";
+    let ast = parse_str(src);
+
+    match ast.statements[0] {
+        Statement::FuncDecl(ref fd) => assert_eq!(Some("Adds one.\n\nReally.".to_string()), fd.doc),
+        ref other => panic!("unexpected {:?}", other),
+    }
+    match ast.statements[1] {
+        Statement::StructDecl(ref sd) => {
+            assert_eq!(None, sd.doc);
+            let docs: std::vec::Vec<Option<String>> =
+                sd.fields.iter().map(|f| f.doc.clone()).collect();
+            assert_eq!(vec![Some("Abscissa".to_string()), None, None], docs);
+        }
+        ref other => panic!("unexpected {:?}", other),
+    }
+    match ast.statements[2] {
+        Statement::FuncDecl(ref fd) => assert_eq!(None, fd.doc),
+        ref other => panic!("unexpected {:?}", other),
+    }
+
+    assert!(ast.to_source().starts_with("/// Adds one.\n///\n///Really.\nfunc inc"));
+}
+
+#[test]
+fn test_print_parse_roundtrip_arbitrary() {
+    for seed in 0..500 {
+        let ast = arbitrary::Generator::new(seed, 5).ast();
+        let src = ast.to_source();
+
+        let mut reader = Reader::new(src.as_ref(), FileId::nil_id());
+        let mut parser = Parser::new(&mut reader, ParserOptions::default());
+        match parser.parse() {
+            Ok(parsed) => {
+                assert!(ast == parsed.strip_spans(),
+                        "seed {} printed as\n{}\nparsed as\n{}",
+                        seed,
+                        src,
+                        parsed)
+            }
+            Err(e) => panic!("seed {} printed as\n{}\nfailed to parse: {}", seed, src, e),
+        }
+    }
+}
+
+/// Parses every `tests/cases/*.ion` file and compares the dump of its AST, or its errors when it
+/// doesn't parse, with the `.ast` or `.err` file next to it. Running the test with `BLESS=1`
+/// writes the current output to these files instead.
+#[test]
+fn test_golden_cases() {
+    use std::io::{Read, Write};
+
+    let cases = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("cases");
+    let bless = std::env::var_os("BLESS").is_some();
+
+    let mut paths: std::vec::Vec<std::path::PathBuf> = std::fs::read_dir(&cases)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |extension| extension == "ion"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no cases in {}", cases.display());
+
+    let mut failures = vec![];
+    for path in &paths {
+        let mut src = String::new();
+        std::fs::File::open(path).unwrap().read_to_string(&mut src).unwrap();
+
+        let mut reader = Reader::new(src.as_ref(), FileId::nil_id());
+        let options = ParserOptions { max_errors: 10, ..ParserOptions::default() };
+        let mut parser = Parser::new(&mut reader, options);
+        let (actual, expected_path, other_path) = match parser.parse() {
+            Ok(ast) => (ast.dump(), path.with_extension("ast"), path.with_extension("err")),
+            Err(_) => {
+                let mut errors = String::new();
+                for e in parser.errors() {
+                    errors.push_str(&format!("{}:{}: {}\n", e.span.srow, e.span.scol, e.message()));
+                    for context in &e.context {
+                        errors.push_str(&format!("{}:{}: note: {}\n",
+                                                 context.span.srow,
+                                                 context.span.scol,
+                                                 context.message()));
+                    }
+                }
+                (errors, path.with_extension("err"), path.with_extension("ast"))
+            }
+        };
+
+        if bless {
+            std::fs::File::create(&expected_path).unwrap().write_all(actual.as_bytes()).unwrap();
+            let _ = std::fs::remove_file(&other_path);
+            continue;
+        }
+
+        let mut expected = String::new();
+        match std::fs::File::open(&expected_path) {
+            Ok(mut f) => {
+                f.read_to_string(&mut expected).unwrap();
+            }
+            Err(_) => expected = format!("<missing {}>", expected_path.display()),
+        }
+        if actual != expected {
+            failures.push(format!("{}:\nexpected:\n{}\nactual:\n{}",
+                                  path.display(),
+                                  expected,
+                                  actual));
+        }
+    }
+
+    assert!(failures.is_empty(),
+            "{} of {} cases differ (rerun with BLESS=1 to update them):\n\n{}",
+            failures.len(),
+            paths.len(),
+            failures.join("\n"));
+}
+
+#[test]
+fn test_expression_index() {
+    let src = "func a() {\nb = c[d + 1] * (d + 1)\ne = (d+1) + c[d + 1]\nif 0 < d < 2 {\n}\n}";
+    let mut reader = Reader::new(src, FileId::nil_id());
+    let mut parser = Parser::new(&mut reader, ParserOptions::default());
+    let ast = parser.parse().unwrap();
+    let index = cse::ExpressionIndex::new(ast);
+
+    let duplicates: std::vec::Vec<_> = index.duplicates()
+        .iter()
+        .map(|class| (class.len(), class[0].strip_spans()))
+        .collect();
+    let expected: std::vec::Vec<_> = [(2, "c[d + 1]"), (2, "c"), (4, "d + 1"), (5, "d"), (4, "1")]
+        .iter()
+        .map(|&(len, src)| (len, parse_expr(src, FileId::nil_id()).unwrap().strip_spans()))
+        .collect();
+    assert_eq!(expected, duplicates);
+
+    let d = find_variable(ast, "d");
+    assert_eq!(5, index.class_of(d).unwrap().len());
+    assert!(index.class_of(NodeId(u32::max_value())).is_none());
+}
+
+#[test]
+fn test_metrics() {
+    let src = "struct A {\nb: int\n}\nfunc c(d: int) -> int {\nif 0 < d < 10 {\nwhile true {\n\
+               d = d + 1\n}\n}\nreturn d\n}\nfunc e() {\nfor f in [1] {\nprint(f)\n}\n}";
+    let metrics = ast::metrics(&parse_str(src));
+
+    assert_eq!(2, metrics.funcs);
+    assert_eq!(1, metrics.structs);
+    assert_eq!(9, metrics.statements);
+    assert_eq!(15, metrics.expressions);
+    assert_eq!(3, metrics.max_depth);
+    assert_eq!(vec![("c".to_string(), 4), ("e".to_string(), 2)], metrics.complexity);
+}
+
+#[test]
+fn test_call_graph() {
+    let src = "func a(b: int) {\nc([c][0](b))\nio::print(\"d\")\n}\n\
+               func c(e: int) {\nfor f in [a] {\nf(c)\n}\nc(e - 1)\n}\nfunc g() {\nc(1)\n}";
+    let ast = parse_str(src);
+    let graph = callgraph::CallGraph::new(&ast);
+
+    assert_eq!(6, graph.calls().len());
+    let callers: std::vec::Vec<_> = graph.callers_of("c").iter().map(|fd| &fd.name[..]).collect();
+    assert_eq!(vec!["a", "c", "g"], callers);
+    assert!(graph.callers_of("a").is_empty());
+    assert!(graph.has_unknown_calls("a"));
+    assert!(graph.has_unknown_calls("c"));
+    assert!(!graph.has_unknown_calls("g"));
+    assert!(graph.is_recursive("c"));
+    assert!(!graph.is_recursive("g"));
+    match graph.calls_from("a")[2].callee {
+        callgraph::Callee::External(path) => assert_eq!("io::print", path.to_string()),
+        ref other => panic!("unexpected {:?}", other),
+    }
+
+    assert_eq!("digraph calls {\n    \"a\";\n    \"c\";\n    \"g\";\n    \"a\" -> \"c\";\n    \
+                \"a\" -> \"<unknown>\" [style=dashed];\n    \"a\" -> \"io::print\";\n    \
+                \"c\" -> \"<unknown>\" [style=dashed];\n    \"c\" -> \"c\";\n    \
+                \"g\" -> \"c\";\n}\n",
+               graph.to_dot());
+}
+
+#[test]
+fn test_call_graph_mutual_recursion() {
+    let src = "func a() {\nb()\n}\nfunc b() {\nif true {\nc()\n}\n}\nfunc c() {\na()\n}\n\
+               func d() {\na()\n}";
+    let ast = parse_str(src);
+    let graph = callgraph::CallGraph::new(&ast);
+
+    let recursive: std::vec::Vec<_> =
+        graph.recursive_funcs().iter().map(|fd| &fd.name[..]).collect();
+    assert_eq!(vec!["a", "b", "c"], recursive);
+}
+
+#[test]
+fn test_parse_keyword_field_names() {
+    let src = "struct Config {\nin: bool,\nif: int,\n}\n\nfunc a(b: Config) -> bool {\n\
+               var c: Config = Config { in: true, if: b.if }\nreturn c.in\n}\n";
+    let ast = parse_str(src);
+
+    match ast.statements[0] {
+        Statement::StructDecl(ref sd) => {
+            let names: std::vec::Vec<_> = sd.fields.iter().map(|f| &f.name[..]).collect();
+            assert_eq!(vec!["in", "if"], names);
+        }
+        ref other => panic!("unexpected {:?}", other),
+    }
+
+    let printed = ast.to_source();
+    assert_eq!(ast.strip_spans(), parse_str(&printed).strip_spans());
+
+    assert!(parse_expr("a.while", FileId::nil_id()).is_ok());
+    assert!(parse_expr("A { return: 1 }", FileId::nil_id()).is_ok());
+}
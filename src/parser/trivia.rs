@@ -0,0 +1,107 @@
+use std;
+use lexer::Span;
+use parser::ast::*;
+use parser::visit;
+use parser::visit::Visitor;
+
+/// Attaches `comments`, in source order, to the statements, struct fields and spawn expressions of
+/// `ast`, replacing its current trivia:
+///
+/// - a comment following a node on the row where it ends is trailing trivia of that node (of the
+///   outermost one if several nodes end there),
+/// - otherwise it is leading trivia of the first node starting after it in the same block,
+/// - otherwise it is dangling trivia of the node containing it, or a trailing comment of the
+///   file if it is after all the top-level statements.
+pub fn attach(ast: &mut Ast, comments: std::vec::Vec<Comment>) {
+    let nodes = {
+        let mut collector = NodeCollector { nodes: vec![] };
+        visit::walk_ast(&mut collector, ast);
+        collector.nodes
+    };
+
+    ast.trivia = NodeMap::new();
+    ast.trailing_comments.clear();
+
+    for comment in comments {
+        let sp = comment.span.clone();
+
+        let preceding = nodes.iter()
+            .filter(|&&(_, ref span)| span.erow == sp.srow && span.end <= sp.start)
+            .max_by_key(|&&(_, ref span)| (span.end, std::cmp::Reverse(span.start)));
+        if let Some(&(id, _)) = preceding {
+            trivia_mut(ast, id).trailing.push(comment);
+            continue;
+        }
+
+        let container = nodes.iter()
+            .filter(|&&(_, ref span)| span.start <= sp.start && sp.end <= span.end)
+            .max_by_key(|&&(_, ref span)| span.start);
+        let following = nodes.iter()
+            .filter(|&&(_, ref span)| {
+                span.start >= sp.end &&
+                container.map_or(true, |&(_, ref container_span)| {
+                    span.end <= container_span.end
+                })
+            })
+            .min_by_key(|&&(_, ref span)| span.start);
+
+        match (following, container) {
+            (Some(&(id, _)), _) => trivia_mut(ast, id).leading.push(comment),
+            (None, Some(&(id, _))) => trivia_mut(ast, id).dangling.push(comment),
+            (None, None) => ast.trailing_comments.push(comment),
+        }
+    }
+}
+
+/// Every comment of `ast`, in source order
+pub fn comments(ast: &Ast) -> std::vec::Vec<Comment> {
+    let mut comments: std::vec::Vec<Comment> = ast.trivia
+        .values()
+        .flat_map(|trivia| {
+            trivia.leading.iter().chain(trivia.trailing.iter()).chain(trivia.dangling.iter())
+        })
+        .chain(ast.trailing_comments.iter())
+        .cloned()
+        .collect();
+    comments.sort_by_key(|comment| comment.span.start);
+
+    comments
+}
+
+fn trivia_mut(ast: &mut Ast, id: NodeId) -> &mut Trivia {
+    if !ast.trivia.contains(id) {
+        ast.trivia.insert(id, Trivia::default());
+    }
+
+    ast.trivia.get_mut(id).unwrap()
+}
+
+/// Nodes comments can be attached to
+struct NodeCollector {
+    nodes: std::vec::Vec<(NodeId, Span)>,
+}
+
+impl<'a> Visitor<'a> for NodeCollector {
+    fn visit_statement(&mut self, statement: &'a Statement) {
+        self.nodes.push((statement.id(), statement.span().clone()));
+        visit::walk_statement(self, statement);
+    }
+
+    fn visit_struct_field(&mut self, field: &'a StructFieldData) {
+        self.nodes.push((field.id, field.span.clone()));
+        visit::walk_struct_field(self, field);
+    }
+
+    fn visit_block_statement(&mut self, block_statement: &'a BlockStatement) {
+        self.nodes.push((block_statement.id(), block_statement.span().clone()));
+        visit::walk_block_statement(self, block_statement);
+    }
+
+    fn visit_expression(&mut self, expression: &'a Expression) {
+        // Blocks of spawn expressions contain statements, and can have dangling comments
+        if let Expression_::Spawn(_, _) = expression.expr {
+            self.nodes.push((expression.id, expression.span.clone()));
+        }
+        visit::walk_expression(self, expression);
+    }
+}
@@ -0,0 +1,371 @@
+use parser::ast::*;
+
+/// Read-only traversal of the AST. Every method defaults to walking the children of the node, so
+/// implementors only need to override the nodes they are interested in, and call the matching
+/// `walk_*` function to keep descending.
+pub trait Visitor<'a>: Sized {
+    fn visit_statement(&mut self, statement: &'a Statement) {
+        walk_statement(self, statement)
+    }
+
+    fn visit_func_decl(&mut self, func_decl: &'a FuncDeclData) {
+        walk_func_decl(self, func_decl)
+    }
+
+    fn visit_func_decl_param(&mut self, param: &'a FuncDeclParamData) {
+        walk_func_decl_param(self, param)
+    }
+
+    fn visit_struct_decl(&mut self, struct_decl: &'a StructDeclData) {
+        walk_struct_decl(self, struct_decl)
+    }
+
+    fn visit_struct_field(&mut self, field: &'a StructFieldData) {
+        walk_struct_field(self, field)
+    }
+
+    fn visit_block_statement(&mut self, block_statement: &'a BlockStatement) {
+        walk_block_statement(self, block_statement)
+    }
+
+    fn visit_expression(&mut self, expression: &'a Expression) {
+        walk_expression(self, expression)
+    }
+
+    fn visit_type(&mut self, _: &'a Type) {}
+}
+
+pub fn walk_ast<'a, V: Visitor<'a>>(visitor: &mut V, ast: &'a Ast) {
+    for statement in &ast.statements {
+        visitor.visit_statement(statement);
+    }
+}
+
+pub fn walk_statement<'a, V: Visitor<'a>>(visitor: &mut V, statement: &'a Statement) {
+    match *statement {
+        Statement::FuncDecl(ref fd) => visitor.visit_func_decl(fd),
+        Statement::StructDecl(ref sd) => visitor.visit_struct_decl(sd),
+        Statement::Import(_) |
+        Statement::Package(_) => (),
+    }
+}
+
+pub fn walk_func_decl<'a, V: Visitor<'a>>(visitor: &mut V, func_decl: &'a FuncDeclData) {
+    for param in &func_decl.parameters {
+        visitor.visit_func_decl_param(param);
+    }
+
+    visitor.visit_type(&func_decl.return_type);
+    walk_block_statements(visitor, &func_decl.statements);
+}
+
+pub fn walk_func_decl_param<'a, V: Visitor<'a>>(visitor: &mut V, param: &'a FuncDeclParamData) {
+    visitor.visit_type(&param.param_type);
+    if let Some(ref e) = param.default_value {
+        visitor.visit_expression(e);
+    }
+}
+
+pub fn walk_struct_decl<'a, V: Visitor<'a>>(visitor: &mut V, struct_decl: &'a StructDeclData) {
+    for field in &struct_decl.fields {
+        visitor.visit_struct_field(field);
+    }
+}
+
+pub fn walk_struct_field<'a, V: Visitor<'a>>(visitor: &mut V, field: &'a StructFieldData) {
+    visitor.visit_type(&field.field_type);
+    if let Some(ref e) = field.default_value {
+        visitor.visit_expression(e);
+    }
+}
+
+pub fn walk_block_statements<'a, V: Visitor<'a>>(visitor: &mut V,
+                                                  statements: &'a [BlockStatement]) {
+    for statement in statements {
+        visitor.visit_block_statement(statement);
+    }
+}
+
+pub fn walk_block_statement<'a, V: Visitor<'a>>(visitor: &mut V,
+                                                block_statement: &'a BlockStatement) {
+    match *block_statement {
+        BlockStatement::Expression(ref e) => visitor.visit_expression(e),
+        BlockStatement::VarDecl(ref vd) => {
+            visitor.visit_type(&vd.var_type);
+            if let Some(ref e) = vd.value {
+                visitor.visit_expression(e);
+            }
+        }
+        BlockStatement::Destructure(ref d) => visitor.visit_expression(&d.value),
+        BlockStatement::VarAssignment(ref va) => {
+            visitor.visit_expression(&va.lhs);
+            visitor.visit_expression(&va.rhs);
+        }
+        BlockStatement::If(ref i) => {
+            visitor.visit_expression(&i.condition);
+            walk_block_statements(visitor, &i.if_statements);
+            if let Some(ref else_statements) = i.else_statements {
+                walk_block_statements(visitor, else_statements);
+            }
+        }
+        BlockStatement::While(ref w) => {
+            visitor.visit_expression(&w.condition);
+            walk_block_statements(visitor, &w.statements);
+        }
+        BlockStatement::Return(ref r) => {
+            if let Some(ref e) = r.value {
+                visitor.visit_expression(e);
+            }
+        }
+        BlockStatement::ForIn(ref fi) => {
+            visitor.visit_expression(&fi.collection);
+            walk_block_statements(visitor, &fi.statements);
+        }
+    }
+}
+
+pub fn walk_expression<'a, V: Visitor<'a>>(visitor: &mut V, expression: &'a Expression) {
+    match expression.expr {
+        Expression_::StringLiteral(_) |
+        Expression_::BytesLiteral(_) |
+        Expression_::IntegerLiteral(_) |
+        Expression_::BoolLiteral(_) |
+        Expression_::CharLiteral(_) |
+        Expression_::Variable(_) => (),
+        Expression_::StructInit(_, ref fields) => {
+            for field in fields {
+                visitor.visit_expression(&field.value);
+            }
+        }
+        Expression_::Array(ref items) |
+        Expression_::Set(ref items) => {
+            for item in items {
+                visitor.visit_expression(item);
+            }
+        }
+        Expression_::Map(ref m) => {
+            for &(ref key, ref value) in &m.entries {
+                visitor.visit_expression(key);
+                visitor.visit_expression(value);
+            }
+        }
+        Expression_::FuncCall(ref func, ref args) => {
+            visitor.visit_expression(func);
+            for arg in args {
+                visitor.visit_expression(arg);
+            }
+        }
+        Expression_::Field(ref e, _) => visitor.visit_expression(e),
+        Expression_::Index(ref indexed, ref index) => {
+            visitor.visit_expression(indexed);
+            if let Some(ref i) = *index {
+                visitor.visit_expression(i);
+            }
+        }
+        Expression_::UnaryOp(_, ref e) => visitor.visit_expression(e),
+        Expression_::BinaryOp(_, ref e1, ref e2) => {
+            visitor.visit_expression(e1);
+            visitor.visit_expression(e2);
+        }
+        Expression_::ComparisonChain(ref operands, _) => {
+            for operand in operands {
+                visitor.visit_expression(operand);
+            }
+        }
+        Expression_::Spawn(ref return_type, ref statements) => {
+            visitor.visit_type(return_type);
+            walk_block_statements(visitor, statements);
+        }
+        Expression_::TypeTest(ref tested_type, ref e) => {
+            visitor.visit_type(tested_type);
+            visitor.visit_expression(e);
+        }
+        Expression_::Spread(ref e) => visitor.visit_expression(e),
+    }
+}
+
+/// In-place transformation of the AST, the mutable counterpart of `Visitor`. Overriding a method
+/// allows rewriting the node it receives (e.g. `*expression = folded`), which is the basis for
+/// desugaring passes and constant folding. Children are walked by the default implementations
+/// only, so an override which replaces a node decides whether to call `walk_*_mut` before or after
+/// doing so.
+pub trait MutVisitor: Sized {
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        walk_statement_mut(self, statement)
+    }
+
+    fn visit_func_decl_mut(&mut self, func_decl: &mut FuncDeclData) {
+        walk_func_decl_mut(self, func_decl)
+    }
+
+    fn visit_func_decl_param_mut(&mut self, param: &mut FuncDeclParamData) {
+        walk_func_decl_param_mut(self, param)
+    }
+
+    fn visit_struct_decl_mut(&mut self, struct_decl: &mut StructDeclData) {
+        walk_struct_decl_mut(self, struct_decl)
+    }
+
+    fn visit_struct_field_mut(&mut self, field: &mut StructFieldData) {
+        walk_struct_field_mut(self, field)
+    }
+
+    fn visit_block_statement_mut(&mut self, block_statement: &mut BlockStatement) {
+        walk_block_statement_mut(self, block_statement)
+    }
+
+    fn visit_expression_mut(&mut self, expression: &mut Expression) {
+        walk_expression_mut(self, expression)
+    }
+
+    fn visit_type_mut(&mut self, _: &mut Type) {}
+}
+
+pub fn walk_ast_mut<V: MutVisitor>(visitor: &mut V, ast: &mut Ast) {
+    for statement in &mut ast.statements {
+        visitor.visit_statement_mut(statement);
+    }
+}
+
+pub fn walk_statement_mut<V: MutVisitor>(visitor: &mut V, statement: &mut Statement) {
+    match *statement {
+        Statement::FuncDecl(ref mut fd) => visitor.visit_func_decl_mut(fd),
+        Statement::StructDecl(ref mut sd) => visitor.visit_struct_decl_mut(sd),
+        Statement::Import(_) |
+        Statement::Package(_) => (),
+    }
+}
+
+pub fn walk_func_decl_mut<V: MutVisitor>(visitor: &mut V, func_decl: &mut FuncDeclData) {
+    for param in &mut func_decl.parameters {
+        visitor.visit_func_decl_param_mut(param);
+    }
+
+    visitor.visit_type_mut(&mut func_decl.return_type);
+    walk_block_statements_mut(visitor, &mut func_decl.statements);
+}
+
+pub fn walk_func_decl_param_mut<V: MutVisitor>(visitor: &mut V, param: &mut FuncDeclParamData) {
+    visitor.visit_type_mut(&mut param.param_type);
+    if let Some(ref mut e) = param.default_value {
+        visitor.visit_expression_mut(e);
+    }
+}
+
+pub fn walk_struct_decl_mut<V: MutVisitor>(visitor: &mut V, struct_decl: &mut StructDeclData) {
+    for field in &mut struct_decl.fields {
+        visitor.visit_struct_field_mut(field);
+    }
+}
+
+pub fn walk_struct_field_mut<V: MutVisitor>(visitor: &mut V, field: &mut StructFieldData) {
+    visitor.visit_type_mut(&mut field.field_type);
+    if let Some(ref mut e) = field.default_value {
+        visitor.visit_expression_mut(e);
+    }
+}
+
+pub fn walk_block_statements_mut<V: MutVisitor>(visitor: &mut V,
+                                                statements: &mut [BlockStatement]) {
+    for statement in statements {
+        visitor.visit_block_statement_mut(statement);
+    }
+}
+
+pub fn walk_block_statement_mut<V: MutVisitor>(visitor: &mut V,
+                                               block_statement: &mut BlockStatement) {
+    match *block_statement {
+        BlockStatement::Expression(ref mut e) => visitor.visit_expression_mut(e),
+        BlockStatement::VarDecl(ref mut vd) => {
+            visitor.visit_type_mut(&mut vd.var_type);
+            if let Some(ref mut e) = vd.value {
+                visitor.visit_expression_mut(e);
+            }
+        }
+        BlockStatement::Destructure(ref mut d) => visitor.visit_expression_mut(&mut d.value),
+        BlockStatement::VarAssignment(ref mut va) => {
+            visitor.visit_expression_mut(&mut va.lhs);
+            visitor.visit_expression_mut(&mut va.rhs);
+        }
+        BlockStatement::If(ref mut i) => {
+            visitor.visit_expression_mut(&mut i.condition);
+            walk_block_statements_mut(visitor, &mut i.if_statements);
+            if let Some(ref mut else_statements) = i.else_statements {
+                walk_block_statements_mut(visitor, else_statements);
+            }
+        }
+        BlockStatement::While(ref mut w) => {
+            visitor.visit_expression_mut(&mut w.condition);
+            walk_block_statements_mut(visitor, &mut w.statements);
+        }
+        BlockStatement::Return(ref mut r) => {
+            if let Some(ref mut e) = r.value {
+                visitor.visit_expression_mut(e);
+            }
+        }
+        BlockStatement::ForIn(ref mut fi) => {
+            visitor.visit_expression_mut(&mut fi.collection);
+            walk_block_statements_mut(visitor, &mut fi.statements);
+        }
+    }
+}
+
+pub fn walk_expression_mut<V: MutVisitor>(visitor: &mut V, expression: &mut Expression) {
+    match expression.expr {
+        Expression_::StringLiteral(_) |
+        Expression_::BytesLiteral(_) |
+        Expression_::IntegerLiteral(_) |
+        Expression_::BoolLiteral(_) |
+        Expression_::CharLiteral(_) |
+        Expression_::Variable(_) => (),
+        Expression_::StructInit(_, ref mut fields) => {
+            for field in fields {
+                visitor.visit_expression_mut(&mut field.value);
+            }
+        }
+        Expression_::Array(ref mut items) |
+        Expression_::Set(ref mut items) => {
+            for item in items {
+                visitor.visit_expression_mut(item);
+            }
+        }
+        Expression_::Map(ref mut m) => {
+            for &mut (ref mut key, ref mut value) in &mut m.entries {
+                visitor.visit_expression_mut(key);
+                visitor.visit_expression_mut(value);
+            }
+        }
+        Expression_::FuncCall(ref mut func, ref mut args) => {
+            visitor.visit_expression_mut(func);
+            for arg in args {
+                visitor.visit_expression_mut(arg);
+            }
+        }
+        Expression_::Field(ref mut e, _) => visitor.visit_expression_mut(e),
+        Expression_::Index(ref mut indexed, ref mut index) => {
+            visitor.visit_expression_mut(indexed);
+            if let Some(ref mut i) = *index {
+                visitor.visit_expression_mut(i);
+            }
+        }
+        Expression_::UnaryOp(_, ref mut e) => visitor.visit_expression_mut(e),
+        Expression_::BinaryOp(_, ref mut e1, ref mut e2) => {
+            visitor.visit_expression_mut(e1);
+            visitor.visit_expression_mut(e2);
+        }
+        Expression_::ComparisonChain(ref mut operands, _) => {
+            for operand in operands {
+                visitor.visit_expression_mut(operand);
+            }
+        }
+        Expression_::Spawn(ref mut return_type, ref mut statements) => {
+            visitor.visit_type_mut(return_type);
+            walk_block_statements_mut(visitor, statements);
+        }
+        Expression_::TypeTest(ref mut tested_type, ref mut e) => {
+            visitor.visit_type_mut(tested_type);
+            visitor.visit_expression_mut(e);
+        }
+        Expression_::Spread(ref mut e) => visitor.visit_expression_mut(e),
+    }
+}
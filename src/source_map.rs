@@ -0,0 +1,251 @@
+use std;
+use std::cell::RefCell;
+use std::error::Error as BaseError;
+use std::fmt;
+use std::fmt::Display;
+use std::io::Read;
+use std::path::Path;
+use std::rc::Rc;
+use lexer::Span;
+
+#[derive(Debug)]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    IO(std::io::Error),
+    /// The span covers the first invalid sequence, the file having been added with it replaced
+    InvalidUtf8,
+}
+
+impl Error {
+    /// Description of the error, without its position
+    pub fn message(&self) -> String {
+        match self.kind {
+            ErrorKind::IO(ref e) => e.to_string(),
+            ErrorKind::InvalidUtf8 => format!("{} (byte {})", self.description(), self.span.start),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ErrorKind::IO(ref e) => e.fmt(f),
+            ErrorKind::InvalidUtf8 => write!(f, "{}: {}", self.span, self.message()),
+        }
+    }
+}
+
+impl BaseError for Error {
+    fn description(&self) -> &str {
+        match self.kind {
+            ErrorKind::IO(ref e) => e.description(),
+            ErrorKind::InvalidUtf8 => "invalid UTF-8",
+        }
+    }
+
+    fn cause(&self) -> Option<&std::error::Error> {
+        match self.kind {
+            ErrorKind::IO(ref e) => Some(e),
+            ErrorKind::InvalidUtf8 => None,
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Identifies a file registered in a `SourceMap`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FileId(pub u32);
+
+impl FileId {
+    /// Id of spans which don't come from any registered file
+    pub fn nil_id() -> FileId {
+        FileId(0)
+    }
+}
+
+#[derive(Debug)]
+pub struct SourceFile {
+    pub name: String,
+    pub src: String,
+    /// Byte offset at which each line starts, computed once so that converting between offsets
+    /// and positions doesn't require re-scanning the source
+    line_starts: std::vec::Vec<usize>,
+}
+
+impl SourceFile {
+    pub fn new(name: String, src: String) -> Self {
+        // A byte order mark isn't part of the first line, as for the lexer
+        let mut line_starts = vec![if src.starts_with('\u{FEFF}') {
+                                       '\u{FEFF}'.len_utf8()
+                                   } else {
+                                       0
+                                   }];
+        for (i, c) in src.char_indices() {
+            if c == '\n' {
+                line_starts.push(i + 1);
+            }
+        }
+
+        SourceFile {
+            name: name,
+            src: src,
+            line_starts: line_starts,
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Text of the (1-based) row, without its line terminator
+    pub fn line(&self, row: i32) -> Option<&str> {
+        self.raw_line(row).map(|line| line.trim_right_matches('\r'))
+    }
+
+    /// Text of the row including a trailing `\r` if any, but not the `\n`
+    fn raw_line(&self, row: i32) -> Option<&str> {
+        if row < 1 || row as usize > self.line_starts.len() {
+            return None;
+        }
+
+        let start = self.line_starts[row as usize - 1];
+        let end = match self.line_starts.get(row as usize) {
+            Some(next) => next - 1,
+            None => self.src.len(),
+        };
+
+        Some(&self.src[start..end])
+    }
+
+    /// Row and column of a byte offset, both 1-based and with columns counted in characters like
+    /// the ones of a `Span`
+    pub fn position(&self, offset: usize) -> Option<(i32, i32)> {
+        if offset > self.src.len() || !self.src.is_char_boundary(offset) {
+            return None;
+        }
+
+        let row = match self.line_starts.binary_search(&offset) {
+            Ok(row) => row,
+            Err(0) => return Some((1, 1)),
+            Err(row) => row - 1,
+        };
+        let col = self.src[self.line_starts[row]..offset].chars().count() + 1;
+
+        Some((row as i32 + 1, col as i32))
+    }
+
+    /// Column in UTF-16 code units (still 1-based) of a row and column as returned by `position`
+    pub fn utf16_column(&self, row: i32, col: i32) -> Option<i32> {
+        self.offset(row, col).map(|offset| {
+            let line_start = self.line_starts[row as usize - 1];
+            self.src[line_start..offset].encode_utf16().count() as i32 + 1
+        })
+    }
+
+    /// Byte offset of a row and column, as returned by `position`
+    pub fn offset(&self, row: i32, col: i32) -> Option<usize> {
+        if col < 1 {
+            return None;
+        }
+
+        let line = match self.raw_line(row) {
+            Some(line) => line,
+            None => return None,
+        };
+        let start = self.line_starts[row as usize - 1];
+
+        match line.char_indices().nth(col as usize - 1) {
+            Some((i, _)) => Some(start + i),
+            None if col as usize == line.chars().count() + 1 => Some(start + line.len()),
+            None => None,
+        }
+    }
+}
+
+/// Owns the contents of every file involved in a program, so that spans only need to carry a
+/// `FileId`. Files can be added while others are being used (e.g. when following imports), which
+/// is why they are handed out as `Rc`s.
+#[derive(Debug)]
+pub struct SourceMap {
+    files: RefCell<std::vec::Vec<Rc<SourceFile>>>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        SourceMap { files: RefCell::new(vec![]) }
+    }
+
+    pub fn add_file(&self, name: String, src: String) -> FileId {
+        let mut files = self.files.borrow_mut();
+        files.push(Rc::new(SourceFile::new(name, src)));
+
+        FileId(files.len() as u32)
+    }
+
+    /// Reads the file at `path` and adds it, named after the path. A file which isn't valid UTF-8
+    /// is still added, with the invalid sequences replaced by U+FFFD, so that the error can point
+    /// into it.
+    pub fn load_file(&self, path: &Path) -> Result<FileId> {
+        let mut bytes = vec![];
+        let read = std::fs::File::open(path).and_then(|mut file| file.read_to_end(&mut bytes));
+        if let Err(e) = read {
+            return Err(Error {
+                kind: ErrorKind::IO(e),
+                span: Span::nil_span(),
+            });
+        }
+
+        let name = path.to_string_lossy().into_owned();
+        match String::from_utf8(bytes) {
+            Ok(src) => Ok(self.add_file(name, src)),
+            Err(e) => {
+                let offset = e.utf8_error().valid_up_to();
+                let id = self.add_file(name, String::from_utf8_lossy(e.as_bytes()).into_owned());
+                let (row, col) = self.file(id).unwrap().position(offset).unwrap();
+
+                Err(Error {
+                    kind: ErrorKind::InvalidUtf8,
+                    span: Span {
+                        srow: row,
+                        scol: col,
+                        erow: row,
+                        ecol: col + 1,
+                        start: offset,
+                        end: offset + '\u{FFFD}'.len_utf8(),
+                        file: id,
+                    },
+                })
+            }
+        }
+    }
+
+    pub fn file(&self, id: FileId) -> Option<Rc<SourceFile>> {
+        if id == FileId::nil_id() {
+            return None;
+        }
+
+        self.files.borrow().get(id.0 as usize - 1).cloned()
+    }
+
+    /// Name of the file, or an empty string for unknown files
+    pub fn name(&self, id: FileId) -> String {
+        match self.file(id) {
+            Some(f) => f.name.clone(),
+            None => "".to_string(),
+        }
+    }
+
+    /// Source text covered by the span
+    pub fn snippet(&self, span: &Span) -> Option<String> {
+        match self.file(span.file) {
+            Some(f) => f.src.get(span.start..span.end).map(|s| s.to_string()),
+            None => None,
+        }
+    }
+}